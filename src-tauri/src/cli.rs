@@ -0,0 +1,755 @@
+use crate::exporter::{self, ExportFormat, ExportOptions};
+use crate::file_manager::{FileManager, FileType};
+use crate::lsp;
+use crate::mermaid_parser::formatter::{self, FormatOptions, QuoteStyle};
+use crate::mermaid_parser::{
+    DiagnosticCode, DiagramType, LintConfig, MermaidParser, Severity, SyntaxError,
+};
+use crate::renderer::Theme;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+mod report;
+
+/// Exit code when every file was processed and no diagram has errors
+pub const EXIT_OK: i32 = 0;
+/// Exit code when at least one diagram has errors or failed to export
+pub const EXIT_DIAGRAM_ERRORS: i32 = 1;
+/// Exit code for bad arguments or unreadable files (clap uses it for usage errors too)
+pub const EXIT_USAGE: i32 = 2;
+
+const SUBCOMMANDS: &[&str] = &[
+    "check",
+    "export",
+    "stats",
+    "fmt",
+    "lsp",
+    "help",
+    "--help",
+    "-h",
+    "--version",
+    "-V",
+];
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "parch",
+    version,
+    about = "Validate, format, export and count Mermaid diagrams"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Validate diagrams and report syntax errors
+    Check {
+        /// Files, directories or glob patterns such as `docs/**/*.md`
+        #[arg(required = true)]
+        paths: Vec<String>,
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+        /// Diagnostic codes to leave out of the report, e.g. `--ignore MMD0003,MMD0020`
+        #[arg(long, value_name = "CODE", value_delimiter = ',', value_parser = parse_code)]
+        ignore: Vec<DiagnosticCode>,
+        /// Lint config to use instead of the nearest `.parch.json` above each file
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+    },
+    /// Render every diagram to an image file
+    Export {
+        #[arg(required = true)]
+        paths: Vec<String>,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Svg)]
+        format: ExportFormat,
+        /// Folder for the exported files, created if missing
+        #[arg(long, default_value = ".")]
+        out_dir: PathBuf,
+        #[arg(long, value_enum, default_value_t = Theme::Light)]
+        theme: Theme,
+        /// Pixel multiplier for PNG and JPEG
+        #[arg(long, default_value_t = 1.0)]
+        scale: f64,
+        /// Canvas colour, or `transparent`
+        #[arg(long)]
+        background: Option<String>,
+    },
+    /// Count diagrams and errors per file
+    Stats {
+        #[arg(required = true)]
+        paths: Vec<String>,
+        #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+        format: StatsFormat,
+    },
+    /// Format diagrams in place, leaving the text around them untouched
+    Fmt {
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Report files that would change instead of writing them
+        #[arg(long)]
+        check: bool,
+        /// Spaces per block level
+        #[arg(long, default_value_t = 4)]
+        indent: usize,
+        #[arg(long, value_enum, default_value_t = QuoteStyle::Minimal)]
+        quotes: QuoteStyle,
+        /// Sort adjacent style statements such as `classDef` and `style`
+        #[arg(long)]
+        sort_declarations: bool,
+    },
+    /// Run a language server for editors over stdin/stdout
+    Lsp {
+        /// Accepted for editors that always pass it; stdio is the only transport
+        #[arg(long)]
+        stdio: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Sarif,
+    /// `::error file=...` workflow commands for GitHub Actions
+    Github,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StatsFormat {
+    Text,
+    Json,
+}
+
+/// One problem found in a diagram, with a document-relative position
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub path: String,
+    pub diagram_id: String,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub code: DiagnosticCode,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CheckSummary {
+    pub files: usize,
+    pub diagrams: usize,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct FileStats {
+    path: String,
+    diagrams: usize,
+    errors: usize,
+    diagram_types: BTreeMap<DiagramType, usize>,
+}
+
+/// Run a CLI subcommand when one is given; `None` means the GUI should start instead
+pub fn run_cli() -> Option<i32> {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let first = args.get(1)?.to_str()?;
+    if !SUBCOMMANDS.contains(&first) {
+        return None;
+    }
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(error) => {
+            let _ = error.print();
+            return Some(if error.use_stderr() {
+                EXIT_USAGE
+            } else {
+                EXIT_OK
+            });
+        }
+    };
+    // The language server writes stdout from its own thread, so it must not hold the lock
+    if let Command::Lsp { .. } = cli.command {
+        return Some(serve_lsp());
+    }
+    Some(run(cli, &mut io::stdout().lock()))
+}
+
+fn run(cli: Cli, out: &mut dyn Write) -> i32 {
+    let parser = MermaidParser::default();
+    match cli.command {
+        Command::Check {
+            paths,
+            format,
+            ignore,
+            config,
+        } => check(&parser, &paths, format, &ignore, config.as_deref(), out),
+        Command::Export {
+            paths,
+            format,
+            out_dir,
+            theme,
+            scale,
+            background,
+        } => {
+            let options = ExportOptions {
+                format,
+                scale,
+                background,
+                theme,
+                ..ExportOptions::default()
+            };
+            export(&parser, &paths, &out_dir, &options, out)
+        }
+        Command::Stats { paths, format } => stats(&parser, &paths, format, out),
+        Command::Fmt {
+            paths,
+            check,
+            indent,
+            quotes,
+            sort_declarations,
+        } => {
+            let options = FormatOptions {
+                indent,
+                quotes,
+                sort_declarations,
+            };
+            fmt(&parser, &paths, &options, check, out)
+        }
+        Command::Lsp { .. } => serve_lsp(),
+    }
+}
+
+fn serve_lsp() -> i32 {
+    match lsp::serve() {
+        Ok(()) => EXIT_OK,
+        Err(error) => {
+            eprintln!("parch: language server failed: {}", error);
+            EXIT_USAGE
+        }
+    }
+}
+
+/// Expand globs and walk directories into a sorted list of supported files
+fn collect_files(patterns: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        if path.is_dir() {
+            walk(path, &mut files).map_err(|e| format!("{}: {}", pattern, e))?;
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        } else if pattern.contains(['*', '?', '[']) {
+            let entries = glob::glob(pattern).map_err(|e| format!("{}: {}", pattern, e))?;
+            let before = files.len();
+            files.extend(
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|path| path.is_file() && FileManager::is_supported_file(path)),
+            );
+            if files.len() == before {
+                return Err(format!("{}: no matching files", pattern));
+            }
+        } else {
+            return Err(format!("{}: no such file or directory", pattern));
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if FileManager::is_supported_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Read every file, reporting the first unreadable one as a usage error
+fn read_files(patterns: &[String]) -> Result<Vec<(PathBuf, String)>, String> {
+    collect_files(patterns)?
+        .into_iter()
+        .map(|path| match fs::read_to_string(&path) {
+            Ok(content) => Ok((path, content)),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        })
+        .collect()
+}
+
+fn display_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Validate and lint every diagram in `content`, keeping all errors rather than the first one
+pub fn check_content(
+    parser: &MermaidParser,
+    lint: &LintConfig,
+    path: &Path,
+    content: &str,
+) -> (usize, Vec<Finding>) {
+    let format = FileType::from_path(path).content_format();
+    let result = parser.parse_content_as(content, format);
+    let findings = result
+        .diagrams
+        .iter()
+        .flat_map(|diagram| {
+            let validation =
+                parser.validate_diagram_with(&diagram.content, diagram.start_line, lint);
+            validation
+                .errors
+                .into_iter()
+                .map(|error: SyntaxError| Finding {
+                    path: display_path(path),
+                    diagram_id: diagram.id.clone(),
+                    line: error.line,
+                    column: error.column,
+                    severity: error.severity,
+                    code: error.code,
+                    message: error.message,
+                })
+        })
+        .collect();
+    (result.diagrams.len(), findings)
+}
+
+fn check(
+    parser: &MermaidParser,
+    patterns: &[String],
+    format: ReportFormat,
+    ignore: &[DiagnosticCode],
+    config: Option<&Path>,
+    out: &mut dyn Write,
+) -> i32 {
+    let files = match read_files(patterns) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("parch: {}", error);
+            return EXIT_USAGE;
+        }
+    };
+    let explicit = match config.map(LintConfig::load).transpose() {
+        Ok(explicit) => explicit,
+        Err(error) => {
+            eprintln!("parch: {}", error);
+            return EXIT_USAGE;
+        }
+    };
+    // Files in the same folder share the config found above it
+    let mut discovered: HashMap<PathBuf, LintConfig> = HashMap::new();
+
+    let mut summary = CheckSummary {
+        files: files.len(),
+        ..CheckSummary::default()
+    };
+    let mut findings = Vec::new();
+    for (path, content) in &files {
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let lint = match (&explicit, discovered.entry(dir)) {
+            (Some(lint), _) => lint,
+            (None, Entry::Occupied(entry)) => entry.into_mut(),
+            (None, Entry::Vacant(entry)) => match LintConfig::for_document(path) {
+                Ok(lint) => entry.insert(lint),
+                Err(error) => {
+                    eprintln!("parch: {}", error);
+                    return EXIT_USAGE;
+                }
+            },
+        };
+        let (diagrams, file_findings) = check_content(parser, lint, path, content);
+        summary.diagrams += diagrams;
+        findings.extend(
+            file_findings
+                .into_iter()
+                .filter(|finding| !ignore.contains(&finding.code)),
+        );
+    }
+    summary.errors = count_severity(&findings, Severity::Error);
+    summary.warnings = count_severity(&findings, Severity::Warning);
+
+    let written = match format {
+        ReportFormat::Text => report::text(&findings, &summary, out),
+        ReportFormat::Json => report::json(&findings, &summary, out),
+        ReportFormat::Sarif => report::sarif(&findings, out),
+        ReportFormat::Github => report::github(&findings, out),
+    };
+    if let Err(error) = written {
+        eprintln!("parch: {}", error);
+        return EXIT_USAGE;
+    }
+
+    if summary.errors > 0 {
+        EXIT_DIAGRAM_ERRORS
+    } else {
+        EXIT_OK
+    }
+}
+
+fn count_severity(findings: &[Finding], severity: Severity) -> usize {
+    findings.iter().filter(|f| f.severity == severity).count()
+}
+
+fn parse_code(code: &str) -> Result<DiagnosticCode, String> {
+    DiagnosticCode::from_code(code).ok_or_else(|| format!("unknown diagnostic code '{}'", code))
+}
+
+fn export(
+    parser: &MermaidParser,
+    patterns: &[String],
+    out_dir: &Path,
+    options: &ExportOptions,
+    out: &mut dyn Write,
+) -> i32 {
+    let files = match read_files(patterns) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("parch: {}", error);
+            return EXIT_USAGE;
+        }
+    };
+    if let Err(error) = fs::create_dir_all(out_dir) {
+        eprintln!("parch: {}: {}", out_dir.display(), error);
+        return EXIT_USAGE;
+    }
+
+    let mut failed = false;
+    for (path, content) in &files {
+        let format = FileType::from_path(path).content_format();
+        let diagrams = parser.parse_content_as(content, format).diagrams;
+        let stem = path.file_stem().and_then(|stem| stem.to_str());
+        let result = exporter::export_all(&diagrams, out_dir, stem, options);
+        for file in &result.files {
+            let _ = writeln!(out, "{}", display_path(Path::new(file)));
+        }
+        for failure in &result.failures {
+            failed = true;
+            eprintln!(
+                "{}: {}: {}",
+                display_path(path),
+                failure.diagram_id,
+                failure.error
+            );
+        }
+    }
+
+    if failed {
+        EXIT_DIAGRAM_ERRORS
+    } else {
+        EXIT_OK
+    }
+}
+
+fn stats(
+    parser: &MermaidParser,
+    patterns: &[String],
+    format: StatsFormat,
+    out: &mut dyn Write,
+) -> i32 {
+    let files = match read_files(patterns) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("parch: {}", error);
+            return EXIT_USAGE;
+        }
+    };
+
+    let stats: Vec<FileStats> = files
+        .iter()
+        .map(|(path, content)| {
+            let format = FileType::from_path(path).content_format();
+            let result = parser.parse_content_as(content, format);
+            let mut diagram_types = BTreeMap::new();
+            for diagram in &result.diagrams {
+                *diagram_types.entry(diagram.diagram_type).or_insert(0) += 1;
+            }
+            FileStats {
+                path: display_path(path),
+                diagrams: result.diagrams.len(),
+                errors: result.total_errors,
+                diagram_types,
+            }
+        })
+        .collect();
+
+    let written = match format {
+        StatsFormat::Json => serde_json::to_writer_pretty(&mut *out, &stats)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(out)),
+        StatsFormat::Text => stats.iter().try_for_each(|file| {
+            let types: Vec<String> = file
+                .diagram_types
+                .iter()
+                .map(|(diagram_type, count)| format!("{} {}", count, diagram_type))
+                .collect();
+            writeln!(
+                out,
+                "{}: {} diagrams, {} errors ({})",
+                file.path,
+                file.diagrams,
+                file.errors,
+                types.join(", ")
+            )
+        }),
+    };
+    if let Err(error) = written {
+        eprintln!("parch: {}", error);
+        return EXIT_USAGE;
+    }
+    EXIT_OK
+}
+
+/// Print the files that changed, or with `check` the files that would change
+fn fmt(
+    parser: &MermaidParser,
+    patterns: &[String],
+    options: &FormatOptions,
+    check: bool,
+    out: &mut dyn Write,
+) -> i32 {
+    let files = match read_files(patterns) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("parch: {}", error);
+            return EXIT_USAGE;
+        }
+    };
+
+    let mut unformatted = false;
+    for (path, content) in &files {
+        let format = FileType::from_path(path).content_format();
+        let result = formatter::format_document(parser, content, format, options);
+        for skipped in &result.skipped {
+            eprintln!(
+                "{}:{}: skipped: {}",
+                display_path(path),
+                skipped.start_line,
+                skipped.reason
+            );
+        }
+        if result.content == *content {
+            continue;
+        }
+        unformatted = true;
+        if !check {
+            if let Err(error) = fs::write(path, &result.content) {
+                eprintln!("parch: {}: {}", path.display(), error);
+                return EXIT_USAGE;
+            }
+        }
+        let _ = writeln!(out, "{}", display_path(path));
+    }
+
+    if check && unformatted {
+        EXIT_DIAGRAM_ERRORS
+    } else {
+        EXIT_OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder per test, since tests run in parallel
+    fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("parch-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, content) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn run_args(args: &[&str]) -> (i32, String) {
+        let cli =
+            Cli::try_parse_from(std::iter::once("parch").chain(args.iter().copied())).unwrap();
+        let mut out = Vec::new();
+        let code = run(cli, &mut out);
+        (code, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_check_exit_codes_and_formats() {
+        let dir = fixture(
+            "check",
+            &[
+                ("docs/good.md", "```mermaid\ngraph TD\n  A --> B\n```\n"),
+                (
+                    "docs/nested/bad.md",
+                    "# Title\n\n```mermaid\ngraph TD\n  A --> B[\n```\n",
+                ),
+                ("docs/raw.mmd", "sequenceDiagram\n  A->>B: hi\n"),
+                ("docs/.hidden/skip.md", "```mermaid\nnot a diagram\n```\n"),
+                ("docs/notes.txt", "ignored"),
+            ],
+        );
+        let docs = dir.join("docs");
+        let docs = docs.to_str().unwrap();
+
+        let (code, _) = run_args(&["check", &format!("{}/good.md", docs)]);
+        assert_eq!(code, EXIT_OK);
+
+        let (code, text) = run_args(&["check", docs]);
+        assert_eq!(code, EXIT_DIAGRAM_ERRORS);
+        assert!(text.contains("bad.md:5:"), "{}", text);
+        assert!(text.contains("3 files, 3 diagrams"), "{}", text);
+
+        let (_, json) = run_args(&["check", docs, "--format", "json"]);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["summary"]["files"], 3);
+        assert_eq!(json["findings"][0]["line"], 5);
+
+        let (_, sarif) = run_args(&["check", docs, "--format", "sarif"]);
+        let sarif: serde_json::Value = serde_json::from_str(&sarif).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["level"], "error");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["region"]["startLine"],
+            5
+        );
+
+        let (_, github) = run_args(&["check", &format!("{}/**/*.md", docs), "--format", "github"]);
+        assert!(github.starts_with("::error file="), "{}", github);
+        assert!(github.contains(",line=5,col="), "{}", github);
+
+        assert_eq!(json["findings"][0]["code"], "MMD0010");
+
+        let (code, text) = run_args(&["check", docs, "--ignore", "mmd0010,MMD0003"]);
+        assert_eq!(code, EXIT_OK, "{}", text);
+        assert!(text.contains("0 errors"), "{}", text);
+        assert!(Cli::try_parse_from(["parch", "check", docs, "--ignore", "E1"]).is_err());
+
+        let (code, _) = run_args(&["check", &format!("{}/missing.md", docs)]);
+        assert_eq!(code, EXIT_USAGE);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_uses_lint_config() {
+        let dir = fixture(
+            "lint-config",
+            &[
+                (
+                    "docs/.parch.json",
+                    r#"{ "rules": { "orphan-node": "error" } }"#,
+                ),
+                ("docs/a.mmd", "graph TD\n  A --> B\n  C\n"),
+                ("quiet.json", r#"{ "rules": { "orphan-node": "off" } }"#),
+            ],
+        );
+        let file = dir.join("docs/a.mmd");
+        let file = file.to_str().unwrap();
+
+        let (code, text) = run_args(&["check", file]);
+        assert_eq!(code, EXIT_DIAGRAM_ERRORS);
+        assert!(text.contains("a.mmd:3:3: error[MMD0101]"), "{}", text);
+
+        let quiet = dir.join("quiet.json");
+        let (code, _) = run_args(&["check", file, "--config", quiet.to_str().unwrap()]);
+        assert_eq!(code, EXIT_OK);
+
+        fs::write(dir.join("docs/.parch.json"), "{ \"rules\": 1 }").unwrap();
+        let (code, _) = run_args(&["check", file]);
+        assert_eq!(code, EXIT_USAGE);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_and_stats() {
+        let dir = fixture("export", &[(
+            "doc.md",
+            "```mermaid\ngraph TD\n  A --> B\n```\n\n```mermaid\nsequenceDiagram\n  A->>B: hi\n```\n",
+        )]);
+        let input = dir.join("doc.md");
+        let out_dir = dir.join("out");
+
+        let (code, written) = run_args(&[
+            "export",
+            input.to_str().unwrap(),
+            "--format",
+            "svg",
+            "--out-dir",
+            out_dir.to_str().unwrap(),
+        ]);
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(written.lines().count(), 2);
+        assert!(written.lines().all(|line| Path::new(line).exists()));
+
+        let (code, text) = run_args(&["stats", input.to_str().unwrap()]);
+        assert_eq!(code, EXIT_OK);
+        assert!(
+            text.contains("2 diagrams, 0 errors (1 flowchart, 1 sequence)"),
+            "{}",
+            text
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fmt_check_and_write() {
+        let dir = fixture(
+            "fmt",
+            &[
+                (
+                    "doc.md",
+                    "Intro  text
+
+```mermaid
+graph TD
+A-->B
+```
+",
+                ),
+                (
+                    "tidy.mmd",
+                    "graph TD
+    A --> B
+",
+                ),
+            ],
+        );
+        let doc = dir.join("doc.md");
+        let dir_arg = dir.to_str().unwrap();
+
+        let (code, listed) = run_args(&["fmt", "--check", dir_arg]);
+        assert_eq!(code, EXIT_DIAGRAM_ERRORS);
+        assert_eq!(listed.trim(), display_path(&doc));
+
+        let (code, _) = run_args(&["fmt", dir_arg]);
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(
+            fs::read_to_string(&doc).unwrap(),
+            "Intro  text\n\n```mermaid\ngraph TD\n    A --> B\n```\n"
+        );
+
+        let (code, listed) = run_args(&["fmt", "--check", dir_arg]);
+        assert_eq!(code, EXIT_OK);
+        assert!(listed.is_empty(), "{}", listed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
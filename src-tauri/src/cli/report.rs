@@ -0,0 +1,155 @@
+use super::{CheckSummary, Finding};
+use crate::mermaid_parser::Severity;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// `path:line:column: severity[code]: message`, one finding per line, then a summary
+pub fn text(findings: &[Finding], summary: &CheckSummary, out: &mut dyn Write) -> io::Result<()> {
+    for finding in findings {
+        writeln!(
+            out,
+            "{}:{}:{}: {}[{}]: {} [{}]",
+            finding.path,
+            finding.line,
+            finding.column,
+            finding.severity,
+            finding.code,
+            finding.message,
+            finding.diagram_id
+        )?;
+    }
+    writeln!(
+        out,
+        "{} files, {} diagrams: {} errors, {} warnings",
+        summary.files, summary.diagrams, summary.errors, summary.warnings
+    )
+}
+
+pub fn json(findings: &[Finding], summary: &CheckSummary, out: &mut dyn Write) -> io::Result<()> {
+    let report = json!({ "findings": findings, "summary": summary });
+    serde_json::to_writer_pretty(&mut *out, &report)?;
+    writeln!(out)
+}
+
+/// SARIF 2.1.0, as accepted by GitHub code scanning
+pub fn sarif(findings: &[Finding], out: &mut dyn Write) -> io::Result<()> {
+    let results: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "ruleId": finding.code,
+                "level": sarif_level(finding.severity),
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.path },
+                        "region": {
+                            "startLine": finding.line,
+                            "startColumn": finding.column,
+                        },
+                    },
+                }],
+                "properties": { "diagramId": finding.diagram_id },
+            })
+        })
+        .collect();
+    // Only the rules that produced results, in code order
+    let rules: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|finding| finding.code)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|code| {
+            json!({
+                "id": code,
+                "shortDescription": { "text": code.description() },
+                "defaultConfiguration": { "level": sarif_level(code.default_severity()) },
+            })
+        })
+        .collect();
+    let report = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "parch",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    });
+    serde_json::to_writer_pretty(&mut *out, &report)?;
+    writeln!(out)
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    }
+}
+
+/// GitHub Actions workflow commands, which show up as annotations on the diff
+pub fn github(findings: &[Finding], out: &mut dyn Write) -> io::Result<()> {
+    for finding in findings {
+        let command = match finding.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "notice",
+        };
+        writeln!(
+            out,
+            "::{} file={},line={},col={},title={}::{}",
+            command,
+            escape_property(&finding.path),
+            finding.line,
+            finding.column,
+            escape_property(&format!("{} ({})", finding.code, finding.diagram_id)),
+            escape_data(&finding.message)
+        )?;
+    }
+    Ok(())
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mermaid_parser::DiagnosticCode;
+
+    #[test]
+    fn test_github_escaping() {
+        let finding = Finding {
+            path: "docs/a,b.md".to_string(),
+            diagram_id: "flow:1".to_string(),
+            line: 3,
+            column: 7,
+            severity: Severity::Warning,
+            code: DiagnosticCode::InvalidNodeId,
+            message: "100% wrong\nreally".to_string(),
+        };
+        let mut out = Vec::new();
+        github(&[finding], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "::warning file=docs/a%2Cb.md,line=3,col=7,title=MMD0003 (flow%3A1)::100%25 wrong%0Areally\n"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::exporter::ExportError;
+use crate::file_io::SaveConflict;
+use crate::mermaid_parser::formatter::FormatError;
+use crate::mermaid_parser::lint::ConfigError;
+use crate::renderer::RenderError;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Stable identifier the frontend can branch on instead of matching message text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    FileRead,
+    FileWrite,
+    FileMetadata,
+    SaveConflict,
+    InvalidPath,
+    NoFilePath,
+    DialogCancelled,
+    StateStore,
+    LintConfig,
+    SessionNotFound,
+    DiagramNotFound,
+    Parse,
+    Format,
+    Render,
+    Export,
+    Watch,
+    Window,
+}
+
+/// Errors returned by Tauri commands.
+///
+/// Serialized as `{ "code": "FILE_READ", "message": "..." }` so the frontend gets both a
+/// stable code and a readable message. Save conflicts add a `conflict` field with both
+/// versions of the file.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("Failed to read file {}: {source}", path.display())]
+    FileRead { path: PathBuf, source: io::Error },
+    #[error("Failed to save file {}: {source}", path.display())]
+    FileWrite { path: PathBuf, source: io::Error },
+    #[error("Failed to get file metadata for {}: {source}", path.display())]
+    FileMetadata { path: PathBuf, source: io::Error },
+    #[error("{} was changed on disk since it was opened", .0.path)]
+    SaveConflict(Box<SaveConflict>),
+    #[error("Invalid file path")]
+    InvalidPath,
+    #[error("No file path specified. Use save_file_as instead.")]
+    NoFilePath,
+    #[error("Dialog cancelled")]
+    DialogCancelled,
+    #[error("Failed to update application state: {0}")]
+    StateStore(#[from] anyhow::Error),
+    #[error(transparent)]
+    LintConfig(#[from] ConfigError),
+    #[error("No parse session for document {0}")]
+    SessionNotFound(String),
+    #[error("Diagram not found")]
+    DiagramNotFound,
+    #[error(transparent)]
+    Format(#[from] FormatError),
+    #[error(transparent)]
+    Render(#[from] RenderError),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    #[error("File watcher failed: {0}")]
+    Watch(#[from] notify::Error),
+    #[error("Window operation failed: {0}")]
+    Window(#[from] tauri::Error),
+}
+
+impl AppError {
+    pub fn read(path: impl AsRef<Path>, source: io::Error) -> Self {
+        AppError::FileRead {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn write(path: impl AsRef<Path>, source: io::Error) -> Self {
+        AppError::FileWrite {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn metadata(path: impl AsRef<Path>, source: io::Error) -> Self {
+        AppError::FileMetadata {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::FileRead { .. } => ErrorCode::FileRead,
+            AppError::FileWrite { .. } => ErrorCode::FileWrite,
+            AppError::FileMetadata { .. } => ErrorCode::FileMetadata,
+            AppError::SaveConflict(_) => ErrorCode::SaveConflict,
+            AppError::InvalidPath => ErrorCode::InvalidPath,
+            AppError::NoFilePath => ErrorCode::NoFilePath,
+            AppError::DialogCancelled => ErrorCode::DialogCancelled,
+            AppError::StateStore(_) => ErrorCode::StateStore,
+            AppError::LintConfig(_) => ErrorCode::LintConfig,
+            AppError::SessionNotFound(_) => ErrorCode::SessionNotFound,
+            AppError::DiagramNotFound => ErrorCode::DiagramNotFound,
+            // A diagram that fails to parse is a parse error wherever it surfaces
+            AppError::Render(RenderError::Syntax(_))
+            | AppError::Export(ExportError::Render(RenderError::Syntax(_)))
+            | AppError::Format(FormatError::Syntax(_)) => ErrorCode::Parse,
+            AppError::Format(_) => ErrorCode::Format,
+            AppError::Render(_) => ErrorCode::Render,
+            AppError::Export(_) => ErrorCode::Export,
+            AppError::Watch(_) => ErrorCode::Watch,
+            AppError::Window(_) => ErrorCode::Window,
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let conflict = match self {
+            AppError::SaveConflict(conflict) => Some(conflict),
+            _ => None,
+        };
+        let mut state = serializer.serialize_struct("AppError", 2 + conflict.is_some() as usize)?;
+        state.serialize_field("code", &self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(conflict) = conflict {
+            state.serialize_field("conflict", conflict)?;
+        }
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_code_and_message() {
+        let error = AppError::read(
+            "notes.md",
+            io::Error::new(io::ErrorKind::NotFound, "not found"),
+        );
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "FILE_READ",
+                "message": "Failed to read file notes.md: not found",
+            })
+        );
+
+        let error = AppError::from(ExportError::Render(RenderError::Syntax(Vec::new())));
+        assert_eq!(error.code(), ErrorCode::Parse);
+
+        let error = AppError::SaveConflict(Box::new(SaveConflict {
+            path: "notes.md".to_string(),
+            ours: "graph TD".to_string(),
+            theirs: "graph LR".to_string(),
+            disk_hash: "abc".to_string(),
+            disk_size: 8,
+        }));
+        let value = serde_json::to_value(&error).unwrap();
+        assert_eq!(value["code"], "SAVE_CONFLICT");
+        assert_eq!(
+            value["message"],
+            "notes.md was changed on disk since it was opened"
+        );
+        assert_eq!(value["conflict"]["theirs"], "graph LR");
+    }
+}
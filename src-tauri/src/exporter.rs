@@ -0,0 +1,385 @@
+use crate::mermaid_parser::ParsedDiagram;
+use crate::renderer::{self, RenderError, RenderOptions, Theme};
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use resvg::usvg::{self, fontdb, PostProcessingSteps, TreeParsing, TreePostProc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+use thiserror::Error;
+
+/// Families tried, in order, when Arial (the default `sans-serif`) is not installed
+const SANS_SERIF_FALLBACKS: &[&str] = &["Helvetica", "DejaVu Sans", "Liberation Sans", "Noto Sans"];
+
+/// Raster exports refuse to allocate more pixels than this
+const MAX_PIXELS: f64 = 100_000_000.0;
+
+static FONT_DATABASE: LazyLock<fontdb::Database> = LazyLock::new(|| {
+    let mut database = fontdb::Database::new();
+    database.load_system_fonts();
+    let has_family = |name: &str| {
+        database
+            .faces()
+            .any(|face| face.families.iter().any(|(family, _)| family == name))
+    };
+    if !has_family("Arial") {
+        if let Some(fallback) = SANS_SERIF_FALLBACKS.iter().find(|name| has_family(name)) {
+            database.set_sans_serif_family(*fallback);
+        }
+    }
+    database
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Svg,
+    Png,
+    Jpeg,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    pub fn filter_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "SVG Image",
+            ExportFormat::Png => "PNG Image",
+            ExportFormat::Jpeg => "JPEG Image",
+            ExportFormat::Pdf => "PDF Document",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Pixel multiplier for PNG and JPEG
+    pub scale: f64,
+    /// Resolution used to turn SVG pixels into PDF points
+    pub dpi: f64,
+    /// Canvas colour; defaults to the theme background, `transparent` for none
+    pub background: Option<String>,
+    pub theme: Theme,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            format: ExportFormat::Png,
+            scale: 1.0,
+            dpi: 96.0,
+            background: None,
+            theme: Theme::Light,
+        }
+    }
+}
+
+/// A diagram picked by its ID or by its position in the document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DiagramRef {
+    Index(usize),
+    Id(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportFailure {
+    pub diagram_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchExportResult {
+    pub success: bool,
+    pub folder: Option<String>,
+    pub files: Vec<String>,
+    pub failures: Vec<ExportFailure>,
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error(transparent)]
+    Render(#[from] RenderError),
+    /// The SVG could not be read back for conversion
+    #[error("Invalid SVG: {0}")]
+    Svg(String),
+    /// The output would be empty or unreasonably large
+    #[error("Cannot export image: {0}")]
+    Size(String),
+    #[error("Failed to encode image: {0}")]
+    Encode(String),
+    #[error("Failed to write export: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Find a diagram by ID or index
+pub fn select<'a>(
+    diagrams: &'a [ParsedDiagram],
+    reference: &DiagramRef,
+) -> Option<(usize, &'a ParsedDiagram)> {
+    match reference {
+        DiagramRef::Index(index) => diagrams.get(*index).map(|diagram| (*index, diagram)),
+        DiagramRef::Id(id) => diagrams
+            .iter()
+            .enumerate()
+            .find(|(_, diagram)| &diagram.id == id),
+    }
+}
+
+/// Render a diagram and encode it in the requested format
+pub fn export_diagram(
+    diagram: &ParsedDiagram,
+    options: &ExportOptions,
+) -> Result<Vec<u8>, ExportError> {
+    let render_options = RenderOptions {
+        theme: options.theme,
+        background: options.background.clone(),
+        ..RenderOptions::default()
+    };
+    let svg = renderer::render_svg(&diagram.content, diagram.diagram_type, &render_options)?;
+    convert_svg(&svg, options)
+}
+
+/// Encode an SVG document as SVG, PNG, JPEG or PDF bytes
+pub fn convert_svg(svg: &str, options: &ExportOptions) -> Result<Vec<u8>, ExportError> {
+    if options.format == ExportFormat::Svg {
+        return Ok(svg.as_bytes().to_vec());
+    }
+
+    let mut tree = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .map_err(|e| ExportError::Svg(e.to_string()))?;
+    tree.postprocess(
+        PostProcessingSteps {
+            convert_text_into_paths: true,
+        },
+        &FONT_DATABASE,
+    );
+
+    match options.format {
+        ExportFormat::Pdf => Ok(svg2pdf::convert_tree(
+            &tree,
+            svg2pdf::Options {
+                dpi: options.dpi as f32,
+                ..svg2pdf::Options::default()
+            },
+        )),
+        ExportFormat::Png => rasterize(&tree, options.scale, None)?
+            .encode_png()
+            .map_err(|e| ExportError::Encode(e.to_string())),
+        ExportFormat::Jpeg => {
+            // JPEG has no alpha channel, so transparent areas become white
+            let pixmap = rasterize(&tree, options.scale, Some(Color::WHITE))?;
+            let mut rgb =
+                Vec::with_capacity(pixmap.width() as usize * pixmap.height() as usize * 3);
+            for pixel in pixmap.pixels() {
+                let pixel = pixel.demultiply();
+                rgb.extend_from_slice(&[pixel.red(), pixel.green(), pixel.blue()]);
+            }
+            let mut bytes = Vec::new();
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 92)
+                .encode(
+                    &rgb,
+                    pixmap.width(),
+                    pixmap.height(),
+                    image::ColorType::Rgb8,
+                )
+                .map_err(|e| ExportError::Encode(e.to_string()))?;
+            Ok(bytes)
+        }
+        ExportFormat::Svg => unreachable!(),
+    }
+}
+
+fn rasterize(tree: &usvg::Tree, scale: f64, fill: Option<Color>) -> Result<Pixmap, ExportError> {
+    if !(scale.is_finite() && scale > 0.0) {
+        return Err(ExportError::Size(format!("invalid scale {}", scale)));
+    }
+    let width = (tree.size.width() as f64 * scale).ceil();
+    let height = (tree.size.height() as f64 * scale).ceil();
+    if width * height > MAX_PIXELS {
+        return Err(ExportError::Size(format!(
+            "{}x{} pixels is too large; lower the scale",
+            width, height
+        )));
+    }
+    let mut pixmap = Pixmap::new(width as u32, height as u32)
+        .ok_or_else(|| ExportError::Size("the diagram has no area".to_string()))?;
+    if let Some(color) = fill {
+        pixmap.fill(color);
+    }
+    resvg::render(
+        tree,
+        Transform::from_scale(scale as f32, scale as f32),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
+
+/// `<stem>-<NN>-<id>.<ext>`, so repeated exports of a document overwrite the same files
+pub fn export_file_name(
+    stem: Option<&str>,
+    index: usize,
+    diagram: &ParsedDiagram,
+    format: ExportFormat,
+) -> String {
+    let id: String = diagram
+        .id
+        .chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name = format!(
+        "{:02}-{}.{}",
+        index + 1,
+        id.trim_matches('-'),
+        format.extension()
+    );
+    match stem.map(str::trim).filter(|stem| !stem.is_empty()) {
+        Some(stem) => format!("{}-{}", stem, name),
+        None => name,
+    }
+}
+
+/// Export every diagram into `folder`; failures are collected rather than stopping the batch
+pub fn export_all(
+    diagrams: &[ParsedDiagram],
+    folder: &Path,
+    stem: Option<&str>,
+    options: &ExportOptions,
+) -> BatchExportResult {
+    let mut files = Vec::new();
+    let mut failures = Vec::new();
+    for (index, diagram) in diagrams.iter().enumerate() {
+        let path = folder.join(export_file_name(stem, index, diagram, options.format));
+        let written = export_diagram(diagram, options)
+            .and_then(|bytes| fs::write(&path, bytes).map_err(ExportError::from));
+        match written {
+            Ok(()) => files.push(path.to_string_lossy().to_string()),
+            Err(error) => failures.push(ExportFailure {
+                diagram_id: diagram.id.clone(),
+                error: error.to_string(),
+            }),
+        }
+    }
+    BatchExportResult {
+        success: failures.is_empty(),
+        folder: Some(folder.to_string_lossy().to_string()),
+        files,
+        failures,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mermaid_parser::{identity, ContentFormat, DiagramType, MermaidParser};
+
+    fn diagrams(content: &str) -> Vec<ParsedDiagram> {
+        MermaidParser::new()
+            .unwrap()
+            .parse_content_as(content, ContentFormat::Markdown)
+            .diagrams
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let diagrams = diagrams("```mermaid\ngraph TD\n  A[Start] --> B[End]\n```\n");
+        let options = |format| ExportOptions {
+            format,
+            ..ExportOptions::default()
+        };
+
+        let svg = export_diagram(&diagrams[0], &options(ExportFormat::Svg)).unwrap();
+        assert!(svg.starts_with(b"<svg"));
+        let png = export_diagram(&diagrams[0], &options(ExportFormat::Png)).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let jpeg = export_diagram(&diagrams[0], &options(ExportFormat::Jpeg)).unwrap();
+        assert!(jpeg.starts_with(&[0xFF, 0xD8]));
+        let pdf = export_diagram(&diagrams[0], &options(ExportFormat::Pdf)).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+
+        // Doubling the scale doubles both PNG dimensions
+        let width = |png: &[u8]| u32::from_be_bytes(png[16..20].try_into().unwrap());
+        let large = export_diagram(
+            &diagrams[0],
+            &ExportOptions {
+                scale: 2.0,
+                ..options(ExportFormat::Png)
+            },
+        )
+        .unwrap();
+        assert_eq!(width(&large), width(&png) * 2);
+
+        let transparent = export_diagram(
+            &diagrams[0],
+            &ExportOptions {
+                background: Some("transparent".to_string()),
+                ..options(ExportFormat::Svg)
+            },
+        )
+        .unwrap();
+        assert!(!String::from_utf8(transparent)
+            .unwrap()
+            .contains("fill=\"#ffffff\"/>"));
+    }
+
+    #[test]
+    fn test_select_and_file_names() {
+        let diagrams = diagrams(
+            "```mermaid\n%% id: login.flow\ngraph TD\n  A --> B\n```\n\n```mermaid\npie\n  \"a\" : 1\n```\n",
+        );
+        let (index, diagram) = select(&diagrams, &DiagramRef::Id(diagrams[1].id.clone())).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(diagram.diagram_type, DiagramType::Pie);
+        assert!(select(&diagrams, &DiagramRef::Index(2)).is_none());
+
+        let name = export_file_name(Some("notes"), 0, &diagrams[0], ExportFormat::Png);
+        assert_eq!(name, "notes-01-login-flow.png");
+        assert!(export_file_name(None, 1, &diagrams[1], ExportFormat::Pdf).starts_with("02-pie-"));
+    }
+
+    #[test]
+    fn test_select_by_id_after_edit() {
+        let opened = diagrams("```mermaid\ngraph TD\n  A --> B\n```\n");
+        let mut edited = diagrams("```mermaid\ngraph TD\n  A --> B\n  B --> C\n```\n");
+        let reference = DiagramRef::Id(opened[0].id.clone());
+        assert!(select(&edited, &reference).is_none());
+
+        identity::reconcile(&opened, &mut edited);
+        let (index, diagram) = select(&edited, &reference).unwrap();
+        assert_eq!(index, 0);
+        assert!(diagram.content.contains("B --> C"));
+    }
+
+    #[test]
+    fn test_export_all_collects_failures() {
+        let diagrams =
+            diagrams("```mermaid\ngraph TD\n  A --> B\n```\n\n```mermaid\npie\n  \"a\" : 1\n```\n");
+        let folder = std::env::temp_dir().join(format!("parch-export-{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+
+        let result = export_all(&diagrams, &folder, Some("doc"), &ExportOptions::default());
+        assert!(!result.success);
+        assert_eq!(result.files.len(), 1);
+        assert!(Path::new(&result.files[0]).exists());
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].diagram_id, diagrams[1].id);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
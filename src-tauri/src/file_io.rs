@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+const UTF8_BOM: &str = "\u{feff}";
+
+/// How a save treats the file it replaces
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SaveOptions {
+    /// Rotating `.bak` copies of the previous version to keep; 0 keeps none
+    #[serde(default)]
+    pub backups: usize,
+}
+
+/// Hex SHA-256 of a file's bytes, used to tell real changes apart from touches
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// The disk version no longer matches the one a save was based on.
+///
+/// Carries both versions so the editor can overwrite, reload or merge. Saving again with
+/// `disk_hash` as the expected hash overwrites the disk version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveConflict {
+    pub path: String,
+    /// The content the editor tried to save
+    pub ours: String,
+    /// The content on disk now
+    pub theirs: String,
+    pub disk_hash: String,
+    pub disk_size: u64,
+}
+
+/// Compare the file at `path` with the hash and size it had when it was loaded.
+///
+/// A file that no longer exists is not a conflict, since saving loses nothing.
+pub fn detect_conflict(
+    path: &Path,
+    expected_hash: &str,
+    expected_size: Option<u64>,
+    ours: &str,
+) -> io::Result<Option<SaveConflict>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let disk_size = bytes.len() as u64;
+    let disk_hash = content_hash(&bytes);
+    if expected_size.is_none_or(|size| size == disk_size) && disk_hash == expected_hash {
+        return Ok(None);
+    }
+    Ok(Some(SaveConflict {
+        path: path.to_string_lossy().into_owned(),
+        ours: ours.to_string(),
+        theirs: String::from_utf8_lossy(&bytes).into_owned(),
+        disk_hash,
+        disk_size,
+    }))
+}
+
+/// The text to write for `content` so it keeps the line endings and byte-order mark of
+/// the file already at `path`
+pub fn text_for_existing(path: &Path, content: &str) -> String {
+    match fs::read(path) {
+        Ok(original) => match_conventions(&String::from_utf8_lossy(&original), content),
+        Err(_) => content.to_string(),
+    }
+}
+
+/// Write `bytes` to a temporary file next to `path`, flush it to disk and rename it over
+/// `path`, so a crash or a full disk leaves either the old file or the new one.
+///
+/// The replaced file's permissions carry over to the new one.
+pub fn atomic_write(path: &Path, bytes: &[u8], options: &SaveOptions) -> io::Result<()> {
+    let original = fs::metadata(path)
+        .ok()
+        .filter(|metadata| metadata.is_file());
+    let temp = temp_path(path);
+    let result = write_temp(&temp, bytes, original.as_ref()).and_then(|()| {
+        if original.is_some() && options.backups > 0 {
+            rotate_backups(path, options.backups)?;
+        }
+        fs::rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+    sync_parent(path);
+    Ok(())
+}
+
+fn write_temp(temp: &Path, bytes: &[u8], original: Option<&fs::Metadata>) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(temp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    if let Some(original) = original {
+        fs::set_permissions(temp, original.permissions())?;
+    }
+    Ok(())
+}
+
+/// A hidden, unsupported name in the same folder, so the rename stays on one filesystem
+/// and workspaces never list it
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, Uuid::new_v4().simple()))
+}
+
+/// `notes.md.bak` is the newest backup, then `notes.md.2.bak` and so on up to `count`
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if index <= 1 {
+        path.with_file_name(format!("{}.bak", name))
+    } else {
+        path.with_file_name(format!("{}.{}.bak", name, index))
+    }
+}
+
+fn rotate_backups(path: &Path, count: usize) -> io::Result<()> {
+    let _ = fs::remove_file(backup_path(path, count));
+    for index in (1..count).rev() {
+        let from = backup_path(path, index);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, index + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Make the rename itself durable; not every platform can open a folder for syncing
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Give `content` the CRLF line endings and byte-order mark of `original` when it had them
+pub fn match_conventions(original: &str, content: &str) -> String {
+    let mut result = String::with_capacity(content.len() + UTF8_BOM.len());
+    if original.starts_with(UTF8_BOM) && !content.starts_with(UTF8_BOM) {
+        result.push_str(UTF8_BOM);
+    }
+    if uses_crlf(original) {
+        for line in content.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(text) => {
+                    result.push_str(text.strip_suffix('\r').unwrap_or(text));
+                    result.push_str("\r\n");
+                }
+                None => result.push_str(line),
+            }
+        }
+    } else {
+        result.push_str(content);
+    }
+    result
+}
+
+/// Whether the first line break is CRLF, which is how editors usually pick a file's style
+fn uses_crlf(text: &str) -> bool {
+    text.find('\n')
+        .is_some_and(|index| text[..index].ends_with('\r'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(b"graph TD\n"),
+            content_hash("graph TD\n".as_bytes())
+        );
+        assert_ne!(content_hash(b"graph TD\n"), content_hash(b"graph LR\n"));
+        assert_eq!(
+            content_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_match_conventions() {
+        assert_eq!(
+            match_conventions("\u{feff}# A\r\nB\r\n", "# A\nB\r\nC"),
+            "\u{feff}# A\r\nB\r\nC"
+        );
+        assert_eq!(match_conventions("# A\nB\r\n", "# A\r\nB\n"), "# A\r\nB\n");
+        assert_eq!(match_conventions("\u{feff}x", "\u{feff}y\n"), "\u{feff}y\n");
+    }
+
+    #[test]
+    fn test_detect_conflict() {
+        let path = std::env::temp_dir().join(format!("parch-conflict-{}.md", std::process::id()));
+        fs::write(&path, "graph TD\n").unwrap();
+        let hash = content_hash(b"graph TD\n");
+
+        assert!(detect_conflict(&path, &hash, Some(9), "ours")
+            .unwrap()
+            .is_none());
+
+        fs::write(&path, "graph LR\n").unwrap();
+        let conflict = detect_conflict(&path, &hash, Some(9), "ours")
+            .unwrap()
+            .unwrap();
+        assert_eq!(conflict.theirs, "graph LR\n");
+        assert_eq!(conflict.ours, "ours");
+        assert_eq!(conflict.disk_hash, content_hash(b"graph LR\n"));
+        // Saving again against the reported disk hash goes through
+        assert!(detect_conflict(&path, &conflict.disk_hash, None, "ours")
+            .unwrap()
+            .is_none());
+
+        fs::remove_file(&path).unwrap();
+        assert!(detect_conflict(&path, &hash, Some(9), "ours")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_atomic_save_keeps_backups_and_permissions() {
+        let folder = std::env::temp_dir().join(format!("parch-save-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("notes.md");
+        let options = SaveOptions { backups: 2 };
+
+        fs::write(&path, "one\r\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        let text = text_for_existing(&path, "two\n");
+        atomic_write(&path, text.as_bytes(), &options).unwrap();
+        let text = text_for_existing(&path, "three\n");
+        atomic_write(&path, text.as_bytes(), &options).unwrap();
+        let text = text_for_existing(&path, "four\n");
+        atomic_write(&path, text.as_bytes(), &options).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "four\r\n");
+        assert_eq!(
+            fs::read_to_string(backup_path(&path, 1)).unwrap(),
+            "three\r\n"
+        );
+        assert_eq!(
+            fs::read_to_string(backup_path(&path, 2)).unwrap(),
+            "two\r\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+        // Only the document and its two backups are left; no temporary files
+        assert_eq!(fs::read_dir(&folder).unwrap().count(), 3);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,11 +1,17 @@
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io;
 use std::time::SystemTime;
 use tauri::Window;
 use tauri_plugin_dialog::DialogExt;
 use uuid::Uuid;
 
+use crate::error::{AppError, ErrorCode};
+use crate::file_io::{self, SaveOptions};
+use crate::file_watcher;
+use crate::mermaid_parser::ContentFormat;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileContent {
     pub id: String,
@@ -18,6 +24,12 @@ pub struct FileContent {
     pub is_saved: bool,
     #[serde(rename = "fileType")]
     pub file_type: FileType,
+    /// Hash and size of the file on disk when it was loaded or last saved; a save is
+    /// refused when the disk version no longer matches
+    #[serde(rename = "contentHash", default)]
+    pub content_hash: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +49,21 @@ impl FileType {
         }
     }
 
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("md");
+        Self::from_extension(extension)
+    }
+
+    /// How diagrams are embedded in files of this type
+    pub fn content_format(&self) -> ContentFormat {
+        match self {
+            FileType::Markdown => ContentFormat::Markdown,
+            FileType::Mermaid | FileType::MermaidMarkdown => ContentFormat::Raw,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn get_extension(&self) -> &'static str {
         match self {
@@ -62,6 +89,30 @@ pub struct FileDialogResult {
     #[serde(rename = "fileContent")]
     pub file_content: Option<FileContent>,
     pub error: Option<String>,
+    /// Set on failure, and to `DIALOG_CANCELLED` when the user dismissed the dialog
+    #[serde(rename = "errorCode", default)]
+    pub error_code: Option<ErrorCode>,
+}
+
+impl FileDialogResult {
+    fn failed(error: AppError) -> Self {
+        Self {
+            success: false,
+            file_content: None,
+            error: Some(error.to_string()),
+            error_code: Some(error.code()),
+        }
+    }
+
+    /// Cancellation is reported through the code only, not as an error message
+    fn cancelled() -> Self {
+        Self {
+            success: false,
+            file_content: None,
+            error: None,
+            error_code: Some(ErrorCode::DialogCancelled),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -69,26 +120,56 @@ pub struct SaveResult {
     pub success: bool,
     #[serde(rename = "filePath")]
     pub file_path: Option<String>,
-    pub error: Option<String>,
+    /// `DIALOG_CANCELLED` when the user dismissed the dialog; failures are returned as
+    /// `AppError`s instead
+    #[serde(rename = "errorCode", default)]
+    pub error_code: Option<ErrorCode>,
+    /// Hash and size of what was written, to carry into the saved `FileContent`
+    #[serde(rename = "contentHash", default)]
+    pub content_hash: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+impl SaveResult {
+    fn saved(path: String, bytes: &[u8]) -> Self {
+        Self {
+            success: true,
+            file_path: Some(path),
+            error_code: None,
+            content_hash: Some(file_io::content_hash(bytes)),
+            size: Some(bytes.len() as u64),
+        }
+    }
+
+    fn cancelled() -> Self {
+        Self {
+            success: false,
+            file_path: None,
+            error_code: Some(ErrorCode::DialogCancelled),
+            content_hash: None,
+            size: None,
+        }
+    }
 }
 
 pub struct FileManager;
 
 /// Internal function to load file from path (used in closures)
-fn load_file_from_path_internal(path: &Path) -> Result<FileContent, String> {
-    let content = fs::read_to_string(path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
+fn load_file_from_path_internal(path: &Path) -> Result<FileContent, AppError> {
+    let bytes = fs::read(path)
+        .map_err(|e| AppError::read(path, e))?;
+    let content_hash = file_io::content_hash(&bytes);
+    let size = bytes.len() as u64;
+    let content = String::from_utf8(bytes)
+        .map_err(|e| AppError::read(path, io::Error::new(io::ErrorKind::InvalidData, e)))?;
 
     let metadata = fs::metadata(path)
-        .map_err(|e| format!("Failed to get file metadata: {}", e))?;
+        .map_err(|e| AppError::metadata(path, e))?;
 
     let last_modified = metadata.modified().ok();
 
-    let extension = path.extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("md");
-
-    let file_type = FileType::from_extension(extension);
+    let file_type = FileType::from_path(path);
 
     let file_name = path.file_name()
         .and_then(|name| name.to_str())
@@ -103,6 +184,8 @@ fn load_file_from_path_internal(path: &Path) -> Result<FileContent, String> {
         last_modified,
         is_saved: true,
         file_type,
+        content_hash: Some(content_hash),
+        size: Some(size),
     })
 }
 
@@ -122,11 +205,13 @@ impl FileManager {
             last_modified: None,
             is_saved: false,
             file_type: FileType::Markdown,
+            content_hash: None,
+            size: None,
         }
     }
 
     /// Open a file using file dialog
-    pub async fn open_file_dialog(window: Window) -> Result<FileDialogResult, String> {
+    pub async fn open_file_dialog(window: Window) -> Result<FileDialogResult, AppError> {
         use tokio::sync::oneshot;
 
         println!("=== RUST: Starting file dialog ===");
@@ -155,35 +240,24 @@ impl FileManager {
                                             success: true,
                                             file_content: Some(file_content),
                                             error: None,
+                                            error_code: None,
                                         }
                                     },
                                     Err(error) => {
                                         println!("Error loading file: {}", error);
-                                        FileDialogResult {
-                                            success: false,
-                                            file_content: None,
-                                            error: Some(error),
-                                        }
+                                        FileDialogResult::failed(error)
                                     },
                                 }
                             }
                             None => {
                                 println!("Invalid file path");
-                                FileDialogResult {
-                                    success: false,
-                                    file_content: None,
-                                    error: Some("Invalid file path".to_string()),
-                                }
+                                FileDialogResult::failed(AppError::InvalidPath)
                             },
                         }
                     }
                     None => {
                         println!("No file selected (cancelled)");
-                        FileDialogResult {
-                            success: false,
-                            file_content: None,
-                            error: None, // Don't treat cancellation as an error
-                        }
+                        FileDialogResult::cancelled()
                     },
                 };
                 
@@ -204,23 +278,19 @@ impl FileManager {
             },
             Err(e) => {
                 println!("Dialog channel error: {:?}", e);
-                Ok(FileDialogResult {
-                    success: false,
-                    file_content: None,
-                    error: Some("Dialog cancelled".to_string()),
-                })
+                Ok(FileDialogResult::failed(AppError::DialogCancelled))
             },
         }
     }
 
     /// Load file from a specific path
     #[allow(dead_code)]
-    pub fn load_file_from_path(path: &Path) -> Result<FileContent, String> {
+    pub fn load_file_from_path(path: &Path) -> Result<FileContent, AppError> {
         load_file_from_path_internal(path)
     }
 
     /// Save file with existing path
-    pub fn save_file(file_content: &FileContent) -> Result<SaveResult, String> {
+    pub fn save_file(file_content: &FileContent, options: &SaveOptions) -> Result<SaveResult, AppError> {
         println!("=== RUST: Saving file ===");
         println!("File name: {}", file_content.name);
         println!("File path: {:?}", file_content.path);
@@ -228,28 +298,37 @@ impl FileManager {
         println!("Content preview: {}", &file_content.content.chars().take(100).collect::<String>());
         
         if let Some(path) = &file_content.path {
+            if let Some(expected_hash) = &file_content.content_hash {
+                let conflict = file_io::detect_conflict(
+                    Path::new(path),
+                    expected_hash,
+                    file_content.size,
+                    &file_content.content,
+                )
+                .map_err(|e| AppError::read(path, e))?;
+                if let Some(conflict) = conflict {
+                    println!("File changed on disk since it was loaded, refusing to save");
+                    return Err(AppError::SaveConflict(Box::new(conflict)));
+                }
+            }
+
             println!("Writing to path: {}", path);
-            match fs::write(path, &file_content.content) {
+            let text = file_io::text_for_existing(Path::new(path), &file_content.content);
+            // Recorded before writing so the watcher never mistakes our own save for an outside edit
+            file_watcher::remember(Path::new(path), text.as_bytes());
+            match file_io::atomic_write(Path::new(path), text.as_bytes(), options) {
                 Ok(_) => {
                     println!("File saved successfully");
-                    Ok(SaveResult {
-                        success: true,
-                        file_path: Some(path.clone()),
-                        error: None,
-                    })
+                    Ok(SaveResult::saved(path.clone(), text.as_bytes()))
                 },
                 Err(e) => {
                     println!("Error saving file: {}", e);
-                    Ok(SaveResult {
-                        success: false,
-                        file_path: None,
-                        error: Some(format!("Failed to save file: {}", e)),
-                    })
+                    Err(AppError::write(path, e))
                 },
             }
         } else {
             println!("No file path specified");
-            Err("No file path specified. Use save_file_as instead.".to_string())
+            Err(AppError::NoFilePath)
         }
     }
 
@@ -258,23 +337,46 @@ impl FileManager {
         window: Window,
         content: &str,
         suggested_name: Option<&str>,
-    ) -> Result<SaveResult, String> {
-        use tokio::sync::oneshot;
-
+        options: SaveOptions,
+    ) -> Result<SaveResult, AppError> {
         println!("=== RUST: Starting Save As dialog ===");
         println!("Content length: {}", content.len());
         println!("Content preview: {}", &content.chars().take(100).collect::<String>());
+
+        Self::save_bytes_as_dialog(
+            window,
+            content.as_bytes().to_vec(),
+            suggested_name,
+            &[
+                ("Markdown Files", &["md"]),
+                ("Mermaid Files", &["mmd"]),
+                ("Mermaid Diagram Files", &["mermaid"]),
+            ],
+            "Save File As",
+            options,
+        )
+        .await
+    }
+
+    /// Ask for a destination with a save dialog and write `bytes` there
+    pub async fn save_bytes_as_dialog(
+        window: Window,
+        bytes: Vec<u8>,
+        suggested_name: Option<&str>,
+        filters: &[(&str, &[&str])],
+        title: &str,
+        options: SaveOptions,
+    ) -> Result<SaveResult, AppError> {
+        use tokio::sync::oneshot;
+
         println!("Suggested name: {:?}", suggested_name);
 
         let (tx, rx) = oneshot::channel();
-        let content_owned = content.to_string();
 
-        let mut dialog = window.dialog()
-            .file()
-            .add_filter("Markdown Files", &["md"])
-            .add_filter("Mermaid Files", &["mmd"])
-            .add_filter("Mermaid Diagram Files", &["mermaid"])
-            .set_title("Save File As");
+        let mut dialog = window.dialog().file().set_title(title);
+        for (name, extensions) in filters {
+            dialog = dialog.add_filter(*name, extensions);
+        }
 
         if let Some(name) = suggested_name {
             dialog = dialog.set_file_name(name);
@@ -290,47 +392,32 @@ impl FileManager {
                     match path.as_path() {
                         Some(path_buf) => {
                             println!("Converting to path: {:?}", path_buf);
-                            println!("Writing content (length: {})", content_owned.len());
-                            match fs::write(&path_buf, &content_owned) {
+                            println!("Writing content (length: {})", bytes.len());
+                            file_watcher::remember(&path_buf, &bytes);
+                            match file_io::atomic_write(&path_buf, &bytes, &options) {
                                 Ok(_) => {
                                     println!("File saved successfully to: {:?}", path_buf);
-                                    SaveResult {
-                                        success: true,
-                                        file_path: Some(path_buf.to_string_lossy().to_string()),
-                                        error: None,
-                                    }
+                                    Ok(SaveResult::saved(path_buf.to_string_lossy().to_string(), &bytes))
                                 },
                                 Err(e) => {
                                     println!("Error saving file: {}", e);
-                                    SaveResult {
-                                        success: false,
-                                        file_path: None,
-                                        error: Some(format!("Failed to save file: {}", e)),
-                                    }
+                                    Err(AppError::write(&path_buf, e))
                                 },
                             }
                         }
                         None => {
                             println!("Invalid file path");
-                            SaveResult {
-                                success: false,
-                                file_path: None,
-                                error: Some("Invalid file path".to_string()),
-                            }
+                            Err(AppError::InvalidPath)
                         },
                     }
                 }
                 None => {
                     println!("No file selected (cancelled)");
-                    SaveResult {
-                        success: false,
-                        file_path: None,
-                        error: None, // Don't treat cancellation as an error
-                    }
+                    Ok(SaveResult::cancelled())
                 },
             };
             
-            println!("Sending save result: success={}", save_result.success);
+            println!("Sending save result: {:?}", save_result.as_ref().map(|result| result.success));
             let send_result = tx.send(save_result);
             if send_result.is_err() {
                 println!("Failed to send save result!");
@@ -342,28 +429,46 @@ impl FileManager {
         println!("Waiting for save dialog result...");
         match rx.await {
             Ok(result) => {
-                println!("Received save result: success={}", result.success);
-                Ok(result)
+                println!("Received save result: {:?}", result.as_ref().map(|result| result.success));
+                result
             },
             Err(e) => {
                 println!("Save dialog channel error: {:?}", e);
-                Ok(SaveResult {
-                    success: false,
-                    file_path: None,
-                    error: Some("Dialog cancelled".to_string()),
-                })
+                Ok(SaveResult::cancelled())
             },
         }
     }
 
-    /// Check if file has been modified externally
-    pub fn check_file_modified(file_content: &FileContent) -> Result<bool, String> {
+    /// Pick a folder; `None` when the dialog is cancelled
+    pub async fn pick_folder_dialog(window: Window, title: &str) -> Option<PathBuf> {
+        use tokio::sync::oneshot;
+
+        let (tx, rx) = oneshot::channel();
+        window.dialog()
+            .file()
+            .set_title(title)
+            .pick_folder(move |folder| {
+                let _ = tx.send(folder.and_then(|folder| folder.as_path().map(Path::to_path_buf)));
+            });
+
+        rx.await.ok().flatten()
+    }
+
+    /// Check if file has been modified externally, by content when the load hash is known
+    /// and by modification time otherwise
+    pub fn check_file_modified(file_content: &FileContent) -> Result<bool, AppError> {
+        if let (Some(path), Some(expected_hash)) = (&file_content.path, &file_content.content_hash) {
+            let conflict = file_io::detect_conflict(Path::new(path), expected_hash, file_content.size, "")
+                .map_err(|e| AppError::read(path, e))?;
+            return Ok(conflict.is_some());
+        }
+
         if let Some(path) = &file_content.path {
             let metadata = fs::metadata(path)
-                .map_err(|e| format!("Failed to get file metadata: {}", e))?;
+                .map_err(|e| AppError::metadata(path, e))?;
 
             let current_modified = metadata.modified()
-                .map_err(|e| format!("Failed to get modification time: {}", e))?;
+                .map_err(|e| AppError::metadata(path, e))?;
 
             if let Some(last_modified) = file_content.last_modified {
                 Ok(current_modified > last_modified)
@@ -381,7 +486,6 @@ impl FileManager {
     }
 
     /// Validate file extension
-    #[allow(dead_code)]
     pub fn is_supported_file(path: &Path) -> bool {
         if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
             Self::get_supported_extensions().contains(&extension.to_lowercase().as_str())
@@ -0,0 +1,344 @@
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, LazyLock, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use tauri::{AppHandle, Emitter};
+
+use crate::error::AppError;
+use crate::file_io::content_hash;
+use crate::file_manager::FileManager;
+use crate::workspace::{self, WorkspaceNode};
+
+/// Sent when a watched file's content changes on disk
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChanged {
+    pub path: String,
+    pub hash: String,
+}
+
+/// Sent when a watched file disappears without being renamed
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDeleted {
+    pub path: String,
+}
+
+/// Sent when a watched file is moved or renamed, with the hash of its content at `to`
+#[derive(Debug, Clone, Serialize)]
+pub struct FileRenamed {
+    pub from: String,
+    pub to: String,
+    pub hash: String,
+}
+
+/// What should be watched, kept apart from the watcher so its event thread never
+/// waits on a lock held while a watch is being added
+#[derive(Default)]
+struct WatchTargets {
+    open_file: Option<PathBuf>,
+    roots: Vec<PathBuf>,
+    /// Last content hash seen for each file, so touches and our own saves raise no events
+    hashes: HashMap<PathBuf, String>,
+}
+
+struct WatcherHandle {
+    watcher: RecommendedWatcher,
+    installed: HashMap<PathBuf, RecursiveMode>,
+}
+
+static TARGETS: LazyLock<Mutex<WatchTargets>> =
+    LazyLock::new(|| Mutex::new(WatchTargets::default()));
+
+static WATCHER: LazyLock<Mutex<Option<WatcherHandle>>> = LazyLock::new(|| Mutex::new(None));
+
+fn targets() -> MutexGuard<'static, WatchTargets> {
+    TARGETS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn watcher() -> MutexGuard<'static, Option<WatcherHandle>> {
+    WATCHER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Start the watcher; events are emitted to every window of `app_handle`.
+///
+/// Events are handled on a thread of our own: handling a rename can add watches, and
+/// adding a watch from notify's own event thread would wait on itself.
+pub fn initialize(app_handle: &AppHandle) -> Result<(), AppError> {
+    let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+    let notifier = RecommendedWatcher::new(sender, Config::default())?;
+    let app_handle = app_handle.clone();
+    thread::spawn(move || {
+        for result in receiver {
+            match result {
+                Ok(event) => handle_event(&app_handle, event),
+                Err(e) => eprintln!("File watcher error: {}", e),
+            }
+        }
+    });
+    *watcher() = Some(WatcherHandle {
+        watcher: notifier,
+        installed: HashMap::new(),
+    });
+    sync()
+}
+
+/// Watch the file open in the editor, replacing the previous one; `None` stops watching it
+pub fn watch_open_file(path: Option<&Path>) -> Result<(), AppError> {
+    let path = path.map(canonical);
+    {
+        let mut targets = targets();
+        if let Some(path) = &path {
+            if let Ok(bytes) = fs::read(path) {
+                targets.hashes.insert(path.clone(), content_hash(&bytes));
+            }
+        }
+        targets.open_file = path;
+    }
+    sync()
+}
+
+/// Watch a workspace folder and every file of its scanned `tree`, starting from the
+/// content they had when they were scanned
+pub fn watch_root(root: &Path, tree: &WorkspaceNode) -> Result<(), AppError> {
+    let canonical_root = canonical(root);
+    {
+        let mut targets = targets();
+        seed(&mut targets.hashes, root, &canonical_root, tree);
+        if !targets.roots.contains(&canonical_root) {
+            targets.roots.push(canonical_root);
+        }
+    }
+    sync()
+}
+
+fn seed(
+    hashes: &mut HashMap<PathBuf, String>,
+    root: &Path,
+    canonical_root: &Path,
+    node: &WorkspaceNode,
+) {
+    if let Some(hash) = &node.content_hash {
+        let path = Path::new(&node.path);
+        let path = match path.strip_prefix(root) {
+            Ok(relative) => canonical_root.join(relative),
+            Err(_) => path.to_path_buf(),
+        };
+        hashes.insert(path, hash.clone());
+    }
+    for child in &node.children {
+        seed(hashes, root, canonical_root, child);
+    }
+}
+
+pub fn unwatch_root(root: &Path) -> Result<(), AppError> {
+    let root = canonical(root);
+    {
+        let mut targets = targets();
+        targets.roots.retain(|existing| *existing != root);
+        targets.hashes.retain(|path, _| !path.starts_with(&root));
+    }
+    sync()
+}
+
+/// Record content we wrote ourselves so the watcher does not report it as an outside change
+pub fn remember(path: &Path, bytes: &[u8]) {
+    targets()
+        .hashes
+        .insert(canonical(path), content_hash(bytes));
+}
+
+/// Add and remove OS watches to match the current targets.
+///
+/// The open file is watched through its folder so saves that replace the file by
+/// renaming over it are still seen; that folder is skipped when a workspace root
+/// already covers it.
+fn sync() -> Result<(), AppError> {
+    let wanted = {
+        let targets = targets();
+        let mut wanted: HashMap<PathBuf, RecursiveMode> = HashMap::new();
+        for root in &targets.roots {
+            let covered = targets
+                .roots
+                .iter()
+                .any(|other| other != root && root.starts_with(other));
+            if !covered {
+                wanted.insert(root.clone(), RecursiveMode::Recursive);
+            }
+        }
+        if let Some(folder) = targets.open_file.as_deref().and_then(Path::parent) {
+            if !targets.roots.iter().any(|root| folder.starts_with(root)) {
+                wanted.insert(folder.to_path_buf(), RecursiveMode::NonRecursive);
+            }
+        }
+        wanted
+    };
+
+    let mut guard = watcher();
+    let Some(handle) = guard.as_mut() else {
+        return Ok(());
+    };
+    let stale: Vec<PathBuf> = handle
+        .installed
+        .iter()
+        .filter(|(path, mode)| wanted.get(*path) != Some(*mode))
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in stale {
+        // The folder may already be gone, which removes its watch anyway
+        let _ = handle.watcher.unwatch(&path);
+        handle.installed.remove(&path);
+    }
+    for (path, mode) in wanted {
+        if !handle.installed.contains_key(&path) {
+            handle.watcher.watch(&path, mode)?;
+            handle.installed.insert(path, mode);
+        }
+    }
+    Ok(())
+}
+
+fn handle_event(app_handle: &AppHandle, event: Event) {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            renamed(app_handle, &event.paths[0], &event.paths[1])
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            for path in &event.paths {
+                removed(app_handle, path);
+            }
+        }
+        EventKind::Create(_) | EventKind::Modify(_) => {
+            let created = matches!(event.kind, EventKind::Create(_));
+            for path in &event.paths {
+                changed(app_handle, path, created);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Report a file whose content differs from the last hash seen. Without a previous hash
+/// only a newly created file counts as a change, so touching a file never does.
+fn changed(app_handle: &AppHandle, path: &Path, created: bool) {
+    if !is_relevant(&targets(), path) {
+        return;
+    }
+    let Ok(bytes) = fs::read(path) else {
+        return removed(app_handle, path);
+    };
+    let hash = content_hash(&bytes);
+    let previous = targets().hashes.insert(path.to_path_buf(), hash.clone());
+    let is_change = match previous {
+        Some(previous) => previous != hash,
+        None => created,
+    };
+    if is_change {
+        emit(
+            app_handle,
+            "file-changed",
+            FileChanged {
+                path: display(path),
+                hash,
+            },
+        );
+    }
+}
+
+fn removed(app_handle: &AppHandle, path: &Path) {
+    // Editors that save by replacing the file briefly remove it; the create event follows
+    if path.exists() {
+        return;
+    }
+    let known = {
+        let mut targets = targets();
+        let known = targets.hashes.remove(path).is_some();
+        known || is_relevant(&targets, path)
+    };
+    if known {
+        emit(
+            app_handle,
+            "file-deleted",
+            FileDeleted {
+                path: display(path),
+            },
+        );
+    }
+}
+
+fn renamed(app_handle: &AppHandle, from: &Path, to: &Path) {
+    let (from_watched, to_watched) = {
+        let targets = targets();
+        (
+            targets.hashes.contains_key(from) || is_relevant(&targets, from),
+            is_relevant(&targets, to),
+        )
+    };
+    // A temporary file renamed over a watched one is a save, not a rename
+    if !from_watched {
+        return changed(app_handle, to, true);
+    }
+    let hash = match fs::read(to) {
+        Ok(bytes) if to_watched || FileManager::is_supported_file(to) => content_hash(&bytes),
+        _ => return removed(app_handle, from),
+    };
+    let follows_open_file = {
+        let mut targets = targets();
+        targets.hashes.remove(from);
+        targets.hashes.insert(to.to_path_buf(), hash.clone());
+        let follows = targets.open_file.as_deref() == Some(from);
+        if follows {
+            targets.open_file = Some(to.to_path_buf());
+        }
+        follows
+    };
+    if follows_open_file {
+        if let Err(e) = sync() {
+            eprintln!("Failed to follow renamed file: {}", e);
+        }
+    }
+    emit(
+        app_handle,
+        "file-renamed",
+        FileRenamed {
+            from: display(from),
+            to: display(to),
+            hash,
+        },
+    );
+}
+
+/// The open file, or a supported file that a workspace root lists
+fn is_relevant(targets: &WatchTargets, path: &Path) -> bool {
+    if targets.open_file.as_deref() == Some(path) {
+        return true;
+    }
+    FileManager::is_supported_file(path)
+        && targets
+            .roots
+            .iter()
+            .any(|root| !workspace::is_excluded(root, path))
+}
+
+fn emit<T: Serialize + Clone>(app_handle: &AppHandle, event: &str, payload: T) {
+    if let Err(e) = app_handle.emit(event, payload) {
+        eprintln!("Failed to emit {}: {}", event, e);
+    }
+}
+
+/// Watch paths are canonical so they compare equal to the paths notify reports
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+// Tauri command implementations
+#[tauri::command]
+pub async fn watch_file(path: Option<String>) -> Result<(), AppError> {
+    watch_open_file(path.as_deref().map(Path::new))
+}
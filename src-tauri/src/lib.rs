@@ -1,19 +1,62 @@
 use tauri::Manager;
-use std::sync::LazyLock;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
 
+mod error;
 mod mermaid_parser;
+mod renderer;
+mod exporter;
+mod cli;
+mod lsp;
+mod recovery;
+mod file_io;
 mod file_manager;
+mod file_watcher;
 mod window_state;
+mod workspace;
 
-use mermaid_parser::{MermaidParser, ParseResult, ValidationResult};
-use file_manager::{FileManager, FileContent, FileDialogResult, SaveResult};
+use mermaid_parser::{AstResult, ContentFormat, Detection, Fix, LintConfig, MermaidParser, ParseResult, ParsedDiagram, ValidationResult};
+use mermaid_parser::identity::{self, IdMapping};
+use mermaid_parser::formatter::{self, FormatOptions, FormattedDocument};
+use mermaid_parser::comments::{self, DiagramTask};
+use mermaid_parser::class_diagram::{self, ClassDiagramAst};
+use mermaid_parser::flowchart::{self, FlowchartAst};
+use mermaid_parser::sequence::{self, SequenceAst};
+use mermaid_parser::state_diagram::{self, StateDiagramAst};
+use mermaid_parser::session::{DiagramDelta, DocumentSession, TextEdit};
+use file_io::SaveOptions;
+use file_manager::{FileManager, FileContent, FileDialogResult, FileType, SaveResult};
+use renderer::RenderOptions;
+use exporter::{BatchExportResult, DiagramRef, ExportOptions};
+use recovery::{Journal, JournalEntry, RecoveryReport};
 use window_state::WindowStateManager;
+use workspace::WorkspaceNode;
+use error::AppError;
 
 // Global Mermaid parser instance
 static MERMAID_PARSER: LazyLock<MermaidParser> = LazyLock::new(|| {
     MermaidParser::new().expect("Failed to initialize Mermaid parser")
 });
 
+// Open documents being parsed incrementally, keyed by document ID
+static PARSE_SESSIONS: LazyLock<Mutex<HashMap<String, DocumentSession>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Crash recovery journal of unsaved buffers; `None` when the app data directory is unusable
+static RECOVERY_JOURNAL: LazyLock<Mutex<Option<Journal>>> = LazyLock::new(|| Mutex::new(None));
+
+pub use cli::run_cli;
+
+/// A poisoned lock only means another command panicked; the sessions themselves are still usable
+fn parse_sessions() -> MutexGuard<'static, HashMap<String, DocumentSession>> {
+    PARSE_SESSIONS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn recovery_journal() -> MutexGuard<'static, Option<Journal>> {
+    RECOVERY_JOURNAL.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
 // Re-export window management commands from the window_state module
 pub use window_state::{
     set_always_on_top,
@@ -30,74 +73,212 @@ pub use window_state::{
     update_cursor_position,
     update_file_state,
     update_tree_view_state,
+    remove_workspace_root,
+    update_backup_count,
 };
 
+pub use file_watcher::watch_file;
+
 // Window control commands
 #[tauri::command]
-async fn minimize_window(window: tauri::Window) -> Result<(), String> {
-    window.minimize().map_err(|e| e.to_string())
+async fn minimize_window(window: tauri::Window) -> Result<(), AppError> {
+    Ok(window.minimize()?)
 }
 
 #[tauri::command]
-async fn maximize_window(window: tauri::Window) -> Result<(), String> {
-    window.maximize().map_err(|e| e.to_string())
+async fn maximize_window(window: tauri::Window) -> Result<(), AppError> {
+    Ok(window.maximize()?)
 }
 
 #[tauri::command]
-async fn unmaximize_window(window: tauri::Window) -> Result<(), String> {
-    window.unmaximize().map_err(|e| e.to_string())
+async fn unmaximize_window(window: tauri::Window) -> Result<(), AppError> {
+    Ok(window.unmaximize()?)
 }
 
 #[tauri::command]
-async fn close_window(window: tauri::Window) -> Result<(), String> {
-    window.close().map_err(|e| e.to_string())
+async fn close_window(window: tauri::Window) -> Result<(), AppError> {
+    Ok(window.close()?)
 }
 
 #[tauri::command]
-async fn is_window_maximized(window: tauri::Window) -> Result<bool, String> {
-    window.is_maximized().map_err(|e| e.to_string())
+async fn is_window_maximized(window: tauri::Window) -> Result<bool, AppError> {
+    Ok(window.is_maximized()?)
+}
+
+/// Pick fenced or raw parsing from an explicit file type, falling back to the path's extension
+fn resolve_content_format(file_type: Option<FileType>, path: Option<&str>) -> ContentFormat {
+    file_type
+        .or_else(|| path.map(|path| FileType::from_path(Path::new(path))))
+        .map(|file_type| file_type.content_format())
+        .unwrap_or(ContentFormat::Markdown)
 }
 
 // Mermaid parsing commands
 #[tauri::command]
-async fn parse_mermaid_content(content: String) -> Result<ParseResult, String> {
+async fn parse_mermaid_content(
+    content: String,
+    file_type: Option<FileType>,
+    path: Option<String>,
+    previous: Option<Vec<ParsedDiagram>>,
+) -> Result<ParseResult, AppError> {
     let parser = &*MERMAID_PARSER;
-    Ok(parser.parse_content(&content))
+    let format = resolve_content_format(file_type, path.as_deref());
+    let mut result = parser.parse_content_as(&content, format);
+    // Carry diagram IDs over from the previous parse so edits don't reset viewer state
+    if let Some(previous) = previous {
+        identity::reconcile(&previous, &mut result.diagrams);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+async fn open_parse_session(
+    document_id: String,
+    content: String,
+    file_type: Option<FileType>,
+    path: Option<String>,
+) -> Result<ParseResult, AppError> {
+    let format = resolve_content_format(file_type, path.as_deref());
+    let (session, result) = DocumentSession::open(&MERMAID_PARSER, content, format);
+    let mut sessions = parse_sessions();
+    sessions.insert(document_id, session);
+    Ok(result)
 }
 
 #[tauri::command]
-async fn validate_mermaid_diagram(content: String, start_line: Option<usize>) -> Result<ValidationResult, String> {
+async fn apply_parse_edits(document_id: String, edits: Vec<TextEdit>) -> Result<DiagramDelta, AppError> {
+    let mut sessions = parse_sessions();
+    let session = sessions
+        .get_mut(&document_id)
+        .ok_or_else(|| AppError::SessionNotFound(document_id.clone()))?;
+    Ok(session.apply_edits(&MERMAID_PARSER, &edits))
+}
+
+#[tauri::command]
+async fn close_parse_session(document_id: String) -> Result<(), AppError> {
+    let mut sessions = parse_sessions();
+    sessions.remove(&document_id);
+    Ok(())
+}
+
+#[tauri::command]
+async fn match_diagram_ids(
+    previous: Vec<ParsedDiagram>,
+    mut current: Vec<ParsedDiagram>,
+) -> Result<Vec<IdMapping>, AppError> {
+    Ok(identity::reconcile(&previous, &mut current))
+}
+
+#[tauri::command]
+async fn validate_mermaid_diagram(
+    content: String,
+    start_line: Option<usize>,
+    path: Option<String>,
+) -> Result<ValidationResult, AppError> {
     let parser = &*MERMAID_PARSER;
-    Ok(parser.validate_diagram(&content, start_line.unwrap_or(1)))
+    let lint = match path {
+        Some(path) => LintConfig::for_document(Path::new(&path))?,
+        None => LintConfig::default(),
+    };
+    Ok(parser.validate_diagram_with(&content, start_line.unwrap_or(1), &lint))
+}
+
+/// Apply a fix suggested by `validate_mermaid_diagram` to the document it was validated in
+#[tauri::command]
+async fn apply_diagnostic_fix(content: String, fix: Fix) -> Result<String, AppError> {
+    Ok(fix.apply(&content))
+}
+
+/// TODO, FIXME, XXX and HACK comments in the document's diagrams
+#[tauri::command]
+async fn list_diagram_tasks(
+    content: String,
+    file_type: Option<FileType>,
+    path: Option<String>,
+) -> Result<Vec<DiagramTask>, AppError> {
+    let format = resolve_content_format(file_type, path.as_deref());
+    Ok(comments::tasks(&MERMAID_PARSER, &content, format))
+}
+
+#[tauri::command]
+async fn format_diagram(content: String, options: Option<FormatOptions>) -> Result<String, AppError> {
+    let options = options.unwrap_or_default();
+    Ok(formatter::format_diagram(&MERMAID_PARSER, &content, &options)?)
+}
+
+/// Format every diagram in a document, leaving the surrounding text alone
+#[tauri::command]
+async fn format_document(
+    content: String,
+    file_type: Option<FileType>,
+    path: Option<String>,
+    options: Option<FormatOptions>,
+) -> Result<FormattedDocument, AppError> {
+    let format = resolve_content_format(file_type, path.as_deref());
+    let options = options.unwrap_or_default();
+    Ok(formatter::format_document(&MERMAID_PARSER, &content, format, &options))
+}
+
+#[tauri::command]
+async fn parse_flowchart(content: String, start_line: Option<usize>) -> Result<AstResult<FlowchartAst>, AppError> {
+    Ok(flowchart::parse(&content, start_line.unwrap_or(1)))
+}
+
+#[tauri::command]
+async fn parse_sequence_diagram(content: String, start_line: Option<usize>) -> Result<AstResult<SequenceAst>, AppError> {
+    Ok(sequence::parse(&content, start_line.unwrap_or(1)))
+}
+
+#[tauri::command]
+async fn parse_class_diagram(content: String, start_line: Option<usize>) -> Result<AstResult<ClassDiagramAst>, AppError> {
+    Ok(class_diagram::parse(&content, start_line.unwrap_or(1)))
+}
+
+#[tauri::command]
+async fn parse_state_diagram(content: String, start_line: Option<usize>) -> Result<AstResult<StateDiagramAst>, AppError> {
+    Ok(state_diagram::parse(&content, start_line.unwrap_or(1)))
 }
 
 #[tauri::command]
-async fn detect_diagram_type(content: String) -> Result<String, String> {
+async fn render_diagram_svg(content: String, options: Option<RenderOptions>) -> Result<String, AppError> {
+    let diagram_type = MERMAID_PARSER.detect_diagram_type(&content);
+    Ok(renderer::render_svg(&content, diagram_type, &options.unwrap_or_default())?)
+}
+
+#[tauri::command]
+async fn detect_diagram_type(content: String) -> Result<Detection, AppError> {
     let parser = &*MERMAID_PARSER;
-    Ok(parser.detect_diagram_type(&content))
+    Ok(parser.detect(&content))
 }
 
 #[tauri::command]
-async fn get_parsing_stats(content: String) -> Result<serde_json::Value, String> {
+async fn get_parsing_stats(
+    content: String,
+    file_type: Option<FileType>,
+    path: Option<String>,
+) -> Result<serde_json::Value, AppError> {
     let parser = &*MERMAID_PARSER;
-    let stats = parser.get_parsing_stats(&content);
+    let format = resolve_content_format(file_type, path.as_deref());
+    let stats = parser.get_parsing_stats(&content, format);
     Ok(serde_json::to_value(stats).unwrap_or_default())
 }
 
 // File management commands
 #[tauri::command]
-async fn create_new_file() -> Result<FileContent, String> {
+async fn create_new_file() -> Result<FileContent, AppError> {
     Ok(FileManager::create_new_file())
 }
 
 #[tauri::command]
-async fn open_file_dialog(window: tauri::Window) -> Result<FileDialogResult, String> {
+async fn open_file_dialog(window: tauri::Window) -> Result<FileDialogResult, AppError> {
     FileManager::open_file_dialog(window).await
 }
 
 #[tauri::command]
-async fn save_file(file_content: FileContent) -> Result<SaveResult, String> {
-    FileManager::save_file(&file_content)
+async fn save_file(file_content: FileContent) -> Result<SaveResult, AppError> {
+    let result = FileManager::save_file(&file_content, &document_save_options())?;
+    forget_journal(&file_content.id);
+    Ok(result)
 }
 
 #[tauri::command]
@@ -105,17 +286,191 @@ async fn save_file_as_dialog(
     window: tauri::Window,
     content: String,
     suggested_name: Option<String>,
-) -> Result<SaveResult, String> {
-    FileManager::save_file_as_dialog(window, &content, suggested_name.as_deref()).await
+    document_id: Option<String>,
+) -> Result<SaveResult, AppError> {
+    let result = FileManager::save_file_as_dialog(window, &content, suggested_name.as_deref(), document_save_options()).await?;
+    if let (true, Some(id)) = (result.success, document_id) {
+        forget_journal(&id);
+    }
+    Ok(result)
+}
+
+/// A saved document no longer needs recovering; failing to delete its journal only means
+/// it is offered once more after a crash
+fn forget_journal(document_id: &str) {
+    if let Some(journal) = recovery_journal().as_mut() {
+        if let Err(e) = journal.remove(document_id) {
+            eprintln!("Failed to remove recovery journal for {}: {}", document_id, e);
+        }
+    }
+}
+
+// Crash recovery commands
+#[tauri::command]
+async fn journal_document(entry: JournalEntry) -> Result<(), AppError> {
+    let mut journal = recovery_journal();
+    match journal.as_mut() {
+        Some(journal) => journal.write(&entry).map_err(|e| AppError::write(journal.dir(), e)),
+        None => Ok(()),
+    }
+}
+
+#[tauri::command]
+async fn get_recovered_documents() -> Result<RecoveryReport, AppError> {
+    Ok(recovery_journal()
+        .as_ref()
+        .map(Journal::report)
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+async fn discard_recovered_document(document_id: String) -> Result<(), AppError> {
+    let mut journal = recovery_journal();
+    match journal.as_mut() {
+        Some(journal) => journal.remove(&document_id).map_err(|e| AppError::write(journal.dir(), e)),
+        None => Ok(()),
+    }
+}
+
+/// Documents keep the configured number of backups; exports never do
+fn document_save_options() -> SaveOptions {
+    SaveOptions {
+        backups: WindowStateManager::get_current_app_state().backup_count,
+    }
+}
+
+// Export commands
+#[tauri::command]
+async fn export_diagram(
+    window: tauri::Window,
+    content: String,
+    diagram: DiagramRef,
+    options: Option<ExportOptions>,
+    file_type: Option<FileType>,
+    path: Option<String>,
+    previous: Option<Vec<ParsedDiagram>>,
+) -> Result<SaveResult, AppError> {
+    let options = options.unwrap_or_default();
+    let format = resolve_content_format(file_type, path.as_deref());
+    let mut diagrams = MERMAID_PARSER.parse_content_as(&content, format).diagrams;
+    // Resolve IDs the same way the editor's last parse did, or edited diagrams can't be found
+    if let Some(previous) = previous {
+        identity::reconcile(&previous, &mut diagrams);
+    }
+    let (index, diagram) = exporter::select(&diagrams, &diagram).ok_or(AppError::DiagramNotFound)?;
+    let bytes = exporter::export_diagram(diagram, &options)?;
+    let name = exporter::export_file_name(document_stem(path.as_deref()), index, diagram, options.format);
+    let extensions = [options.format.extension()];
+    FileManager::save_bytes_as_dialog(
+        window,
+        bytes,
+        Some(&name),
+        &[(options.format.filter_name(), &extensions)],
+        "Export Diagram",
+        SaveOptions::default(),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn export_all_diagrams(
+    window: tauri::Window,
+    content: String,
+    options: Option<ExportOptions>,
+    file_type: Option<FileType>,
+    path: Option<String>,
+) -> Result<BatchExportResult, AppError> {
+    let Some(folder) = FileManager::pick_folder_dialog(window, "Export Diagrams To").await else {
+        return Ok(BatchExportResult {
+            success: false,
+            folder: None,
+            files: Vec::new(),
+            failures: Vec::new(),
+        });
+    };
+    let options = options.unwrap_or_default();
+    let format = resolve_content_format(file_type, path.as_deref());
+    let diagrams = MERMAID_PARSER.parse_content_as(&content, format).diagrams;
+    Ok(exporter::export_all(&diagrams, &folder, document_stem(path.as_deref()), &options))
+}
+
+fn document_stem(path: Option<&str>) -> Option<&str> {
+    path.and_then(|path| Path::new(path).file_stem())
+        .and_then(|stem| stem.to_str())
 }
 
 #[tauri::command]
-async fn check_file_modified(file_content: FileContent) -> Result<bool, String> {
+async fn check_file_modified(file_content: FileContent) -> Result<bool, AppError> {
     FileManager::check_file_modified(&file_content)
 }
 
+// Workspace commands
+#[tauri::command]
+async fn open_workspace_dialog(window: tauri::Window) -> Result<Option<WorkspaceNode>, AppError> {
+    match FileManager::pick_folder_dialog(window, "Open Folder").await {
+        Some(folder) => open_workspace(folder.to_string_lossy().into_owned()).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Scan a workspace folder and remember it for the next session
 #[tauri::command]
-async fn get_supported_extensions() -> Result<Vec<String>, String> {
+async fn open_workspace(path: String) -> Result<WorkspaceNode, AppError> {
+    let root = Path::new(&path);
+    if !root.is_dir() {
+        return Err(AppError::InvalidPath);
+    }
+    let tree = load_workspace(root)?;
+    WindowStateManager::update_app_state(|app_state| {
+        if !app_state.workspace_roots.contains(&path) {
+            app_state.workspace_roots.push(path.clone());
+        }
+    })?;
+    Ok(tree)
+}
+
+/// The workspace folders remembered from earlier sessions, scanned again. Folders that
+/// are unavailable right now are left out but not forgotten.
+#[tauri::command]
+async fn get_workspaces() -> Result<Vec<WorkspaceNode>, AppError> {
+    let roots = WindowStateManager::get_current_app_state().workspace_roots;
+    Ok(roots
+        .iter()
+        .map(Path::new)
+        .filter(|root| root.is_dir())
+        .filter_map(|root| match load_workspace(root) {
+            Ok(tree) => Some(tree),
+            Err(e) => {
+                eprintln!("Failed to scan workspace {}: {}", root.display(), e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Scan a workspace folder and watch it, starting from the content that was scanned
+fn load_workspace(root: &Path) -> Result<WorkspaceNode, AppError> {
+    let tree = workspace::scan(&MERMAID_PARSER, root).map_err(|e| AppError::read(root, e))?;
+    // The tree is still usable without live updates, so a watch failure is only logged
+    if let Err(e) = file_watcher::watch_root(root, &tree) {
+        eprintln!("Failed to watch workspace {}: {}", root.display(), e);
+    }
+    Ok(tree)
+}
+
+/// Watch the workspace folders of the last session again. Folders that are missing, such
+/// as ones on an unmounted drive, are skipped for now but stay remembered until removed.
+fn restore_workspaces() {
+    let roots = WindowStateManager::get_current_app_state().workspace_roots;
+    for root in roots.iter().map(Path::new).filter(|root| root.is_dir()) {
+        if let Err(e) = load_workspace(root) {
+            eprintln!("Failed to restore workspace {}: {}", root.display(), e);
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_supported_extensions() -> Result<Vec<String>, AppError> {
     Ok(FileManager::get_supported_extensions().iter().map(|s| s.to_string()).collect())
 }
 
@@ -126,7 +481,7 @@ async fn get_app_version() -> String {
 }
 
 #[tauri::command]
-async fn get_app_info() -> Result<serde_json::Value, String> {
+async fn get_app_info() -> Result<serde_json::Value, AppError> {
     Ok(serde_json::json!({
         "name": env!("CARGO_PKG_NAME"),
         "version": env!("CARGO_PKG_VERSION"),
@@ -158,20 +513,44 @@ pub fn run() {
             update_cursor_position,
             update_file_state,
             update_tree_view_state,
+            remove_workspace_root,
+            update_backup_count,
             minimize_window,
             maximize_window,
             unmaximize_window,
             close_window,
             is_window_maximized,
             parse_mermaid_content,
+            match_diagram_ids,
+            open_parse_session,
+            apply_parse_edits,
+            close_parse_session,
             validate_mermaid_diagram,
+            apply_diagnostic_fix,
+            list_diagram_tasks,
+            format_diagram,
+            format_document,
+            parse_flowchart,
+            parse_sequence_diagram,
+            parse_class_diagram,
+            parse_state_diagram,
+            render_diagram_svg,
             detect_diagram_type,
             get_parsing_stats,
             create_new_file,
             open_file_dialog,
             save_file,
             save_file_as_dialog,
+            export_diagram,
+            export_all_diagrams,
             check_file_modified,
+            open_workspace_dialog,
+            open_workspace,
+            get_workspaces,
+            watch_file,
+            journal_document,
+            get_recovered_documents,
+            discard_recovered_document,
             get_supported_extensions,
             get_app_version,
             get_app_info
@@ -182,6 +561,22 @@ pub fn run() {
                 eprintln!("Failed to initialize window state manager: {}", e);
             }
 
+            // Open the recovery journal, noticing whether the last session crashed
+            match app.path().app_data_dir() {
+                Ok(dir) => match Journal::open(&dir.join("recovery")) {
+                    Ok(journal) => *recovery_journal() = Some(journal),
+                    Err(e) => eprintln!("Failed to open recovery journal: {}", e),
+                },
+                Err(e) => eprintln!("Failed to locate app data directory: {}", e),
+            }
+
+            // Start watching for outside changes to open files and workspaces
+            if let Err(e) = file_watcher::initialize(app.handle()) {
+                eprintln!("Failed to start file watcher: {}", e);
+            }
+            // Scanning large folders would hold up the window, so it happens in the background
+            std::thread::spawn(restore_workspaces);
+
             // Get the main window
             let window = app.get_webview_window("main").unwrap();
             
@@ -209,6 +604,16 @@ pub fn run() {
             
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app_handle, event| {
+            // Reaching exit means a clean shutdown; unsaved buffers stay journaled for the next start
+            if let tauri::RunEvent::Exit = event {
+                if let Some(journal) = recovery_journal().as_mut() {
+                    if let Err(e) = journal.close() {
+                        eprintln!("Failed to close recovery journal: {}", e);
+                    }
+                }
+            }
+        });
 }
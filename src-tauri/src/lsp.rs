@@ -0,0 +1,973 @@
+use crate::file_manager::FileType;
+use crate::mermaid_parser::comments;
+use crate::mermaid_parser::fence;
+use crate::mermaid_parser::formatter::{self, FormatOptions};
+use crate::mermaid_parser::session::{DocumentSession, TextEdit};
+use crate::mermaid_parser::{
+    class_diagram, flowchart, sequence, state_diagram, ContentFormat, DiagramType, LintConfig,
+    MermaidParser, Position, Severity, SyntaxError,
+};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics,
+};
+use lsp_types::request::{
+    CodeActionRequest, Completion, DocumentSymbolRequest, FoldingRangeRequest, Formatting,
+    Request as LspRequest,
+};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CompletionItem, CompletionItemKind, CompletionOptions,
+    CompletionParams, CompletionResponse, Diagnostic, DiagnosticRelatedInformation,
+    DiagnosticSeverity, DocumentFormattingParams, DocumentSymbol, DocumentSymbolParams,
+    DocumentSymbolResponse, FoldingRange, FoldingRangeKind, FoldingRangeParams,
+    FoldingRangeProviderCapability, InitializeParams, Location, NumberOrString, OneOf,
+    PositionEncodingKind, PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind,
+    TextDocumentContentChangeEvent, TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+    WorkspaceEdit,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+type LspResult<T> = Result<T, Box<dyn Error + Sync + Send>>;
+
+/// Declarations offered at the top of an empty diagram
+const DECLARATIONS: &[(&str, &str)] = &[
+    ("flowchart TD", "Flowchart"),
+    ("graph LR", "Flowchart"),
+    ("sequenceDiagram", "Sequence diagram"),
+    ("classDiagram", "Class diagram"),
+    ("stateDiagram-v2", "State diagram"),
+    ("erDiagram", "Entity relationship diagram"),
+    ("gantt", "Gantt chart"),
+    ("pie", "Pie chart"),
+    ("journey", "User journey"),
+    ("gitGraph", "Git graph"),
+    ("requirementDiagram", "Requirement diagram"),
+    ("C4Context", "C4 system context"),
+    ("C4Container", "C4 container diagram"),
+    ("C4Component", "C4 component diagram"),
+    ("C4Dynamic", "C4 dynamic diagram"),
+    ("C4Deployment", "C4 deployment diagram"),
+    ("mindmap", "Mindmap"),
+    ("timeline", "Timeline"),
+    ("quadrantChart", "Quadrant chart"),
+    ("sankey-beta", "Sankey diagram"),
+    ("xychart-beta", "XY chart"),
+    ("block-beta", "Block diagram"),
+    ("packet-beta", "Packet diagram"),
+    ("architecture-beta", "Architecture diagram"),
+    ("kanban", "Kanban board"),
+    ("zenuml", "ZenUML sequence diagram"),
+    ("radar-beta", "Radar chart"),
+    ("treemap-beta", "Treemap"),
+];
+
+const FLOWCHART_KEYWORDS: &[&str] = &[
+    "subgraph",
+    "end",
+    "direction",
+    "classDef",
+    "class",
+    "style",
+    "linkStyle",
+    "click",
+];
+const SEQUENCE_KEYWORDS: &[&str] = &[
+    "participant",
+    "actor",
+    "activate",
+    "deactivate",
+    "Note left of",
+    "Note right of",
+    "Note over",
+    "loop",
+    "alt",
+    "else",
+    "opt",
+    "par",
+    "and",
+    "critical",
+    "break",
+    "rect",
+    "end",
+    "autonumber",
+    "title",
+];
+const CLASS_KEYWORDS: &[&str] = &[
+    "class",
+    "namespace",
+    "note",
+    "note for",
+    "direction",
+    "classDef",
+];
+const STATE_KEYWORDS: &[&str] = &[
+    "state",
+    "note left of",
+    "note right of",
+    "end note",
+    "direction",
+    "[*]",
+];
+
+/// An open document; validation results are cached by diagram content and position
+struct Document {
+    session: DocumentSession,
+    validations: HashMap<(String, usize), Vec<SyntaxError>>,
+    /// Read once when the document opens
+    lint: LintConfig,
+}
+
+struct Server {
+    parser: MermaidParser,
+    documents: HashMap<Uri, Document>,
+    /// Whether positions count Unicode scalar values (UTF-32) instead of UTF-16 code units
+    utf32: bool,
+}
+
+/// Run a language server on stdin/stdout until the client shuts it down
+pub fn serve() -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve_connection(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+fn serve_connection(connection: &Connection) -> LspResult<()> {
+    let (id, params) = connection.initialize_start()?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    let utf32 = params
+        .capabilities
+        .general
+        .and_then(|general| general.position_encodings)
+        .is_some_and(|encodings| encodings.contains(&PositionEncodingKind::UTF32));
+
+    let capabilities = ServerCapabilities {
+        position_encoding: Some(if utf32 {
+            PositionEncodingKind::UTF32
+        } else {
+            PositionEncodingKind::UTF16
+        }),
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize_finish(
+        id,
+        serde_json::json!({
+            "capabilities": capabilities,
+            "serverInfo": { "name": "parch", "version": env!("CARGO_PKG_VERSION") },
+        }),
+    )?;
+
+    let mut server = Server {
+        parser: MermaidParser::default(),
+        documents: HashMap::new(),
+        utf32,
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                for published in server.handle_notification(notification)? {
+                    connection.sender.send(Message::Notification(published))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Deserialize a request's params and answer with the handler's result
+fn respond<P: DeserializeOwned, R: Serialize>(
+    request: Request,
+    handler: impl FnOnce(P) -> R,
+) -> Response {
+    match serde_json::from_value::<P>(request.params) {
+        Ok(params) => Response::new_ok(request.id, handler(params)),
+        Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+impl Server {
+    fn handle_request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            DocumentSymbolRequest::METHOD => respond(request, |params: DocumentSymbolParams| {
+                let symbols = self
+                    .documents
+                    .get(&params.text_document.uri)
+                    .map(|document| self.document_symbols(document))
+                    .unwrap_or_default();
+                DocumentSymbolResponse::Nested(symbols)
+            }),
+            FoldingRangeRequest::METHOD => respond(request, |params: FoldingRangeParams| {
+                self.documents
+                    .get(&params.text_document.uri)
+                    .map(|document| folding_ranges(&document.session))
+                    .unwrap_or_default()
+            }),
+            Completion::METHOD => respond(request, |params: CompletionParams| {
+                let position = params.text_document_position;
+                let items = self
+                    .documents
+                    .get(&position.text_document.uri)
+                    .map(|document| self.completions(document, position.position))
+                    .unwrap_or_default();
+                CompletionResponse::Array(items)
+            }),
+            CodeActionRequest::METHOD => respond(request, |params: CodeActionParams| {
+                let uri = params.text_document.uri;
+                self.documents
+                    .get(&uri)
+                    .map(|document| self.code_actions(document, &uri, params.range))
+                    .unwrap_or_default()
+            }),
+            Formatting::METHOD => respond(request, |params: DocumentFormattingParams| {
+                let options = FormatOptions {
+                    indent: params.options.tab_size as usize,
+                    ..FormatOptions::default()
+                };
+                self.documents
+                    .get(&params.text_document.uri)
+                    .map(|document| self.format(document, &options))
+                    .unwrap_or_default()
+            }),
+            method => {
+                let message = format!("Unhandled method {}", method);
+                Response::new_err(request.id, ErrorCode::MethodNotFound as i32, message)
+            }
+        }
+    }
+
+    /// Update documents and return the diagnostics to publish
+    fn handle_notification(&mut self, notification: Notification) -> LspResult<Vec<Notification>> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let document = params.text_document;
+                let format = content_format(&document.uri);
+                let (session, _) = DocumentSession::open(&self.parser, document.text, format);
+                self.documents.insert(
+                    document.uri.clone(),
+                    Document {
+                        session,
+                        validations: HashMap::new(),
+                        lint: lint_config(&document.uri),
+                    },
+                );
+                document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let Some(document) = self.documents.get_mut(&params.text_document.uri) else {
+                    return Ok(Vec::new());
+                };
+                // Each change is relative to the text after the previous one
+                for change in &params.content_changes {
+                    let edit = text_edit(document.session.content(), change, self.utf32);
+                    document.session.apply_edits(&self.parser, &[edit]);
+                }
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(vec![publish(params.text_document.uri, Vec::new())]);
+            }
+            _ => return Ok(Vec::new()),
+        };
+
+        let diagnostics = match self.documents.get_mut(&uri) {
+            Some(document) => diagnostics(&self.parser, document, &uri, self.utf32),
+            None => Vec::new(),
+        };
+        Ok(vec![publish(uri, diagnostics)])
+    }
+
+    fn document_symbols(&self, document: &Document) -> Vec<DocumentSymbol> {
+        let session = &document.session;
+        let lines: Vec<&str> = session.content().lines().collect();
+        session
+            .diagrams()
+            .iter()
+            .map(|diagram| {
+                let (first, last) = block_lines(
+                    diagram.start_line,
+                    diagram.end_line,
+                    session.format(),
+                    lines.len(),
+                );
+                let declaration = diagram.start_line.saturating_sub(1) as u32;
+                #[allow(deprecated)]
+                DocumentSymbol {
+                    name: diagram.name.clone().unwrap_or_else(|| diagram.id.clone()),
+                    detail: Some(diagram.diagram_type.to_string()),
+                    kind: SymbolKind::MODULE,
+                    tags: None,
+                    deprecated: None,
+                    range: Range::new(
+                        lsp_types::Position::new(first, 0),
+                        lsp_types::Position::new(
+                            last,
+                            line_length(&lines, last as usize, self.utf32),
+                        ),
+                    ),
+                    selection_range: Range::new(
+                        lsp_types::Position::new(declaration, 0),
+                        lsp_types::Position::new(
+                            declaration,
+                            line_length(&lines, declaration as usize, self.utf32),
+                        ),
+                    ),
+                    children: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Quick fixes for diagnostics on the requested lines
+    fn code_actions(
+        &self,
+        document: &Document,
+        uri: &Uri,
+        range: Range,
+    ) -> Vec<CodeActionOrCommand> {
+        let lines: Vec<&str> = document.session.content().lines().collect();
+        let mut errors: Vec<&SyntaxError> = document
+            .validations
+            .values()
+            .flatten()
+            .filter(|error| {
+                let line = error.line.saturating_sub(1) as u32;
+                (range.start.line..=range.end.line).contains(&line)
+            })
+            .collect();
+        errors.sort_by_key(|error| (error.line, error.column));
+
+        let mut actions = Vec::new();
+        for error in errors {
+            for (index, fix) in error.fixes.iter().enumerate() {
+                let edits = fix
+                    .edits
+                    .iter()
+                    .map(|edit| lsp_types::TextEdit {
+                        range: Range::new(
+                            lsp_position(&lines, edit.start.line, edit.start.column, self.utf32),
+                            lsp_position(&lines, edit.end.line, edit.end.column, self.utf32),
+                        ),
+                        new_text: edit.text.clone(),
+                    })
+                    .collect();
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title.clone(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), edits)])),
+                        ..WorkspaceEdit::default()
+                    }),
+                    is_preferred: Some(index == 0),
+                    ..CodeAction::default()
+                }));
+            }
+        }
+        actions
+    }
+
+    /// Replace the whole document when any diagram in it changes
+    fn format(&self, document: &Document, options: &FormatOptions) -> Vec<lsp_types::TextEdit> {
+        let session = &document.session;
+        let content = session.content();
+        let formatted =
+            formatter::format_document(&self.parser, content, session.format(), options);
+        if formatted.content == content {
+            return Vec::new();
+        }
+        let lines: Vec<&str> = content.lines().collect();
+        let end = if content.ends_with('\n') || lines.is_empty() {
+            lsp_types::Position::new(lines.len() as u32, 0)
+        } else {
+            let last = lines.len() - 1;
+            lsp_types::Position::new(last as u32, line_length(&lines, last, self.utf32))
+        };
+        vec![lsp_types::TextEdit {
+            range: Range::new(lsp_types::Position::new(0, 0), end),
+            new_text: formatted.content,
+        }]
+    }
+
+    fn completions(
+        &self,
+        document: &Document,
+        position: lsp_types::Position,
+    ) -> Vec<CompletionItem> {
+        let session = &document.session;
+        let line = position.line as usize + 1;
+        let diagram = session
+            .diagrams()
+            .iter()
+            .find(|diagram| diagram.start_line <= line && line <= diagram.end_line);
+
+        let Some(diagram) = diagram.filter(|diagram| diagram.start_line < line) else {
+            // On a declaration line, or inside a fence that has no diagram yet
+            let previous = session
+                .content()
+                .lines()
+                .nth(line.saturating_sub(2))
+                .unwrap_or("");
+            let in_empty_fence =
+                session.format() == ContentFormat::Markdown && fence::opens_diagram(previous);
+            if diagram.is_none() && !in_empty_fence && session.format() == ContentFormat::Markdown {
+                return Vec::new();
+            }
+            return DECLARATIONS
+                .iter()
+                .map(|(label, detail)| CompletionItem {
+                    label: label.to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    detail: Some(detail.to_string()),
+                    ..CompletionItem::default()
+                })
+                .collect();
+        };
+
+        let (keywords, ids, id_detail): (&[&str], Vec<String>, &str) = match diagram.diagram_type {
+            DiagramType::Flowchart => (
+                FLOWCHART_KEYWORDS,
+                flowchart::parse(&diagram.content, 1)
+                    .ast
+                    .nodes
+                    .into_iter()
+                    .map(|node| node.id)
+                    .collect(),
+                "node",
+            ),
+            DiagramType::Sequence => (
+                SEQUENCE_KEYWORDS,
+                sequence::parse(&diagram.content, 1)
+                    .ast
+                    .participants
+                    .into_iter()
+                    .map(|participant| participant.id)
+                    .collect(),
+                "participant",
+            ),
+            DiagramType::Class => (
+                CLASS_KEYWORDS,
+                class_diagram::parse(&diagram.content, 1)
+                    .ast
+                    .classes
+                    .into_iter()
+                    .map(|class| class.name)
+                    .collect(),
+                "class",
+            ),
+            DiagramType::State => (
+                STATE_KEYWORDS,
+                state_diagram::parse(&diagram.content, 1)
+                    .ast
+                    .states
+                    .into_iter()
+                    .filter(|state| !state.id.contains("[*]"))
+                    .map(|state| state.id)
+                    .collect(),
+                "state",
+            ),
+            _ => (&[], Vec::new(), ""),
+        };
+
+        let mut items: Vec<CompletionItem> = ids
+            .into_iter()
+            .map(|id| CompletionItem {
+                label: id,
+                kind: Some(CompletionItemKind::VARIABLE),
+                detail: Some(id_detail.to_string()),
+                ..CompletionItem::default()
+            })
+            .collect();
+        items.extend(keywords.iter().map(|keyword| CompletionItem {
+            label: keyword.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            ..CompletionItem::default()
+        }));
+        items
+    }
+}
+
+fn content_format(uri: &Uri) -> ContentFormat {
+    FileType::from_path(Path::new(uri.path().as_str())).content_format()
+}
+
+/// The `.parch.json` nearest to a `file:` document; stderr ends up in the editor's server log
+fn lint_config(uri: &Uri) -> LintConfig {
+    if !uri
+        .scheme()
+        .is_some_and(|scheme| scheme.eq_lowercase("file"))
+    {
+        return LintConfig::default();
+    }
+    let Ok(path) = uri.path().as_estr().decode().into_string() else {
+        return LintConfig::default();
+    };
+    LintConfig::for_document(Path::new(path.as_ref())).unwrap_or_else(|error| {
+        eprintln!("parch: {}", error);
+        LintConfig::default()
+    })
+}
+
+fn publish(uri: Uri, diagnostics: Vec<Diagnostic>) -> Notification {
+    Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        },
+    )
+}
+
+/// Validate every diagram, reusing results for diagrams that did not change or move
+fn diagnostics(
+    parser: &MermaidParser,
+    document: &mut Document,
+    uri: &Uri,
+    utf32: bool,
+) -> Vec<Diagnostic> {
+    let session = &document.session;
+    let mut validations = HashMap::new();
+    for diagram in session.diagrams() {
+        let key = (diagram.content.clone(), diagram.start_line);
+        let errors = document.validations.remove(&key).unwrap_or_else(|| {
+            parser
+                .validate_diagram_with(&diagram.content, diagram.start_line, &document.lint)
+                .errors
+        });
+        validations.insert(key, errors);
+    }
+    document.validations = validations;
+
+    let lines: Vec<&str> = session.content().lines().collect();
+    let mut diagnostics: Vec<Diagnostic> = document
+        .validations
+        .values()
+        .flatten()
+        .map(|error| {
+            let start = lsp_position(&lines, error.line, error.column, utf32);
+            let end = lsp_types::Position::new(
+                start.line,
+                line_length(&lines, start.line as usize, utf32).max(start.character),
+            );
+            Diagnostic {
+                range: Range::new(start, end),
+                severity: Some(match error.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                    Severity::Info => DiagnosticSeverity::INFORMATION,
+                }),
+                code: Some(NumberOrString::String(error.code.to_string())),
+                source: Some("parch".to_string()),
+                message: error.message.clone(),
+                related_information: error.related.map(|related| {
+                    let position = lsp_position(&lines, related.line, related.column, utf32);
+                    vec![DiagnosticRelatedInformation {
+                        location: Location::new(uri.clone(), Range::new(position, position)),
+                        message: "Expected the closer here".to_string(),
+                    }]
+                }),
+                ..Diagnostic::default()
+            }
+        })
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| {
+        (
+            diagnostic.range.start.line,
+            diagnostic.range.start.character,
+        )
+    });
+    diagnostics
+}
+
+fn folding_ranges(session: &DocumentSession) -> Vec<FoldingRange> {
+    let line_count = session.content().lines().count();
+    let mut ranges = Vec::new();
+    for diagram in session.diagrams() {
+        let (first, last) = block_lines(
+            diagram.start_line,
+            diagram.end_line,
+            session.format(),
+            line_count,
+        );
+        ranges.push(FoldingRange {
+            start_line: first,
+            end_line: last,
+            kind: Some(FoldingRangeKind::Region),
+            collapsed_text: Some(diagram.diagram_type.to_string()),
+            ..FoldingRange::default()
+        });
+        ranges.extend(comment_ranges(&diagram.content, diagram.start_line));
+    }
+    ranges.retain(|range| range.end_line > range.start_line);
+    ranges
+}
+
+/// Runs of whole-line comments, folded down to their first line
+fn comment_ranges(content: &str, start_line: usize) -> Vec<FoldingRange> {
+    let mut ranges: Vec<FoldingRange> = Vec::new();
+    for comment in comments::scan(content, start_line) {
+        if comment.trailing {
+            continue;
+        }
+        let line = comment.position.line.saturating_sub(1) as u32;
+        match ranges.last_mut() {
+            Some(range) if range.end_line + 1 == line => range.end_line = line,
+            _ => ranges.push(FoldingRange {
+                start_line: line,
+                end_line: line,
+                kind: Some(FoldingRangeKind::Comment),
+                collapsed_text: Some(format!("%% {}", comment.text)),
+                ..FoldingRange::default()
+            }),
+        }
+    }
+    ranges
+}
+
+/// Zero-based first and last line of a diagram, including its fences in Markdown
+fn block_lines(
+    start_line: usize,
+    end_line: usize,
+    format: ContentFormat,
+    line_count: usize,
+) -> (u32, u32) {
+    let last_line = line_count.saturating_sub(1);
+    let (first, last) = match format {
+        ContentFormat::Markdown => (start_line.saturating_sub(2), end_line),
+        ContentFormat::Raw => (start_line.saturating_sub(1), end_line.saturating_sub(1)),
+    };
+    (first.min(last_line) as u32, last.min(last_line) as u32)
+}
+
+/// Length of a zero-based line in the negotiated encoding
+fn line_length(lines: &[&str], line: usize, utf32: bool) -> u32 {
+    let text = lines.get(line).copied().unwrap_or("");
+    if utf32 {
+        text.chars().count() as u32
+    } else {
+        text.encode_utf16().count() as u32
+    }
+}
+
+/// Convert a 1-based line and character column into an LSP position
+fn lsp_position(lines: &[&str], line: usize, column: usize, utf32: bool) -> lsp_types::Position {
+    let line = line.saturating_sub(1);
+    let chars = column.saturating_sub(1);
+    let character = if utf32 {
+        chars
+    } else {
+        let text = lines.get(line).copied().unwrap_or("");
+        text.chars().take(chars).map(char::len_utf16).sum::<usize>()
+            + chars.saturating_sub(text.chars().count())
+    };
+    lsp_types::Position::new(line as u32, character as u32)
+}
+
+/// Convert an LSP position into a 1-based line and character column
+fn session_position(content: &str, position: lsp_types::Position, utf32: bool) -> Position {
+    let column = if utf32 {
+        position.character as usize
+    } else {
+        let text = content.lines().nth(position.line as usize).unwrap_or("");
+        let mut units = 0;
+        text.chars()
+            .take_while(|ch| {
+                units += ch.len_utf16();
+                units <= position.character as usize
+            })
+            .count()
+    };
+    Position {
+        line: position.line as usize + 1,
+        column: column + 1,
+    }
+}
+
+fn text_edit(content: &str, change: &TextDocumentContentChangeEvent, utf32: bool) -> TextEdit {
+    match change.range {
+        Some(range) => TextEdit {
+            start: session_position(content, range.start, utf32),
+            end: session_position(content, range.end, utf32),
+            text: change.text.clone(),
+        },
+        // A change without a range replaces the whole document
+        None => TextEdit {
+            start: Position { line: 1, column: 1 },
+            end: Position {
+                line: usize::MAX,
+                column: 1,
+            },
+            text: change.text.clone(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{
+        ClientCapabilities, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
+        TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+        VersionedTextDocumentIdentifier,
+    };
+    use std::str::FromStr;
+    use std::thread;
+
+    fn request<R: LspRequest>(
+        client: &Connection,
+        id: i32,
+        params: R::Params,
+    ) -> serde_json::Value {
+        client
+            .sender
+            .send(Message::Request(Request::new(
+                id.into(),
+                R::METHOD.to_string(),
+                params,
+            )))
+            .unwrap();
+        match client.receiver.recv().unwrap() {
+            Message::Response(response) => response.result.unwrap(),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    fn notify<N: LspNotification>(client: &Connection, params: N::Params) {
+        client
+            .sender
+            .send(Message::Notification(Notification::new(
+                N::METHOD.to_string(),
+                params,
+            )))
+            .unwrap();
+    }
+
+    fn diagnostics(client: &Connection) -> Vec<Diagnostic> {
+        match client.receiver.recv().unwrap() {
+            Message::Notification(notification) => {
+                assert_eq!(notification.method, PublishDiagnostics::METHOD);
+                serde_json::from_value::<PublishDiagnosticsParams>(notification.params)
+                    .unwrap()
+                    .diagnostics
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_language_server_session() {
+        let (server, client) = Connection::memory();
+        let handle = thread::spawn(move || serve_connection(&server).unwrap());
+
+        #[allow(deprecated)]
+        let initialize = InitializeParams {
+            capabilities: ClientCapabilities::default(),
+            ..InitializeParams::default()
+        };
+        let result = request::<lsp_types::request::Initialize>(&client, 1, initialize);
+        assert_eq!(result["capabilities"]["positionEncoding"], "utf-16");
+        notify::<lsp_types::notification::Initialized>(&client, lsp_types::InitializedParams {});
+
+        let uri = Uri::from_str("file:///docs/design.md").unwrap();
+        let text = "# Design\n\n```mermaid\ngraph TD\n  A[Start] --> B\n```\n\n```mermaid\nsequenceDiagram\n  Alice->>Bob: hi\n```\n";
+        notify::<DidOpenTextDocument>(
+            &client,
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "markdown".to_string(),
+                    1,
+                    text.to_string(),
+                ),
+            },
+        );
+        assert!(diagnostics(&client).is_empty());
+
+        // Break the flowchart: `B` becomes `B(`
+        notify::<DidChangeTextDocument>(
+            &client,
+            DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: Some(Range::new(
+                        lsp_types::Position::new(4, 16),
+                        lsp_types::Position::new(4, 16),
+                    )),
+                    range_length: None,
+                    text: "(".to_string(),
+                }],
+            },
+        );
+        let published = diagnostics(&client);
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].range.start.line, 4);
+        assert_eq!(published[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            published[0].code,
+            Some(NumberOrString::String("MMD0010".to_string()))
+        );
+
+        let symbols: Vec<DocumentSymbol> =
+            serde_json::from_value(request::<DocumentSymbolRequest>(
+                &client,
+                2,
+                DocumentSymbolParams {
+                    text_document: TextDocumentIdentifier::new(uri.clone()),
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                },
+            ))
+            .unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[1].detail.as_deref(), Some("sequence"));
+        assert_eq!(
+            (symbols[1].range.start.line, symbols[1].range.end.line),
+            (7, 10)
+        );
+
+        let folds: Vec<FoldingRange> = serde_json::from_value(request::<FoldingRangeRequest>(
+            &client,
+            3,
+            FoldingRangeParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        ))
+        .unwrap();
+        assert_eq!((folds[0].start_line, folds[0].end_line), (2, 5));
+
+        let completion = |line, character| -> Vec<String> {
+            let value = request::<Completion>(
+                &client,
+                4,
+                CompletionParams {
+                    text_document_position: TextDocumentPositionParams::new(
+                        TextDocumentIdentifier::new(uri.clone()),
+                        lsp_types::Position::new(line, character),
+                    ),
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                    context: None,
+                },
+            );
+            let items: Vec<CompletionItem> = serde_json::from_value(value).unwrap();
+            items.into_iter().map(|item| item.label).collect()
+        };
+        let labels = completion(9, 2);
+        assert!(labels.contains(&"Alice".to_string()));
+        assert!(labels.contains(&"participant".to_string()));
+        assert!(completion(8, 0).contains(&"sequenceDiagram".to_string()));
+        assert!(completion(0, 0).is_empty());
+
+        let actions: Vec<CodeAction> = serde_json::from_value(request::<CodeActionRequest>(
+            &client,
+            5,
+            CodeActionParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                range: Range::new(
+                    lsp_types::Position::new(4, 0),
+                    lsp_types::Position::new(4, 0),
+                ),
+                context: Default::default(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        ))
+        .unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].title, "Insert missing ')'");
+        let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+        assert_eq!(edits[0].range.start, lsp_types::Position::new(4, 17));
+        assert_eq!(edits[0].new_text, ")");
+
+        // The broken flowchart is left alone while the sequence diagram is reindented
+        let edits: Vec<lsp_types::TextEdit> = serde_json::from_value(request::<Formatting>(
+            &client,
+            6,
+            DocumentFormattingParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                options: lsp_types::FormattingOptions {
+                    tab_size: 4,
+                    insert_spaces: true,
+                    ..Default::default()
+                },
+                work_done_progress_params: Default::default(),
+            },
+        ))
+        .unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.end, lsp_types::Position::new(11, 0));
+        assert!(edits[0].new_text.contains("  A[Start] --> B(\n"));
+        assert!(edits[0]
+            .new_text
+            .contains("sequenceDiagram\n    Alice->>Bob: hi\n"));
+
+        client
+            .sender
+            .send(Message::Request(Request::new(
+                7.into(),
+                "shutdown".to_string(),
+                (),
+            )))
+            .unwrap();
+        client.receiver.recv().unwrap();
+        notify::<lsp_types::notification::Exit>(&client, ());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_comment_folding() {
+        let content = "graph TD\n  %% Inputs\n  %% from the form\n  A --> B %% trailing\n  %% single\n  B --> C";
+        let ranges = comment_ranges(content, 4);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!((ranges[0].start_line, ranges[0].end_line), (4, 5));
+        assert_eq!(ranges[0].kind, Some(FoldingRangeKind::Comment));
+        assert_eq!(ranges[0].collapsed_text.as_deref(), Some("%% Inputs"));
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        let content = "A 😀 B";
+        let position = session_position(content, lsp_types::Position::new(0, 5), false);
+        assert_eq!(position, Position { line: 1, column: 5 });
+        let lines = vec![content];
+        assert_eq!(
+            lsp_position(&lines, 1, 5, false),
+            lsp_types::Position::new(0, 5)
+        );
+        assert_eq!(
+            lsp_position(&lines, 1, 5, true),
+            lsp_types::Position::new(0, 4)
+        );
+    }
+}
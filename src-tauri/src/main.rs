@@ -2,5 +2,27 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    #[cfg(windows)]
+    if std::env::args_os().len() > 1 {
+        attach_parent_console();
+    }
+    // `parch check|export|stats ...` runs headless; anything else starts the GUI
+    if let Some(code) = parch_lib::run_cli() {
+        std::process::exit(code);
+    }
     parch_lib::run()
 }
+
+/// Release builds have no console of their own, so CLI output goes to the terminal that
+/// started them. Fails harmlessly when there is none, e.g. when opened from Explorer.
+#[cfg(windows)]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
@@ -1,12 +1,47 @@
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use uuid::Uuid;
+use std::sync::LazyLock;
+
+mod balance;
+pub mod class_diagram;
+pub mod codes;
+pub mod comments;
+pub mod config;
+mod diagram_type;
+pub mod fence;
+pub mod fixes;
+pub mod flowchart;
+pub mod formatter;
+pub mod identity;
+pub mod lint;
+pub mod sequence;
+pub mod session;
+mod source;
+pub mod state_diagram;
+mod structure;
+
+pub use codes::{DiagnosticCode, Severity};
+pub use config::DiagramConfig;
+pub use diagram_type::{Detection, DiagramType};
+pub use fence::{Fence, FenceKind};
+pub use fixes::Fix;
+pub use lint::LintConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedDiagram {
     pub id: String,
-    pub diagram_type: String,
+    /// Explicit name from a `%% id:` comment or fence attribute
+    pub name: Option<String>,
+    /// Whitespace-insensitive hash of the diagram content
+    pub fingerprint: String,
+    pub diagram_type: DiagramType,
+    /// Frontmatter and `%%{init}%%` configuration, when the diagram has any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<DiagramConfig>,
+    /// The Markdown fence or HTML block around the diagram, with its attributes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fence: Option<Fence>,
     pub content: String,
     pub start_line: usize,
     pub end_line: usize,
@@ -19,7 +54,53 @@ pub struct SyntaxError {
     pub line: usize,
     pub column: usize,
     pub message: String,
-    pub severity: String, // "error", "warning", "info"
+    pub severity: Severity,
+    /// Stable `MMDnnnn` identifier, see [`DiagnosticCode`]
+    pub code: DiagnosticCode,
+    /// Secondary location, e.g. where a missing closing bracket was expected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub related: Option<Position>,
+    /// Suggested edits that resolve this diagnostic, best first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixes: Vec<Fix>,
+}
+
+impl SyntaxError {
+    /// A diagnostic with the code's default severity
+    pub fn new(
+        code: DiagnosticCode,
+        line: usize,
+        column: usize,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+            severity: code.default_severity(),
+            code,
+            related: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// A 1-indexed line/column location inside the source document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Typed AST for a single diagram along with the errors found while building it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AstResult<T> {
+    pub ast: T,
+    pub errors: Vec<SyntaxError>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +109,30 @@ pub struct ValidationResult {
     pub errors: Vec<SyntaxError>,
 }
 
+/// How diagrams are embedded in a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentFormat {
+    /// Markdown with fenced ```` ```mermaid ```` blocks
+    Markdown,
+    /// A bare diagram file; several diagrams may be separated by `---` lines
+    Raw,
+}
+
+/// A diagram body located in the source, before detection and validation
+pub(crate) struct DiagramBlock {
+    pub(crate) content: String,
+    pub(crate) start_line: usize,
+    pub(crate) end_line: usize,
+    pub(crate) fence: Option<Fence>,
+}
+
+impl DiagramBlock {
+    /// The fence info string or HTML attributes, where an explicit `#name` may be
+    pub(crate) fn fence_info(&self) -> Option<&str> {
+        self.fence.as_ref().map(|fence| fence.info.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParseResult {
     pub diagrams: Vec<ParsedDiagram>,
@@ -38,126 +143,30 @@ pub struct ParseResult {
 pub struct MermaidParser {
     #[allow(dead_code)]
     code_block_regex: Regex,
-    diagram_type_patterns: HashMap<String, Regex>,
 }
 
 impl MermaidParser {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
         let code_block_regex = Regex::new(r"```(?:mermaid|mmd)\s*\n([\s\S]*?)\n```")?;
 
-        let mut diagram_type_patterns = HashMap::new();
-
-        // Define patterns for different Mermaid diagram types
-        diagram_type_patterns.insert(
-            "flowchart".to_string(),
-            Regex::new(r"^\s*(?:graph|flowchart)\s+(?:TD|TB|BT|RL|LR|TOP|BOTTOM|LEFT|RIGHT)")?,
-        );
-        diagram_type_patterns.insert("sequence".to_string(), Regex::new(r"^\s*sequenceDiagram")?);
-        diagram_type_patterns.insert("class".to_string(), Regex::new(r"^\s*classDiagram")?);
-        diagram_type_patterns.insert(
-            "state".to_string(),
-            Regex::new(r"^\s*stateDiagram(?:-v2)?")?,
-        );
-        diagram_type_patterns.insert("er".to_string(), Regex::new(r"^\s*erDiagram")?);
-        diagram_type_patterns.insert("gantt".to_string(), Regex::new(r"^\s*gantt")?);
-        diagram_type_patterns.insert("pie".to_string(), Regex::new(r"^\s*pie(?:\s+title)?")?);
-        diagram_type_patterns.insert("journey".to_string(), Regex::new(r"^\s*journey")?);
-        diagram_type_patterns.insert("gitgraph".to_string(), Regex::new(r"^\s*gitgraph")?);
-        diagram_type_patterns.insert(
-            "requirement".to_string(),
-            Regex::new(r"^\s*requirementDiagram")?,
-        );
-        diagram_type_patterns.insert("c4context".to_string(), Regex::new(r"^\s*C4Context")?);
-        diagram_type_patterns.insert("mindmap".to_string(), Regex::new(r"^\s*mindmap")?);
-        diagram_type_patterns.insert("timeline".to_string(), Regex::new(r"^\s*timeline")?);
-
-        Ok(MermaidParser {
-            code_block_regex,
-            diagram_type_patterns,
-        })
+        Ok(MermaidParser { code_block_regex })
     }
 
-    /// Parse content to extract all Mermaid diagrams
-    pub fn parse_content(&self, content: &str) -> ParseResult {
+    /// Parse content to extract all Mermaid diagrams, using the given source format
+    pub fn parse_content_as(&self, content: &str, format: ContentFormat) -> ParseResult {
         let start_time = std::time::Instant::now();
-        let mut diagrams = Vec::new();
         let mut total_errors = 0;
-
-        let lines: Vec<&str> = content.lines().collect();
-        let mut in_code_block = false;
-        let mut current_diagram_lines = Vec::new();
-        let mut start_line = 0;
-        let mut _block_start_line = 0;
-
-        for (line_idx, line) in lines.iter().enumerate() {
-            let trimmed = line.trim();
-
-            // Check for start of Mermaid code block
-            if trimmed.starts_with("```mermaid") || trimmed.starts_with("```mmd") {
-                in_code_block = true;
-                _block_start_line = line_idx + 1; // 1-indexed
-                start_line = line_idx + 2; // Start after the opening ```
-                current_diagram_lines.clear();
-                continue;
-            }
-
-            // Check for end of code block
-            if in_code_block && trimmed == "```" {
-                if !current_diagram_lines.is_empty() {
-                    let diagram_content = current_diagram_lines.join("\n");
-                    let diagram_type = self.detect_diagram_type(&diagram_content);
-                    let validation = self.validate_diagram(&diagram_content, start_line);
-
-                    if !validation.is_valid {
-                        total_errors += validation.errors.len();
-                    }
-
-                    let diagram = ParsedDiagram {
-                        id: Uuid::new_v4().to_string(),
-                        diagram_type,
-                        content: diagram_content,
-                        start_line,
-                        end_line: line_idx, // 1-indexed
-                        has_error: !validation.is_valid,
-                        error_message: validation.errors.first().map(|e| e.message.clone()),
-                    };
-
-                    diagrams.push(diagram);
-                }
-
-                in_code_block = false;
-                current_diagram_lines.clear();
-                continue;
-            }
-
-            // Collect diagram content
-            if in_code_block {
-                current_diagram_lines.push(*line);
-            }
-        }
-
-        // Handle unclosed code block
-        if in_code_block && !current_diagram_lines.is_empty() {
-            let diagram_content = current_diagram_lines.join("\n");
-            let diagram_type = self.detect_diagram_type(&diagram_content);
-            let validation = self.validate_diagram(&diagram_content, start_line);
-
-            if !validation.is_valid {
-                total_errors += validation.errors.len();
-            }
-
-            let diagram = ParsedDiagram {
-                id: Uuid::new_v4().to_string(),
-                diagram_type,
-                content: diagram_content,
-                start_line,
-                end_line: lines.len(),
-                has_error: !validation.is_valid,
-                error_message: validation.errors.first().map(|e| e.message.clone()),
-            };
-
-            diagrams.push(diagram);
-        }
+        let mut seen_ids = HashMap::new();
+
+        let diagrams = self
+            .blocks(content, format)
+            .into_iter()
+            .map(|block| {
+                let (diagram, error_count) = self.build_diagram(block, &mut seen_ids);
+                total_errors += error_count;
+                diagram
+            })
+            .collect();
 
         let parsing_time_ms = start_time.elapsed().as_millis();
 
@@ -168,172 +177,219 @@ impl MermaidParser {
         }
     }
 
-    /// Detect the type of Mermaid diagram from content
-    pub fn detect_diagram_type(&self, content: &str) -> String {
-        let first_line = content.lines().next().unwrap_or("").trim();
+    /// Split content into diagram blocks according to its source format
+    pub(crate) fn blocks(&self, content: &str, format: ContentFormat) -> Vec<DiagramBlock> {
+        match format {
+            ContentFormat::Markdown => fence::scan(content),
+            ContentFormat::Raw => self.split_raw_diagrams(content),
+        }
+    }
+
+    /// Validate a block and assign its ID; also returns the number of errors counted
+    pub(crate) fn build_diagram(
+        &self,
+        block: DiagramBlock,
+        seen_ids: &mut HashMap<String, usize>,
+    ) -> (ParsedDiagram, usize) {
+        let diagram_type = self.detect_diagram_type(&block.content);
+        let (config, _) = config::Preamble::scan(&block.content).config(&block.content, 1);
+        let validation = self.validate_diagram(&block.content, block.start_line);
+        let error_count = if validation.is_valid {
+            0
+        } else {
+            validation.errors.len()
+        };
+
+        let name = identity::explicit_name(&block.content, block.fence_info());
+        let fingerprint = identity::fingerprint(&block.content);
+        let id = identity::base_id(name.as_deref(), diagram_type, &fingerprint, seen_ids);
+
+        let diagram = ParsedDiagram {
+            id,
+            name,
+            fingerprint,
+            diagram_type,
+            config,
+            fence: block.fence,
+            content: block.content,
+            start_line: block.start_line,
+            end_line: block.end_line,
+            has_error: !validation.is_valid,
+            error_message: validation.errors.first().map(|e| e.message.clone()),
+        };
+        (diagram, error_count)
+    }
 
-        for (diagram_type, pattern) in &self.diagram_type_patterns {
-            if pattern.is_match(first_line) {
-                return diagram_type.clone();
+    /// Split a bare `.mmd`/`.mermaid` file into diagrams separated by `---` lines.
+    ///
+    /// A `---` at the start of a diagram followed by a `key:` line opens YAML frontmatter,
+    /// which belongs to the diagram instead of separating it.
+    fn split_raw_diagrams(&self, content: &str) -> Vec<DiagramBlock> {
+        let mut blocks = Vec::new();
+        let mut current: Vec<(usize, &str)> = Vec::new();
+
+        let mut flush = |current: &mut Vec<(usize, &str)>| {
+            // Leading and trailing blank lines don't belong to the diagram
+            let first = current.iter().position(|(_, line)| !line.trim().is_empty());
+            let last = current
+                .iter()
+                .rposition(|(_, line)| !line.trim().is_empty());
+            if let (Some(first), Some(last)) = (first, last) {
+                let body = &current[first..=last];
+                blocks.push(DiagramBlock {
+                    content: body
+                        .iter()
+                        .map(|(_, line)| *line)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    start_line: body[0].0,
+                    end_line: body[body.len() - 1].0,
+                    fence: None,
+                });
             }
-        }
+            current.clear();
+        };
 
-        // Fallback detection for common patterns
-        let first_line_lower = first_line.to_lowercase();
-        if first_line_lower.contains("graph") || first_line_lower.contains("flowchart") {
-            return "flowchart".to_string();
-        }
-        if first_line_lower.contains("sequencediagram") {
-            return "sequence".to_string();
-        }
-        if first_line_lower.contains("classdiagram") {
-            return "class".to_string();
-        }
-        if first_line_lower.contains("statediagram") {
-            return "state".to_string();
-        }
-        if first_line_lower.contains("erdiagram") {
-            return "er".to_string();
-        }
-        if first_line_lower.contains("gantt") {
-            return "gantt".to_string();
-        }
-        if first_line_lower.contains("pie") {
-            return "pie".to_string();
+        let lines: Vec<&str> = content.lines().collect();
+        let mut in_frontmatter = false;
+        for (line_idx, line) in lines.iter().enumerate() {
+            let starts_diagram = current.iter().all(|(_, line)| line.trim().is_empty());
+            if line.trim() != "---" {
+                current.push((line_idx + 1, line));
+            } else if in_frontmatter {
+                in_frontmatter = false;
+                current.push((line_idx + 1, line));
+            } else if starts_diagram && opens_frontmatter(&lines[line_idx + 1..]) {
+                in_frontmatter = true;
+                current.push((line_idx + 1, line));
+            } else {
+                flush(&mut current);
+            }
         }
+        flush(&mut current);
+
+        blocks
+    }
+
+    /// Detect the type of Mermaid diagram from its first statement, after any
+    /// frontmatter, directives and comments
+    pub fn detect_diagram_type(&self, content: &str) -> DiagramType {
+        self.detect(content).diagram_type
+    }
 
-        "unknown".to_string()
+    /// Detect the diagram type along with the declaration keyword, suggesting a known
+    /// keyword when the declaration is close to one
+    pub fn detect(&self, content: &str) -> Detection {
+        Detection::of(config::declaration(content).map(|(_, line)| line))
     }
 
-    /// Validate Mermaid diagram syntax
+    /// Validate Mermaid diagram syntax and run the default lint rules
     pub fn validate_diagram(&self, content: &str, start_line: usize) -> ValidationResult {
+        self.validate_diagram_with(content, start_line, &LintConfig::default())
+    }
+
+    /// Validate Mermaid diagram syntax and run the lint rules enabled in `lint`
+    pub fn validate_diagram_with(
+        &self,
+        content: &str,
+        start_line: usize,
+        lint: &LintConfig,
+    ) -> ValidationResult {
         let mut errors = Vec::new();
 
         if content.trim().is_empty() {
-            errors.push(SyntaxError {
-                line: start_line,
-                column: 1,
-                message: "Empty diagram content".to_string(),
-                severity: "error".to_string(),
-            });
+            errors.push(SyntaxError::new(
+                DiagnosticCode::EmptyDiagram,
+                start_line,
+                1,
+                "Empty diagram content",
+            ));
             return ValidationResult {
                 is_valid: false,
                 errors,
             };
         }
 
-        // Basic syntax validation
-        let lines: Vec<&str> = content.lines().collect();
+        // Frontmatter and directives are checked here, then hidden from the statement checks
+        let preamble = config::Preamble::scan(content);
+        let (_, config_errors) = preamble.config(content, start_line);
+        errors.extend(config_errors);
+        let body = preamble.mask(content);
+        // Comments only matter to lint suppressions; every other check sees blank space
+        let code = comments::strip(&body);
+        let content = code.as_ref();
 
         // Check for valid diagram declaration
-        if let Some(first_line) = lines.first() {
-            let trimmed = first_line.trim();
-            if !self.is_valid_diagram_declaration(trimmed) {
-                errors.push(SyntaxError {
-                    line: start_line,
-                    column: 1,
-                    message: format!("Invalid diagram declaration: '{}'", trimmed),
-                    severity: "error".to_string(),
-                });
+        let detection = self.detect(content);
+        match preamble.declaration(content) {
+            Some((index, declaration)) if detection.diagram_type == DiagramType::Unknown => {
+                let message = match &detection.suggestion {
+                    Some(suggestion) => format!(
+                        "Invalid diagram declaration: '{}'. Did you mean '{}'?",
+                        declaration, suggestion
+                    ),
+                    None => format!("Invalid diagram declaration: '{}'", declaration),
+                };
+                errors.push(SyntaxError::new(
+                    DiagnosticCode::InvalidDeclaration,
+                    start_line + index,
+                    1,
+                    message,
+                ));
             }
+            Some(_) => {}
+            None => errors.push(SyntaxError::new(
+                DiagnosticCode::InvalidDeclaration,
+                start_line + content.lines().count().saturating_sub(1),
+                1,
+                "Missing diagram declaration",
+            )),
         }
 
-        // Check for common syntax issues
-        for (line_idx, line) in lines.iter().enumerate() {
-            let line_number = start_line + line_idx;
-
-            // Check for unmatched brackets
-            if self.has_unmatched_brackets(line) {
-                errors.push(SyntaxError {
-                    line: line_number,
-                    column: 1,
-                    message: "Unmatched brackets detected".to_string(),
-                    severity: "warning".to_string(),
-                });
-            }
-
-            // Check for invalid characters in node IDs
-            if self.has_invalid_node_id(line) {
-                errors.push(SyntaxError {
-                    line: line_number,
-                    column: 1,
-                    message: "Invalid characters in node ID".to_string(),
-                    severity: "warning".to_string(),
-                });
-            }
-        }
+        // Balance brackets, quotes and blocks across the whole diagram
+        let diagram_type = detection.diagram_type;
+        let balance_errors = balance::check(content, start_line, diagram_type);
+
+        // Grammar errors at an already reported opener would only repeat it
+        let declared = !errors
+            .iter()
+            .any(|e| e.code == DiagnosticCode::InvalidDeclaration);
+        let grammar_errors = match diagram_type {
+            _ if !declared => Vec::new(),
+            DiagramType::Flowchart => flowchart::parse(content, start_line).errors,
+            DiagramType::Sequence => sequence::parse(content, start_line).errors,
+            DiagramType::Class => class_diagram::parse(content, start_line).errors,
+            DiagramType::State => state_diagram::parse(content, start_line).errors,
+            other => structure::check(content, start_line, other),
+        };
+        let grammar_errors: Vec<SyntaxError> = grammar_errors
+            .into_iter()
+            .filter(|e| {
+                !balance_errors
+                    .iter()
+                    .any(|b| (b.line, b.column) == (e.line, e.column))
+            })
+            .collect();
+        errors.extend(balance_errors);
+        errors.extend(grammar_errors);
+        fixes::suggest(content, start_line, &mut errors);
+
+        errors.extend(lint::lint_diagram(&body, start_line, diagram_type, lint));
 
         ValidationResult {
-            is_valid: errors.iter().all(|e| e.severity != "error"),
+            is_valid: !errors.iter().any(SyntaxError::is_error),
             errors,
         }
     }
 
-    /// Check if the line contains a valid diagram declaration
-    fn is_valid_diagram_declaration(&self, line: &str) -> bool {
-        for pattern in self.diagram_type_patterns.values() {
-            if pattern.is_match(line) {
-                return true;
-            }
-        }
-
-        // Additional checks for common variations
-        let line_lower = line.to_lowercase();
-        line_lower.starts_with("graph")
-            || line_lower.starts_with("flowchart")
-            || line_lower.starts_with("sequencediagram")
-            || line_lower.starts_with("classdiagram")
-            || line_lower.starts_with("statediagram")
-            || line_lower.starts_with("erdiagram")
-            || line_lower.starts_with("gantt")
-            || line_lower.starts_with("pie")
-            || line_lower.starts_with("journey")
-            || line_lower.starts_with("gitgraph")
-    }
-
-    /// Check for unmatched brackets in a line
-    fn has_unmatched_brackets(&self, line: &str) -> bool {
-        let mut stack = Vec::new();
-        let brackets = [('(', ')'), ('[', ']'), ('{', '}')];
-
-        for ch in line.chars() {
-            for (open, close) in &brackets {
-                if ch == *open {
-                    stack.push(*close);
-                } else if ch == *close {
-                    if stack.pop() != Some(*close) {
-                        return true;
-                    }
-                }
-            }
-        }
-
-        !stack.is_empty()
-    }
-
-    /// Check for invalid characters in node IDs
-    fn has_invalid_node_id(&self, line: &str) -> bool {
-        // This is a simplified check - in practice, Mermaid has complex rules
-        // for node IDs depending on the diagram type
-        let node_id_regex = Regex::new(r"\b([A-Za-z_][A-Za-z0-9_]*)\s*[\[\(]").unwrap();
-
-        for captures in node_id_regex.captures_iter(line) {
-            if let Some(node_id) = captures.get(1) {
-                let id = node_id.as_str();
-                // Check for reserved keywords or invalid patterns
-                if id.len() > 50 || id.contains("--") || id.starts_with("__") {
-                    return true;
-                }
-            }
-        }
-
-        false
-    }
-
     /// Get statistics about the parsed content
-    pub fn get_parsing_stats(&self, content: &str) -> HashMap<String, serde_json::Value> {
+    pub fn get_parsing_stats(
+        &self,
+        content: &str,
+        format: ContentFormat,
+    ) -> HashMap<String, serde_json::Value> {
         let mut stats = HashMap::new();
-        let parse_result = self.parse_content(content);
+        let parse_result = self.parse_content_as(content, format);
 
         stats.insert(
             "total_diagrams".to_string(),
@@ -351,7 +407,7 @@ impl MermaidParser {
         // Count diagrams by type
         let mut type_counts = HashMap::new();
         for diagram in &parse_result.diagrams {
-            *type_counts.entry(diagram.diagram_type.clone()).or_insert(0) += 1;
+            *type_counts.entry(diagram.diagram_type).or_insert(0) += 1;
         }
 
         stats.insert(
@@ -363,6 +419,15 @@ impl MermaidParser {
     }
 }
 
+/// Whether the lines after a `---` look like YAML that is closed by another `---`
+fn opens_frontmatter(rest: &[&str]) -> bool {
+    static YAML_KEY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^\s*[A-Za-z_][\w-]*\s*:").unwrap());
+    let first = rest.iter().find(|line| !line.trim().is_empty());
+    first.is_some_and(|line| YAML_KEY.is_match(line))
+        && rest.iter().any(|line| line.trim() == "---")
+}
+
 impl Default for MermaidParser {
     fn default() -> Self {
         Self::new().expect("Failed to create MermaidParser")
@@ -384,9 +449,9 @@ graph TD
 ```
 "#;
 
-        let result = parser.parse_content(content);
+        let result = parser.parse_content_as(content, ContentFormat::Markdown);
         assert_eq!(result.diagrams.len(), 1);
-        assert_eq!(result.diagrams[0].diagram_type, "flowchart");
+        assert_eq!(result.diagrams[0].diagram_type, DiagramType::Flowchart);
         assert!(!result.diagrams[0].has_error);
     }
 
@@ -407,24 +472,110 @@ sequenceDiagram
 ```
 "#;
 
-        let result = parser.parse_content(content);
+        let result = parser.parse_content_as(content, ContentFormat::Markdown);
+        assert_eq!(result.diagrams.len(), 2);
+        assert_eq!(result.diagrams[0].diagram_type, DiagramType::Flowchart);
+        assert_eq!(result.diagrams[1].diagram_type, DiagramType::Sequence);
+    }
+
+    #[test]
+    fn test_parse_raw_diagrams() {
+        let parser = MermaidParser::new().unwrap();
+        let content = "graph TD\n    A --> B\n\n---\n\nsequenceDiagram\n    Alice->>Bob: Hello\n";
+
+        let result = parser.parse_content_as(content, ContentFormat::Raw);
+        assert_eq!(result.diagrams.len(), 2);
+        assert_eq!(result.diagrams[0].diagram_type, DiagramType::Flowchart);
+        assert_eq!(
+            (result.diagrams[0].start_line, result.diagrams[0].end_line),
+            (1, 2)
+        );
+        assert_eq!(result.diagrams[1].diagram_type, DiagramType::Sequence);
+        assert_eq!(result.diagrams[1].start_line, 6);
+
+        // Markdown mode finds nothing in a bare diagram
+        assert!(parser
+            .parse_content_as(content, ContentFormat::Markdown)
+            .diagrams
+            .is_empty());
+    }
+
+    #[test]
+    fn test_frontmatter_and_directives() {
+        let parser = MermaidParser::new().unwrap();
+        let diagram = "---\ntitle: Login\nconfig:\n  theme: dark\n---\n%% entry\n%%{init: {'flowchart': {'curve': 'basis'}}}%%\nflowchart LR\n    A --> B";
+        assert_eq!(parser.detect_diagram_type(diagram), DiagramType::Flowchart);
+        assert!(parser.validate_diagram(diagram, 1).errors.is_empty());
+
+        let content = format!("{}\n---\nsequenceDiagram\n    A->>B: hi\n", diagram);
+        let result = parser.parse_content_as(&content, ContentFormat::Raw);
         assert_eq!(result.diagrams.len(), 2);
-        assert_eq!(result.diagrams[0].diagram_type, "flowchart");
-        assert_eq!(result.diagrams[1].diagram_type, "sequence");
+        let config = result.diagrams[0].config.as_ref().unwrap();
+        assert_eq!(config.title.as_deref(), Some("Login"));
+        assert_eq!(config.theme.as_deref(), Some("dark"));
+        assert_eq!(config.config["flowchart"]["curve"], "basis");
+        assert!(result.diagrams[1].config.is_none());
+
+        // Positions in the config and the body are document lines
+        let broken =
+            parser.validate_diagram("---\nconfig:\n  theme: sunset\n---\ngraph TD\n    A -->", 3);
+        let found: Vec<_> = broken.errors.iter().map(|e| (e.code, e.line)).collect();
+        assert_eq!(found[0], (DiagnosticCode::UnknownConfig, 5));
+        assert_eq!(found[1].1, 8);
     }
 
     #[test]
     fn test_detect_diagram_types() {
         let parser = MermaidParser::new().unwrap();
 
-        assert_eq!(parser.detect_diagram_type("graph TD"), "flowchart");
-        assert_eq!(parser.detect_diagram_type("flowchart LR"), "flowchart");
-        assert_eq!(parser.detect_diagram_type("sequenceDiagram"), "sequence");
-        assert_eq!(parser.detect_diagram_type("classDiagram"), "class");
-        assert_eq!(parser.detect_diagram_type("stateDiagram-v2"), "state");
-        assert_eq!(parser.detect_diagram_type("erDiagram"), "er");
-        assert_eq!(parser.detect_diagram_type("gantt"), "gantt");
-        assert_eq!(parser.detect_diagram_type("pie title My Pie"), "pie");
+        assert_eq!(
+            parser.detect_diagram_type("graph TD"),
+            DiagramType::Flowchart
+        );
+        assert_eq!(
+            parser.detect_diagram_type("flowchart LR"),
+            DiagramType::Flowchart
+        );
+        assert_eq!(
+            parser.detect_diagram_type("sequenceDiagram"),
+            DiagramType::Sequence
+        );
+        assert_eq!(
+            parser.detect_diagram_type("classDiagram"),
+            DiagramType::Class
+        );
+        assert_eq!(
+            parser.detect_diagram_type("stateDiagram-v2"),
+            DiagramType::State
+        );
+        assert_eq!(parser.detect_diagram_type("erDiagram"), DiagramType::Er);
+        assert_eq!(parser.detect_diagram_type("gantt"), DiagramType::Gantt);
+        assert_eq!(
+            parser.detect_diagram_type("pie title My Pie"),
+            DiagramType::Pie
+        );
+
+        let newer = [
+            ("quadrantChart", DiagramType::Quadrant),
+            ("sankey-beta", DiagramType::Sankey),
+            ("xychart-beta horizontal", DiagramType::XyChart),
+            ("block-beta", DiagramType::Block),
+            ("packet-beta", DiagramType::Packet),
+            ("architecture-beta", DiagramType::Architecture),
+            ("kanban", DiagramType::Kanban),
+            ("zenuml", DiagramType::ZenUml),
+            ("radar-beta", DiagramType::Radar),
+            ("treemap-beta", DiagramType::Treemap),
+            ("C4Container", DiagramType::C4Container),
+            ("C4Component", DiagramType::C4Component),
+            ("C4Dynamic", DiagramType::C4Dynamic),
+            ("C4Deployment", DiagramType::C4Deployment),
+        ];
+        for (declaration, expected) in newer {
+            assert_eq!(parser.detect_diagram_type(declaration), expected);
+            let result = parser.validate_diagram(declaration, 1);
+            assert!(result.is_valid, "{}: {:?}", declaration, result.errors);
+        }
     }
 
     #[test]
@@ -439,5 +590,45 @@ sequenceDiagram
         let empty_result = parser.validate_diagram("", 1);
         assert!(!empty_result.is_valid);
         assert_eq!(empty_result.errors[0].message, "Empty diagram content");
+        assert_eq!(empty_result.errors[0].code, DiagnosticCode::EmptyDiagram);
+        assert_eq!(empty_result.errors[0].severity, Severity::Error);
+
+        // Near-miss declarations suggest the keyword Mermaid expects
+        let gitgraph_result = parser.validate_diagram("gitgraph\n    commit", 1);
+        assert_eq!(
+            gitgraph_result.errors[0].message,
+            "Invalid diagram declaration: 'gitgraph'. Did you mean 'gitGraph'?"
+        );
+
+        // Broken flowcharts are caught by the grammar
+        let dangling_result = parser.validate_diagram("graph TD\n    A -->", 1);
+        assert!(!dangling_result.is_valid);
+        assert_eq!(dangling_result.errors[0].line, 2);
+
+        // Multi-line bodies are balanced across lines, not per line
+        let class_result = parser.validate_diagram(
+            "classDiagram\n    class Box~T~ {\n        +List~T~ items\n    }",
+            1,
+        );
+        assert!(class_result.errors.is_empty());
+    }
+
+    #[test]
+    fn test_structure_of_newer_types() {
+        let parser = MermaidParser::new().unwrap();
+
+        let block = parser.validate_diagram("block-beta\n  columns 2\n  block:api\n    a b\n", 1);
+        assert_eq!(block.errors[0].code, DiagnosticCode::UnclosedBlock);
+
+        let quadrant = parser.validate_diagram("quadrantChart\n  A: [0.2, 2]", 1);
+        assert!(!quadrant.is_valid);
+        assert_eq!(quadrant.errors[0].code, DiagnosticCode::InvalidValue);
+        assert_eq!(
+            (quadrant.errors[0].line, quadrant.errors[0].column),
+            (2, 12)
+        );
+
+        let c4 = parser.validate_diagram("C4Deployment\n  Deployment_Node(dc, \"DC\") {\n  }", 1);
+        assert!(c4.is_valid, "{:?}", c4.errors);
     }
 }
@@ -0,0 +1,436 @@
+use super::{DiagnosticCode, DiagramType, Fix, Position, SyntaxError};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// ER crow's-foot operators such as `||--o{` contain braces that are not brackets
+static ER_RELATIONSHIP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:\|o|\|\||\}o|\}\|)(?:--|\.\.)(?:o\||\|\||o\{|\|\{)").unwrap());
+
+/// Flowchart link labels are free-form text, e.g. `A -->|call (x| B`
+static FLOWCHART_PIPE_LABEL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:[-=.]{2,}[>ox]?|~~~)\s*\|(?P<label>[^|]*)\|").unwrap());
+
+/// Inline labels such as `A -- text (paren --> B`, opened by a bare `--`, `==` or `-.`
+static FLOWCHART_INLINE_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[^-=.])(?:--|==|-\.)(?P<label>\s.*?)(?:-{2,}|={2,}|\.-+)").unwrap()
+});
+
+/// Which part of a line takes part in bracket and quote balancing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BracketScope {
+    /// The whole line, e.g. flowchart node shapes
+    Everywhere,
+    /// Only the part before the first unquoted `:`; the rest is free-form label text
+    BeforeColon,
+    /// Only keyword blocks are balanced, e.g. sequence message text is free-form
+    KeywordsOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Opener {
+    Bracket(char),
+    Quote,
+    Keyword(String),
+}
+
+impl Opener {
+    fn display(&self) -> String {
+        match self {
+            Opener::Bracket(ch) => ch.to_string(),
+            Opener::Quote => "\"".to_string(),
+            Opener::Keyword(keyword) => keyword.clone(),
+        }
+    }
+
+    fn unclosed_code(&self) -> DiagnosticCode {
+        match self {
+            Opener::Bracket(_) => DiagnosticCode::UnclosedBracket,
+            Opener::Quote => DiagnosticCode::UnterminatedString,
+            Opener::Keyword(_) => DiagnosticCode::UnclosedBlock,
+        }
+    }
+
+    fn closer(&self) -> &str {
+        match self {
+            Opener::Bracket('(') => ")",
+            Opener::Bracket('[') | Opener::Bracket('>') => "]",
+            Opener::Bracket(_) => "}",
+            Opener::Quote => "\"",
+            Opener::Keyword(keyword) if keyword == "note" => "end note",
+            Opener::Keyword(_) => "end",
+        }
+    }
+}
+
+struct Balancer<'a> {
+    diagram_type: DiagramType,
+    lines: Vec<&'a str>,
+    start_line: usize,
+    stack: Vec<(Opener, Position)>,
+    errors: Vec<SyntaxError>,
+}
+
+/// Check that brackets, quotes and keyword blocks are balanced across the whole diagram.
+///
+/// Unclosed openers are reported at the opener, with `related` pointing at the place
+/// where the closer was expected and a fix that inserts it.
+pub fn check(content: &str, start_line: usize, diagram_type: DiagramType) -> Vec<SyntaxError> {
+    let scope = match diagram_type {
+        // Mindmap cloud and bang shapes like `)text(` are deliberately reversed
+        DiagramType::Sequence | DiagramType::Mindmap => BracketScope::KeywordsOnly,
+        // Radar curves hold `key: value` pairs inside their braces
+        DiagramType::Flowchart | DiagramType::Radar | DiagramType::Unknown => {
+            BracketScope::Everywhere
+        }
+        c4 if c4.is_c4() => BracketScope::Everywhere,
+        _ => BracketScope::BeforeColon,
+    };
+
+    let mut balancer = Balancer {
+        diagram_type,
+        lines: content.lines().collect(),
+        start_line,
+        stack: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let mut end = Position {
+        line: start_line,
+        column: 1,
+    };
+    // The declaration never opens a block, even when it reads like one as `block` does
+    let mut declared = false;
+    for (index, raw) in content.lines().enumerate() {
+        let line = start_line + index;
+        end = Position {
+            line,
+            column: raw.chars().count() + 1,
+        };
+
+        let in_quote = matches!(balancer.stack.last(), Some((Opener::Quote, _)));
+        if !in_quote && raw.trim_start().starts_with("%%") {
+            continue;
+        }
+        if !in_quote && declared {
+            balancer.check_keyword(raw, line);
+        }
+        declared |= !raw.trim().is_empty();
+        if scope != BracketScope::KeywordsOnly {
+            balancer.check_brackets(raw, line, scope);
+        }
+    }
+
+    while let Some((opener, position)) = balancer.stack.pop() {
+        balancer.unclosed(&opener, position, end, true);
+    }
+    balancer
+        .errors
+        .sort_by_key(|error| (error.line, error.column));
+    balancer.errors
+}
+
+impl Balancer<'_> {
+    /// `at_end` when the diagram ended before the closer, rather than an outer closer
+    fn unclosed(&mut self, opener: &Opener, position: Position, expected: Position, at_end: bool) {
+        let mut error = SyntaxError::new(
+            opener.unclosed_code(),
+            position.line,
+            position.column,
+            format!(
+                "Unclosed '{}': expected '{}' by line {}, column {}",
+                opener.display(),
+                opener.closer(),
+                expected.line,
+                expected.column
+            ),
+        );
+        error.related = Some(expected);
+        error.fixes = vec![self.closing_fix(opener, position, expected, at_end)];
+        self.errors.push(error);
+    }
+
+    fn line_text(&self, line: usize) -> &str {
+        self.lines
+            .get(line - self.start_line)
+            .copied()
+            .unwrap_or("")
+    }
+
+    /// Blocks get the closer on its own line at the opener's indentation; anything else
+    /// is closed at the end of the line it was opened on
+    fn closing_fix(
+        &self,
+        opener: &Opener,
+        position: Position,
+        expected: Position,
+        at_end: bool,
+    ) -> Fix {
+        let title = format!("Insert missing '{}'", opener.closer());
+        let opened_on = self.line_text(position.line);
+        let is_block = match opener {
+            Opener::Keyword(_) => true,
+            Opener::Bracket('{') => opened_on.trim_end().ends_with('{'),
+            _ => false,
+        };
+
+        if expected.line == position.line {
+            return Fix::insert(title, expected, opener.closer());
+        }
+        if !is_block {
+            let end_of_line = Position {
+                line: position.line,
+                column: opened_on.trim_end().chars().count() + 1,
+            };
+            return Fix::insert(title, end_of_line, opener.closer());
+        }
+
+        let indent = &opened_on[..opened_on.len() - opened_on.trim_start().len()];
+        if at_end {
+            let text = format!("\n{}{}", indent, opener.closer());
+            Fix::insert(title, expected, text)
+        } else {
+            let start_of_line = Position {
+                line: expected.line,
+                column: 1,
+            };
+            let text = format!("{}{}\n", indent, opener.closer());
+            Fix::insert(title, start_of_line, text)
+        }
+    }
+
+    fn keyword_opener(&self, line: &str) -> Option<String> {
+        let first = line.split_whitespace().next()?;
+        match self.diagram_type {
+            DiagramType::Flowchart if first == "subgraph" => Some(first.to_string()),
+            // Block diagrams nest `block` or `block:id:width` groups
+            DiagramType::Block if first == "block" || first.starts_with("block:") => {
+                Some("block".to_string())
+            }
+            DiagramType::Sequence => {
+                let lower = first.to_lowercase();
+                matches!(
+                    lower.as_str(),
+                    "loop" | "alt" | "opt" | "par" | "critical" | "break" | "rect" | "box"
+                )
+                .then_some(lower)
+            }
+            // State notes without an inline `:` run until `end note`
+            DiagramType::State if first == "note" && !line.contains(':') => Some(first.to_string()),
+            _ => None,
+        }
+    }
+
+    fn keyword_closer(&self, line: &str) -> bool {
+        match self.diagram_type {
+            DiagramType::Flowchart | DiagramType::Block => line == "end",
+            DiagramType::Sequence => line.eq_ignore_ascii_case("end"),
+            DiagramType::State => line == "end note",
+            _ => false,
+        }
+    }
+
+    fn check_keyword(&mut self, raw: &str, line: usize) {
+        let trimmed = raw.trim().trim_end_matches(';');
+        let position = Position {
+            line,
+            column: raw.chars().count() - raw.trim_start().chars().count() + 1,
+        };
+
+        if let Some(keyword) = self.keyword_opener(trimmed) {
+            self.stack.push((Opener::Keyword(keyword), position));
+        } else if self.keyword_closer(trimmed) {
+            if !self
+                .stack
+                .iter()
+                .any(|(opener, _)| matches!(opener, Opener::Keyword(_)))
+            {
+                self.errors.push(SyntaxError::new(
+                    DiagnosticCode::UnmatchedEnd,
+                    line,
+                    position.column,
+                    format!("'{}' without a matching block", trimmed),
+                ));
+                return;
+            }
+            while let Some((opener, opened_at)) = self.stack.pop() {
+                if matches!(opener, Opener::Keyword(_)) {
+                    break;
+                }
+                self.unclosed(&opener, opened_at, position, false);
+            }
+        }
+    }
+
+    fn check_brackets(&mut self, raw: &str, line: usize, scope: BracketScope) {
+        let masked;
+        let text = match self.diagram_type {
+            DiagramType::Er => {
+                masked = ER_RELATIONSHIP
+                    .replace_all(raw, |caps: &regex::Captures| " ".repeat(caps[0].len()))
+                    .into_owned();
+                masked.as_str()
+            }
+            DiagramType::Flowchart => {
+                let piped = mask_label(&FLOWCHART_PIPE_LABEL, raw);
+                masked = mask_label(&FLOWCHART_INLINE_LABEL, &piped);
+                masked.as_str()
+            }
+            _ => raw,
+        };
+
+        let mut previous = ' ';
+        for (index, ch) in text.chars().enumerate() {
+            let position = Position {
+                line,
+                column: index + 1,
+            };
+            let in_quote = matches!(self.stack.last(), Some((Opener::Quote, _)));
+
+            match ch {
+                '"' if in_quote => {
+                    self.stack.pop();
+                }
+                '"' => self.stack.push((Opener::Quote, position)),
+                _ if in_quote => {}
+                ':' if scope == BracketScope::BeforeColon => return,
+                '(' | '[' | '{' => self.stack.push((Opener::Bracket(ch), position)),
+                // Asymmetric flowchart nodes such as `A>text]`, but not links like `-->`
+                // or a `>` inside another node's label
+                '>' if self.diagram_type == DiagramType::Flowchart
+                    && (previous.is_alphanumeric() || previous == '_')
+                    && !self.in_bracket() =>
+                {
+                    self.stack.push((Opener::Bracket(ch), position))
+                }
+                ')' | ']' | '}' => self.close_bracket(ch, position),
+                _ => {}
+            }
+            previous = ch;
+        }
+    }
+
+    fn in_bracket(&self) -> bool {
+        self.stack
+            .iter()
+            .any(|(opener, _)| matches!(opener, Opener::Bracket(_)))
+    }
+
+    fn close_bracket(&mut self, ch: char, position: Position) {
+        let matches_bracket = |opener: &Opener| {
+            opener.closer().starts_with(ch) && matches!(opener, Opener::Bracket(_))
+        };
+        let Some(depth) = self
+            .stack
+            .iter()
+            .rposition(|(opener, _)| matches_bracket(opener))
+        else {
+            self.errors.push(SyntaxError::new(
+                DiagnosticCode::UnexpectedCloser,
+                position.line,
+                position.column,
+                format!("Unexpected '{}' without a matching opener", ch),
+            ));
+            return;
+        };
+
+        // Everything opened after the matching bracket was left unclosed
+        while self.stack.len() > depth + 1 {
+            let (opener, opened_at) = self.stack.pop().unwrap();
+            self.unclosed(&opener, opened_at, position, false);
+        }
+        self.stack.pop();
+    }
+}
+
+/// Blank out the `label` group of every match, keeping columns where they were
+fn mask_label(regex: &Regex, text: &str) -> String {
+    regex
+        .replace_all(text, |caps: &regex::Captures| {
+            let whole = caps.get(0).unwrap();
+            let label = caps.name("label").unwrap();
+            format!(
+                "{}{}{}",
+                &text[whole.start()..label.start()],
+                " ".repeat(label.as_str().chars().count()),
+                &text[label.end()..whole.end()]
+            )
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_line_blocks_are_balanced() {
+        let class = "classDiagram\n  class Animal {\n    +List~T~ items\n    +run(int x) bool\n  }";
+        assert!(check(class, 1, DiagramType::Class).is_empty());
+
+        let flowchart = "graph TD\n  subgraph one\n    A[\"multi\n    line\"] --> B\n  end";
+        assert!(check(flowchart, 1, DiagramType::Flowchart).is_empty());
+
+        let er = "erDiagram\n  CUSTOMER ||--o{ ORDER : places\n  ORDER }|..|{ ITEM : contains";
+        assert!(check(er, 1, DiagramType::Er).is_empty());
+    }
+
+    #[test]
+    fn test_flowchart_labels_are_free_text() {
+        let labels =
+            "graph LR\n  A -->|call (x| B\n  A -- text (paren --> B\n  C -. see [docs .-> D";
+        assert!(check(labels, 1, DiagramType::Flowchart).is_empty());
+
+        // Node shapes around a label are still balanced
+        let errors = check("graph LR\n  A(x -- text --> B", 1, DiagramType::Flowchart);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (2, 4));
+    }
+
+    #[test]
+    fn test_unclosed_opener_reports_both_positions() {
+        let errors = check(
+            "stateDiagram-v2\n  state Busy {\n    a --> b",
+            5,
+            DiagramType::State,
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (6, 14));
+        assert_eq!(
+            errors[0].related,
+            Some(Position {
+                line: 7,
+                column: 12
+            })
+        );
+
+        let errors = check(
+            "graph TD\n  subgraph s\n    A(open --> B\n  end",
+            1,
+            DiagramType::Flowchart,
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (3, 6));
+        assert_eq!(errors[0].related, Some(Position { line: 4, column: 3 }));
+    }
+
+    #[test]
+    fn test_stray_closers() {
+        let errors = check(
+            "sequenceDiagram\n  A->>B: hi (\n  end",
+            1,
+            DiagramType::Sequence,
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "'end' without a matching block");
+
+        assert!(check("graph LR\n  A>flag] --> B", 1, DiagramType::Flowchart).is_empty());
+        let errors = check("graph TD\n  A] --> B", 1, DiagramType::Flowchart);
+        assert_eq!((errors[0].line, errors[0].column), (2, 4));
+    }
+
+    #[test]
+    fn test_greater_than_inside_labels() {
+        let labels = "graph TD\n  A --> B{count>10}\n  C[a>b] --> D(x>y)\n  E>flag a>b] --> F";
+        assert!(check(labels, 1, DiagramType::Flowchart).is_empty());
+    }
+}
@@ -0,0 +1,722 @@
+use super::flowchart::Direction;
+use super::source::{self, Line};
+use super::{AstResult, DiagnosticCode, Position, SyntaxError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+static CLASS_DECLARATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^class\s+(?P<name>\w+|`[^`]+`)(?P<generic>~[^{\[:]*)?(?:\["(?P<label>[^"]*)"\])?(?::::(?P<css>\w+))?\s*(?P<body>\{.*)?$"#,
+    )
+    .unwrap()
+});
+
+/// Names are matched whole, so `Zoo--Bar` is not class `Zo` with an aggregation end
+static RELATIONSHIP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^(?P<from>\w+)\b(?P<from_generic>~[\w~,]+~)?\s*(?:"(?P<from_card>[^"]*)"\s*)?(?P<left><\||\*|o|<|\(\))?(?P<line>--|\.\.)(?P<right>\|>|\*|o|>|\(\))?\s*(?:"(?P<to_card>[^"]*)"\s*)?\b(?P<to>\w+)(?P<to_generic>~[\w~,]+~)?\s*(?::\s*(?P<label>.*))?$"#,
+    )
+    .unwrap()
+});
+
+static MEMBER_STATEMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?P<class>\w+)\s*:\s*(?P<member>.+)$").unwrap());
+
+static ANNOTATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^<<(?P<annotation>[^>]+)>>\s*(?P<class>\w+)?$").unwrap());
+
+static NOTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^note\s+(?:for\s+(?P<class>\w+)\s+)?"(?P<text>[^"]*)"$"#).unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+    Package,
+}
+
+impl Visibility {
+    fn from_marker(marker: char) -> Option<Self> {
+        match marker {
+            '+' => Some(Visibility::Public),
+            '-' => Some(Visibility::Private),
+            '#' => Some(Visibility::Protected),
+            '~' => Some(Visibility::Package),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attribute {
+    pub visibility: Option<Visibility>,
+    pub name: String,
+    pub type_name: Option<String>,
+    pub is_static: bool,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Method {
+    pub visibility: Option<Visibility>,
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub return_type: Option<String>,
+    pub is_static: bool,
+    pub is_abstract: bool,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassNode {
+    pub name: String,
+    pub label: Option<String>,
+    /// Generic parameter as written between `~`, e.g. `T` for `Box~T~`
+    pub generic: Option<String>,
+    pub annotations: Vec<String>,
+    pub attributes: Vec<Attribute>,
+    pub methods: Vec<Method>,
+    pub namespace: Option<String>,
+    pub css_class: Option<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelationEnd {
+    Inheritance,
+    Composition,
+    Aggregation,
+    Association,
+    Lollipop,
+}
+
+impl RelationEnd {
+    fn from_marker(marker: &str) -> Option<Self> {
+        match marker {
+            "<|" | "|>" => Some(RelationEnd::Inheritance),
+            "*" => Some(RelationEnd::Composition),
+            "o" => Some(RelationEnd::Aggregation),
+            "<" | ">" => Some(RelationEnd::Association),
+            "()" => Some(RelationEnd::Lollipop),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelationLine {
+    Solid,
+    Dashed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Relationship {
+    pub from: String,
+    pub to: String,
+    /// Marker drawn at the `from` end, e.g. `<|` in `A <|-- B`
+    pub from_end: Option<RelationEnd>,
+    pub to_end: Option<RelationEnd>,
+    pub line: RelationLine,
+    pub from_cardinality: Option<String>,
+    pub to_cardinality: Option<String>,
+    pub label: Option<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Namespace {
+    pub name: String,
+    pub classes: Vec<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassNote {
+    pub class: Option<String>,
+    pub text: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassDiagramAst {
+    pub direction: Option<Direction>,
+    pub classes: Vec<ClassNode>,
+    pub relationships: Vec<Relationship>,
+    pub namespaces: Vec<Namespace>,
+    pub notes: Vec<ClassNote>,
+}
+
+/// Innermost open `{` scope
+enum Scope {
+    Namespace(usize),
+    ClassBody(String, Position),
+}
+
+struct Parser {
+    ast: ClassDiagramAst,
+    errors: Vec<SyntaxError>,
+    scopes: Vec<Scope>,
+}
+
+/// Parse a `classDiagram` into a semantic model of classes, members and relationships
+pub fn parse(content: &str, start_line: usize) -> AstResult<ClassDiagramAst> {
+    let mut parser = Parser {
+        ast: ClassDiagramAst::default(),
+        errors: Vec::new(),
+        scopes: Vec::new(),
+    };
+
+    let mut lines = source::lines(content, start_line);
+    match lines.next() {
+        Some(line) if line.text == "classDiagram" || line.text == "classDiagram-v2" => {}
+        Some(line) => {
+            parser.error(
+                DiagnosticCode::InvalidDeclaration,
+                line.position_at(0),
+                format!("Expected 'classDiagram' declaration, found '{}'", line.text),
+            );
+            return parser.finish();
+        }
+        None => return parser.finish(),
+    }
+
+    for line in lines {
+        parser.parse_line(&line);
+    }
+    parser.finish()
+}
+
+impl Parser {
+    fn error(&mut self, code: DiagnosticCode, position: Position, message: impl Into<String>) {
+        self.errors.push(SyntaxError::new(
+            code,
+            position.line,
+            position.column,
+            message,
+        ));
+    }
+
+    fn finish(mut self) -> AstResult<ClassDiagramAst> {
+        while let Some(scope) = self.scopes.pop() {
+            match scope {
+                Scope::ClassBody(name, position) => self.error(
+                    DiagnosticCode::UnclosedBlock,
+                    position,
+                    format!("Unclosed '{{' in class '{}': missing '}}'", name),
+                ),
+                Scope::Namespace(index) => {
+                    let namespace = &self.ast.namespaces[index];
+                    let (name, position) = (namespace.name.clone(), namespace.position);
+                    self.error(
+                        DiagnosticCode::UnclosedBlock,
+                        position,
+                        format!("Unclosed namespace '{}': missing '}}'", name),
+                    );
+                }
+            }
+        }
+        AstResult {
+            ast: self.ast,
+            errors: self.errors,
+        }
+    }
+
+    fn current_namespace(&self) -> Option<String> {
+        self.scopes.iter().rev().find_map(|scope| match scope {
+            Scope::Namespace(index) => Some(self.ast.namespaces[*index].name.clone()),
+            Scope::ClassBody(..) => None,
+        })
+    }
+
+    /// Find or implicitly create a class, as Mermaid does for relationships and members
+    fn class_mut(&mut self, name: &str, position: Position) -> &mut ClassNode {
+        let namespace = self.current_namespace();
+        let index = match self.ast.classes.iter().position(|class| class.name == name) {
+            Some(index) => index,
+            None => {
+                if let Some(namespace) = &namespace {
+                    if let Some(ns) = self
+                        .ast
+                        .namespaces
+                        .iter_mut()
+                        .find(|ns| &ns.name == namespace)
+                    {
+                        ns.classes.push(name.to_string());
+                    }
+                }
+                self.ast.classes.push(ClassNode {
+                    name: name.to_string(),
+                    label: None,
+                    generic: None,
+                    annotations: Vec::new(),
+                    attributes: Vec::new(),
+                    methods: Vec::new(),
+                    namespace,
+                    css_class: None,
+                    position,
+                });
+                self.ast.classes.len() - 1
+            }
+        };
+        &mut self.ast.classes[index]
+    }
+
+    fn parse_line(&mut self, line: &Line) {
+        let text = line.text;
+
+        if let Some(Scope::ClassBody(name, _)) = self.scopes.last() {
+            let name = name.clone();
+            if text == "}" {
+                self.scopes.pop();
+            } else if let Some(captures) = ANNOTATION
+                .captures(text)
+                .filter(|c| c.name("class").is_none())
+            {
+                let annotation = captures["annotation"].trim().to_string();
+                self.class_mut(&name, line.position_at(0))
+                    .annotations
+                    .push(annotation);
+            } else {
+                let body = text.strip_suffix('}');
+                self.add_member(&name, line, body.unwrap_or(text).trim_end());
+                if body.is_some() {
+                    self.scopes.pop();
+                }
+            }
+            return;
+        }
+
+        if text == "}" {
+            if self.scopes.pop().is_none() {
+                self.error(
+                    DiagnosticCode::UnexpectedCloser,
+                    line.position_at(0),
+                    "'}' without a matching '{'",
+                );
+            }
+            return;
+        }
+
+        let (keyword, rest) = match text.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim()),
+            None => (text, ""),
+        };
+        match keyword {
+            "class" => self.parse_class_declaration(line),
+            "namespace" => self.parse_namespace(line, rest),
+            "direction" => match Direction::from_keyword(rest) {
+                Some(direction) => self.ast.direction = Some(direction),
+                None if rest.is_empty() => self.error(
+                    DiagnosticCode::UnknownDirection,
+                    line.position_at(line.text.len()),
+                    "Expected a direction",
+                ),
+                None => self.error(
+                    DiagnosticCode::UnknownDirection,
+                    line.position_of(rest),
+                    format!("Unknown direction '{}'", rest),
+                ),
+            },
+            "note" => match NOTE.captures(text) {
+                Some(captures) => self.ast.notes.push(ClassNote {
+                    class: captures.name("class").map(|c| c.as_str().to_string()),
+                    text: captures["text"].to_string(),
+                    position: line.position_at(0),
+                }),
+                None => self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line.position_at(0),
+                    "Expected 'note \"text\"' or 'note for <class> \"text\"'",
+                ),
+            },
+            "cssClass" | "classDef" | "style" | "click" | "link" | "callback" | "title" => {}
+            _ if keyword.starts_with("accTitle") || keyword.starts_with("accDescr") => {}
+            _ => self.parse_relation_or_member(line),
+        }
+    }
+
+    fn parse_class_declaration(&mut self, line: &Line) {
+        let Some(captures) = CLASS_DECLARATION.captures(line.text) else {
+            self.error(
+                DiagnosticCode::InvalidIdentifier,
+                line.position_at(0),
+                format!("Invalid class declaration '{}'", line.text),
+            );
+            return;
+        };
+
+        let name = captures["name"].trim_matches('`').to_string();
+        let position = line.position_of(captures.name("name").unwrap().as_str());
+        let generic = match captures.name("generic") {
+            Some(generic) => match parse_generic(generic.as_str()) {
+                Some(inner) => Some(inner),
+                None => {
+                    self.error(
+                        DiagnosticCode::UnclosedBracket,
+                        line.position_of(generic.as_str()),
+                        "Unclosed generic: expected a closing '~'",
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let class = self.class_mut(&name, position);
+        class.position = position;
+        if generic.is_some() {
+            class.generic = generic;
+        }
+        if let Some(label) = captures.name("label") {
+            class.label = Some(label.as_str().to_string());
+        }
+        if let Some(css) = captures.name("css") {
+            class.css_class = Some(css.as_str().to_string());
+        }
+
+        if let Some(body) = captures.name("body") {
+            let brace = line.position_of(body.as_str());
+            let inline = body.as_str()[1..].trim();
+            match inline.strip_suffix('}') {
+                // Single-line body such as `class Empty {}` or `class A { +int x }`
+                Some(members) => {
+                    let members = members.trim();
+                    if !members.is_empty() {
+                        self.add_member(&name, line, members);
+                    }
+                }
+                None => {
+                    if !inline.is_empty() {
+                        self.add_member(&name, line, inline);
+                    }
+                    self.scopes.push(Scope::ClassBody(name, brace));
+                }
+            }
+        }
+    }
+
+    fn parse_namespace(&mut self, line: &Line, rest: &str) {
+        let Some(name) = rest.strip_suffix('{').map(str::trim) else {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(line.text.len()),
+                "Expected '{' after the namespace name",
+            );
+            return;
+        };
+        if name.is_empty() {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_of(rest),
+                "Expected a namespace name",
+            );
+            return;
+        }
+        self.ast.namespaces.push(Namespace {
+            name: name.to_string(),
+            classes: Vec::new(),
+            position: line.position_at(0),
+        });
+        self.scopes
+            .push(Scope::Namespace(self.ast.namespaces.len() - 1));
+    }
+
+    fn parse_relation_or_member(&mut self, line: &Line) {
+        let text = line.text;
+
+        if let Some(captures) = RELATIONSHIP.captures(text) {
+            let from = captures["from"].to_string();
+            let to = captures["to"].to_string();
+            let position = line.position_at(0);
+            let to_position = line.position_of(captures.name("to").unwrap().as_str());
+            // `Animal~T~ <|-- Duck` names the generic the same way a declaration does
+            for (name, group, at) in [
+                (&from, "from_generic", position),
+                (&to, "to_generic", to_position),
+            ] {
+                let generic = captures.name(group).and_then(|m| parse_generic(m.as_str()));
+                let class = self.class_mut(name, at);
+                if class.generic.is_none() {
+                    class.generic = generic;
+                }
+            }
+
+            let marker = |group: &str| {
+                captures
+                    .name(group)
+                    .and_then(|m| RelationEnd::from_marker(m.as_str()))
+            };
+            let text_of = |group: &str| captures.name(group).map(|m| m.as_str().trim().to_string());
+            self.ast.relationships.push(Relationship {
+                from,
+                to,
+                from_end: marker("left"),
+                to_end: marker("right"),
+                line: if &captures["line"] == ".." {
+                    RelationLine::Dashed
+                } else {
+                    RelationLine::Solid
+                },
+                from_cardinality: text_of("from_card"),
+                to_cardinality: text_of("to_card"),
+                label: text_of("label").filter(|label| !label.is_empty()),
+                position,
+            });
+            return;
+        }
+
+        if let Some(captures) = ANNOTATION.captures(text) {
+            match captures.name("class") {
+                Some(class) => {
+                    let annotation = captures["annotation"].trim().to_string();
+                    self.class_mut(class.as_str(), line.position_of(class.as_str()))
+                        .annotations
+                        .push(annotation);
+                }
+                None => self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line.position_at(0),
+                    "Expected a class name after the annotation",
+                ),
+            }
+            return;
+        }
+
+        if let Some(captures) = MEMBER_STATEMENT.captures(text) {
+            let class = captures["class"].to_string();
+            let member = captures.name("member").unwrap().as_str();
+            self.class_mut(&class, line.position_at(0));
+            self.add_member(&class, line, member);
+            return;
+        }
+
+        self.error(
+            DiagnosticCode::UnrecognizedStatement,
+            line.position_at(0),
+            format!("Unrecognized statement '{}'", text),
+        );
+    }
+
+    /// Parse an attribute or method; `text` must be a subslice of the line
+    fn add_member(&mut self, class: &str, line: &Line, text: &str) {
+        let position = line.position_of(text);
+        let mut chars = text.chars();
+        let visibility = chars.next().and_then(Visibility::from_marker);
+        let body = if visibility.is_some() {
+            chars.as_str().trim_start()
+        } else {
+            text
+        };
+
+        match body.find('(') {
+            Some(open) => {
+                let Some(close) = body.rfind(')').filter(|&close| close > open) else {
+                    self.error(
+                        DiagnosticCode::UnclosedBracket,
+                        line.position_of(&body[open..]),
+                        "Unclosed '(' in method: expected ')'",
+                    );
+                    return;
+                };
+
+                let mut rest = body[close + 1..].trim();
+                let mut is_static = false;
+                let mut is_abstract = false;
+                // Classifiers usually follow the parentheses but are accepted at the end too
+                for _ in 0..2 {
+                    if let Some(stripped) =
+                        rest.strip_prefix('$').or_else(|| rest.strip_suffix('$'))
+                    {
+                        is_static = true;
+                        rest = stripped.trim();
+                    }
+                    if let Some(stripped) =
+                        rest.strip_prefix('*').or_else(|| rest.strip_suffix('*'))
+                    {
+                        is_abstract = true;
+                        rest = stripped.trim();
+                    }
+                }
+
+                let method = Method {
+                    visibility,
+                    name: body[..open].trim().to_string(),
+                    parameters: split_parameters(&body[open + 1..close]),
+                    return_type: (!rest.is_empty()).then(|| rest.to_string()),
+                    is_static,
+                    is_abstract,
+                    position,
+                };
+                self.class_mut(class, position).methods.push(method);
+            }
+            None => {
+                let (body, is_static) = match body.strip_suffix('$') {
+                    Some(stripped) => (stripped.trim_end(), true),
+                    None => (body, false),
+                };
+                let (type_name, name) = match body.rsplit_once(char::is_whitespace) {
+                    Some((type_name, name)) => (Some(type_name.trim().to_string()), name),
+                    None => (None, body),
+                };
+                if name.is_empty() {
+                    self.error(
+                        DiagnosticCode::IncompleteStatement,
+                        position,
+                        "Expected a member name",
+                    );
+                    return;
+                }
+                let attribute = Attribute {
+                    visibility,
+                    name: name.to_string(),
+                    type_name,
+                    is_static,
+                    position,
+                };
+                self.class_mut(class, position).attributes.push(attribute);
+            }
+        }
+    }
+}
+
+/// Strip the `~` delimiters from a generic, e.g. `~List~int~~` becomes `List~int~`
+fn parse_generic(generic: &str) -> Option<String> {
+    let generic = generic.trim_end();
+    let inner = generic.strip_prefix('~')?.strip_suffix('~')?;
+    (!inner.is_empty() && inner.matches('~').count() % 2 == 0).then(|| inner.to_string())
+}
+
+/// Split a parameter list on commas that are not inside a `~generic~`
+fn split_parameters(parameters: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut in_generic = false;
+    for ch in parameters.chars() {
+        match ch {
+            '~' => {
+                in_generic = !in_generic;
+                current.push(ch);
+            }
+            ',' if !in_generic => result.push(std::mem::take(&mut current)),
+            _ => current.push(ch),
+        }
+    }
+    result.push(current);
+    result
+        .into_iter()
+        .map(|parameter| parameter.trim().to_string())
+        .filter(|parameter| !parameter.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class<'a>(ast: &'a ClassDiagramAst, name: &str) -> &'a ClassNode {
+        ast.classes.iter().find(|class| class.name == name).unwrap()
+    }
+
+    /// Classes that inherit from or realize `name`
+    fn subclasses_of<'a>(ast: &'a ClassDiagramAst, name: &str) -> Vec<&'a str> {
+        ast.relationships
+            .iter()
+            .filter_map(|relation| match (relation.from_end, relation.to_end) {
+                (Some(RelationEnd::Inheritance), _) if relation.from == name => {
+                    Some(relation.to.as_str())
+                }
+                (_, Some(RelationEnd::Inheritance)) if relation.to == name => {
+                    Some(relation.from.as_str())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_class_bodies_and_members() {
+        let content = "classDiagram\n    class Animal~T~ {\n        <<interface>>\n        +String name\n        -List~int~ ids$\n        +speak(String word, Map~K,V~ opts)* bool\n        +create()$ Animal\n    }\n    Animal : #int age";
+        let result = parse(content, 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let animal = class(&result.ast, "Animal");
+        assert_eq!(animal.generic.as_deref(), Some("T"));
+        assert_eq!(animal.annotations, vec!["interface"]);
+        assert_eq!(animal.attributes.len(), 3);
+        assert_eq!(animal.attributes[1].type_name.as_deref(), Some("List~int~"));
+        assert!(animal.attributes[1].is_static);
+        assert_eq!(animal.attributes[2].visibility, Some(Visibility::Protected));
+
+        let speak = &animal.methods[0];
+        assert_eq!(speak.parameters, vec!["String word", "Map~K,V~ opts"]);
+        assert!(speak.is_abstract);
+        assert_eq!(speak.return_type.as_deref(), Some("bool"));
+        assert!(animal.methods[1].is_static);
+    }
+
+    #[test]
+    fn test_parse_relationships() {
+        let content = "classDiagram\n  Animal <|-- Duck\n  Customer \"1\" --> \"*\" Ticket : buys\n  Car *-- Wheel\n  Shape ..|> Drawable\n  namespace Zoo {\n    class Keeper\n  }";
+        let result = parse(content, 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let ast = result.ast;
+        assert_eq!(subclasses_of(&ast, "Animal"), vec!["Duck"]);
+        assert_eq!(subclasses_of(&ast, "Drawable"), vec!["Shape"]);
+        let buys = &ast.relationships[1];
+        assert_eq!(buys.from_cardinality.as_deref(), Some("1"));
+        assert_eq!(buys.to_cardinality.as_deref(), Some("*"));
+        assert_eq!(buys.label.as_deref(), Some("buys"));
+        assert_eq!(
+            ast.relationships[2].from_end,
+            Some(RelationEnd::Composition)
+        );
+        assert_eq!(ast.relationships[3].line, RelationLine::Dashed);
+        assert_eq!(class(&ast, "Keeper").namespace.as_deref(), Some("Zoo"));
+    }
+
+    #[test]
+    fn test_parse_relationship_names_and_generics() {
+        let content = "classDiagram\n  Zoo--Bar\n  Animal~T~ <|-- Duck~List~int~~\n  Pond o-- Duck";
+        let result = parse(content, 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let ast = result.ast;
+        let zoo = &ast.relationships[0];
+        assert_eq!((zoo.from.as_str(), zoo.to.as_str()), ("Zoo", "Bar"));
+        assert_eq!((zoo.from_end, zoo.to_end), (None, None));
+        assert_eq!(subclasses_of(&ast, "Animal"), vec!["Duck"]);
+        assert_eq!(class(&ast, "Animal").generic.as_deref(), Some("T"));
+        assert_eq!(class(&ast, "Duck").generic.as_deref(), Some("List~int~"));
+        assert_eq!(
+            ast.relationships[2].from_end,
+            Some(RelationEnd::Aggregation)
+        );
+        assert_eq!(ast.classes.len(), 5);
+    }
+
+    #[test]
+    fn test_class_errors() {
+        let unclosed = parse("classDiagram\n  class Open {\n    +int x", 1);
+        assert_eq!(unclosed.errors.len(), 1);
+        assert_eq!(
+            (unclosed.errors[0].line, unclosed.errors[0].column),
+            (2, 14)
+        );
+
+        let method = parse("classDiagram\n  class A {\n    +run(int x\n  }", 1);
+        assert_eq!(
+            method.errors[0].message,
+            "Unclosed '(' in method: expected ')'"
+        );
+        assert_eq!(method.errors[0].column, 9);
+
+        let direction = parse("classDiagram\n  direction", 1);
+        assert_eq!(direction.errors.len(), 1);
+        assert_eq!(direction.errors[0].message, "Expected a direction");
+        assert_eq!(direction.errors[0].column, 12);
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How serious a diagnostic is; only errors make a diagram invalid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+macro_rules! diagnostic_codes {
+    ($($variant:ident = $code:literal, $severity:ident, $description:literal;)*) => {
+        /// Stable identifier for every diagnostic the parser can report.
+        ///
+        /// Codes are never renumbered or reused, so they are safe to reference
+        /// from documentation, lint configuration and CI filters.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum DiagnosticCode {
+            $($variant,)*
+        }
+
+        impl DiagnosticCode {
+            pub const ALL: &'static [DiagnosticCode] = &[$(DiagnosticCode::$variant,)*];
+
+            /// The `MMDnnnn` form shown to users
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(DiagnosticCode::$variant => $code,)*
+                }
+            }
+
+            /// Severity reported unless configured otherwise
+            pub fn default_severity(self) -> Severity {
+                match self {
+                    $(DiagnosticCode::$variant => Severity::$severity,)*
+                }
+            }
+
+            /// One-line explanation for documentation and SARIF rule metadata
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(DiagnosticCode::$variant => $description,)*
+                }
+            }
+        }
+    };
+}
+
+diagnostic_codes! {
+    EmptyDiagram = "MMD0001", Error, "The diagram block contains no content";
+    InvalidDeclaration = "MMD0002", Error, "The first statement is not a recognized diagram declaration";
+    InvalidNodeId = "MMD0003", Warning, "A node id that is too long or looks reserved (lint rule invalid-node-id)";
+    UnknownDirection = "MMD0004", Error, "A direction other than TB, TD, BT, LR or RL";
+    InvalidConfig = "MMD0005", Error, "Frontmatter or a %%{init}%% directive that cannot be read";
+    UnknownConfig = "MMD0006", Warning, "An unknown frontmatter key, directive or theme";
+    UnclosedBracket = "MMD0010", Error, "A bracket, parenthesis or delimiter is never closed";
+    UnexpectedCloser = "MMD0011", Error, "A closing bracket or brace without a matching opener";
+    UnterminatedString = "MMD0012", Error, "A quoted string is missing its closing quote";
+    UnclosedBlock = "MMD0013", Error, "A block such as subgraph, loop or state body is missing its end";
+    UnmatchedEnd = "MMD0014", Error, "An 'end' keyword without a block to close";
+    UnrecognizedStatement = "MMD0020", Error, "A line that is not a statement of this diagram type";
+    IncompleteStatement = "MMD0021", Error, "A statement is missing a required part";
+    InvalidIdentifier = "MMD0022", Error, "A node, state, class or subgraph id is not valid";
+    UnexpectedCharacter = "MMD0023", Error, "A character that is not allowed at this position";
+    InvalidValue = "MMD0024", Error, "A keyword argument is out of range or not recognized";
+    DuplicateParticipant = "MMD0030", Error, "A sequence participant is declared more than once";
+    UndeclaredParticipant = "MMD0031", Error, "A message refers to a participant that was never declared";
+    AliasReference = "MMD0032", Error, "A participant is referred to by its alias instead of its id";
+    InactiveParticipant = "MMD0033", Error, "A participant is deactivated while not active";
+    MisplacedBranch = "MMD0034", Error, "An 'else', 'and' or 'option' outside its enclosing block";
+    UnusedClassDef = "MMD0100", Warning, "A classDef that no node uses (lint rule unused-class-def)";
+    OrphanNode = "MMD0101", Warning, "A node without any edges (lint rule orphan-node)";
+    DuplicateEdge = "MMD0102", Warning, "The same edge is declared twice (lint rule duplicate-edge)";
+    InconsistentNaming = "MMD0103", Info, "An id whose case style differs from the rest (lint rule inconsistent-naming)";
+    SelfLoop = "MMD0104", Info, "An edge from a node to itself (lint rule self-loop)";
+    TooManyNodes = "MMD0105", Warning, "The diagram has more nodes than configured (lint rule max-nodes)";
+}
+
+impl DiagnosticCode {
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|candidate| candidate.as_str().eq_ignore_ascii_case(code))
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for DiagnosticCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DiagnosticCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Self::from_code(&code)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown diagnostic code '{}'", code)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_are_unique_and_well_formed() {
+        let mut seen = HashSet::new();
+        for code in DiagnosticCode::ALL {
+            let text = code.as_str();
+            assert!(seen.insert(text), "duplicate code {}", text);
+            assert_eq!(text.len(), 7);
+            assert!(text.starts_with("MMD") && text[3..].chars().all(|c| c.is_ascii_digit()));
+            assert_eq!(DiagnosticCode::from_code(&text.to_lowercase()), Some(*code));
+        }
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let json = serde_json::to_string(&DiagnosticCode::UnterminatedString).unwrap();
+        assert_eq!(json, "\"MMD0012\"");
+        let code: DiagnosticCode = serde_json::from_str(&json).unwrap();
+        assert_eq!(code, DiagnosticCode::UnterminatedString);
+        assert_eq!(
+            serde_json::to_string(&Severity::Warning).unwrap(),
+            "\"warning\""
+        );
+    }
+}
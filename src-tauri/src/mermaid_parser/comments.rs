@@ -0,0 +1,182 @@
+use super::config::Preamble;
+use super::{ContentFormat, MermaidParser, Position};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Words that turn a comment into a task in [`tasks`]
+const TASK_MARKERS: &[&str] = &["TODO", "FIXME", "XXX", "HACK"];
+
+/// A `%%` comment, on its own line or after a statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    /// Text after the `%%`, trimmed
+    pub text: String,
+    /// Where the `%%` is
+    pub position: Position,
+    /// Follows a statement on the same line
+    pub trailing: bool,
+}
+
+impl Comment {
+    /// The task marker the comment starts with, such as `TODO` in `%% TODO: split this`
+    pub fn task_marker(&self) -> Option<&'static str> {
+        TASK_MARKERS.iter().copied().find(|marker| {
+            self.text
+                .strip_prefix(marker)
+                .is_some_and(|rest| !rest.starts_with(|ch: char| ch.is_alphanumeric() || ch == '_'))
+        })
+    }
+}
+
+/// A TODO, FIXME, XXX or HACK comment in one of a document's diagrams
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagramTask {
+    pub diagram_id: String,
+    pub marker: String,
+    /// The comment after the marker and an optional `:`
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Byte offset of the `%%` that starts a comment on `line`.
+///
+/// `%%` inside quotes or brackets, or straight after other text as in `50%%`, is label
+/// text, and `%%{` starts a directive.
+pub(crate) fn comment_start(line: &str) -> Option<usize> {
+    let mut in_quote = false;
+    let mut depth = 0usize;
+    let mut chars = line.char_indices().peekable();
+    while let Some((offset, ch)) = chars.next() {
+        match ch {
+            '"' => in_quote = !in_quote,
+            _ if in_quote => {}
+            '[' | '(' | '{' => depth += 1,
+            ']' | ')' | '}' => depth = depth.saturating_sub(1),
+            '%' if depth == 0
+                && chars.peek().is_some_and(|(_, next)| *next == '%')
+                && (offset == 0 || line[..offset].ends_with(char::is_whitespace)) =>
+            {
+                return (!line[offset + 2..].starts_with('{')).then_some(offset);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `line` without its comment, and without the whitespace before it
+pub(crate) fn code(line: &str) -> &str {
+    match comment_start(line) {
+        Some(offset) => line[..offset].trim_end(),
+        None => line,
+    }
+}
+
+/// Every comment in a diagram, in source order
+pub fn scan(content: &str, start_line: usize) -> Vec<Comment> {
+    let preamble = Preamble::scan(content);
+    content
+        .lines()
+        .enumerate()
+        .filter(|(index, _)| !preamble.contains(*index))
+        .filter_map(|(index, line)| {
+            let offset = comment_start(line)?;
+            Some(Comment {
+                text: line[offset + 2..].trim().to_string(),
+                position: Position {
+                    line: start_line + index,
+                    column: line[..offset].chars().count() + 1,
+                },
+                trailing: !line[..offset].trim().is_empty(),
+            })
+        })
+        .collect()
+}
+
+/// The diagram with comments removed, keeping line and column numbers of everything else
+pub(crate) fn strip(content: &str) -> Cow<'_, str> {
+    if !content.contains("%%") {
+        return Cow::Borrowed(content);
+    }
+    let lines: Vec<&str> = content.lines().map(code).collect();
+    Cow::Owned(lines.join("\n"))
+}
+
+/// TODO and FIXME style comments in every diagram of a document, with document positions
+pub fn tasks(parser: &MermaidParser, content: &str, format: ContentFormat) -> Vec<DiagramTask> {
+    let mut tasks = Vec::new();
+    for diagram in parser.parse_content_as(content, format).diagrams {
+        for comment in scan(&diagram.content, diagram.start_line) {
+            let Some(marker) = comment.task_marker() else {
+                continue;
+            };
+            let text = comment.text[marker.len()..].trim_start();
+            tasks.push(DiagramTask {
+                diagram_id: diagram.id.clone(),
+                marker: marker.to_string(),
+                text: text.strip_prefix(':').unwrap_or(text).trim().to_string(),
+                line: comment.position.line,
+                column: comment.position.column,
+            });
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_and_strip() {
+        let content = "%%{init: {'theme': 'dark'}}%%\ngraph TD\n  %% TODO (fix\n  A[\"50%% off\"] --> B %% trailing [note\n  C[x%%y]";
+        let comments = scan(content, 3);
+        assert_eq!(
+            comments,
+            vec![
+                Comment {
+                    text: "TODO (fix".to_string(),
+                    position: Position { line: 5, column: 3 },
+                    trailing: false,
+                },
+                Comment {
+                    text: "trailing [note".to_string(),
+                    position: Position {
+                        line: 6,
+                        column: 23
+                    },
+                    trailing: true,
+                },
+            ]
+        );
+        assert_eq!(comments[0].task_marker(), Some("TODO"));
+        assert_eq!(comments[1].task_marker(), None);
+        assert_eq!(
+            strip(content),
+            "%%{init: {'theme': 'dark'}}%%\ngraph TD\n\n  A[\"50%% off\"] --> B\n  C[x%%y]"
+        );
+
+        let parser = MermaidParser::new().unwrap();
+        assert!(parser.validate_diagram(content, 1).is_valid);
+    }
+
+    #[test]
+    fn test_tasks_in_document() {
+        let parser = MermaidParser::new().unwrap();
+        let content = "# Plan\n\n```mermaid\n%% id: checkout\ngraph TD\n  A --> B %% FIXME: wrong target\n  %% TODOS are not tasks\n```\n\n```mermaid\nsequenceDiagram\n  %% TODO ask about retries\n  A->>B: hi\n```\n";
+        let tasks = tasks(&parser, content, ContentFormat::Markdown);
+        let found: Vec<_> = tasks
+            .iter()
+            .map(|task| (task.marker.as_str(), task.text.as_str(), task.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("FIXME", "wrong target", 6),
+                ("TODO", "ask about retries", 12)
+            ]
+        );
+        assert_eq!(tasks[0].diagram_id, "checkout");
+    }
+}
@@ -0,0 +1,458 @@
+use super::{DiagnosticCode, SyntaxError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Themes bundled with Mermaid
+const THEMES: &[&str] = &["default", "base", "dark", "forest", "neutral", "null"];
+
+/// Top-level frontmatter keys Mermaid reads
+const FRONTMATTER_KEYS: &[&str] = &["title", "displayMode", "config"];
+
+/// Directive types Mermaid accepts; `init` and `initialize` carry config
+const DIRECTIVES: &[&str] = &["init", "initialize", "wrap"];
+
+/// Configuration given in a diagram's YAML frontmatter and `%%{init: ...}%%` directives.
+///
+/// Directives are applied after the frontmatter, so their values win.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiagramConfig {
+    pub title: Option<String>,
+    pub theme: Option<String>,
+    /// `themeVariables`, as given
+    pub theme_variables: Option<Map<String, Value>>,
+    /// Every other config key, such as `flowchart: { curve: basis }` or `fontFamily`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub config: Map<String, Value>,
+}
+
+/// Lines before and around the diagram's statements that hold configuration, by zero-based index
+#[derive(Debug, Default)]
+pub(crate) struct Preamble {
+    /// Including both `---` lines
+    frontmatter: Option<Range<usize>>,
+    /// Missing its closing `---`; then it runs to the end of the diagram
+    unclosed: bool,
+    directives: Vec<Range<usize>>,
+}
+
+impl Preamble {
+    pub fn scan(content: &str) -> Self {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut preamble = Preamble::default();
+
+        let mut index = lines
+            .iter()
+            .position(|line| !line.trim().is_empty())
+            .unwrap_or(lines.len());
+        if lines.get(index).is_some_and(|line| line.trim() == "---") {
+            let close = lines[index + 1..]
+                .iter()
+                .position(|line| line.trim() == "---");
+            let end = match close {
+                Some(offset) => index + 1 + offset + 1,
+                None => {
+                    preamble.unclosed = true;
+                    lines.len()
+                }
+            };
+            preamble.frontmatter = Some(index..end);
+            index = end;
+        }
+
+        // Directives may be split over several lines: `%%{` up to `}%%`
+        while index < lines.len() {
+            let line = lines[index].trim();
+            if line.starts_with("%%{") {
+                let end = lines[index..]
+                    .iter()
+                    .position(|line| line.trim_end().ends_with("}%%"))
+                    .map_or(index + 1, |offset| index + offset + 1);
+                preamble.directives.push(index..end);
+                index = end;
+            } else {
+                index += 1;
+            }
+        }
+        preamble
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frontmatter.is_none() && self.directives.is_empty()
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.frontmatter
+            .iter()
+            .chain(&self.directives)
+            .any(|range| range.contains(&index))
+    }
+
+    /// The content with configuration lines blanked out, so line numbers stay the same
+    pub fn mask<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        if self.is_empty() {
+            return Cow::Borrowed(content);
+        }
+        let lines: Vec<&str> = content
+            .lines()
+            .enumerate()
+            .map(|(index, line)| if self.contains(index) { "" } else { line })
+            .collect();
+        Cow::Owned(lines.join("\n"))
+    }
+
+    /// Zero-based index and trimmed text of the first statement, which declares the diagram type
+    pub fn declaration<'a>(&self, content: &'a str) -> Option<(usize, &'a str)> {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(index, _)| !self.contains(*index))
+            .map(|(index, line)| (index, line.trim()))
+            .find(|(_, line)| !line.is_empty() && !line.starts_with("%%"))
+    }
+
+    /// Read and check the configuration; `None` when the diagram has none
+    pub fn config(
+        &self,
+        content: &str,
+        start_line: usize,
+    ) -> (Option<DiagramConfig>, Vec<SyntaxError>) {
+        if self.is_empty() {
+            return (None, Vec::new());
+        }
+        let lines: Vec<&str> = content.lines().collect();
+        let mut reader = Reader {
+            lines: &lines,
+            start_line,
+            merged: Map::new(),
+            title: None,
+            errors: Vec::new(),
+        };
+        if let Some(range) = &self.frontmatter {
+            reader.frontmatter(range.clone(), self.unclosed);
+        }
+        for range in &self.directives {
+            reader.directive(range.clone());
+        }
+        reader.finish()
+    }
+}
+
+/// Blank out frontmatter and directives so statement parsers only see statements
+pub(crate) fn mask(content: &str) -> Cow<'_, str> {
+    Preamble::scan(content).mask(content)
+}
+
+/// Zero-based index and trimmed text of a diagram's declaration line
+pub(crate) fn declaration(content: &str) -> Option<(usize, &str)> {
+    Preamble::scan(content).declaration(content)
+}
+
+struct Reader<'a> {
+    lines: &'a [&'a str],
+    start_line: usize,
+    /// Config keys from every block, later blocks winning
+    merged: Map<String, Value>,
+    title: Option<String>,
+    errors: Vec<SyntaxError>,
+}
+
+impl Reader<'_> {
+    fn error(&mut self, code: DiagnosticCode, index: usize, message: String) {
+        let line = self.lines.get(index).copied().unwrap_or("");
+        let column = line.chars().count() - line.trim_start().chars().count() + 1;
+        self.errors.push(SyntaxError::new(
+            code,
+            self.start_line + index,
+            column,
+            message,
+        ));
+    }
+
+    /// First line of `range` that mentions `key`, for pointing at a bad value
+    fn key_line(&self, range: &Range<usize>, key: &str) -> usize {
+        range
+            .clone()
+            .find(|&index| {
+                self.lines.get(index).is_some_and(|line| {
+                    line.match_indices(key).any(|(offset, _)| {
+                        let after = line[offset + key.len()..].trim_start_matches(['"', '\'']);
+                        after.trim_start().starts_with(':')
+                    })
+                })
+            })
+            .unwrap_or(range.start)
+    }
+
+    fn frontmatter(&mut self, range: Range<usize>, unclosed: bool) {
+        if unclosed {
+            self.error(
+                DiagnosticCode::InvalidConfig,
+                range.start,
+                "Frontmatter is missing its closing '---'".to_string(),
+            );
+            return;
+        }
+
+        let body = self.lines[range.start + 1..range.end - 1].join("\n");
+        if body.trim().is_empty() {
+            return;
+        }
+        let value: Value = match serde_yaml::from_str(&body) {
+            Ok(value) => value,
+            Err(error) => {
+                let index = error
+                    .location()
+                    .map_or(range.start, |location| range.start + location.line());
+                self.error(
+                    DiagnosticCode::InvalidConfig,
+                    index,
+                    format!("Invalid frontmatter: {}", error),
+                );
+                return;
+            }
+        };
+        let Value::Object(map) = value else {
+            self.error(
+                DiagnosticCode::InvalidConfig,
+                range.start,
+                "Frontmatter must be a set of 'key: value' pairs".to_string(),
+            );
+            return;
+        };
+
+        for (key, value) in map {
+            let index = self.key_line(&range, &key);
+            match (key.as_str(), value) {
+                ("title", Value::String(title)) => self.title = Some(title),
+                ("title", Value::Number(number)) => self.title = Some(number.to_string()),
+                ("title", _) => self.error(
+                    DiagnosticCode::InvalidConfig,
+                    index,
+                    "Frontmatter 'title' must be text".to_string(),
+                ),
+                ("config", Value::Object(config)) => self.merge(config, &range),
+                ("config", Value::Null) => {}
+                ("config", _) => self.error(
+                    DiagnosticCode::InvalidConfig,
+                    index,
+                    "Frontmatter 'config' must be a set of 'key: value' pairs".to_string(),
+                ),
+                (key, _) if FRONTMATTER_KEYS.contains(&key) => {}
+                (key, _) => self.error(
+                    DiagnosticCode::UnknownConfig,
+                    index,
+                    format!(
+                        "Unknown frontmatter key '{}', expected one of: {}",
+                        key,
+                        FRONTMATTER_KEYS.join(", ")
+                    ),
+                ),
+            }
+        }
+    }
+
+    /// `%%{init: {"theme": "dark"}}%%`, where the value is JSON5 so single quotes and bare keys work
+    fn directive(&mut self, range: Range<usize>) {
+        let text = self.lines[range.clone()].join("\n");
+        let Some(inner) = text
+            .trim()
+            .strip_prefix("%%{")
+            .and_then(|rest| rest.strip_suffix("}%%"))
+        else {
+            self.error(
+                DiagnosticCode::InvalidConfig,
+                range.start,
+                "Directive is missing its closing '}%%'".to_string(),
+            );
+            return;
+        };
+
+        // A directive without a value, such as `%%{wrap}%%`
+        let inner = inner.trim();
+        if inner.chars().all(|ch| ch.is_alphanumeric() || ch == '_') {
+            self.check_directive_type(inner, range.start);
+            return;
+        }
+
+        let map = match json5::from_str::<Value>(&format!("{{{}}}", inner)) {
+            Ok(Value::Object(map)) => map,
+            Ok(_) => unreachable!("a braced JSON5 value is an object"),
+            Err(error) => {
+                self.error(
+                    DiagnosticCode::InvalidConfig,
+                    range.start,
+                    format!("Invalid directive: {}", error),
+                );
+                return;
+            }
+        };
+        for (key, value) in map {
+            if !self.check_directive_type(&key, range.start) {
+                continue;
+            }
+            match value {
+                Value::Object(config) if key.starts_with("init") => self.merge(config, &range),
+                _ if key.starts_with("init") => self.error(
+                    DiagnosticCode::InvalidConfig,
+                    range.start,
+                    format!("The '{}' directive takes an object of config values", key),
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_directive_type(&mut self, name: &str, index: usize) -> bool {
+        let known = DIRECTIVES.contains(&name);
+        if !known {
+            self.error(
+                DiagnosticCode::UnknownConfig,
+                index,
+                format!(
+                    "Unknown directive '{}', expected one of: {}",
+                    name,
+                    DIRECTIVES.join(", ")
+                ),
+            );
+        }
+        known
+    }
+
+    fn merge(&mut self, config: Map<String, Value>, range: &Range<usize>) {
+        for (key, value) in config {
+            let index = self.key_line(range, &key);
+            match (key.as_str(), &value) {
+                ("theme", Value::String(theme)) if !THEMES.contains(&theme.as_str()) => self.error(
+                    DiagnosticCode::UnknownConfig,
+                    index,
+                    format!(
+                        "Unknown theme '{}', expected one of: {}",
+                        theme,
+                        THEMES.join(", ")
+                    ),
+                ),
+                ("theme", Value::String(_)) => {}
+                ("theme", _) => {
+                    self.error(
+                        DiagnosticCode::InvalidConfig,
+                        index,
+                        "'theme' must be a theme name".to_string(),
+                    );
+                    continue;
+                }
+                ("themeVariables", Value::Object(_)) => {}
+                ("themeVariables", _) => {
+                    self.error(
+                        DiagnosticCode::InvalidConfig,
+                        index,
+                        "'themeVariables' must be a set of 'key: value' pairs".to_string(),
+                    );
+                    continue;
+                }
+                _ => {}
+            }
+            merge_value(&mut self.merged, key, value);
+        }
+    }
+
+    fn finish(mut self) -> (Option<DiagramConfig>, Vec<SyntaxError>) {
+        let theme = match self.merged.remove("theme") {
+            Some(Value::String(theme)) => Some(theme),
+            _ => None,
+        };
+        let theme_variables = match self.merged.remove("themeVariables") {
+            Some(Value::Object(variables)) => Some(variables),
+            _ => None,
+        };
+        let config = DiagramConfig {
+            title: self.title,
+            theme,
+            theme_variables,
+            config: self.merged,
+        };
+        (Some(config), self.errors)
+    }
+}
+
+/// Insert `value`, merging nested objects key by key like Mermaid does
+fn merge_value(map: &mut Map<String, Value>, key: String, value: Value) {
+    match (map.get_mut(&key), value) {
+        (Some(Value::Object(existing)), Value::Object(value)) => {
+            for (key, value) in value {
+                merge_value(existing, key, value);
+            }
+        }
+        (_, value) => {
+            map.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(content: &str) -> (DiagramConfig, Vec<SyntaxError>) {
+        let (config, errors) = Preamble::scan(content).config(content, 1);
+        (config.unwrap(), errors)
+    }
+
+    #[test]
+    fn test_frontmatter_and_directives_merge() {
+        let content = "---\ntitle: Checkout\nconfig:\n  theme: forest\n  flowchart:\n    curve: basis\n    htmlLabels: false\n---\n%%{init: {'theme': 'dark', 'themeVariables': {'primaryColor': '#ff0000'}, flowchart: {curve: 'linear'}}}%%\ngraph TD\n  A --> B";
+        let (config, errors) = read(content);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(config.title.as_deref(), Some("Checkout"));
+        assert_eq!(config.theme.as_deref(), Some("dark"));
+        assert_eq!(config.theme_variables.unwrap()["primaryColor"], "#ff0000");
+        assert_eq!(
+            Value::Object(config.config),
+            serde_json::json!({ "flowchart": { "curve": "linear", "htmlLabels": false } })
+        );
+
+        let preamble = Preamble::scan(content);
+        assert_eq!(preamble.declaration(content), Some((9, "graph TD")));
+        assert_eq!(
+            preamble.mask(content),
+            "\n\n\n\n\n\n\n\n\ngraph TD\n  A --> B"
+        );
+        assert!(Preamble::scan("graph TD\n  A --> B")
+            .config("", 1)
+            .0
+            .is_none());
+    }
+
+    #[test]
+    fn test_reports_invalid_config() {
+        let (_, errors) = read("---\ntitle: [unclosed\n---\ngraph TD");
+        assert_eq!(errors[0].code, DiagnosticCode::InvalidConfig);
+
+        let (_, errors) =
+            read("---\ntitle: x\nconfig:\n  theme: sunset\nlayout: elk\n---\ngraph TD");
+        let found: Vec<_> = errors.iter().map(|e| (e.code, e.line)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (DiagnosticCode::UnknownConfig, 4),
+                (DiagnosticCode::UnknownConfig, 5)
+            ]
+        );
+
+        let (_, errors) = read("%%{init: {theme: 'dark'}\ngraph TD");
+        assert_eq!(errors[0].code, DiagnosticCode::InvalidConfig);
+        let (_, errors) = read("%%{wrap}%%\n%%{colour: 1}%%\ngraph TD");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            (errors[0].code, errors[0].line),
+            (DiagnosticCode::UnknownConfig, 2)
+        );
+
+        let (_, errors) = read("---\ntitle: x\ngraph TD");
+        assert_eq!(
+            errors[0].message,
+            "Frontmatter is missing its closing '---'"
+        );
+    }
+}
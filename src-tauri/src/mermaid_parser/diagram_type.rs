@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The outcome of reading a diagram's declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Detection {
+    pub diagram_type: DiagramType,
+    /// The declaration keyword as written, when the diagram has a first statement
+    pub keyword: Option<String>,
+    /// A known keyword close to an unrecognized one, e.g. `gitGraph` for `gitgraph`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+impl Detection {
+    /// Detect the type declared by a diagram's first statement
+    pub fn of(declaration: Option<&str>) -> Self {
+        let keyword = declaration.map(keyword);
+        let diagram_type = keyword
+            .and_then(DiagramType::from_keyword)
+            .unwrap_or(DiagramType::Unknown);
+        let suggestion = match keyword {
+            Some(keyword) if diagram_type == DiagramType::Unknown => suggest(keyword),
+            _ => None,
+        };
+        Detection {
+            diagram_type,
+            keyword: keyword.map(str::to_string),
+            suggestion: suggestion.map(str::to_string),
+        }
+    }
+}
+
+/// Every diagram type Mermaid can render, plus `Unknown` for anything else
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramType {
+    Flowchart,
+    Sequence,
+    Class,
+    State,
+    Er,
+    Gantt,
+    Pie,
+    Journey,
+    GitGraph,
+    Requirement,
+    C4Context,
+    C4Container,
+    C4Component,
+    C4Dynamic,
+    C4Deployment,
+    Mindmap,
+    Timeline,
+    Quadrant,
+    Sankey,
+    XyChart,
+    Block,
+    Packet,
+    Architecture,
+    Kanban,
+    ZenUml,
+    Radar,
+    Treemap,
+    Unknown,
+}
+
+impl DiagramType {
+    pub const ALL: &'static [DiagramType] = &[
+        DiagramType::Flowchart,
+        DiagramType::Sequence,
+        DiagramType::Class,
+        DiagramType::State,
+        DiagramType::Er,
+        DiagramType::Gantt,
+        DiagramType::Pie,
+        DiagramType::Journey,
+        DiagramType::GitGraph,
+        DiagramType::Requirement,
+        DiagramType::C4Context,
+        DiagramType::C4Container,
+        DiagramType::C4Component,
+        DiagramType::C4Dynamic,
+        DiagramType::C4Deployment,
+        DiagramType::Mindmap,
+        DiagramType::Timeline,
+        DiagramType::Quadrant,
+        DiagramType::Sankey,
+        DiagramType::XyChart,
+        DiagramType::Block,
+        DiagramType::Packet,
+        DiagramType::Architecture,
+        DiagramType::Kanban,
+        DiagramType::ZenUml,
+        DiagramType::Radar,
+        DiagramType::Treemap,
+        DiagramType::Unknown,
+    ];
+
+    /// The name used in IDs, statistics and the frontend, e.g. `flowchart` or `c4context`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagramType::Flowchart => "flowchart",
+            DiagramType::Sequence => "sequence",
+            DiagramType::Class => "class",
+            DiagramType::State => "state",
+            DiagramType::Er => "er",
+            DiagramType::Gantt => "gantt",
+            DiagramType::Pie => "pie",
+            DiagramType::Journey => "journey",
+            DiagramType::GitGraph => "gitgraph",
+            DiagramType::Requirement => "requirement",
+            DiagramType::C4Context => "c4context",
+            DiagramType::C4Container => "c4container",
+            DiagramType::C4Component => "c4component",
+            DiagramType::C4Dynamic => "c4dynamic",
+            DiagramType::C4Deployment => "c4deployment",
+            DiagramType::Mindmap => "mindmap",
+            DiagramType::Timeline => "timeline",
+            DiagramType::Quadrant => "quadrant",
+            DiagramType::Sankey => "sankey",
+            DiagramType::XyChart => "xychart",
+            DiagramType::Block => "block",
+            DiagramType::Packet => "packet",
+            DiagramType::Architecture => "architecture",
+            DiagramType::Kanban => "kanban",
+            DiagramType::ZenUml => "zenuml",
+            DiagramType::Radar => "radar",
+            DiagramType::Treemap => "treemap",
+            DiagramType::Unknown => "unknown",
+        }
+    }
+
+    /// Keywords that declare this type, the preferred spelling first
+    pub fn declarations(self) -> &'static [&'static str] {
+        match self {
+            DiagramType::Flowchart => &["flowchart", "graph", "flowchart-elk"],
+            DiagramType::Sequence => &["sequenceDiagram"],
+            DiagramType::Class => &["classDiagram", "classDiagram-v2"],
+            DiagramType::State => &["stateDiagram-v2", "stateDiagram"],
+            DiagramType::Er => &["erDiagram"],
+            DiagramType::Gantt => &["gantt"],
+            DiagramType::Pie => &["pie"],
+            DiagramType::Journey => &["journey"],
+            DiagramType::GitGraph => &["gitGraph"],
+            DiagramType::Requirement => &["requirementDiagram"],
+            DiagramType::C4Context => &["C4Context"],
+            DiagramType::C4Container => &["C4Container"],
+            DiagramType::C4Component => &["C4Component"],
+            DiagramType::C4Dynamic => &["C4Dynamic"],
+            DiagramType::C4Deployment => &["C4Deployment"],
+            DiagramType::Mindmap => &["mindmap"],
+            DiagramType::Timeline => &["timeline"],
+            DiagramType::Quadrant => &["quadrantChart"],
+            DiagramType::Sankey => &["sankey-beta", "sankey"],
+            DiagramType::XyChart => &["xychart-beta", "xychart"],
+            DiagramType::Block => &["block-beta", "block"],
+            DiagramType::Packet => &["packet-beta", "packet"],
+            DiagramType::Architecture => &["architecture-beta"],
+            DiagramType::Kanban => &["kanban"],
+            DiagramType::ZenUml => &["zenuml"],
+            DiagramType::Radar => &["radar-beta"],
+            DiagramType::Treemap => &["treemap-beta"],
+            DiagramType::Unknown => &[],
+        }
+    }
+
+    /// The type a declaration keyword names. Keywords are case-sensitive, as in Mermaid:
+    /// `gitGraph` declares a git graph, `gitgraph` declares nothing.
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        DiagramType::ALL
+            .iter()
+            .copied()
+            .find(|diagram_type| diagram_type.declarations().contains(&keyword))
+    }
+
+    /// Whether this is one of the C4 architecture diagrams
+    pub fn is_c4(self) -> bool {
+        matches!(
+            self,
+            DiagramType::C4Context
+                | DiagramType::C4Container
+                | DiagramType::C4Component
+                | DiagramType::C4Dynamic
+                | DiagramType::C4Deployment
+        )
+    }
+}
+
+impl fmt::Display for DiagramType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The keyword of a declaration statement: its first word, up to whitespace, `;` or the
+/// `:` of `gitGraph:`
+pub fn keyword(declaration: &str) -> &str {
+    let declaration = declaration.trim_start();
+    let end = declaration
+        .find(|ch: char| ch.is_whitespace() || ch == ';' || ch == ':')
+        .unwrap_or(declaration.len());
+    &declaration[..end]
+}
+
+/// The declaration keyword `keyword` was most likely meant to be: one that differs only
+/// in case, or by a few typos for longer keywords. Ties go to the earlier type in
+/// [`DiagramType::ALL`].
+pub fn suggest(keyword: &str) -> Option<&'static str> {
+    let lower = keyword.to_lowercase();
+    let mut best: Option<(usize, &'static str)> = None;
+    for candidate in DiagramType::ALL
+        .iter()
+        .flat_map(|diagram_type| diagram_type.declarations())
+    {
+        let distance = edit_distance(&lower, &candidate.to_lowercase());
+        let allowed = (candidate.len() / 4).max(1);
+        if distance <= allowed && best.is_none_or(|(closest, _)| distance < closest) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_match_serde() {
+        for diagram_type in DiagramType::ALL {
+            assert_eq!(
+                serde_json::to_value(diagram_type).unwrap(),
+                diagram_type.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn test_exact_detection_and_suggestions() {
+        let detect = |line: &str| Detection::of(Some(line));
+
+        assert_eq!(detect("gitGraph:").diagram_type, DiagramType::GitGraph);
+        assert_eq!(detect("stateDiagram-v2").diagram_type, DiagramType::State);
+        assert_eq!(detect("graph;").diagram_type, DiagramType::Flowchart);
+        assert_eq!(
+            detect("flowchart-elk TD").diagram_type,
+            DiagramType::Flowchart
+        );
+        assert_eq!(detect("pie showData").diagram_type, DiagramType::Pie);
+
+        let gitgraph = detect("gitgraph");
+        assert_eq!(gitgraph.diagram_type, DiagramType::Unknown);
+        assert_eq!(gitgraph.keyword.as_deref(), Some("gitgraph"));
+        assert_eq!(gitgraph.suggestion.as_deref(), Some("gitGraph"));
+
+        assert_eq!(
+            detect("sequenceDiagam").suggestion.as_deref(),
+            Some("sequenceDiagram")
+        );
+        assert_eq!(
+            detect("flowchar LR").suggestion.as_deref(),
+            Some("flowchart")
+        );
+
+        let graphql = detect("graphQL notes");
+        assert_eq!(graphql.diagram_type, DiagramType::Unknown);
+        assert_eq!(graphql.suggestion, None);
+        assert_eq!(detect("piechart").diagram_type, DiagramType::Unknown);
+        assert_eq!(Detection::of(None).keyword, None);
+    }
+}
@@ -0,0 +1,502 @@
+use super::DiagramBlock;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+/// Language tags that mark a fenced code block as a Mermaid diagram
+const LANGUAGES: &[&str] = &["mermaid", "mmd"];
+
+static HTML_OPEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^<(?P<tag>pre|div)\b(?P<attributes>[^>]*)>").unwrap());
+
+static LIST_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:[-+*]|\d{1,9}[.)])[ \t]+").unwrap());
+
+/// How a diagram is delimited in Markdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FenceKind {
+    /// A ```` ``` ```` fence, three or more backticks long
+    Backtick,
+    /// A `~~~` fence
+    Tilde,
+    /// A `<pre class="mermaid">` or `<div class="mermaid">` block
+    Html,
+}
+
+/// The opening fence of a Markdown diagram
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fence {
+    pub kind: FenceKind,
+    /// The info string, such as `mermaid {title="x"}`, or the HTML tag's attributes
+    pub info: String,
+    /// Attributes from the info string or tag. `#name` is stored as `id` and `.name`
+    /// is added to `class`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// A block that is open while scanning
+struct OpenBlock {
+    kind: FenceKind,
+    /// Fence character and length; a closing fence must be at least as long
+    marker: char,
+    length: usize,
+    /// Blockquote nesting of the opening line; the block ends with its blockquote
+    depth: usize,
+    /// Content column of the enclosing list item or blockquote, which fence indentation
+    /// is measured from
+    base: usize,
+    /// `None` for code blocks in other languages, whose text is skipped
+    fence: Option<Fence>,
+    start_line: usize,
+    lines: Vec<String>,
+}
+
+impl OpenBlock {
+    fn finish(self, end_line: usize, blocks: &mut Vec<DiagramBlock>) {
+        let Some(fence) = self.fence else {
+            return;
+        };
+        if self.lines.is_empty() {
+            return;
+        }
+        blocks.push(DiagramBlock {
+            content: self.lines.join("\n"),
+            start_line: self.start_line,
+            end_line,
+            fence: Some(fence),
+        });
+    }
+}
+
+/// Find the Mermaid diagrams in a Markdown document.
+///
+/// Follows CommonMark fences: backtick or tilde fences of any length, closed only by a
+/// fence of the same character that is at least as long, inside list items and
+/// blockquotes. Fences may be indented at most three spaces past their container's
+/// content column; anything deeper is an indented code block. Code blocks in other
+/// languages are skipped whole, so an example fence inside a ```` ```markdown ````
+/// block is not a diagram. HTML `<pre class="mermaid">` and `<div class="mermaid">`
+/// blocks count too.
+///
+/// Blockquote markers become spaces in the diagram content, so line and column
+/// positions in diagnostics are document positions.
+pub(crate) fn scan(content: &str) -> Vec<DiagramBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<OpenBlock> = None;
+    // Content columns of the list items the current line may belong to, innermost last
+    let mut lists: Vec<usize> = Vec::new();
+    let mut list_depth = 0;
+    let line_count = content.lines().count();
+
+    for (index, raw) in content.lines().enumerate() {
+        // Inside a block, `>` past the block's own blockquote depth is diagram text
+        let limit = open.as_ref().map_or(usize::MAX, |block| block.depth);
+        let (depth, quote_column, text) = unquote(raw, limit);
+
+        if let Some(block) = open.as_mut() {
+            if depth < block.depth {
+                // Leaving the blockquote closes the block at the previous line
+                if let Some(block) = open.take() {
+                    block.finish(index, &mut blocks);
+                }
+            } else if block.kind == FenceKind::Html {
+                match html_close(&text) {
+                    Some(before) => {
+                        let mut end_line = index;
+                        if !before.trim().is_empty() {
+                            block.lines.push(decode_entities(before));
+                            end_line += 1;
+                        }
+                        if let Some(block) = open.take() {
+                            block.finish(end_line, &mut blocks);
+                        }
+                    }
+                    None => block.lines.push(decode_entities(&text)),
+                }
+                continue;
+            } else {
+                if closes(&text, block.marker, block.length, block.base) {
+                    if let Some(block) = open.take() {
+                        block.finish(index, &mut blocks);
+                    }
+                } else {
+                    block.lines.push(text);
+                }
+                continue;
+            }
+        }
+
+        if depth != list_depth {
+            lists.clear();
+            list_depth = depth;
+        }
+        let (indent, body) = split_indent(&text);
+        // A non-blank line indented less than a list item's content ends that item
+        if !body.is_empty() {
+            while lists.last().is_some_and(|column| indent < *column) {
+                lists.pop();
+            }
+        }
+        let base = lists.last().copied().unwrap_or(quote_column);
+        if indent <= base + 3 {
+            if let Some((width, _)) = list_item(body) {
+                lists.push(indent + width);
+            }
+        }
+
+        open = opening(&text, depth, base, index);
+        // An HTML block may close on its opening line
+        if let Some(block) = open.as_mut().filter(|block| block.kind == FenceKind::Html) {
+            let rest = html_rest(&text);
+            match html_close(&rest) {
+                Some(before) => {
+                    block.start_line = index + 1;
+                    if !before.trim().is_empty() {
+                        block.lines.push(decode_entities(before));
+                    }
+                    if let Some(block) = open.take() {
+                        block.finish(index + 1, &mut blocks);
+                    }
+                }
+                None if !rest.trim().is_empty() => {
+                    block.start_line = index + 1;
+                    block.lines.push(decode_entities(&rest));
+                }
+                None => {}
+            }
+        }
+    }
+
+    // An unclosed block runs to the end of the document
+    if let Some(block) = open {
+        block.finish(line_count, &mut blocks);
+    }
+    blocks
+}
+
+/// Whether a line opens a Mermaid fence or HTML block, e.g. to offer declarations
+/// inside a fence that has no diagram yet
+pub(crate) fn opens_diagram(line: &str) -> bool {
+    let (depth, quote_column, text) = unquote(line, usize::MAX);
+    opening(&text, depth, quote_column, 0).is_some_and(|block| block.fence.is_some())
+}
+
+/// Replace up to `limit` blockquote markers with spaces, keeping columns; also returns
+/// the number replaced and the column where the blockquote content starts
+fn unquote(line: &str, limit: usize) -> (usize, usize, String) {
+    let mut text = line.to_string();
+    let mut depth = 0;
+    let mut offset = 0;
+    loop {
+        let rest = &text[offset..];
+        let indent = rest.len() - rest.trim_start_matches(' ').len();
+        if depth == limit || indent > 3 || !rest[indent..].starts_with('>') {
+            break;
+        }
+        let mut end = offset + indent + 1;
+        if text[end..].starts_with(' ') {
+            end += 1;
+        }
+        text.replace_range(offset..end, &" ".repeat(end - offset));
+        offset = end;
+        depth += 1;
+    }
+    (depth, offset, text)
+}
+
+/// Indentation width in columns, with tabs stopping every four columns, and the rest
+fn split_indent(text: &str) -> (usize, &str) {
+    let mut width = 0;
+    for (offset, ch) in text.char_indices() {
+        match ch {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => return (width, &text[offset..]),
+        }
+    }
+    (width, "")
+}
+
+/// A list marker's width up to the item's content column, and the text after it.
+/// Five or more spaces after the marker start an indented code block, so the content
+/// column is then one past the marker.
+fn list_item(body: &str) -> Option<(usize, &str)> {
+    let marker = LIST_MARKER.find(body)?;
+    let bullet = marker.as_str().trim_end().len();
+    let (spaces, _) = split_indent(&marker.as_str()[bullet..]);
+    if spaces > 4 {
+        Some((bullet + 1, &body[bullet + 1..]))
+    } else {
+        Some((bullet + spaces, &body[marker.end()..]))
+    }
+}
+
+/// The block a line opens, if any. `base` is the content column of the container the
+/// line is in.
+fn opening(text: &str, depth: usize, base: usize, index: usize) -> Option<OpenBlock> {
+    let (indent, body) = split_indent(text);
+    if indent > base + 3 {
+        return None;
+    }
+    let (base, body) = match list_item(body) {
+        Some((width, rest)) => (indent + width, rest),
+        None => (base, body),
+    };
+    // Relative to a list item on the same line, the fence may still be indented
+    let (inner, body) = split_indent(body);
+    if inner > 3 {
+        return None;
+    }
+
+    if let Some(caps) = HTML_OPEN.captures(body) {
+        let attributes = &caps["attributes"];
+        let parsed = parse_attributes(attributes);
+        let is_mermaid = parsed
+            .get("class")
+            .is_some_and(|classes| classes.split_whitespace().any(|class| class == "mermaid"));
+        return is_mermaid.then(|| OpenBlock {
+            kind: FenceKind::Html,
+            marker: '<',
+            length: 0,
+            depth,
+            base,
+            fence: Some(Fence {
+                kind: FenceKind::Html,
+                info: attributes.trim().to_string(),
+                attributes: parsed,
+            }),
+            start_line: index + 2,
+            lines: Vec::new(),
+        });
+    }
+
+    let marker = body.chars().next().filter(|ch| *ch == '`' || *ch == '~')?;
+    let length = body.len() - body.trim_start_matches(marker).len();
+    if length < 3 {
+        return None;
+    }
+    let info = body[length..].trim();
+    // Backticks in a backtick fence's info string make it inline code instead
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+
+    let language_end = info
+        .find(|ch: char| ch.is_whitespace() || ch == '{')
+        .unwrap_or(info.len());
+    let kind = if marker == '`' {
+        FenceKind::Backtick
+    } else {
+        FenceKind::Tilde
+    };
+    let fence = LANGUAGES.contains(&&info[..language_end]).then(|| Fence {
+        kind,
+        info: info.to_string(),
+        attributes: parse_attributes(&info[language_end..]),
+    });
+    Some(OpenBlock {
+        kind,
+        marker,
+        length,
+        depth,
+        base,
+        fence,
+        start_line: index + 2,
+        lines: Vec::new(),
+    })
+}
+
+/// Whether a line is a closing fence for a block opened with `length` × `marker` in a
+/// container whose content starts at column `base`
+fn closes(text: &str, marker: char, length: usize, base: usize) -> bool {
+    let (indent, body) = split_indent(text);
+    if indent > base + 3 {
+        return false;
+    }
+    let body = body.trim_end();
+    let run = body.len() - body.trim_start_matches(marker).len();
+    run >= length && body[run..].trim().is_empty()
+}
+
+/// Text after the opening HTML tag, with the tag replaced by spaces to keep columns
+fn html_rest(text: &str) -> String {
+    let indent = text.len() - text.trim_start().len();
+    let body = &text[indent..];
+    let body = LIST_MARKER
+        .find(body)
+        .map_or(body, |marker| &body[marker.end()..]);
+    let tag_end = HTML_OPEN.find(body).map_or(0, |tag| tag.end());
+    let consumed = text.len() - body.len() + tag_end;
+    format!(
+        "{}{}",
+        " ".repeat(text[..consumed].chars().count()),
+        &text[consumed..]
+    )
+}
+
+/// The text before a closing `</pre>` or `</div>` on this line
+fn html_close(text: &str) -> Option<&str> {
+    ["</pre>", "</div>"]
+        .iter()
+        .filter_map(|closer| text.find(closer))
+        .min()
+        .map(|offset| &text[..offset])
+}
+
+/// Browsers hand Mermaid the decoded text of HTML blocks
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parse `{#id .class key="value" flag}` or `key="value"` HTML attributes
+fn parse_attributes(text: &str) -> BTreeMap<String, String> {
+    let mut attributes = BTreeMap::new();
+    let mut chars = text.chars().peekable();
+    let is_separator = |ch: char| ch.is_whitespace() || ch == '{' || ch == '}';
+
+    loop {
+        while chars.next_if(|ch| is_separator(*ch)).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(ch) = chars.next_if(|ch| !is_separator(*ch) && *ch != '=') {
+            key.push(ch);
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            match chars.next_if(|ch| *ch == '"' || *ch == '\'') {
+                Some(quote) => {
+                    for ch in chars.by_ref() {
+                        if ch == quote {
+                            break;
+                        }
+                        value.push(ch);
+                    }
+                }
+                None => {
+                    while let Some(ch) = chars.next_if(|ch| !is_separator(*ch)) {
+                        value.push(ch);
+                    }
+                }
+            }
+        }
+
+        if let Some(id) = key.strip_prefix('#') {
+            attributes.insert("id".to_string(), id.to_string());
+        } else if let Some(class) = key.strip_prefix('.') {
+            let classes = attributes.entry("class".to_string()).or_default();
+            if !classes.is_empty() {
+                classes.push(' ');
+            }
+            classes.push_str(class);
+        } else if !key.is_empty() {
+            attributes.insert(key, value);
+        }
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(content: &str) -> Vec<(String, usize, usize)> {
+        scan(content)
+            .into_iter()
+            .map(|block| (block.content, block.start_line, block.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn test_fence_variants() {
+        let content = "~~~mermaid\ngraph TD\n~~~\n\n````mermaid\npie\n```\n````\n\n```mermaid {title=\"Flow\" #login .wide}\nflowchart LR\n```\n";
+        assert_eq!(
+            found(content),
+            vec![
+                ("graph TD".to_string(), 2, 2),
+                ("pie\n```".to_string(), 6, 7),
+                ("flowchart LR".to_string(), 11, 11),
+            ]
+        );
+
+        let fence = scan(content).pop().unwrap().fence.unwrap();
+        assert_eq!(fence.kind, FenceKind::Backtick);
+        assert_eq!(fence.info, "mermaid {title=\"Flow\" #login .wide}");
+        assert_eq!(fence.attributes["title"], "Flow");
+        assert_eq!(fence.attributes["id"], "login");
+        assert_eq!(fence.attributes["class"], "wide");
+
+        // Other languages are skipped whole, including example fences inside them
+        let nested = "````markdown\n```mermaid\ngraph TD\n```\n````\n";
+        assert!(found(nested).is_empty());
+    }
+
+    #[test]
+    fn test_containers_and_html() {
+        let arrow = "```mermaid\ngraph LR\n>A] --> B\n```";
+        assert_eq!(
+            found(arrow),
+            vec![("graph LR\n>A] --> B".to_string(), 2, 3)]
+        );
+
+        let list = "1. Steps\n   ```mermaid\n   graph TD\n     A --> B\n   ```\n";
+        assert_eq!(
+            found(list),
+            vec![("   graph TD\n     A --> B".to_string(), 3, 4)]
+        );
+        let item = "- ```mermaid\n  pie\n  ```";
+        assert_eq!(found(item), vec![("  pie".to_string(), 2, 2)]);
+
+        // Blockquote markers keep their columns as spaces; leaving the quote ends the block
+        let quote = "> ```mermaid\n> graph TD\n>   A --> B\n\nafter";
+        assert_eq!(
+            found(quote),
+            vec![("  graph TD\n    A --> B".to_string(), 2, 3)]
+        );
+
+        let html = "<pre class=\"mermaid\" id=\"net\">\ngraph LR\n  A --&gt; B\n</pre>\n<div class=\"mermaid\">pie</div>\n<pre class=\"code\">\ngraph TD\n</pre>";
+        let blocks = scan(html);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].content, "graph LR\n  A --> B");
+        assert_eq!((blocks[0].start_line, blocks[0].end_line), (2, 3));
+        let fence = blocks[0].fence.as_ref().unwrap();
+        assert_eq!(fence.kind, FenceKind::Html);
+        assert_eq!(fence.attributes["id"], "net");
+        assert_eq!(blocks[1].content, format!("{}pie", " ".repeat(21)));
+        assert_eq!((blocks[1].start_line, blocks[1].end_line), (5, 5));
+    }
+
+    #[test]
+    fn test_fence_indentation() {
+        // Four spaces make an indented code block, not a fence
+        assert!(found("    ```mermaid\n    graph TD\n    ```\n").is_empty());
+        assert_eq!(
+            found("   ```mermaid\ngraph TD\n   ```\n"),
+            vec![("graph TD".to_string(), 2, 2)]
+        );
+        // A closing fence indented too far is diagram text
+        assert_eq!(
+            found("```mermaid\ngraph TD\n    ```\n```"),
+            vec![("graph TD\n    ```".to_string(), 2, 3)]
+        );
+
+        // Indentation is measured from the list item's or blockquote's content
+        let list = "1. Steps\n\n      ```mermaid\n      pie\n      ```\n";
+        assert_eq!(found(list), vec![("      pie".to_string(), 4, 4)]);
+        let list = "1. Steps\n\n       ```mermaid\n       pie\n       ```\n";
+        assert!(found(list).is_empty());
+        assert!(found(">     ```mermaid\n>     pie\n>     ```").is_empty());
+        assert!(found("-     ```mermaid\n      pie\n      ```").is_empty());
+    }
+}
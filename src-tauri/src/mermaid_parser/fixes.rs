@@ -0,0 +1,212 @@
+use super::config;
+use super::diagram_type;
+use super::session::{self, TextEdit};
+use super::{DiagnosticCode, Position, SyntaxError};
+use serde::{Deserialize, Serialize};
+
+const DIRECTIONS: &[&str] = &["TB", "TD", "BT", "RL", "LR"];
+
+/// A suggested change that resolves a diagnostic.
+///
+/// Edit positions are in the same 1-based document coordinates as the diagnostic, so the
+/// edits apply to the whole document the diagram was validated in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fix {
+    pub title: String,
+    /// Applied in order, each relative to the text after the previous one
+    pub edits: Vec<TextEdit>,
+}
+
+impl Fix {
+    pub fn insert(title: impl Into<String>, position: Position, text: impl Into<String>) -> Self {
+        Self::replace(title, position, position, text)
+    }
+
+    pub fn replace(
+        title: impl Into<String>,
+        start: Position,
+        end: Position,
+        text: impl Into<String>,
+    ) -> Self {
+        Fix {
+            title: title.into(),
+            edits: vec![TextEdit {
+                start,
+                end,
+                text: text.into(),
+            }],
+        }
+    }
+
+    /// The document with this fix's edits applied
+    pub fn apply(&self, content: &str) -> String {
+        let mut content = content.to_string();
+        session::apply_text_edits(&mut content, &self.edits);
+        content
+    }
+}
+
+/// Suggest fixes for diagnostics that were reported without one
+pub(crate) fn suggest(content: &str, start_line: usize, errors: &mut [SyntaxError]) {
+    for error in errors.iter_mut().filter(|error| error.fixes.is_empty()) {
+        error.fixes = match error.code {
+            DiagnosticCode::InvalidDeclaration => declaration(content, start_line),
+            DiagnosticCode::UnknownDirection => direction(content, start_line, error),
+            _ => Vec::new(),
+        };
+    }
+}
+
+/// Respell a misspelled declaration keyword, e.g. `sequencediagram` or `flowchar`
+fn declaration(content: &str, start_line: usize) -> Vec<Fix> {
+    let Some((index, _)) = config::declaration(content) else {
+        return Vec::new();
+    };
+    let line = content.lines().nth(index).unwrap_or("");
+    let word = diagram_type::keyword(line);
+    let Some(keyword) = diagram_type::suggest(word).filter(|keyword| *keyword != word) else {
+        return Vec::new();
+    };
+
+    let indent = line.chars().count() - line.trim_start().chars().count();
+    let start = Position {
+        line: start_line + index,
+        column: indent + 1,
+    };
+    let end = Position {
+        line: start_line + index,
+        column: start.column + word.chars().count(),
+    };
+    let has_direction = line.split_whitespace().nth(1).is_some();
+    let replacement = match keyword {
+        "graph" | "flowchart" if !has_direction => format!("{} TD", keyword),
+        _ => keyword.to_string(),
+    };
+    vec![Fix::replace(
+        format!("Change '{}' to '{}'", word, replacement),
+        start,
+        end,
+        replacement,
+    )]
+}
+
+/// Replace an unknown flowchart direction with a valid one
+fn direction(content: &str, start_line: usize, error: &SyntaxError) -> Vec<Fix> {
+    let line = content
+        .lines()
+        .nth(error.line.saturating_sub(start_line))
+        .unwrap_or("");
+    let word: String = line
+        .chars()
+        .skip(error.column.saturating_sub(1))
+        .take_while(|ch| ch.is_alphanumeric() || *ch == '_')
+        .collect();
+    if word.is_empty() {
+        return Vec::new();
+    }
+
+    let start = Position {
+        line: error.line,
+        column: error.column,
+    };
+    let end = Position {
+        line: error.line,
+        column: error.column + word.chars().count(),
+    };
+    let upper = word.to_uppercase();
+    let candidates = match DIRECTIONS.iter().find(|direction| **direction == upper) {
+        Some(direction) => vec![*direction],
+        None => vec!["TD", "LR"],
+    };
+    candidates
+        .into_iter()
+        .map(|direction| {
+            Fix::replace(
+                format!("Change direction to '{}'", direction),
+                start,
+                end,
+                direction,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mermaid_parser::MermaidParser;
+
+    fn fixed(content: &str, code: DiagnosticCode) -> Vec<String> {
+        let parser = MermaidParser::new().unwrap();
+        let result = parser.validate_diagram(content, 1);
+        let error = result
+            .errors
+            .iter()
+            .find(|error| error.code == code)
+            .unwrap_or_else(|| panic!("no {} in {:?}", code, result.errors));
+        error.fixes.iter().map(|fix| fix.apply(content)).collect()
+    }
+
+    #[test]
+    fn test_declaration_and_direction_fixes() {
+        assert_eq!(
+            fixed(
+                "sequencediagram\n  A->>B: hi",
+                DiagnosticCode::InvalidDeclaration
+            ),
+            vec!["sequenceDiagram\n  A->>B: hi"]
+        );
+        assert_eq!(
+            fixed("Graph\n  A --> B", DiagnosticCode::InvalidDeclaration),
+            vec!["graph TD\n  A --> B"]
+        );
+        assert_eq!(
+            fixed("gitgraph\n  commit", DiagnosticCode::InvalidDeclaration),
+            vec!["gitGraph\n  commit"]
+        );
+        assert_eq!(
+            fixed("flowchar LR\n  A --> B", DiagnosticCode::InvalidDeclaration),
+            vec!["flowchart LR\n  A --> B"]
+        );
+        assert_eq!(
+            fixed("graph lr\n  A --> B", DiagnosticCode::UnknownDirection),
+            vec!["graph LR\n  A --> B"]
+        );
+        assert_eq!(
+            fixed("flowchart XY\n  A --> B", DiagnosticCode::UnknownDirection),
+            vec!["flowchart TD\n  A --> B", "flowchart LR\n  A --> B"]
+        );
+    }
+
+    #[test]
+    fn test_closing_fixes() {
+        assert_eq!(
+            fixed(
+                "graph TD\n  subgraph one\n    A --> B",
+                DiagnosticCode::UnclosedBlock
+            ),
+            vec!["graph TD\n  subgraph one\n    A --> B\n  end"]
+        );
+        assert_eq!(
+            fixed(
+                "graph TD\n  A[open --> B\n  B --> C",
+                DiagnosticCode::UnclosedBracket
+            ),
+            vec!["graph TD\n  A[open --> B]\n  B --> C"]
+        );
+        assert_eq!(
+            fixed(
+                "sequenceDiagram\n  loop every minute\n    A->>B: ping",
+                DiagnosticCode::UnclosedBlock
+            ),
+            vec!["sequenceDiagram\n  loop every minute\n    A->>B: ping\n  end"]
+        );
+        assert_eq!(
+            fixed(
+                "classDiagram\n  class Animal {\n    +name\n  class Dog",
+                DiagnosticCode::UnclosedBracket
+            ),
+            vec!["classDiagram\n  class Animal {\n    +name\n  class Dog\n  }"]
+        );
+    }
+}
@@ -0,0 +1,1421 @@
+use super::config;
+use super::formatter::{self, QuoteStyle};
+use super::{AstResult, DiagnosticCode, DiagramType, Position, SyntaxError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    TB,
+    TD,
+    BT,
+    RL,
+    LR,
+}
+
+impl Direction {
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "TB" => Some(Direction::TB),
+            "TD" => Some(Direction::TD),
+            "BT" => Some(Direction::BT),
+            "RL" => Some(Direction::RL),
+            "LR" => Some(Direction::LR),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeShape {
+    Default,
+    Rectangle,
+    Round,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    DoubleCircle,
+    Asymmetric,
+    Rhombus,
+    Hexagon,
+    Parallelogram,
+    ParallelogramAlt,
+    Trapezoid,
+    TrapezoidAlt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EdgeStroke {
+    Normal,
+    Thick,
+    Dotted,
+    Invisible,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArrowHead {
+    Arrow,
+    Circle,
+    Cross,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowNode {
+    pub id: String,
+    pub label: Option<String>,
+    pub shape: NodeShape,
+    pub classes: Vec<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowEdge {
+    pub from: String,
+    pub to: String,
+    pub stroke: EdgeStroke,
+    pub start_head: Option<ArrowHead>,
+    pub end_head: Option<ArrowHead>,
+    /// Number of extra rank steps requested by a longer link such as `--->`
+    pub length: usize,
+    pub label: Option<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subgraph {
+    pub id: String,
+    pub title: Option<String>,
+    pub direction: Option<Direction>,
+    pub nodes: Vec<String>,
+    pub subgraphs: Vec<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassDef {
+    pub names: Vec<String>,
+    pub styles: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassAssignment {
+    pub nodes: Vec<String>,
+    pub class_name: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyleStatement {
+    pub node: String,
+    pub styles: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkStyle {
+    /// Edge indices, or `default`
+    pub links: Vec<String>,
+    pub styles: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClickStatement {
+    pub node: String,
+    pub action: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlowchartAst {
+    pub keyword: String,
+    pub direction: Option<Direction>,
+    pub nodes: Vec<FlowNode>,
+    pub edges: Vec<FlowEdge>,
+    pub subgraphs: Vec<Subgraph>,
+    pub class_defs: Vec<ClassDef>,
+    pub class_assignments: Vec<ClassAssignment>,
+    pub styles: Vec<StyleStatement>,
+    pub link_styles: Vec<LinkStyle>,
+    pub clicks: Vec<ClickStatement>,
+}
+
+impl FlowchartAst {
+    #[cfg(test)]
+    pub fn node(&self, id: &str) -> Option<&FlowNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Shape(NodeShape, String),
+    Link(LinkToken),
+    PipeText(String),
+    Separator,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LinkToken {
+    stroke: EdgeStroke,
+    start_head: Option<ArrowHead>,
+    end_head: Option<ArrowHead>,
+    length: usize,
+    label: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: Position,
+}
+
+/// Opening delimiter, closing delimiter and shape, longest opener first
+const SHAPE_DELIMITERS: &[(&str, &str, NodeShape)] = &[
+    ("(((", ")))", NodeShape::DoubleCircle),
+    ("((", "))", NodeShape::Circle),
+    ("([", "])", NodeShape::Stadium),
+    ("(", ")", NodeShape::Round),
+    ("[[", "]]", NodeShape::Subroutine),
+    ("[(", ")]", NodeShape::Cylinder),
+    ("[/", "/]", NodeShape::Parallelogram),
+    ("[\\", "\\]", NodeShape::ParallelogramAlt),
+    ("[", "]", NodeShape::Rectangle),
+    ("{{", "}}", NodeShape::Hexagon),
+    ("{", "}", NodeShape::Rhombus),
+    (">", "]", NodeShape::Asymmetric),
+];
+
+/// Context-sensitive tokenizer; the parser asks for the token kind it expects next
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer {
+    fn new(content: &str, start_line: usize) -> Self {
+        Self {
+            chars: content.chars().collect(),
+            pos: 0,
+            line: start_line,
+            column: 1,
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, pattern: &str) -> bool {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(i, ch)| self.peek_at(i) == Some(ch))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += 1;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn bump_n(&mut self, count: usize) {
+        for _ in 0..count {
+            self.bump();
+        }
+    }
+
+    fn save(&self) -> (usize, usize, usize) {
+        (self.pos, self.line, self.column)
+    }
+
+    fn restore(&mut self, state: (usize, usize, usize)) {
+        (self.pos, self.line, self.column) = state;
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t') | Some('\r')) {
+            self.bump();
+        }
+    }
+
+    /// Skip whitespace, blank lines, statement separators and `%%` comments
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') | Some('\r') | Some('\n') | Some(';') => {
+                    self.bump();
+                }
+                Some('%') if self.peek_at(1) == Some('%') => self.skip_to_line_end(),
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_to_line_end(&mut self) {
+        while let Some(ch) = self.peek() {
+            if ch == '\n' {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    /// Skip the rest of a broken statement so parsing can resume at the next one
+    fn recover(&mut self) {
+        while let Some(ch) = self.peek() {
+            if ch == '\n' || ch == ';' {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn is_word_char(ch: char) -> bool {
+        ch.is_alphanumeric() || ch == '_'
+    }
+
+    fn at_word(&self) -> bool {
+        self.peek().is_some_and(Self::is_word_char)
+    }
+
+    /// The word `lex_word` would read, so `end-node` is an id rather than the `end` keyword
+    fn peek_word(&self) -> Option<String> {
+        let mut word = String::new();
+        let mut offset = 0;
+        while let Some(ch) = self.peek_at(offset) {
+            let hyphenated = ch == '-'
+                && self.peek_at(offset + 1).is_some_and(Self::is_word_char)
+                && !word.is_empty();
+            if !Self::is_word_char(ch) && !hyphenated {
+                break;
+            }
+            word.push(ch);
+            offset += 1;
+        }
+        (!word.is_empty()).then_some(word)
+    }
+
+    fn lex_word(&mut self) -> Option<Token> {
+        let position = self.position();
+        let mut word = String::new();
+        while let Some(ch) = self.peek() {
+            if Self::is_word_char(ch) {
+                word.push(ch);
+                self.bump();
+            } else if ch == '-'
+                && self.peek_at(1).is_some_and(Self::is_word_char)
+                && !word.is_empty()
+            {
+                // Hyphenated IDs like `api-gateway`; `A-->B` stops before the link
+                word.push(ch);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        (!word.is_empty()).then_some(Token {
+            kind: TokenKind::Word(word),
+            position,
+        })
+    }
+
+    /// Read the raw remainder of the current line, without the trailing `;`
+    fn rest_of_line(&mut self) -> (String, Position) {
+        self.skip_inline_whitespace();
+        let position = self.position();
+        let mut text = String::new();
+        while let Some(ch) = self.peek() {
+            if ch == '\n' {
+                break;
+            }
+            text.push(ch);
+            self.bump();
+        }
+        let text = text.trim_end().trim_end_matches(';').trim_end().to_string();
+        (text, position)
+    }
+
+    fn lex_shape(&mut self) -> Option<Result<Token, SyntaxError>> {
+        let position = self.position();
+        let &(open, close, shape) = SHAPE_DELIMITERS
+            .iter()
+            .find(|(open, _, _)| self.starts_with(open))?;
+        self.bump_n(open.chars().count());
+
+        // Slanted shapes may close with either slash, which picks the variant
+        let closers: Vec<(&str, NodeShape)> = match shape {
+            NodeShape::Parallelogram => vec![
+                ("/]", NodeShape::Parallelogram),
+                ("\\]", NodeShape::Trapezoid),
+            ],
+            NodeShape::ParallelogramAlt => vec![
+                ("\\]", NodeShape::ParallelogramAlt),
+                ("/]", NodeShape::TrapezoidAlt),
+            ],
+            _ => vec![(close, shape)],
+        };
+
+        let unclosed = |pos: Position| {
+            SyntaxError::new(
+                DiagnosticCode::UnclosedBracket,
+                pos.line,
+                pos.column,
+                format!(
+                    "Unclosed '{}': expected '{}' before the end of the line",
+                    open, close
+                ),
+            )
+        };
+
+        let text = if self.peek() == Some('"') {
+            match self.lex_quoted() {
+                Ok(text) => {
+                    self.skip_inline_whitespace();
+                    text
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        } else {
+            let mut text = String::new();
+            loop {
+                match self.peek() {
+                    None | Some('\n') => return Some(Err(unclosed(position))),
+                    Some(_) if closers.iter().any(|(c, _)| self.starts_with(c)) => break,
+                    Some(ch) if "[](){}".contains(ch) => {
+                        let at = self.position();
+                        return Some(Err(SyntaxError::new(
+                            DiagnosticCode::UnexpectedCharacter,
+                            at.line,
+                            at.column,
+                            format!(
+                                "Unexpected '{}' inside node text; quote the label to use it",
+                                ch
+                            ),
+                        )));
+                    }
+                    Some(ch) => {
+                        text.push(ch);
+                        self.bump();
+                    }
+                }
+            }
+            text.trim().to_string()
+        };
+
+        match closers.iter().find(|(c, _)| self.starts_with(c)) {
+            Some(&(c, shape)) => {
+                self.bump_n(c.chars().count());
+                Some(Ok(Token {
+                    kind: TokenKind::Shape(shape, text),
+                    position,
+                }))
+            }
+            None => Some(Err(unclosed(position))),
+        }
+    }
+
+    /// Read a `"..."` string; quoted labels may span several lines
+    fn lex_quoted(&mut self) -> Result<String, SyntaxError> {
+        let position = self.position();
+        self.bump();
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some(ch) => text.push(ch),
+                None => {
+                    return Err(SyntaxError::new(
+                        DiagnosticCode::UnterminatedString,
+                        position.line,
+                        position.column,
+                        "Unterminated string: missing closing '\"'",
+                    ))
+                }
+            }
+        }
+    }
+
+    fn lex_pipe_text(&mut self) -> Option<Result<Token, SyntaxError>> {
+        if self.peek() != Some('|') {
+            return None;
+        }
+        let position = self.position();
+        self.bump();
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                Some('|') => {
+                    self.bump();
+                    return Some(Ok(Token {
+                        kind: TokenKind::PipeText(text.trim().trim_matches('"').to_string()),
+                        position,
+                    }));
+                }
+                Some('"') => match self.lex_quoted() {
+                    Ok(quoted) => text.push_str(&quoted),
+                    Err(error) => return Some(Err(error)),
+                },
+                None | Some('\n') => {
+                    return Some(Err(SyntaxError::new(
+                        DiagnosticCode::UnclosedBracket,
+                        position.line,
+                        position.column,
+                        "Unclosed edge label: expected a closing '|'",
+                    )))
+                }
+                Some(ch) => {
+                    text.push(ch);
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    fn count_run(&self, ch: char) -> usize {
+        let mut count = 0;
+        while self.peek_at(count) == Some(ch) {
+            count += 1;
+        }
+        count
+    }
+
+    fn lex_head(&mut self) -> Option<ArrowHead> {
+        let head = match self.peek()? {
+            '>' => ArrowHead::Arrow,
+            'o' => ArrowHead::Circle,
+            'x' => ArrowHead::Cross,
+            _ => return None,
+        };
+        // Like Mermaid, `A--xB` ends in a cross head rather than linking to a node `xB`
+        self.bump();
+        Some(head)
+    }
+
+    /// Lex the stroke and end head of a link, without a start head or label
+    fn lex_link_body(&mut self) -> Option<(EdgeStroke, usize, Option<ArrowHead>)> {
+        match self.peek()? {
+            '~' => {
+                let run = self.count_run('~');
+                if run < 3 {
+                    return None;
+                }
+                self.bump_n(run);
+                Some((EdgeStroke::Invisible, run - 3, None))
+            }
+            '=' | '-' if self.peek_at(1) != Some('.') => {
+                let stroke_char = self.peek()?;
+                let stroke = if stroke_char == '=' {
+                    EdgeStroke::Thick
+                } else {
+                    EdgeStroke::Normal
+                };
+                let run = self.count_run(stroke_char);
+                if run < 2 {
+                    return None;
+                }
+                self.bump_n(run);
+                let head = self.lex_head();
+                Some((
+                    stroke,
+                    run.saturating_sub(if head.is_some() { 2 } else { 3 }),
+                    head,
+                ))
+            }
+            '-' => {
+                self.bump();
+                let dots = self.count_run('.');
+                self.bump_n(dots);
+                if self.peek() != Some('-') {
+                    // `-.` without its closing dash can only open an inline label
+                    return (dots == 1).then_some((EdgeStroke::Dotted, 0, None));
+                }
+                self.bump();
+                Some((EdgeStroke::Dotted, dots - 1, self.lex_head()))
+            }
+            _ => None,
+        }
+    }
+
+    fn lex_link(&mut self) -> Option<Result<Token, SyntaxError>> {
+        let state = self.save();
+        let position = self.position();
+
+        let start_head = match (self.peek(), self.peek_at(1)) {
+            (Some('<'), Some('-' | '=')) => Some(ArrowHead::Arrow),
+            (Some('o'), Some('-' | '=')) if matches!(self.peek_at(2), Some('-' | '=' | '.')) => {
+                Some(ArrowHead::Circle)
+            }
+            (Some('x'), Some('-' | '=')) if matches!(self.peek_at(2), Some('-' | '=' | '.')) => {
+                Some(ArrowHead::Cross)
+            }
+            _ => None,
+        };
+        if start_head.is_some() {
+            self.bump();
+        }
+
+        let body_start = self.save();
+        let Some((stroke, length, end_head)) = self.lex_link_body() else {
+            self.restore(state);
+            return None;
+        };
+
+        // `A -- text --> B`, `A -. text .-> B` and `A == text ==> B` carry an inline label
+        let consumed: String = self.chars[body_start.0..self.pos].iter().collect();
+        let opens_label = matches!(consumed.as_str(), "--" | "==" | "-.")
+            && matches!(self.peek(), Some(' ' | '\t'));
+        if opens_label {
+            return Some(self.lex_inline_label(position, stroke, start_head));
+        }
+
+        if end_head.is_none() && matches!(consumed.as_str(), "--" | "==" | "-.") {
+            self.restore(state);
+            return None;
+        }
+
+        Some(Ok(Token {
+            kind: TokenKind::Link(LinkToken {
+                stroke,
+                start_head,
+                end_head,
+                length,
+                label: None,
+            }),
+            position,
+        }))
+    }
+
+    fn lex_inline_label(
+        &mut self,
+        position: Position,
+        stroke: EdgeStroke,
+        start_head: Option<ArrowHead>,
+    ) -> Result<Token, SyntaxError> {
+        let closers: &[&str] = match stroke {
+            EdgeStroke::Thick => &["=="],
+            EdgeStroke::Dotted => &[".-"],
+            _ => &["--"],
+        };
+        let mut label = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => {
+                    return Err(SyntaxError::new(
+                        DiagnosticCode::IncompleteStatement,
+                        position.line,
+                        position.column,
+                        "Unterminated edge label: expected a closing link such as '-->'",
+                    ))
+                }
+                Some(_) if closers.iter().any(|c| self.starts_with(c)) => break,
+                Some(ch) => {
+                    label.push(ch);
+                    self.bump();
+                }
+            }
+        }
+
+        let (length, end_head) = if stroke == EdgeStroke::Dotted {
+            self.bump();
+            let dashes = self.count_run('-');
+            self.bump_n(dashes);
+            (0, self.lex_head())
+        } else {
+            let stroke_char = if stroke == EdgeStroke::Thick {
+                '='
+            } else {
+                '-'
+            };
+            let run = self.count_run(stroke_char);
+            self.bump_n(run);
+            let head = self.lex_head();
+            (run.saturating_sub(if head.is_some() { 2 } else { 3 }), head)
+        };
+
+        Ok(Token {
+            kind: TokenKind::Link(LinkToken {
+                stroke,
+                start_head,
+                end_head,
+                length,
+                label: Some(label.trim().trim_matches('"').to_string()),
+            }),
+            position,
+        })
+    }
+
+    /// Lex whatever may follow a node group: a link, a separator or the end of input
+    fn next_token(&mut self) -> Option<Result<Token, SyntaxError>> {
+        self.skip_inline_whitespace();
+        let position = self.position();
+        let simple = |kind| Some(Ok(Token { kind, position }));
+        match self.peek() {
+            None => simple(TokenKind::Eof),
+            Some('\n') | Some(';') => simple(TokenKind::Separator),
+            Some('%') if self.peek_at(1) == Some('%') => simple(TokenKind::Separator),
+            Some(_) => self.lex_link(),
+        }
+    }
+}
+
+struct Parser {
+    lexer: Lexer,
+    ast: FlowchartAst,
+    errors: Vec<SyntaxError>,
+    /// Indices into `ast.subgraphs` for the currently open `subgraph` blocks
+    open_subgraphs: Vec<usize>,
+}
+
+/// Parse a `graph`/`flowchart` diagram into a typed AST
+pub fn parse(content: &str, start_line: usize) -> AstResult<FlowchartAst> {
+    let mut parser = Parser {
+        lexer: Lexer::new(&config::mask(content), start_line),
+        ast: FlowchartAst::default(),
+        errors: Vec::new(),
+        open_subgraphs: Vec::new(),
+    };
+    parser.parse_document();
+    AstResult {
+        ast: parser.ast,
+        errors: parser.errors,
+    }
+}
+
+impl Parser {
+    fn error_at(
+        code: DiagnosticCode,
+        position: Position,
+        message: impl Into<String>,
+    ) -> SyntaxError {
+        SyntaxError::new(code, position.line, position.column, message)
+    }
+
+    fn parse_document(&mut self) {
+        self.lexer.skip_trivia();
+        if let Err(error) = self.parse_header() {
+            self.errors.push(error);
+            return;
+        }
+
+        loop {
+            self.lexer.skip_trivia();
+            if self.lexer.peek().is_none() {
+                break;
+            }
+            if let Err(error) = self.parse_statement() {
+                self.errors.push(error);
+                self.lexer.recover();
+            }
+        }
+
+        for &index in &self.open_subgraphs {
+            let subgraph = &self.ast.subgraphs[index];
+            self.errors.push(Self::error_at(
+                DiagnosticCode::UnclosedBlock,
+                subgraph.position,
+                format!("Unclosed subgraph '{}': missing 'end'", subgraph.id),
+            ));
+        }
+    }
+
+    fn parse_header(&mut self) -> Result<(), SyntaxError> {
+        let position = self.lexer.position();
+        let keyword = match self.lexer.lex_word() {
+            Some(Token {
+                kind: TokenKind::Word(word),
+                ..
+            }) if DiagramType::Flowchart
+                .declarations()
+                .contains(&word.as_str()) =>
+            {
+                word
+            }
+            _ => {
+                let (line, _) = self.lexer.rest_of_line();
+                return Err(Self::error_at(
+                    DiagnosticCode::InvalidDeclaration,
+                    position,
+                    format!(
+                        "Expected 'graph' or 'flowchart' declaration, found '{}'",
+                        line
+                    ),
+                ));
+            }
+        };
+        self.ast.keyword = keyword;
+
+        self.lexer.skip_inline_whitespace();
+        if let Some(Token {
+            kind: TokenKind::Word(word),
+            position,
+        }) = self.lexer.lex_word()
+        {
+            self.ast.direction = Some(Direction::from_keyword(&word).ok_or_else(|| {
+                Self::error_at(
+                    DiagnosticCode::UnknownDirection,
+                    position,
+                    format!("Unknown direction '{}'", word),
+                )
+            })?);
+        }
+
+        self.expect_statement_end()
+    }
+
+    fn expect_statement_end(&mut self) -> Result<(), SyntaxError> {
+        self.lexer.skip_inline_whitespace();
+        match self.lexer.peek() {
+            None | Some('\n') | Some(';') => Ok(()),
+            Some('%') if self.lexer.peek_at(1) == Some('%') => Ok(()),
+            Some(ch) => Err(Self::error_at(
+                DiagnosticCode::UnexpectedCharacter,
+                self.lexer.position(),
+                format!("Unexpected '{}'", ch),
+            )),
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<(), SyntaxError> {
+        let position = self.lexer.position();
+        let keyword = self.lexer.peek_word().unwrap_or_default();
+        match keyword.as_str() {
+            "subgraph" => self.parse_subgraph(position),
+            "end" => {
+                self.lexer.bump_n(3);
+                if self.open_subgraphs.pop().is_none() {
+                    return Err(Self::error_at(
+                        DiagnosticCode::UnmatchedEnd,
+                        position,
+                        "'end' without a matching 'subgraph'",
+                    ));
+                }
+                self.expect_statement_end()
+            }
+            "direction" => {
+                self.lexer.bump_n(keyword.len());
+                let (text, at) = self.lexer.rest_of_line();
+                let direction = Direction::from_keyword(&text).ok_or_else(|| {
+                    Self::error_at(
+                        DiagnosticCode::UnknownDirection,
+                        at,
+                        format!("Unknown direction '{}'", text),
+                    )
+                })?;
+                match self.open_subgraphs.last() {
+                    Some(&index) => self.ast.subgraphs[index].direction = Some(direction),
+                    None => self.ast.direction = Some(direction),
+                }
+                Ok(())
+            }
+            "classDef" | "class" | "style" | "linkStyle" | "click" => {
+                self.lexer.bump_n(keyword.chars().count());
+                let (text, at) = self.lexer.rest_of_line();
+                self.parse_keyword_statement(&keyword, &text, position, at)
+            }
+            "accTitle" | "accDescr" => {
+                self.lexer.rest_of_line();
+                Ok(())
+            }
+            _ => self.parse_chain(),
+        }
+    }
+
+    fn parse_subgraph(&mut self, position: Position) -> Result<(), SyntaxError> {
+        self.lexer.bump_n("subgraph".len());
+        let (text, at) = self.lexer.rest_of_line();
+        if text.is_empty() {
+            return Err(Self::error_at(
+                DiagnosticCode::IncompleteStatement,
+                at,
+                "Expected a subgraph id or title after 'subgraph'",
+            ));
+        }
+
+        let (id, title) = if let Some(quoted) = text.strip_prefix('"') {
+            let title = quoted.trim_end_matches('"').to_string();
+            (title.clone(), Some(title))
+        } else {
+            let id_len = text
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '-'))
+                .unwrap_or(text.len());
+            let (id, rest) = text.split_at(id_len);
+            let rest = rest.trim();
+            if id.is_empty() {
+                return Err(Self::error_at(
+                    DiagnosticCode::InvalidIdentifier,
+                    at,
+                    format!("Invalid subgraph id '{}'", text),
+                ));
+            } else if rest.is_empty() {
+                (id.to_string(), None)
+            } else if let Some(inner) = rest.strip_prefix('[') {
+                let inner = inner.strip_suffix(']').ok_or_else(|| {
+                    Self::error_at(
+                        DiagnosticCode::UnclosedBracket,
+                        at,
+                        "Unclosed '[' in subgraph title: expected ']'",
+                    )
+                })?;
+                (
+                    id.to_string(),
+                    Some(inner.trim().trim_matches('"').to_string()),
+                )
+            } else {
+                (text.clone(), Some(text.clone()))
+            }
+        };
+
+        let index = self.ast.subgraphs.len();
+        if let Some(&parent) = self.open_subgraphs.last() {
+            self.ast.subgraphs[parent].subgraphs.push(id.clone());
+        }
+        self.ast.subgraphs.push(Subgraph {
+            id,
+            title,
+            direction: None,
+            nodes: Vec::new(),
+            subgraphs: Vec::new(),
+            position,
+        });
+        self.open_subgraphs.push(index);
+        Ok(())
+    }
+
+    fn parse_keyword_statement(
+        &mut self,
+        keyword: &str,
+        text: &str,
+        position: Position,
+        args_position: Position,
+    ) -> Result<(), SyntaxError> {
+        let (first, rest) = match text.split_once(char::is_whitespace) {
+            Some((first, rest)) => (first.to_string(), rest.trim().to_string()),
+            None => (text.to_string(), String::new()),
+        };
+        let list = |value: &str| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        if first.is_empty() || rest.is_empty() {
+            return Err(Self::error_at(
+                DiagnosticCode::IncompleteStatement,
+                args_position,
+                match keyword {
+                    "classDef" => "Expected 'classDef <name> <styles>'",
+                    "class" => "Expected 'class <nodeIds> <className>'",
+                    "style" => "Expected 'style <nodeId> <styles>'",
+                    "linkStyle" => "Expected 'linkStyle <indices|default> <styles>'",
+                    _ => "Expected 'click <nodeId> <callback|href>'",
+                },
+            ));
+        }
+
+        match keyword {
+            "classDef" => self.ast.class_defs.push(ClassDef {
+                names: list(&first),
+                styles: rest,
+                position,
+            }),
+            "class" => self.ast.class_assignments.push(ClassAssignment {
+                nodes: list(&first),
+                class_name: rest,
+                position,
+            }),
+            "style" => self.ast.styles.push(StyleStatement {
+                node: first,
+                styles: rest,
+                position,
+            }),
+            "linkStyle" => {
+                let links = list(&first);
+                if let Some(bad) = links
+                    .iter()
+                    .find(|link| *link != "default" && link.parse::<usize>().is_err())
+                {
+                    return Err(Self::error_at(
+                        DiagnosticCode::InvalidValue,
+                        args_position,
+                        format!("Invalid link index '{}' in linkStyle", bad),
+                    ));
+                }
+                self.ast.link_styles.push(LinkStyle {
+                    links,
+                    styles: rest,
+                    position,
+                })
+            }
+            _ => self.ast.clicks.push(ClickStatement {
+                node: first,
+                action: rest,
+                position,
+            }),
+        }
+        Ok(())
+    }
+
+    /// `group (link group)*` where `group` is `node ('&' node)*`
+    fn parse_chain(&mut self) -> Result<(), SyntaxError> {
+        let mut previous = self.parse_group()?;
+        loop {
+            let token = self.lexer.next_token().unwrap_or_else(|| {
+                let position = self.lexer.position();
+                let ch = self.lexer.peek().unwrap_or(' ');
+                Err(Self::error_at(
+                    DiagnosticCode::UnexpectedCharacter,
+                    position,
+                    format!("Unexpected '{}'", ch),
+                ))
+            })?;
+            match token.kind {
+                TokenKind::Separator | TokenKind::Eof => return Ok(()),
+                TokenKind::Link(link) => {
+                    self.lexer.skip_inline_whitespace();
+                    let pipe_label = match self.lexer.lex_pipe_text() {
+                        Some(Ok(Token {
+                            kind: TokenKind::PipeText(text),
+                            ..
+                        })) => Some(text),
+                        Some(Err(error)) => return Err(error),
+                        _ => None,
+                    };
+                    self.lexer.skip_inline_whitespace();
+                    if !self.lexer.at_word() {
+                        return Err(Self::error_at(
+                            DiagnosticCode::IncompleteStatement,
+                            self.lexer.position(),
+                            "Expected a node after the link",
+                        ));
+                    }
+                    let next = self.parse_group()?;
+                    for from in &previous {
+                        for to in &next {
+                            self.ast.edges.push(FlowEdge {
+                                from: from.clone(),
+                                to: to.clone(),
+                                stroke: link.stroke,
+                                start_head: link.start_head,
+                                end_head: link.end_head,
+                                length: link.length,
+                                label: pipe_label.clone().or_else(|| link.label.clone()),
+                                position: token.position,
+                            });
+                        }
+                    }
+                    previous = next;
+                }
+                _ => {
+                    return Err(Self::error_at(
+                        DiagnosticCode::UnexpectedCharacter,
+                        token.position,
+                        "Unexpected token",
+                    ))
+                }
+            }
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Vec<String>, SyntaxError> {
+        let mut ids = vec![self.parse_node()?];
+        loop {
+            let state = self.lexer.save();
+            self.lexer.skip_inline_whitespace();
+            if self.lexer.peek() != Some('&') {
+                self.lexer.restore(state);
+                return Ok(ids);
+            }
+            self.lexer.bump();
+            self.lexer.skip_inline_whitespace();
+            ids.push(self.parse_node()?);
+        }
+    }
+
+    fn parse_node(&mut self) -> Result<String, SyntaxError> {
+        let position = self.lexer.position();
+        let id = match self.lexer.lex_word() {
+            Some(Token {
+                kind: TokenKind::Word(word),
+                ..
+            }) => word,
+            _ => {
+                let found = self
+                    .lexer
+                    .peek()
+                    .map(String::from)
+                    .unwrap_or_else(|| "end of input".to_string());
+                return Err(Self::error_at(
+                    DiagnosticCode::InvalidIdentifier,
+                    position,
+                    format!("Expected a node id, found '{}'", found),
+                ));
+            }
+        };
+        if id == "end" {
+            return Err(Self::error_at(
+                DiagnosticCode::InvalidIdentifier,
+                position,
+                "'end' is reserved; capitalize it to use it as a node id",
+            ));
+        }
+
+        // `A>text]` must be adjacent; other shapes tolerate `A [text]`
+        let state = self.lexer.save();
+        self.lexer.skip_inline_whitespace();
+        let adjacent = self.lexer.pos == state.0;
+        let shape = match self.lexer.lex_shape() {
+            Some(Ok(Token {
+                kind: TokenKind::Shape(shape, text),
+                ..
+            })) if adjacent || shape != NodeShape::Asymmetric => Some((shape, text)),
+            Some(Err(error)) => return Err(error),
+            _ => {
+                self.lexer.restore(state);
+                None
+            }
+        };
+
+        let mut classes = Vec::new();
+        if self.lexer.starts_with(":::") {
+            self.lexer.bump_n(3);
+            match self.lexer.lex_word() {
+                Some(Token {
+                    kind: TokenKind::Word(class),
+                    ..
+                }) => classes.push(class),
+                _ => {
+                    return Err(Self::error_at(
+                        DiagnosticCode::IncompleteStatement,
+                        self.lexer.position(),
+                        "Expected a class name after ':::'",
+                    ))
+                }
+            }
+        }
+
+        self.record_node(&id, shape, classes, position);
+        Ok(id)
+    }
+
+    fn record_node(
+        &mut self,
+        id: &str,
+        shape: Option<(NodeShape, String)>,
+        classes: Vec<String>,
+        position: Position,
+    ) {
+        match self.ast.nodes.iter_mut().find(|node| node.id == id) {
+            Some(node) => {
+                if let Some((shape, label)) = shape {
+                    node.shape = shape;
+                    node.label = Some(label);
+                }
+                node.classes.extend(classes);
+            }
+            None => {
+                let (shape, label) = match shape {
+                    Some((shape, label)) => (shape, Some(label)),
+                    None => (NodeShape::Default, None),
+                };
+                self.ast.nodes.push(FlowNode {
+                    id: id.to_string(),
+                    label,
+                    shape,
+                    classes,
+                    position,
+                });
+            }
+        }
+
+        if let Some(&index) = self.open_subgraphs.last() {
+            let already_placed = self
+                .ast
+                .subgraphs
+                .iter()
+                .any(|subgraph| subgraph.nodes.iter().any(|n| n == id));
+            if !already_placed {
+                self.ast.subgraphs[index].nodes.push(id.to_string());
+            }
+        }
+    }
+}
+
+/// Reprint one node-and-link statement with canonical spacing, link syntax and label
+/// quoting. `None` for keyword statements and anything the chain grammar rejects.
+pub(crate) fn format_statement(statement: &str, quotes: QuoteStyle) -> Option<String> {
+    let mut lexer = Lexer::new(statement, 1);
+    if matches!(
+        lexer.peek_word().as_deref(),
+        Some(
+            "subgraph"
+                | "end"
+                | "direction"
+                | "classDef"
+                | "class"
+                | "style"
+                | "linkStyle"
+                | "click"
+                | "accTitle"
+                | "accDescr"
+        )
+    ) {
+        return None;
+    }
+
+    let mut out = String::new();
+    print_group(&mut lexer, &mut out, quotes)?;
+    loop {
+        lexer.skip_inline_whitespace();
+        if lexer.peek().is_none() {
+            return Some(out);
+        }
+        let Some(Ok(Token {
+            kind: TokenKind::Link(link),
+            ..
+        })) = lexer.lex_link()
+        else {
+            return None;
+        };
+        lexer.skip_inline_whitespace();
+        let pipe_label = match lexer.lex_pipe_text() {
+            Some(Ok(Token {
+                kind: TokenKind::PipeText(text),
+                ..
+            })) => Some(text),
+            Some(_) => return None,
+            None => None,
+        };
+
+        out.push(' ');
+        out.push_str(&link_syntax(&link));
+        // Inline `-- text -->` labels are printed in the pipe form
+        if let Some(label) = pipe_label.or(link.label) {
+            out.push('|');
+            out.push_str(&formatter::label(&label, quotes));
+            out.push('|');
+        }
+        out.push(' ');
+        lexer.skip_inline_whitespace();
+        print_group(&mut lexer, &mut out, quotes)?;
+    }
+}
+
+fn print_group(lexer: &mut Lexer, out: &mut String, quotes: QuoteStyle) -> Option<()> {
+    print_node(lexer, out, quotes)?;
+    loop {
+        let state = lexer.save();
+        lexer.skip_inline_whitespace();
+        if lexer.peek() != Some('&') {
+            lexer.restore(state);
+            return Some(());
+        }
+        lexer.bump();
+        lexer.skip_inline_whitespace();
+        out.push_str(" & ");
+        print_node(lexer, out, quotes)?;
+    }
+}
+
+fn print_node(lexer: &mut Lexer, out: &mut String, quotes: QuoteStyle) -> Option<()> {
+    let Some(Token {
+        kind: TokenKind::Word(id),
+        ..
+    }) = lexer.lex_word()
+    else {
+        return None;
+    };
+    out.push_str(&id);
+
+    let state = lexer.save();
+    lexer.skip_inline_whitespace();
+    let adjacent = lexer.pos == state.0;
+    match lexer.lex_shape() {
+        Some(Ok(Token {
+            kind: TokenKind::Shape(shape, text),
+            ..
+        })) if adjacent || shape != NodeShape::Asymmetric => {
+            let (open, close) = shape_delimiters(shape);
+            out.push_str(open);
+            out.push_str(&formatter::label(&text, quotes));
+            out.push_str(close);
+        }
+        Some(Err(_)) => return None,
+        _ => lexer.restore(state),
+    }
+
+    if lexer.starts_with(":::") {
+        lexer.bump_n(3);
+        let Some(Token {
+            kind: TokenKind::Word(class),
+            ..
+        }) = lexer.lex_word()
+        else {
+            return None;
+        };
+        out.push_str(":::");
+        out.push_str(&class);
+    }
+    Some(())
+}
+
+fn shape_delimiters(shape: NodeShape) -> (&'static str, &'static str) {
+    match shape {
+        NodeShape::Trapezoid => ("[/", "\\]"),
+        NodeShape::TrapezoidAlt => ("[\\", "/]"),
+        _ => SHAPE_DELIMITERS
+            .iter()
+            .find(|(_, _, candidate)| *candidate == shape)
+            .map_or(("[", "]"), |&(open, close, _)| (open, close)),
+    }
+}
+
+fn link_syntax(link: &LinkToken) -> String {
+    let head = |head: ArrowHead| match head {
+        ArrowHead::Arrow => '>',
+        ArrowHead::Circle => 'o',
+        ArrowHead::Cross => 'x',
+    };
+    let mut syntax = String::new();
+    match link.start_head {
+        Some(ArrowHead::Arrow) => syntax.push('<'),
+        Some(start) => syntax.push(head(start)),
+        None => {}
+    }
+    // A head takes the place of the third stroke character, as in `-->` and `---`
+    let run = link.length + if link.end_head.is_some() { 2 } else { 3 };
+    match link.stroke {
+        EdgeStroke::Normal => syntax.push_str(&"-".repeat(run)),
+        EdgeStroke::Thick => syntax.push_str(&"=".repeat(run)),
+        EdgeStroke::Dotted => syntax.push_str(&format!("-{}-", ".".repeat(link.length + 1))),
+        EdgeStroke::Invisible => syntax.push_str(&"~".repeat(link.length + 3)),
+    }
+    syntax.extend(link.end_head.map(head));
+    syntax
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nodes_and_edges() {
+        let result = parse(
+            "graph TD\n    A[Start] -->|go| B{Decide}\n    B -- yes --> C((Done))",
+            1,
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let ast = result.ast;
+        assert_eq!(ast.direction, Some(Direction::TD));
+        assert_eq!(ast.nodes.len(), 3);
+        assert_eq!(ast.node("B").unwrap().shape, NodeShape::Rhombus);
+        assert_eq!(ast.node("C").unwrap().shape, NodeShape::Circle);
+        assert_eq!(ast.edges.len(), 2);
+        assert_eq!(ast.edges[0].label.as_deref(), Some("go"));
+        assert_eq!(ast.edges[1].label.as_deref(), Some("yes"));
+        assert_eq!(ast.edges[1].end_head, Some(ArrowHead::Arrow));
+
+        let elk = parse("flowchart-elk LR\n  A --> B", 1);
+        assert!(elk.errors.is_empty(), "{:?}", elk.errors);
+        assert_eq!(elk.ast.keyword, "flowchart-elk");
+    }
+
+    #[test]
+    fn test_parse_link_kinds_and_groups() {
+        let result = parse(
+            "flowchart LR\n  A & B ==> C -.-> D --- E ~~~ F\n  G <--> H --x I",
+            1,
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let edges = &result.ast.edges;
+        assert_eq!(edges.len(), 7);
+        assert_eq!(edges[0].stroke, EdgeStroke::Thick);
+        assert_eq!(edges[2].stroke, EdgeStroke::Dotted);
+        assert_eq!(edges[3].end_head, None);
+        assert_eq!(edges[4].stroke, EdgeStroke::Invisible);
+        assert_eq!(edges[5].start_head, Some(ArrowHead::Arrow));
+        assert_eq!(edges[6].end_head, Some(ArrowHead::Cross));
+    }
+
+    #[test]
+    fn test_parse_adjacent_heads_and_hyphenated_ids() {
+        let result = parse("graph TD\n  A--xB\n  C--oD\n  end-node --> B", 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let ast = result.ast;
+        assert_eq!(ast.edges.len(), 3);
+        assert_eq!(
+            (ast.edges[0].from.as_str(), ast.edges[0].to.as_str()),
+            ("A", "B")
+        );
+        assert_eq!(ast.edges[0].end_head, Some(ArrowHead::Cross));
+        assert_eq!(ast.edges[1].to, "D");
+        assert_eq!(ast.edges[1].end_head, Some(ArrowHead::Circle));
+        assert!(ast.node("end-node").is_some());
+        assert_eq!(ast.edges[2].from, "end-node");
+    }
+
+    #[test]
+    fn test_parse_subgraphs_and_statements() {
+        let content = "graph TB\n  subgraph one [First]\n    direction LR\n    a1 --> a2\n  end\n  classDef hot fill:#f96\n  class a1 hot\n  style a2 stroke:#333\n  linkStyle 0 stroke:red\n  click a1 callback";
+        let result = parse(content, 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let ast = result.ast;
+        assert_eq!(ast.subgraphs[0].id, "one");
+        assert_eq!(ast.subgraphs[0].title.as_deref(), Some("First"));
+        assert_eq!(ast.subgraphs[0].direction, Some(Direction::LR));
+        assert_eq!(ast.subgraphs[0].nodes, vec!["a1", "a2"]);
+        assert_eq!(ast.class_defs[0].names, vec!["hot"]);
+        assert_eq!(ast.class_assignments[0].class_name, "hot");
+        assert_eq!(ast.styles.len(), 1);
+        assert_eq!(ast.link_styles.len(), 1);
+        assert_eq!(ast.clicks[0].node, "a1");
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let dangling = parse("graph TD\n    A -->", 10);
+        assert_eq!(dangling.errors.len(), 1);
+        assert_eq!(
+            (dangling.errors[0].line, dangling.errors[0].column),
+            (11, 10)
+        );
+
+        let unclosed = parse("graph TD\n  A[label\n  B --> C", 1);
+        assert_eq!(unclosed.errors.len(), 1);
+        assert_eq!((unclosed.errors[0].line, unclosed.errors[0].column), (2, 4));
+
+        let subgraph = parse("graph TD\n  subgraph s\n  A --> B", 1);
+        assert_eq!(
+            subgraph.errors[0].message,
+            "Unclosed subgraph 's': missing 'end'"
+        );
+        assert_eq!(subgraph.errors[0].line, 2);
+    }
+}
@@ -0,0 +1,950 @@
+use super::class_diagram::{self, RelationEnd, RelationLine};
+use super::comments;
+use super::config::Preamble;
+use super::sequence::{self, NotePlacement, SequenceStatement};
+use super::{
+    flowchart, state_diagram, ContentFormat, DiagramType, FenceKind, MermaidParser, SyntaxError,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Style statements that `sort_declarations` may reorder, in the order they are sorted into
+const FLOWCHART_DECLARATIONS: &[&str] = &["classDef", "class", "style", "linkStyle", "click"];
+const CLASS_DECLARATIONS: &[&str] = &["classDef", "style", "cssClass"];
+const STATE_DECLARATIONS: &[&str] = &["classDef", "class", "style"];
+
+/// Characters Mermaid reads as syntax inside an unquoted label
+const LABEL_SYNTAX: &[char] = &['[', ']', '(', ')', '{', '}', '|', '<', '>', ';'];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    /// Quote labels only when they contain characters Mermaid would read as syntax
+    #[default]
+    Minimal,
+    /// Quote every node and edge label
+    Always,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatOptions {
+    /// Spaces per block level
+    pub indent: usize,
+    pub quotes: QuoteStyle,
+    /// Sort runs of adjacent style statements such as `classDef` and `style`
+    pub sort_declarations: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            quotes: QuoteStyle::default(),
+            sort_declarations: false,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("Cannot format a diagram with syntax errors: {}", describe_first(.0))]
+    Syntax(Vec<SyntaxError>),
+    #[error("Formatting {0} diagrams is not supported")]
+    Unsupported(DiagramType),
+    /// A safety net: the formatted diagram must parse to the same AST
+    #[error("Formatting would change the diagram, so it was left as is")]
+    ChangesMeaning,
+}
+
+fn describe_first(errors: &[SyntaxError]) -> String {
+    match errors.first() {
+        Some(error) => format!(
+            "line {}, column {}: {} [{}]",
+            error.line, error.column, error.message, error.code
+        ),
+        None => "unknown error".to_string(),
+    }
+}
+
+/// A diagram `format_document` left unchanged, and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedDiagram {
+    pub start_line: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormattedDocument {
+    pub content: String,
+    /// Number of diagrams whose text changed
+    pub changed: usize,
+    pub skipped: Vec<SkippedDiagram>,
+}
+
+/// Format one diagram: block indentation, spacing around arrows and labels, label quoting
+/// and optionally the order of style declarations. Comments and blank lines are kept,
+/// though runs of blank lines shrink to one.
+pub fn format_diagram(
+    parser: &MermaidParser,
+    content: &str,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    let errors: Vec<SyntaxError> = parser
+        .validate_diagram(content, 1)
+        .errors
+        .into_iter()
+        .filter(SyntaxError::is_error)
+        .collect();
+    if !errors.is_empty() {
+        return Err(FormatError::Syntax(errors));
+    }
+
+    let diagram_type = parser.detect_diagram_type(content);
+    let mut rules: Box<dyn Rules> = match diagram_type {
+        DiagramType::Flowchart => Box::new(FlowchartRules {
+            quotes: options.quotes,
+            in_string: false,
+        }),
+        DiagramType::Sequence => Box::new(SequenceRules::new(content)),
+        DiagramType::Class => Box::new(ClassRules::new(content)),
+        DiagramType::State => Box::new(StateRules::new(content)),
+        _ => return Err(FormatError::Unsupported(diagram_type)),
+    };
+
+    let mut formatted = render(layout(content, rules.as_mut()), options);
+    if content.ends_with('\n') {
+        formatted.push('\n');
+    }
+
+    let sorted = options.sort_declarations;
+    match (
+        canonical_ast(diagram_type, content, sorted),
+        canonical_ast(diagram_type, &formatted, sorted),
+    ) {
+        (Some(before), Some(after)) if before == after => Ok(formatted),
+        _ => Err(FormatError::ChangesMeaning),
+    }
+}
+
+/// Format every diagram in a document without touching the text around them.
+///
+/// Diagrams that cannot be formatted are left as they are and listed in `skipped`.
+pub fn format_document(
+    parser: &MermaidParser,
+    content: &str,
+    format: ContentFormat,
+    options: &FormatOptions,
+) -> FormattedDocument {
+    let newline = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut next = 0;
+    let mut changed = 0;
+    let mut skipped = Vec::new();
+
+    for block in parser.blocks(content, format) {
+        let (first, last) = (block.start_line - 1, block.end_line - 1);
+        output.extend(lines[next..first].iter().map(|line| line.to_string()));
+        next = last + 1;
+
+        // HTML blocks hold entity-encoded text that may share lines with the tags
+        let source = &lines[first..=last];
+        if block
+            .fence
+            .as_ref()
+            .is_some_and(|fence| fence.kind == FenceKind::Html)
+        {
+            output.extend(source.iter().map(|line| line.to_string()));
+            continue;
+        }
+
+        // Fences inside list items and blockquotes keep their indentation and markers.
+        // The block content has the markers as spaces, the source line still has them.
+        let body: Vec<&str> = block.content.lines().collect();
+        let indent = common_indent(&body);
+        let prefix = body
+            .iter()
+            .position(|line| !line.trim().is_empty())
+            .and_then(|index| source[index].get(..indent.len()))
+            .unwrap_or(indent);
+        let diagram: Vec<&str> = body
+            .iter()
+            .map(|line| line.get(indent.len()..).unwrap_or(""))
+            .collect();
+        let diagram = diagram.join("\n");
+
+        match format_diagram(parser, &diagram, options) {
+            Ok(formatted) => {
+                if formatted != diagram {
+                    changed += 1;
+                }
+                output.extend(formatted.lines().map(|line| match line {
+                    "" => prefix.trim_end().to_string(),
+                    line => format!("{}{}", prefix, line),
+                }));
+            }
+            Err(error) => {
+                skipped.push(SkippedDiagram {
+                    start_line: block.start_line,
+                    reason: error.to_string(),
+                });
+                output.extend(source.iter().map(|line| line.to_string()));
+            }
+        }
+    }
+    output.extend(lines[next..].iter().map(|line| line.to_string()));
+
+    let mut formatted = output.join(newline);
+    if content.ends_with('\n') {
+        formatted.push_str(newline);
+    }
+    FormattedDocument {
+        content: formatted,
+        changed,
+        skipped,
+    }
+}
+
+/// A label as it should be written between its delimiters
+pub(crate) fn label(text: &str, quotes: QuoteStyle) -> String {
+    // A label containing `"` cannot be quoted, and markdown strings must stay quoted
+    let needs_quotes = text.is_empty()
+        || text != text.trim()
+        || text.starts_with('`')
+        || text.contains(LABEL_SYNTAX);
+    if text.contains('"') || (quotes == QuoteStyle::Minimal && !needs_quotes) {
+        text.to_string()
+    } else {
+        format!("\"{}\"", text)
+    }
+}
+
+fn common_indent<'a>(lines: &[&'a str]) -> &'a str {
+    let mut indented = lines.iter().filter(|line| !line.trim().is_empty());
+    let Some(first) = indented.next() else {
+        return "";
+    };
+    let mut indent = &first[..first.len() - first.trim_start().len()];
+    for line in indented {
+        let common = indent
+            .chars()
+            .zip(line.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        indent = &indent[..common];
+    }
+    indent
+}
+
+/// Collapse runs of spaces and tabs outside double quotes
+fn collapse(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_quote = false;
+    let mut space = false;
+    for ch in text.trim().chars() {
+        if ch == '"' {
+            in_quote = !in_quote;
+        }
+        if !in_quote && (ch == ' ' || ch == '\t') {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(ch);
+    }
+    out
+}
+
+fn first_word(line: &str) -> &str {
+    line.split(|ch: char| ch.is_whitespace() || ch == ';')
+        .next()
+        .unwrap_or("")
+}
+
+fn declaration_rank(line: &str, declarations: &[&str]) -> Option<usize> {
+    let keyword = first_word(line);
+    declarations
+        .iter()
+        .position(|candidate| *candidate == keyword)
+}
+
+/// How a statement line moves the block depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shift {
+    None,
+    /// Opens a block; the following lines are indented one more level
+    Open,
+    /// Closes a block and sits at the outer level, like `end`
+    Close,
+    /// Ends a block's body on its own last line, like `+run() }`
+    CloseAfter,
+    /// Starts another branch of the open block and sits at its level, like `else`
+    Branch,
+}
+
+struct Placement {
+    text: String,
+    shift: Shift,
+    /// Rank among the sortable declarations
+    declaration: Option<usize>,
+}
+
+impl Placement {
+    fn new(text: impl Into<String>, shift: Shift) -> Self {
+        Placement {
+            text: text.into(),
+            shift,
+            declaration: None,
+        }
+    }
+}
+
+/// The diagram-specific part of formatting
+trait Rules {
+    /// Whether a raw line must be kept byte for byte, e.g. inside a multi-line string
+    fn verbatim(&mut self, _raw: &str) -> bool {
+        false
+    }
+
+    /// Reprint a trimmed, non-comment statement line and place it in the block structure
+    fn place(&mut self, line: &str, number: usize) -> Placement;
+}
+
+enum Out {
+    Blank,
+    Verbatim(String),
+    Line {
+        depth: usize,
+        text: String,
+        declaration: Option<usize>,
+    },
+}
+
+fn layout(content: &str, rules: &mut dyn Rules) -> Vec<Out> {
+    let mut out = Vec::new();
+    let mut depth = 1;
+    let mut declared = false;
+    // Frontmatter and directives are kept as written
+    let preamble = Preamble::scan(content);
+    for (index, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if preamble.contains(index) || (declared && rules.verbatim(raw)) {
+            out.push(Out::Verbatim(raw.trim_end().to_string()));
+            continue;
+        }
+        if line.is_empty() {
+            out.push(Out::Blank);
+            continue;
+        }
+        let comment = line.starts_with("%%");
+        if !declared {
+            // Comments and directives before the declaration stay at the top level
+            declared = !comment;
+            out.push(Out::Line {
+                depth: 0,
+                text: if comment {
+                    line.to_string()
+                } else {
+                    collapse(line)
+                },
+                declaration: None,
+            });
+            continue;
+        }
+        if comment {
+            out.push(Out::Line {
+                depth,
+                text: line.to_string(),
+                declaration: None,
+            });
+            continue;
+        }
+
+        // A trailing comment is put back after the reprinted statement
+        let (line, comment) = match comments::comment_start(line) {
+            Some(offset) => (line[..offset].trim_end(), Some(&line[offset..])),
+            None => (line, None),
+        };
+        let mut placement = rules.place(line, index + 1);
+        if let Some(comment) = comment {
+            placement.text = format!("{} {}", placement.text, comment);
+        }
+        let at = match placement.shift {
+            Shift::Close => {
+                depth = depth.saturating_sub(1).max(1);
+                depth
+            }
+            Shift::Branch => depth.saturating_sub(1).max(1),
+            _ => depth,
+        };
+        out.push(Out::Line {
+            depth: at,
+            text: placement.text,
+            declaration: placement.declaration,
+        });
+        match placement.shift {
+            Shift::Open => depth += 1,
+            Shift::CloseAfter => depth = depth.saturating_sub(1).max(1),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn render(mut out: Vec<Out>, options: &FormatOptions) -> String {
+    // One blank line at most, and none at either end
+    out.dedup_by(|next, previous| matches!((previous, next), (Out::Blank, Out::Blank)));
+    while matches!(out.last(), Some(Out::Blank)) {
+        out.pop();
+    }
+    if matches!(out.first(), Some(Out::Blank)) {
+        out.remove(0);
+    }
+
+    if options.sort_declarations {
+        let mut start = 0;
+        while start < out.len() {
+            let run = out[start..]
+                .iter()
+                .take_while(|entry| match (entry, &out[start]) {
+                    (
+                        Out::Line {
+                            depth,
+                            declaration: Some(_),
+                            ..
+                        },
+                        Out::Line {
+                            depth: first_depth, ..
+                        },
+                    ) => depth == first_depth,
+                    _ => false,
+                })
+                .count();
+            out[start..start + run].sort_by(|a, b| match (a, b) {
+                (
+                    Out::Line {
+                        text: a_text,
+                        declaration: a_rank,
+                        ..
+                    },
+                    Out::Line {
+                        text: b_text,
+                        declaration: b_rank,
+                        ..
+                    },
+                ) => (a_rank, a_text).cmp(&(b_rank, b_text)),
+                _ => std::cmp::Ordering::Equal,
+            });
+            start += run.max(1);
+        }
+    }
+
+    out.iter()
+        .map(|entry| match entry {
+            Out::Blank => String::new(),
+            Out::Verbatim(text) => text.clone(),
+            Out::Line { depth, text, .. } => {
+                format!("{}{}", " ".repeat(depth * options.indent), text)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Split a line into `;`-separated statements, ignoring `;` inside labels
+fn statements(line: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut in_quote = false;
+    let mut in_pipe = false;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            '"' => in_quote = !in_quote,
+            _ if in_quote => {}
+            '[' | '(' | '{' => depth += 1,
+            ']' | ')' | '}' => depth = depth.saturating_sub(1),
+            '|' if depth == 0 => in_pipe = !in_pipe,
+            ';' if depth == 0 && !in_pipe => {
+                statements.push(line[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(line[start..].trim());
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+struct FlowchartRules {
+    quotes: QuoteStyle,
+    /// Inside a quoted label that continues on the next line
+    in_string: bool,
+}
+
+impl Rules for FlowchartRules {
+    fn verbatim(&mut self, raw: &str) -> bool {
+        if self.in_string {
+            self.in_string = raw.matches('"').count().is_multiple_of(2);
+            return true;
+        }
+        false
+    }
+
+    fn place(&mut self, line: &str, _number: usize) -> Placement {
+        if !line.matches('"').count().is_multiple_of(2) {
+            self.in_string = true;
+            return Placement::new(line, Shift::None);
+        }
+        match first_word(line) {
+            "end" => return Placement::new(collapse(line), Shift::Close),
+            "subgraph" => return Placement::new(collapse(line), Shift::Open),
+            _ => {}
+        }
+        let text = statements(line)
+            .into_iter()
+            .map(|statement| {
+                flowchart::format_statement(statement, self.quotes)
+                    .unwrap_or_else(|| collapse(statement))
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        Placement {
+            declaration: declaration_rank(line, FLOWCHART_DECLARATIONS),
+            ..Placement::new(text, Shift::None)
+        }
+    }
+}
+
+/// Messages, notes and blocks are reprinted from the parsed AST by line number
+struct SequenceRules {
+    statements: HashMap<usize, String>,
+    open_blocks: usize,
+}
+
+impl SequenceRules {
+    fn new(content: &str) -> Self {
+        fn collect(statements: &[SequenceStatement], out: &mut HashMap<usize, String>) {
+            for statement in statements {
+                let (line, text) = match statement {
+                    SequenceStatement::Message(message) => (
+                        message.position.line,
+                        format!(
+                            "{}{}{}{}: {}",
+                            message.from,
+                            message.arrow.syntax(),
+                            if message.activate_target {
+                                "+"
+                            } else if message.deactivate_source {
+                                "-"
+                            } else {
+                                ""
+                            },
+                            message.to,
+                            message.text
+                        ),
+                    ),
+                    SequenceStatement::Activation(activation) => (
+                        activation.position.line,
+                        format!(
+                            "{} {}",
+                            if activation.active {
+                                "activate"
+                            } else {
+                                "deactivate"
+                            },
+                            activation.participant
+                        ),
+                    ),
+                    SequenceStatement::Note(note) => (
+                        note.position.line,
+                        format!(
+                            "Note {} {}: {}",
+                            match note.placement {
+                                NotePlacement::LeftOf => "left of",
+                                NotePlacement::RightOf => "right of",
+                                NotePlacement::Over => "over",
+                            },
+                            note.participants.join(","),
+                            note.text
+                        ),
+                    ),
+                    SequenceStatement::Block(block) => {
+                        for (index, branch) in block.branches.iter().enumerate() {
+                            let keyword = match index {
+                                0 => sequence::block_keyword(block.kind),
+                                _ => block.kind.branch_keyword().unwrap_or_default(),
+                            };
+                            let text = format!("{} {}", keyword, collapse(&branch.label));
+                            out.insert(branch.position.line, text.trim_end().to_string());
+                            collect(&branch.statements, out);
+                        }
+                        if let Some(end) = block.end {
+                            out.insert(end.line, "end".to_string());
+                        }
+                        continue;
+                    }
+                };
+                out.insert(line, text.trim_end().to_string());
+            }
+        }
+
+        let mut statements = HashMap::new();
+        collect(&sequence::parse(content, 1).ast.statements, &mut statements);
+        SequenceRules {
+            statements,
+            open_blocks: 0,
+        }
+    }
+}
+
+impl Rules for SequenceRules {
+    fn place(&mut self, line: &str, number: usize) -> Placement {
+        let text = self
+            .statements
+            .remove(&number)
+            .unwrap_or_else(|| collapse(line));
+        let shift = match first_word(line).to_lowercase().as_str() {
+            "loop" | "alt" | "opt" | "par" | "critical" | "break" | "rect" | "box" => {
+                self.open_blocks += 1;
+                Shift::Open
+            }
+            "else" | "and" | "option" if self.open_blocks > 0 => Shift::Branch,
+            "end" => {
+                self.open_blocks = self.open_blocks.saturating_sub(1);
+                Shift::Close
+            }
+            _ => Shift::None,
+        };
+        Placement::new(text, shift)
+    }
+}
+
+/// Relationships are reprinted from the parsed AST by line number
+struct ClassRules {
+    relationships: HashMap<usize, String>,
+}
+
+impl ClassRules {
+    fn new(content: &str) -> Self {
+        let marker = |end: Option<RelationEnd>, from: bool| match end {
+            None => "",
+            Some(RelationEnd::Inheritance) if from => "<|",
+            Some(RelationEnd::Inheritance) => "|>",
+            Some(RelationEnd::Composition) => "*",
+            Some(RelationEnd::Aggregation) => "o",
+            Some(RelationEnd::Association) if from => "<",
+            Some(RelationEnd::Association) => ">",
+            Some(RelationEnd::Lollipop) => "()",
+        };
+        let cardinality = |value: &Option<String>| match value {
+            Some(value) => format!("\"{}\" ", value),
+            None => String::new(),
+        };
+
+        let relationships = class_diagram::parse(content, 1)
+            .ast
+            .relationships
+            .iter()
+            .map(|relation| {
+                let mut text = format!(
+                    "{} {}{}{}{} {}{}",
+                    relation.from,
+                    cardinality(&relation.from_cardinality),
+                    marker(relation.from_end, true),
+                    match relation.line {
+                        RelationLine::Solid => "--",
+                        RelationLine::Dashed => "..",
+                    },
+                    marker(relation.to_end, false),
+                    cardinality(&relation.to_cardinality),
+                    relation.to
+                );
+                if let Some(label) = &relation.label {
+                    text.push_str(" : ");
+                    text.push_str(label);
+                }
+                (relation.position.line, text)
+            })
+            .collect();
+        ClassRules { relationships }
+    }
+}
+
+impl Rules for ClassRules {
+    fn place(&mut self, line: &str, number: usize) -> Placement {
+        let text = self
+            .relationships
+            .remove(&number)
+            .unwrap_or_else(|| collapse(line));
+        let shift = if line.starts_with('}') {
+            Shift::Close
+        } else if line.ends_with('{') {
+            Shift::Open
+        } else if line.ends_with('}') && !line.contains('{') {
+            Shift::CloseAfter
+        } else {
+            Shift::None
+        };
+        Placement {
+            declaration: declaration_rank(line, CLASS_DECLARATIONS),
+            ..Placement::new(text, shift)
+        }
+    }
+}
+
+/// Transitions are reprinted from the parsed AST by line number
+struct StateRules {
+    transitions: HashMap<usize, String>,
+    in_note: bool,
+}
+
+impl StateRules {
+    fn new(content: &str) -> Self {
+        // Start and end pseudo-states are scoped in the AST but written `[*]`
+        let endpoint = |id: &str| {
+            if id.ends_with("[*]start") || id.ends_with("[*]end") {
+                "[*]".to_string()
+            } else {
+                id.to_string()
+            }
+        };
+        let transitions = state_diagram::parse(content, 1)
+            .ast
+            .transitions
+            .iter()
+            .map(|transition| {
+                let mut text = format!(
+                    "{} --> {}",
+                    endpoint(&transition.from),
+                    endpoint(&transition.to)
+                );
+                if let Some(label) = &transition.label {
+                    text.push_str(" : ");
+                    text.push_str(label);
+                }
+                (transition.position.line, text)
+            })
+            .collect();
+        StateRules {
+            transitions,
+            in_note: false,
+        }
+    }
+}
+
+impl Rules for StateRules {
+    fn place(&mut self, line: &str, number: usize) -> Placement {
+        if self.in_note {
+            if line == "end note" {
+                self.in_note = false;
+                return Placement::new(line, Shift::Close);
+            }
+            return Placement::new(line, Shift::None);
+        }
+        if first_word(line) == "note" && !line.contains(':') {
+            self.in_note = true;
+            return Placement::new(collapse(line), Shift::Open);
+        }
+
+        let text = self
+            .transitions
+            .remove(&number)
+            .unwrap_or_else(|| collapse(line));
+        let shift = if line.starts_with('}') {
+            Shift::Close
+        } else if line.ends_with('{') {
+            Shift::Open
+        } else {
+            Shift::None
+        };
+        Placement {
+            declaration: declaration_rank(line, STATE_DECLARATIONS),
+            ..Placement::new(text, shift)
+        }
+    }
+}
+
+/// The diagram's AST without positions, for checking that formatting preserved it
+fn canonical_ast(diagram_type: DiagramType, content: &str, sorted: bool) -> Option<Value> {
+    fn parsed<T: Serialize>(result: super::AstResult<T>) -> Option<Value> {
+        if result.errors.is_empty() {
+            serde_json::to_value(result.ast).ok()
+        } else {
+            None
+        }
+    }
+
+    fn canonical(value: Value, sorted: bool) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .filter(|(_, value)| !is_position(value))
+                    .map(|(key, value)| (key, canonical(value, sorted)))
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                let mut items: Vec<Value> = items
+                    .into_iter()
+                    .map(|item| canonical(item, sorted))
+                    .collect();
+                if sorted {
+                    items.sort_by_cached_key(Value::to_string);
+                }
+                Value::Array(items)
+            }
+            // Mermaid collapses whitespace in labels when it renders them
+            Value::String(text) => Value::String(collapse_all(&text)),
+            other => other,
+        }
+    }
+
+    fn is_position(value: &Value) -> bool {
+        value.as_object().is_some_and(|map| {
+            map.len() == 2 && map.contains_key("line") && map.contains_key("column")
+        })
+    }
+
+    fn collapse_all(text: &str) -> String {
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    let value = match diagram_type {
+        DiagramType::Flowchart => parsed(flowchart::parse(content, 1)),
+        DiagramType::Sequence => parsed(sequence::parse(content, 1)),
+        DiagramType::Class => parsed(class_diagram::parse(content, 1)),
+        DiagramType::State => parsed(state_diagram::parse(content, 1)),
+        _ => None,
+    }?;
+    Some(canonical(value, sorted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(content: &str) -> String {
+        let parser = MermaidParser::new().unwrap();
+        let formatted = format_diagram(&parser, content, &FormatOptions::default()).unwrap();
+        // Formatting is idempotent
+        assert_eq!(
+            format_diagram(&parser, &formatted, &FormatOptions::default()).unwrap(),
+            formatted
+        );
+        formatted
+    }
+
+    #[test]
+    fn test_flowchart_spacing_links_and_quotes() {
+        let content = "graph   TD\n%% entry point\nA[\"Start\"]-->B{Is it?}\n  B -- Yes -->C(\"Done (really)\")\nB-.->|\"No\"|D\n\n\n      subgraph inner\nE==>F & G;\nend";
+        assert_eq!(
+            format(content),
+            "graph TD\n    %% entry point\n    A[Start] --> B{Is it?}\n    B -->|Yes| C(\"Done (really)\")\n    B -.->|No| D\n\n    subgraph inner\n        E ==> F & G\n    end"
+        );
+
+        assert_eq!(
+            format("graph TD\nA-->B   %% TODO (fix\nC[\"50%% off\"]"),
+            "graph TD\n    A --> B %% TODO (fix\n    C[50%% off]"
+        );
+        assert_eq!(
+            format("---\ntitle:  Flow\n---\n%%{init: {'theme':'dark'}}%%\ngraph TD\nA-->B"),
+            "---\ntitle:  Flow\n---\n%%{init: {'theme':'dark'}}%%\ngraph TD\n    A --> B"
+        );
+
+        let parser = MermaidParser::new().unwrap();
+        let options = FormatOptions {
+            indent: 2,
+            quotes: QuoteStyle::Always,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_diagram(&parser, "graph LR\nA[Start]---B", &options).unwrap(),
+            "graph LR\n  A[\"Start\"] --- B"
+        );
+    }
+
+    #[test]
+    fn test_sequence_class_and_state() {
+        assert_eq!(
+            format("sequenceDiagram\nparticipant   A as Alice\nA->>+B :hello\n alt   every minute\nB-->>A:  pong\nelse  late\nend\nNote over A,B:done"),
+            "sequenceDiagram\n    participant A as Alice\n    A->>+B: hello\n    alt every minute\n        B-->>A: pong\n    else late\n    end\n    Note over A,B: done"
+        );
+        assert_eq!(
+            format("classDiagram\nAnimal<|--Dog:is a\nclass Dog{\n+bark()\n}"),
+            "classDiagram\n    Animal <|-- Dog : is a\n    class Dog{\n        +bark()\n    }"
+        );
+        assert_eq!(
+            format("stateDiagram-v2\n[*]-->Idle\nstate Busy {\n  a-->b:go\n}\nnote right of Idle\nwaiting\nend note"),
+            "stateDiagram-v2\n    [*] --> Idle\n    state Busy {\n        a --> b : go\n    }\n    note right of Idle\n        waiting\n    end note"
+        );
+    }
+
+    #[test]
+    fn test_sort_declarations_and_refusals() {
+        let parser = MermaidParser::new().unwrap();
+        let options = FormatOptions {
+            sort_declarations: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_diagram(
+                &parser,
+                "graph TD\nA --> B\nstyle B fill:#f9f\nclass A hot\nclassDef hot fill:#f00",
+                &options
+            )
+            .unwrap(),
+            "graph TD\n    A --> B\n    classDef hot fill:#f00\n    class A hot\n    style B fill:#f9f"
+        );
+
+        assert!(matches!(
+            format_diagram(&parser, "graph TD\n  A[open --> B", &options),
+            Err(FormatError::Syntax(_))
+        ));
+        assert!(matches!(
+            format_diagram(&parser, "pie\n  \"a\" : 1", &options),
+            Err(FormatError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_document_keeps_surrounding_markdown() {
+        let parser = MermaidParser::new().unwrap();
+        let content = "# Title\n\n  text  with  spaces\n\n- item\n  ```mermaid\n  graph TD\n  A-->B\n  ```\n\n```mermaid\ngantt\n  title  x\n```\n";
+        let result = format_document(
+            &parser,
+            content,
+            ContentFormat::Markdown,
+            &FormatOptions::default(),
+        );
+        assert_eq!(
+            result.content,
+            "# Title\n\n  text  with  spaces\n\n- item\n  ```mermaid\n  graph TD\n      A --> B\n  ```\n\n```mermaid\ngantt\n  title  x\n```\n"
+        );
+        assert_eq!(result.changed, 1);
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(result.skipped[0].start_line, 12);
+
+        let quoted = "> Note\n> ```mermaid\n> graph TD\n>\n> A-->B\n> ```\n";
+        let result = format_document(
+            &parser,
+            quoted,
+            ContentFormat::Markdown,
+            &FormatOptions::default(),
+        );
+        assert_eq!(
+            result.content,
+            "> Note\n> ```mermaid\n> graph TD\n>\n>     A --> B\n> ```\n"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use super::{DiagramType, ParsedDiagram};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+static ID_COMMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*%%\s*id\s*:\s*([\w.-]+)\s*$").unwrap());
+
+static FENCE_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:^|[\s{])(?:#([\w.-]+)|id\s*=\s*"?([\w.-]+)"?)"#).unwrap());
+
+/// Minimum line similarity for an edited block to keep its previous identity
+const SIMILARITY_THRESHOLD: f64 = 0.3;
+
+/// How a diagram from the previous parse corresponds to one in the current parse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdMapping {
+    pub old_id: String,
+    pub new_id: String,
+    pub old_index: usize,
+    pub new_index: usize,
+}
+
+/// Explicit name from a `%% id: name` comment or a fence attribute like `id=name` / `#name`
+pub fn explicit_name(content: &str, fence_info: Option<&str>) -> Option<String> {
+    let from_fence = fence_info
+        .and_then(|info| FENCE_ID.captures(info))
+        .and_then(|captures| captures.get(1).or_else(|| captures.get(2)))
+        .map(|name| name.as_str().to_string());
+
+    from_fence.or_else(|| {
+        content
+            .lines()
+            .find_map(|line| ID_COMMENT.captures(line))
+            .map(|captures| captures[1].to_string())
+    })
+}
+
+/// Whitespace-insensitive FNV-1a hash of the diagram body, stable across runs
+pub fn fingerprint(content: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for line in normalized_lines(content) {
+        for byte in line.bytes().chain(std::iter::once(b'\n')) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+fn normalized_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+}
+
+/// Derive an ID from the explicit name or fingerprint; `seen` disambiguates duplicates
+/// by their position in the document
+pub fn base_id(
+    name: Option<&str>,
+    diagram_type: DiagramType,
+    fingerprint: &str,
+    seen: &mut HashMap<String, usize>,
+) -> String {
+    let base = match name {
+        Some(name) => name.to_string(),
+        None => format!("{}-{}", diagram_type, &fingerprint[..12]),
+    };
+    let count = seen.entry(base.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        base
+    } else {
+        format!("{}-{}", base, count)
+    }
+}
+
+/// Dice coefficient over the multiset of non-blank trimmed lines
+fn similarity(a: &str, b: &str) -> f64 {
+    let mut counts: HashMap<&str, isize> = HashMap::new();
+    let mut total = 0;
+    for line in normalized_lines(a) {
+        *counts.entry(line).or_default() += 1;
+        total += 1;
+    }
+    let mut common = 0;
+    for line in normalized_lines(b) {
+        total += 1;
+        if let Some(count) = counts.get_mut(line) {
+            if *count > 0 {
+                *count -= 1;
+                common += 1;
+            }
+        }
+    }
+    if total == 0 {
+        return 1.0;
+    }
+    2.0 * common as f64 / total as f64
+}
+
+/// Match the current diagrams against the previous parse and carry identities forward.
+///
+/// Blocks are paired by explicit name, then identical fingerprint, then by line
+/// similarity, so edited, inserted and moved diagrams keep the ID they had before.
+/// Explicit names always win, so a renamed block maps its old ID to the new name.
+/// Returns every pairing; unpaired current diagrams are new and keep their fresh ID.
+pub fn reconcile(previous: &[ParsedDiagram], current: &mut [ParsedDiagram]) -> Vec<IdMapping> {
+    let mut old_taken = vec![false; previous.len()];
+    let mut pairs: Vec<Option<usize>> = vec![None; current.len()];
+
+    let pair_by = |pairs: &mut Vec<Option<usize>>,
+                   old_taken: &mut Vec<bool>,
+                   matches: &dyn Fn(&ParsedDiagram, &ParsedDiagram) -> bool| {
+        for (new_index, diagram) in current.iter().enumerate() {
+            if pairs[new_index].is_some() {
+                continue;
+            }
+            let found = previous
+                .iter()
+                .enumerate()
+                .find(|(old_index, old)| !old_taken[*old_index] && matches(old, diagram));
+            if let Some((old_index, _)) = found {
+                old_taken[old_index] = true;
+                pairs[new_index] = Some(old_index);
+            }
+        }
+    };
+
+    pair_by(&mut pairs, &mut old_taken, &|old, new| {
+        old.name.is_some() && old.name == new.name
+    });
+    pair_by(&mut pairs, &mut old_taken, &|old, new| {
+        old.name.is_none() && new.name.is_none() && old.fingerprint == new.fingerprint
+    });
+
+    // Edited or renamed blocks: best similarity among the remaining blocks of the same type
+    for (new_index, diagram) in current.iter().enumerate() {
+        if pairs[new_index].is_some() {
+            continue;
+        }
+        let best = previous
+            .iter()
+            .enumerate()
+            .filter(|(old_index, old)| {
+                !old_taken[*old_index] && old.diagram_type == diagram.diagram_type
+            })
+            .map(|(old_index, old)| (old_index, similarity(&old.content, &diagram.content)))
+            .filter(|(_, score)| *score >= SIMILARITY_THRESHOLD)
+            .max_by(|a, b| {
+                a.1.total_cmp(&b.1)
+                    .then_with(|| new_index.abs_diff(b.0).cmp(&new_index.abs_diff(a.0)))
+            });
+        if let Some((old_index, _)) = best {
+            old_taken[old_index] = true;
+            pairs[new_index] = Some(old_index);
+        }
+    }
+
+    // A single remaining block on each side is almost always the one being typed in
+    let remaining_new: Vec<usize> = (0..current.len())
+        .filter(|&i| pairs[i].is_none() && current[i].name.is_none())
+        .collect();
+    let remaining_old: Vec<usize> = (0..previous.len())
+        .filter(|&i| !old_taken[i] && previous[i].name.is_none())
+        .collect();
+    if let ([new_index], [old_index]) = (remaining_new.as_slice(), remaining_old.as_slice()) {
+        pairs[*new_index] = Some(*old_index);
+    }
+
+    let mut mappings = Vec::new();
+    for (new_index, old_index) in pairs.into_iter().enumerate() {
+        if let Some(old_index) = old_index {
+            let old_id = previous[old_index].id.clone();
+            if current[new_index].name.is_none() {
+                current[new_index].id = old_id.clone();
+            }
+            mappings.push(IdMapping {
+                old_id,
+                new_id: current[new_index].id.clone(),
+                old_index,
+                new_index,
+            });
+        }
+    }
+
+    // A carried-over ID may collide with a fresh one; fresh IDs give way
+    let mut used: HashSet<String> = mappings.iter().map(|m| m.new_id.clone()).collect();
+    for (index, diagram) in current.iter_mut().enumerate() {
+        let is_fresh = !mappings.iter().any(|m| m.new_index == index);
+        if is_fresh && used.contains(&diagram.id) {
+            let mut suffix = 2;
+            while used.contains(&format!("{}-{}", diagram.id, suffix)) {
+                suffix += 1;
+            }
+            diagram.id = format!("{}-{}", diagram.id, suffix);
+        }
+        used.insert(diagram.id.clone());
+    }
+
+    mappings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{ContentFormat, MermaidParser};
+    use super::*;
+
+    #[test]
+    fn test_ids_survive_reparse_and_insertion() {
+        let parser = MermaidParser::new().unwrap();
+        let before = "```mermaid\ngraph TD\n  A --> B\n```\n\n```mermaid\nsequenceDiagram\n  A->>B: hi\n```\n";
+        let after = "```mermaid\npie\n  \"x\" : 1\n```\n\n".to_string() + before;
+
+        let first = parser
+            .parse_content_as(before, ContentFormat::Markdown)
+            .diagrams;
+        assert_eq!(
+            first.iter().map(|d| &d.id).collect::<Vec<_>>(),
+            parser
+                .parse_content_as(before, ContentFormat::Markdown)
+                .diagrams
+                .iter()
+                .map(|d| &d.id)
+                .collect::<Vec<_>>()
+        );
+
+        let second = parser
+            .parse_content_as(&after, ContentFormat::Markdown)
+            .diagrams;
+        assert_eq!(second[1].id, first[0].id);
+        assert_eq!(second[2].id, first[1].id);
+    }
+
+    #[test]
+    fn test_explicit_names() {
+        assert_eq!(
+            explicit_name("graph TD\n  %% id: auth-flow\n  A --> B", None).as_deref(),
+            Some("auth-flow")
+        );
+        assert_eq!(
+            explicit_name("graph TD", Some("mermaid {#login id=other}")).as_deref(),
+            Some("login")
+        );
+        assert_eq!(
+            explicit_name("graph TD", Some("mermaid id=\"checkout\"")).as_deref(),
+            Some("checkout")
+        );
+    }
+
+    #[test]
+    fn test_reconcile_keeps_ids_for_edited_blocks() {
+        let parser = MermaidParser::new().unwrap();
+        let previous = parser
+            .parse_content_as(
+                "graph TD\n  A --> B\n  B --> C\n---\nsequenceDiagram\n  A->>B: hi",
+                ContentFormat::Raw,
+            )
+            .diagrams;
+        let mut current = parser
+            .parse_content_as(
+                "sequenceDiagram\n  A->>B: hi\n---\ngraph TD\n  A --> B\n  B --> D",
+                ContentFormat::Raw,
+            )
+            .diagrams;
+        assert_ne!(current[1].id, previous[0].id);
+
+        let mappings = reconcile(&previous, &mut current);
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(current[0].id, previous[1].id);
+        assert_eq!(current[1].id, previous[0].id);
+        assert!(mappings
+            .iter()
+            .any(|m| m.old_index == 0 && m.new_index == 1));
+    }
+
+    #[test]
+    fn test_reconcile_maps_renamed_blocks() {
+        let parser = MermaidParser::new().unwrap();
+        let previous = parser
+            .parse_content_as("graph TD\n  A --> B\n  B --> C", ContentFormat::Raw)
+            .diagrams;
+        let mut current = parser
+            .parse_content_as(
+                "graph TD\n  %% id: checkout\n  A --> B\n  B --> C",
+                ContentFormat::Raw,
+            )
+            .diagrams;
+
+        let mappings = reconcile(&previous, &mut current);
+        assert_eq!(current[0].id, "checkout");
+        assert_eq!(mappings[0].old_id, previous[0].id);
+        assert_eq!(mappings[0].new_id, "checkout");
+    }
+}
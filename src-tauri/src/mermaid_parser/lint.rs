@@ -0,0 +1,421 @@
+use super::comments::{self, Comment};
+use super::{DiagnosticCode, DiagramType, Position, Severity, SyntaxError};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+mod rules;
+
+/// Project-level lint configuration, looked up from the document's folder upwards
+pub const CONFIG_FILE_NAME: &str = ".parch.json";
+
+/// Nodes allowed by `max-nodes` unless configured otherwise
+const DEFAULT_MAX_NODES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    InvalidNodeId,
+    UnusedClassDef,
+    OrphanNode,
+    DuplicateEdge,
+    InconsistentNaming,
+    SelfLoop,
+    MaxNodes,
+}
+
+impl Rule {
+    pub const ALL: &'static [Rule] = &[
+        Rule::InvalidNodeId,
+        Rule::UnusedClassDef,
+        Rule::OrphanNode,
+        Rule::DuplicateEdge,
+        Rule::InconsistentNaming,
+        Rule::SelfLoop,
+        Rule::MaxNodes,
+    ];
+
+    /// The name used in config files and `%% parch-disable` comments
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::InvalidNodeId => "invalid-node-id",
+            Rule::UnusedClassDef => "unused-class-def",
+            Rule::OrphanNode => "orphan-node",
+            Rule::DuplicateEdge => "duplicate-edge",
+            Rule::InconsistentNaming => "inconsistent-naming",
+            Rule::SelfLoop => "self-loop",
+            Rule::MaxNodes => "max-nodes",
+        }
+    }
+
+    pub fn code(self) -> DiagnosticCode {
+        match self {
+            Rule::InvalidNodeId => DiagnosticCode::InvalidNodeId,
+            Rule::UnusedClassDef => DiagnosticCode::UnusedClassDef,
+            Rule::OrphanNode => DiagnosticCode::OrphanNode,
+            Rule::DuplicateEdge => DiagnosticCode::DuplicateEdge,
+            Rule::InconsistentNaming => DiagnosticCode::InconsistentNaming,
+            Rule::SelfLoop => DiagnosticCode::SelfLoop,
+            Rule::MaxNodes => DiagnosticCode::TooManyNodes,
+        }
+    }
+
+    /// Opinionated rules stay off until a config file turns them on
+    pub fn enabled_by_default(self) -> bool {
+        matches!(
+            self,
+            Rule::InvalidNodeId | Rule::UnusedClassDef | Rule::DuplicateEdge | Rule::MaxNodes
+        )
+    }
+
+    /// Look a rule up by name, e.g. `orphan-node`, or by code, e.g. `MMD0101`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|rule| rule.name() == name || rule.code().as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Self::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown lint rule '{}'", name)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    Off,
+    Error,
+    Warning,
+    Info,
+}
+
+impl RuleLevel {
+    fn severity(self) -> Option<Severity> {
+        match self {
+            RuleLevel::Off => None,
+            RuleLevel::Error => Some(Severity::Error),
+            RuleLevel::Warning => Some(Severity::Warning),
+            RuleLevel::Info => Some(Severity::Info),
+        }
+    }
+}
+
+/// `"warning"`, or `{ "severity": "warning", "max": 40 }` for rules with options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleSetting {
+    Level(RuleLevel),
+    Options {
+        /// Defaults to the rule's own severity, which also turns the rule on
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<RuleLevel>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<usize>,
+    },
+}
+
+/// Which lint rules run and how loudly, as read from [`CONFIG_FILE_NAME`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    pub rules: HashMap<Rule, RuleSetting>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Invalid lint config {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl LintConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Load the nearest config file in `dir` or one of its ancestors
+    pub fn discover(dir: &Path) -> Result<Option<(PathBuf, Self)>, ConfigError> {
+        for ancestor in dir.ancestors() {
+            let path = ancestor.join(CONFIG_FILE_NAME);
+            if path.is_file() {
+                let config = Self::load(&path)?;
+                return Ok(Some((path, config)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like [`LintConfig::discover`] starting at a document's folder, falling back to
+    /// the defaults when no config file exists
+    pub fn for_document(path: &Path) -> Result<Self, ConfigError> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Ok(Self::discover(dir)?
+            .map(|(_, config)| config)
+            .unwrap_or_default())
+    }
+
+    /// Severity a rule reports at, or `None` when it is turned off
+    pub fn severity(&self, rule: Rule) -> Option<Severity> {
+        match self.rules.get(&rule) {
+            Some(RuleSetting::Level(level))
+            | Some(RuleSetting::Options {
+                severity: Some(level),
+                ..
+            }) => level.severity(),
+            Some(RuleSetting::Options { severity: None, .. }) => {
+                Some(rule.code().default_severity())
+            }
+            None if rule.enabled_by_default() => Some(rule.code().default_severity()),
+            None => None,
+        }
+    }
+
+    pub fn max_nodes(&self) -> usize {
+        match self.rules.get(&Rule::MaxNodes) {
+            Some(RuleSetting::Options { max: Some(max), .. }) => *max,
+            _ => DEFAULT_MAX_NODES,
+        }
+    }
+}
+
+/// A rule violation before severity and suppressions are applied
+struct Finding {
+    rule: Rule,
+    position: Position,
+    message: String,
+}
+
+/// Rules named by `%% parch-disable` comments; `None` means every rule
+struct Suppression {
+    /// `None` for the whole diagram, otherwise the one line it covers
+    line: Option<usize>,
+    rules: Option<HashSet<Rule>>,
+}
+
+impl Suppression {
+    fn covers(&self, finding: &Finding) -> bool {
+        self.line.is_none_or(|line| line == finding.position.line)
+            && self
+                .rules
+                .as_ref()
+                .is_none_or(|rules| rules.contains(&finding.rule))
+    }
+}
+
+/// Read `%% parch-disable rule, ...` (whole diagram) and
+/// `%% parch-disable-next-line rule, ...` comments; without rules they silence everything
+fn suppressions(comments: &[Comment]) -> Vec<Suppression> {
+    comments
+        .iter()
+        .filter(|comment| !comment.trailing)
+        .filter_map(|comment| {
+            let (line, rest) = match comment.text.strip_prefix("parch-disable-next-line") {
+                Some(rest) => (Some(comment.position.line + 1), rest),
+                None => (None, comment.text.strip_prefix("parch-disable")?),
+            };
+            // Reject longer words such as `parch-disabled`
+            if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                return None;
+            }
+            let names: Vec<&str> = rest
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|name| !name.is_empty())
+                .collect();
+            let rules = (!names.is_empty())
+                .then(|| names.into_iter().filter_map(Rule::from_name).collect());
+            Some(Suppression { line, rules })
+        })
+        .collect()
+}
+
+/// Run the enabled lint rules over one diagram.
+///
+/// Rules that need the diagram's structure only run once it parses without errors,
+/// so lint findings never pile up on top of syntax errors.
+pub fn lint_diagram(
+    content: &str,
+    start_line: usize,
+    diagram_type: DiagramType,
+    config: &LintConfig,
+) -> Vec<SyntaxError> {
+    let enabled = |rule: Rule| config.severity(rule).is_some();
+    let comments = comments::scan(content, start_line);
+    let code = comments::strip(content);
+    let content = code.as_ref();
+    let mut findings = Vec::new();
+    if enabled(Rule::InvalidNodeId) {
+        findings.extend(rules::invalid_node_ids(content, start_line));
+    }
+    if let Some(graph) = rules::Graph::build(content, start_line, diagram_type) {
+        if enabled(Rule::UnusedClassDef) {
+            findings.extend(rules::unused_class_defs(&graph));
+        }
+        if enabled(Rule::OrphanNode) {
+            findings.extend(rules::orphan_nodes(&graph));
+        }
+        if enabled(Rule::DuplicateEdge) {
+            findings.extend(rules::duplicate_edges(&graph));
+        }
+        if enabled(Rule::InconsistentNaming) {
+            findings.extend(rules::inconsistent_naming(&graph));
+        }
+        if enabled(Rule::SelfLoop) {
+            findings.extend(rules::self_loops(&graph));
+        }
+        if enabled(Rule::MaxNodes) {
+            findings.extend(rules::too_many_nodes(
+                &graph,
+                config.max_nodes(),
+                start_line,
+            ));
+        }
+    }
+
+    let suppressions = suppressions(&comments);
+    let mut errors: Vec<SyntaxError> = findings
+        .into_iter()
+        .filter(|finding| !suppressions.iter().any(|s| s.covers(finding)))
+        .filter_map(|finding| {
+            let mut error = SyntaxError::new(
+                finding.rule.code(),
+                finding.position.line,
+                finding.position.column,
+                finding.message,
+            );
+            error.severity = config.severity(finding.rule)?;
+            Some(error)
+        })
+        .collect();
+    errors.sort_by_key(|error| (error.line, error.column));
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(content: &str, config: &LintConfig) -> Vec<(DiagnosticCode, usize)> {
+        let diagram_type = if content.starts_with("stateDiagram") {
+            DiagramType::State
+        } else {
+            DiagramType::Flowchart
+        };
+        lint_diagram(content, 1, diagram_type, config)
+            .into_iter()
+            .map(|error| (error.code, error.line))
+            .collect()
+    }
+
+    fn config(json: &str) -> LintConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let findings = lint(
+            "graph TD\n  classDef hot fill:#f00\n  classDef cold fill:#00f\n  A --> B:::hot\n  A --> B\n  C",
+            &LintConfig::default(),
+        );
+        assert_eq!(
+            findings,
+            vec![
+                (DiagnosticCode::UnusedClassDef, 3),
+                (DiagnosticCode::DuplicateEdge, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_levels_and_options() {
+        let config = config(
+            r#"{ "rules": {
+                "orphan-node": "error",
+                "self-loop": "info",
+                "duplicate-edge": "off",
+                "max-nodes": { "max": 2 }
+            } }"#,
+        );
+        assert_eq!(config.severity(Rule::OrphanNode), Some(Severity::Error));
+        assert_eq!(config.severity(Rule::DuplicateEdge), None);
+        assert_eq!(
+            lint("graph TD\n  A --> A\n  A --> B\n  A --> B\n  C", &config),
+            vec![
+                (DiagnosticCode::TooManyNodes, 1),
+                (DiagnosticCode::SelfLoop, 2),
+                (DiagnosticCode::OrphanNode, 5),
+            ]
+        );
+
+        assert!(serde_json::from_str::<LintConfig>(r#"{ "rules": { "nope": "off" } }"#).is_err());
+    }
+
+    #[test]
+    fn test_naming_and_state_diagrams() {
+        let config = config(r#"{ "rules": { "inconsistent-naming": "warning" } }"#);
+        assert_eq!(
+            lint(
+                "stateDiagram-v2\n  [*] --> idleState\n  idleState --> runningState\n  runningState --> shut_down",
+                &config
+            ),
+            vec![(DiagnosticCode::InconsistentNaming, 4)]
+        );
+    }
+
+    #[test]
+    fn test_inline_suppressions() {
+        let config = config(r#"{ "rules": { "orphan-node": "warning" } }"#);
+        let content = "graph TD\n  %% parch-disable duplicate-edge\n  A --> B\n  A --> B\n  %% parch-disable-next-line MMD0101\n  C\n  D";
+        assert_eq!(
+            lint(content, &config),
+            vec![(DiagnosticCode::OrphanNode, 7)]
+        );
+        assert!(lint(
+            "graph TD\n  %% parch-disable\n  A --> B\n  A --> B",
+            &config
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_invalid_node_ids_and_broken_diagrams() {
+        let findings = lint("graph TD\n  __hidden[Label] --> B", &LintConfig::default());
+        assert_eq!(findings, vec![(DiagnosticCode::InvalidNodeId, 2)]);
+        // Graph rules wait until the syntax errors are fixed
+        assert!(lint(
+            "graph TD\n  A --> B\n  A --> B\n  C -->",
+            &LintConfig::default()
+        )
+        .is_empty());
+    }
+}
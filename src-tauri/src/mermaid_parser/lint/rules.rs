@@ -0,0 +1,378 @@
+use super::{Finding, Rule};
+use crate::mermaid_parser::state_diagram::StateKind;
+use crate::mermaid_parser::{class_diagram, flowchart, state_diagram, DiagramType, Position};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+static NODE_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b([A-Za-z_][A-Za-z0-9_]*)\s*[\[\(]").unwrap());
+
+/// Longest node ID `invalid-node-id` accepts
+const MAX_NODE_ID_LENGTH: usize = 50;
+
+pub(super) struct GraphNode {
+    id: String,
+    position: Position,
+    /// Composite states and pseudo-states are connected through their children
+    can_be_orphan: bool,
+}
+
+pub(super) struct GraphEdge {
+    from: String,
+    to: String,
+    /// Arrow style, so `A --> B` and `A -.-> B` are not duplicates of each other
+    kind: String,
+    label: Option<String>,
+    position: Position,
+}
+
+/// The parts of flowcharts, state and class diagrams the structural rules look at
+pub(super) struct Graph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    class_defs: Vec<(String, Position)>,
+    used_classes: HashSet<String>,
+}
+
+impl Graph {
+    /// `None` for diagram types without a graph, or when the diagram has syntax errors
+    pub(super) fn build(
+        content: &str,
+        start_line: usize,
+        diagram_type: DiagramType,
+    ) -> Option<Self> {
+        match diagram_type {
+            DiagramType::Flowchart => {
+                let result = flowchart::parse(content, start_line);
+                result
+                    .errors
+                    .is_empty()
+                    .then(|| Self::flowchart(result.ast))
+            }
+            DiagramType::State => {
+                let result = state_diagram::parse(content, start_line);
+                result.errors.is_empty().then(|| Self::state(result.ast))
+            }
+            DiagramType::Class => {
+                let result = class_diagram::parse(content, start_line);
+                result.errors.is_empty().then(|| Self::class(result.ast))
+            }
+            _ => None,
+        }
+    }
+
+    fn flowchart(ast: flowchart::FlowchartAst) -> Self {
+        let mut used_classes: HashSet<String> = ast
+            .class_assignments
+            .iter()
+            .map(|assignment| assignment.class_name.clone())
+            .collect();
+        used_classes.extend(ast.nodes.iter().flat_map(|node| node.classes.clone()));
+        Graph {
+            nodes: ast
+                .nodes
+                .into_iter()
+                .map(|node| GraphNode {
+                    id: node.id,
+                    position: node.position,
+                    can_be_orphan: true,
+                })
+                .collect(),
+            edges: ast
+                .edges
+                .into_iter()
+                .map(|edge| GraphEdge {
+                    kind: format!("{:?}{:?}{:?}", edge.start_head, edge.stroke, edge.end_head),
+                    from: edge.from,
+                    to: edge.to,
+                    label: edge.label,
+                    position: edge.position,
+                })
+                .collect(),
+            class_defs: ast
+                .class_defs
+                .into_iter()
+                .flat_map(|class_def| {
+                    let position = class_def.position;
+                    class_def
+                        .names
+                        .into_iter()
+                        .map(move |name| (name, position))
+                })
+                // `default` applies to every node without being assigned
+                .filter(|(name, _)| name != "default")
+                .collect(),
+            used_classes,
+        }
+    }
+
+    fn state(ast: state_diagram::StateDiagramAst) -> Self {
+        let parents: HashSet<String> = ast
+            .states
+            .iter()
+            .filter_map(|state| state.parent.clone())
+            .collect();
+        Graph {
+            nodes: ast
+                .states
+                .into_iter()
+                .filter(|state| !matches!(state.kind, StateKind::Start | StateKind::End))
+                .map(|state| GraphNode {
+                    can_be_orphan: !parents.contains(&state.id),
+                    id: state.id,
+                    position: state.position,
+                })
+                .collect(),
+            edges: ast
+                .transitions
+                .into_iter()
+                .map(|transition| GraphEdge {
+                    from: transition.from,
+                    to: transition.to,
+                    kind: String::new(),
+                    label: transition.label,
+                    position: transition.position,
+                })
+                .collect(),
+            class_defs: Vec::new(),
+            used_classes: HashSet::new(),
+        }
+    }
+
+    fn class(ast: class_diagram::ClassDiagramAst) -> Self {
+        Graph {
+            nodes: ast
+                .classes
+                .into_iter()
+                .map(|class| GraphNode {
+                    id: class.name,
+                    position: class.position,
+                    can_be_orphan: true,
+                })
+                .collect(),
+            edges: ast
+                .relationships
+                .into_iter()
+                .map(|relationship| GraphEdge {
+                    kind: format!(
+                        "{:?}{:?}{:?}",
+                        relationship.from_end, relationship.line, relationship.to_end
+                    ),
+                    from: relationship.from,
+                    to: relationship.to,
+                    label: relationship.label,
+                    position: relationship.position,
+                })
+                .collect(),
+            class_defs: Vec::new(),
+            used_classes: HashSet::new(),
+        }
+    }
+}
+
+fn finding(rule: Rule, position: Position, message: String) -> Finding {
+    Finding {
+        rule,
+        position,
+        message,
+    }
+}
+
+/// IDs Mermaid may reject or treat specially: very long ones and `__`-prefixed ones
+pub(super) fn invalid_node_ids(content: &str, start_line: usize) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with("%%") {
+            continue;
+        }
+        for captures in NODE_ID.captures_iter(line) {
+            let id = captures.get(1).unwrap();
+            let reason = if id.as_str().chars().count() > MAX_NODE_ID_LENGTH {
+                format!("is longer than {} characters", MAX_NODE_ID_LENGTH)
+            } else if id.as_str().starts_with("__") {
+                "starts with '__', which is reserved".to_string()
+            } else {
+                continue;
+            };
+            let position = Position {
+                line: start_line + index,
+                column: line[..id.start()].chars().count() + 1,
+            };
+            findings.push(finding(
+                Rule::InvalidNodeId,
+                position,
+                format!("Node ID '{}' {}", id.as_str(), reason),
+            ));
+        }
+    }
+    findings
+}
+
+pub(super) fn unused_class_defs(graph: &Graph) -> Vec<Finding> {
+    graph
+        .class_defs
+        .iter()
+        .filter(|(name, _)| !graph.used_classes.contains(name))
+        .map(|(name, position)| {
+            finding(
+                Rule::UnusedClassDef,
+                *position,
+                format!("classDef '{}' is never used", name),
+            )
+        })
+        .collect()
+}
+
+/// Nodes without edges, in diagrams that have edges at all
+pub(super) fn orphan_nodes(graph: &Graph) -> Vec<Finding> {
+    if graph.edges.is_empty() {
+        return Vec::new();
+    }
+    let connected: HashSet<&str> = graph
+        .edges
+        .iter()
+        .flat_map(|edge| [edge.from.as_str(), edge.to.as_str()])
+        .collect();
+    graph
+        .nodes
+        .iter()
+        .filter(|node| node.can_be_orphan && !connected.contains(node.id.as_str()))
+        .map(|node| {
+            finding(
+                Rule::OrphanNode,
+                node.position,
+                format!("'{}' is not connected to anything", node.id),
+            )
+        })
+        .collect()
+}
+
+pub(super) fn duplicate_edges(graph: &Graph) -> Vec<Finding> {
+    let mut seen = HashMap::new();
+    let mut findings = Vec::new();
+    for edge in &graph.edges {
+        let key = (&edge.from, &edge.to, &edge.kind, &edge.label);
+        match seen.get(&key) {
+            Some(Position { line, .. }) => findings.push(finding(
+                Rule::DuplicateEdge,
+                edge.position,
+                format!(
+                    "Edge '{}' to '{}' repeats the one on line {}",
+                    edge.from, edge.to, line
+                ),
+            )),
+            None => {
+                seen.insert(key, edge.position);
+            }
+        }
+    }
+    findings
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CaseStyle {
+    Camel,
+    Pascal,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+}
+
+impl CaseStyle {
+    /// `None` for IDs that fit several styles, such as `a` or `A1`
+    fn of(id: &str) -> Option<Self> {
+        let has_upper = id.chars().any(|c| c.is_uppercase());
+        let has_lower = id.chars().any(|c| c.is_lowercase());
+        let first_upper = id.chars().next()?.is_uppercase();
+        match (id.contains('_'), id.contains('-')) {
+            (true, true) => None,
+            (true, false) if !has_upper => Some(CaseStyle::Snake),
+            (true, false) if !has_lower => Some(CaseStyle::ScreamingSnake),
+            (false, true) if !has_upper => Some(CaseStyle::Kebab),
+            (false, false) if has_upper && has_lower && first_upper => Some(CaseStyle::Pascal),
+            (false, false) if has_upper && has_lower => Some(CaseStyle::Camel),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CaseStyle::Camel => "camelCase",
+            CaseStyle::Pascal => "PascalCase",
+            CaseStyle::Snake => "snake_case",
+            CaseStyle::ScreamingSnake => "SCREAMING_SNAKE_CASE",
+            CaseStyle::Kebab => "kebab-case",
+        }
+    }
+}
+
+/// IDs whose case style differs from the style most IDs in the diagram use
+pub(super) fn inconsistent_naming(graph: &Graph) -> Vec<Finding> {
+    let styled: Vec<(&GraphNode, CaseStyle)> = graph
+        .nodes
+        .iter()
+        .filter_map(|node| Some((node, CaseStyle::of(&node.id)?)))
+        .collect();
+    let mut counts: Vec<(CaseStyle, usize)> = Vec::new();
+    for (_, style) in &styled {
+        match counts.iter_mut().find(|(counted, _)| counted == style) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*style, 1)),
+        }
+    }
+    // Ties go to the style seen first
+    let Some(&(majority, _)) = counts.iter().rev().max_by_key(|(_, count)| *count) else {
+        return Vec::new();
+    };
+    styled
+        .into_iter()
+        .filter(|(_, style)| *style != majority)
+        .map(|(node, style)| {
+            finding(
+                Rule::InconsistentNaming,
+                node.position,
+                format!(
+                    "'{}' is {} but most IDs in this diagram are {}",
+                    node.id,
+                    style.name(),
+                    majority.name()
+                ),
+            )
+        })
+        .collect()
+}
+
+pub(super) fn self_loops(graph: &Graph) -> Vec<Finding> {
+    graph
+        .edges
+        .iter()
+        .filter(|edge| edge.from == edge.to)
+        .map(|edge| {
+            finding(
+                Rule::SelfLoop,
+                edge.position,
+                format!("'{}' links to itself", edge.from),
+            )
+        })
+        .collect()
+}
+
+/// Reported on the declaration line, since the whole diagram is at fault
+pub(super) fn too_many_nodes(graph: &Graph, max: usize, start_line: usize) -> Vec<Finding> {
+    if graph.nodes.len() <= max {
+        return Vec::new();
+    }
+    vec![finding(
+        Rule::MaxNodes,
+        Position {
+            line: start_line,
+            column: 1,
+        },
+        format!(
+            "Diagram has {} nodes, more than the maximum of {}",
+            graph.nodes.len(),
+            max
+        ),
+    )]
+}
@@ -0,0 +1,788 @@
+use super::source::{self, Line};
+use super::{AstResult, DiagnosticCode, Position, SyntaxError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParticipantKind {
+    Participant,
+    Actor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Participant {
+    pub id: String,
+    pub alias: Option<String>,
+    pub kind: ParticipantKind,
+    /// False when the participant only appears implicitly through a message
+    pub declared: bool,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageArrow {
+    Solid,
+    Dotted,
+    SolidArrow,
+    DottedArrow,
+    SolidCross,
+    DottedCross,
+    SolidOpen,
+    DottedOpen,
+    BidirectionalSolid,
+    BidirectionalDotted,
+}
+
+/// Arrow syntax and kind, longest first so `-->>` wins over `-->` and `->`
+const ARROWS: &[(&str, MessageArrow)] = &[
+    ("<<-->>", MessageArrow::BidirectionalDotted),
+    ("<<->>", MessageArrow::BidirectionalSolid),
+    ("-->>", MessageArrow::DottedArrow),
+    ("->>", MessageArrow::SolidArrow),
+    ("--x", MessageArrow::DottedCross),
+    ("-x", MessageArrow::SolidCross),
+    ("--)", MessageArrow::DottedOpen),
+    ("-)", MessageArrow::SolidOpen),
+    ("-->", MessageArrow::Dotted),
+    ("->", MessageArrow::Solid),
+];
+
+impl MessageArrow {
+    /// Mermaid syntax for the arrow, e.g. `->>`
+    pub fn syntax(self) -> &'static str {
+        ARROWS
+            .iter()
+            .find(|(_, arrow)| *arrow == self)
+            .map(|(syntax, _)| *syntax)
+            .expect("every arrow has a syntax")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub from: String,
+    pub to: String,
+    pub arrow: MessageArrow,
+    pub text: String,
+    /// `+` shorthand: activates the receiver
+    pub activate_target: bool,
+    /// `-` shorthand: deactivates the sender
+    pub deactivate_source: bool,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activation {
+    pub participant: String,
+    pub active: bool,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotePlacement {
+    LeftOf,
+    RightOf,
+    Over,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub placement: NotePlacement,
+    pub participants: Vec<String>,
+    pub text: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockKind {
+    Loop,
+    Alt,
+    Opt,
+    Par,
+    Critical,
+    Break,
+    Rect,
+    Box,
+}
+
+impl BlockKind {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "loop" => Some(BlockKind::Loop),
+            "alt" => Some(BlockKind::Alt),
+            "opt" => Some(BlockKind::Opt),
+            "par" => Some(BlockKind::Par),
+            "critical" => Some(BlockKind::Critical),
+            "break" => Some(BlockKind::Break),
+            "rect" => Some(BlockKind::Rect),
+            "box" => Some(BlockKind::Box),
+            _ => None,
+        }
+    }
+
+    /// Keyword that starts another branch of this block, like `else` in `alt`
+    pub(crate) fn branch_keyword(&self) -> Option<&'static str> {
+        match self {
+            BlockKind::Alt => Some("else"),
+            BlockKind::Par => Some("and"),
+            BlockKind::Critical => Some("option"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockBranch {
+    pub label: String,
+    pub statements: Vec<SequenceStatement>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub branches: Vec<BlockBranch>,
+    pub position: Position,
+    pub end: Option<Position>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SequenceStatement {
+    Message(Message),
+    Activation(Activation),
+    Note(Note),
+    Block(Block),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SequenceAst {
+    pub title: Option<String>,
+    pub autonumber: bool,
+    pub participants: Vec<Participant>,
+    pub statements: Vec<SequenceStatement>,
+}
+
+impl SequenceAst {
+    pub fn participant(&self, id: &str) -> Option<&Participant> {
+        self.participants
+            .iter()
+            .find(|participant| participant.id == id)
+    }
+
+    /// All messages in document order, including those nested inside blocks
+    pub fn messages(&self) -> Vec<&Message> {
+        fn collect<'a>(statements: &'a [SequenceStatement], out: &mut Vec<&'a Message>) {
+            for statement in statements {
+                match statement {
+                    SequenceStatement::Message(message) => out.push(message),
+                    SequenceStatement::Block(block) => {
+                        for branch in &block.branches {
+                            collect(&branch.statements, out);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut messages = Vec::new();
+        collect(&self.statements, &mut messages);
+        messages
+    }
+}
+
+struct Parser {
+    ast: SequenceAst,
+    errors: Vec<SyntaxError>,
+    open_blocks: Vec<Block>,
+    active: HashMap<String, usize>,
+    /// Inside a multi-line `accDescr { ... }`
+    in_description: bool,
+}
+
+/// Parse a `sequenceDiagram` into a typed AST.
+///
+/// Messages declare participants implicitly, as in Mermaid. Activations, notes and
+/// `destroy` must refer to a known participant, and using a display alias in place
+/// of its participant id is reported.
+pub fn parse(content: &str, start_line: usize) -> AstResult<SequenceAst> {
+    let mut parser = Parser {
+        ast: SequenceAst::default(),
+        errors: Vec::new(),
+        open_blocks: Vec::new(),
+        active: HashMap::new(),
+        in_description: false,
+    };
+
+    let mut lines = source::lines(content, start_line);
+
+    match lines.next() {
+        Some(line) if line.text == "sequenceDiagram" => {}
+        Some(line) => {
+            parser.error(
+                DiagnosticCode::InvalidDeclaration,
+                line.position_at(0),
+                format!(
+                    "Expected 'sequenceDiagram' declaration, found '{}'",
+                    line.text
+                ),
+            );
+            return parser.finish();
+        }
+        None => return parser.finish(),
+    }
+
+    for line in lines {
+        parser.parse_line(&line);
+    }
+    parser.finish()
+}
+
+impl Parser {
+    fn error(&mut self, code: DiagnosticCode, position: Position, message: impl Into<String>) {
+        self.errors.push(SyntaxError::new(
+            code,
+            position.line,
+            position.column,
+            message,
+        ));
+    }
+
+    fn finish(mut self) -> AstResult<SequenceAst> {
+        while let Some(block) = self.open_blocks.pop() {
+            self.error(
+                DiagnosticCode::UnclosedBlock,
+                block.position,
+                format!(
+                    "Unclosed '{}' block: missing 'end'",
+                    block_keyword(block.kind)
+                ),
+            );
+            self.push_statement(SequenceStatement::Block(block));
+        }
+        AstResult {
+            ast: self.ast,
+            errors: self.errors,
+        }
+    }
+
+    fn push_statement(&mut self, statement: SequenceStatement) {
+        match self.open_blocks.last_mut() {
+            Some(block) => block
+                .branches
+                .last_mut()
+                .expect("blocks always have a branch")
+                .statements
+                .push(statement),
+            None => self.ast.statements.push(statement),
+        }
+    }
+
+    fn parse_line(&mut self, line: &Line) {
+        if self.in_description {
+            self.in_description = !line.text.contains('}');
+            return;
+        }
+
+        let (keyword, rest) = match line.text.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim()),
+            None => (line.text, ""),
+        };
+        let keyword_lower = keyword.to_lowercase();
+
+        match keyword_lower.as_str() {
+            "participant" | "actor" => {
+                let kind = if keyword_lower == "actor" {
+                    ParticipantKind::Actor
+                } else {
+                    ParticipantKind::Participant
+                };
+                self.parse_participant(line, rest, kind);
+            }
+            "create" => match rest.split_once(char::is_whitespace) {
+                Some((kind @ ("participant" | "actor"), declaration)) => {
+                    let kind = if kind == "actor" {
+                        ParticipantKind::Actor
+                    } else {
+                        ParticipantKind::Participant
+                    };
+                    self.parse_participant(line, declaration.trim(), kind);
+                }
+                _ => self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line.position_at(0),
+                    "Expected 'create participant <id>' or 'create actor <id>'",
+                ),
+            },
+            "destroy" => {
+                self.resolve(line, rest);
+            }
+            "activate" | "deactivate" => {
+                self.parse_activation(line, rest, keyword_lower == "activate")
+            }
+            "note" => self.parse_note(line, rest),
+            "end" => match self.open_blocks.pop() {
+                Some(mut block) => {
+                    block.end = Some(line.position_at(0));
+                    self.push_statement(SequenceStatement::Block(block));
+                }
+                None => self.error(
+                    DiagnosticCode::UnmatchedEnd,
+                    line.position_at(0),
+                    "'end' without a matching block",
+                ),
+            },
+            "else" | "and" | "option" => self.parse_branch(line, &keyword_lower, rest),
+            "autonumber" => self.ast.autonumber = true,
+            "title" => self.ast.title = Some(rest.trim_start_matches(':').trim().to_string()),
+            _ if keyword_lower.starts_with("title:") => {
+                self.ast.title = Some(line.text["title:".len()..].trim().to_string())
+            }
+            "accdescr" if rest.starts_with('{') => self.in_description = !rest.contains('}'),
+            _ if [
+                "acctitle",
+                "accdescr",
+                "link",
+                "links",
+                "properties",
+                "details",
+            ]
+            .iter()
+            .any(|k| keyword_lower.trim_end_matches(':') == *k) => {}
+            _ => match BlockKind::from_keyword(&keyword_lower) {
+                Some(kind) => {
+                    if kind == BlockKind::Rect && rest.is_empty() {
+                        self.error(
+                            DiagnosticCode::IncompleteStatement,
+                            line.position_at(0),
+                            "Expected a color after 'rect'",
+                        );
+                    }
+                    self.open_blocks.push(Block {
+                        kind,
+                        branches: vec![BlockBranch {
+                            label: rest.to_string(),
+                            statements: Vec::new(),
+                            position: line.position_at(0),
+                        }],
+                        position: line.position_at(0),
+                        end: None,
+                    });
+                }
+                None => self.parse_message(line),
+            },
+        }
+    }
+
+    fn parse_participant(&mut self, line: &Line, declaration: &str, kind: ParticipantKind) {
+        let (id, alias) = match declaration.split_once(" as ") {
+            Some((id, alias)) => (id.trim(), Some(alias.trim().to_string())),
+            None => (declaration.trim(), None),
+        };
+        if id.is_empty() {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(line.text.len()),
+                "Expected a participant id",
+            );
+            return;
+        }
+
+        let position = line.position_of(id);
+        match self.ast.participants.iter_mut().find(|p| p.id == id) {
+            Some(existing) if existing.declared => {
+                self.error(
+                    DiagnosticCode::DuplicateParticipant,
+                    position,
+                    format!("Participant '{}' is already declared", id),
+                );
+            }
+            Some(existing) => {
+                existing.declared = true;
+                existing.alias = alias;
+                existing.kind = kind;
+            }
+            None => self.ast.participants.push(Participant {
+                id: id.to_string(),
+                alias,
+                kind,
+                declared: true,
+                position,
+            }),
+        }
+    }
+
+    /// Look up a participant reference, reporting unknown ids and misused aliases
+    fn resolve(&mut self, line: &Line, name: &str) -> Option<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(line.text.len()),
+                "Expected a participant id",
+            );
+            return None;
+        }
+        let position = line.position_of(name);
+        if self.ast.participant(name).is_some() {
+            return Some(name.to_string());
+        }
+        match self.alias_owner(name) {
+            Some(id) => self.error(
+                DiagnosticCode::AliasReference,
+                position,
+                format!(
+                    "'{}' is the alias of participant '{}'; refer to it as '{}'",
+                    name, id, id
+                ),
+            ),
+            None => self.error(
+                DiagnosticCode::UndeclaredParticipant,
+                position,
+                format!("Undeclared participant '{}'", name),
+            ),
+        }
+        None
+    }
+
+    fn alias_owner(&self, name: &str) -> Option<String> {
+        self.ast
+            .participants
+            .iter()
+            .find(|p| p.alias.as_deref() == Some(name))
+            .map(|p| p.id.clone())
+    }
+
+    /// Messages may introduce participants; aliases still have to be spelled as ids
+    fn resolve_or_declare(&mut self, line: &Line, name: &str) -> Option<String> {
+        if self.ast.participant(name).is_none() && self.alias_owner(name).is_none() {
+            self.ast.participants.push(Participant {
+                id: name.to_string(),
+                alias: None,
+                kind: ParticipantKind::Participant,
+                declared: false,
+                position: line.position_of(name),
+            });
+        }
+        self.resolve(line, name)
+    }
+
+    fn parse_activation(&mut self, line: &Line, rest: &str, activate: bool) {
+        let Some(participant) = self.resolve(line, rest) else {
+            return;
+        };
+        let position = line.position_of(rest);
+        if activate {
+            *self.active.entry(participant.clone()).or_default() += 1;
+        } else if !self.deactivate(&participant) {
+            self.error(
+                DiagnosticCode::InactiveParticipant,
+                position,
+                format!("Cannot deactivate '{}': it is not active", participant),
+            );
+        }
+        self.push_statement(SequenceStatement::Activation(Activation {
+            participant,
+            active: activate,
+            position: line.position_at(0),
+        }));
+    }
+
+    fn deactivate(&mut self, participant: &str) -> bool {
+        match self.active.get_mut(participant) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_note(&mut self, line: &Line, rest: &str) {
+        let Some((target, text)) = rest.split_once(':') else {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(0),
+                "Expected ':' followed by note text",
+            );
+            return;
+        };
+        let target = target.trim();
+        let lower = target.to_lowercase();
+        let (placement, names) = if lower.starts_with("left of ") {
+            (NotePlacement::LeftOf, &target["left of ".len()..])
+        } else if lower.starts_with("right of ") {
+            (NotePlacement::RightOf, &target["right of ".len()..])
+        } else if lower.starts_with("over ") {
+            (NotePlacement::Over, &target["over ".len()..])
+        } else {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_of(target),
+                "Expected 'left of', 'right of' or 'over' after 'Note'",
+            );
+            return;
+        };
+
+        let mut participants = Vec::new();
+        for name in names.split(',') {
+            match self.resolve(line, name) {
+                Some(id) => participants.push(id),
+                None => return,
+            }
+        }
+        if placement != NotePlacement::Over && participants.len() > 1 {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line.position_of(names.trim()),
+                "Only 'Note over' can span several participants",
+            );
+        }
+
+        self.push_statement(SequenceStatement::Note(Note {
+            placement,
+            participants,
+            text: text.trim().to_string(),
+            position: line.position_at(0),
+        }));
+    }
+
+    fn parse_branch(&mut self, line: &Line, keyword: &str, label: &str) {
+        let position = line.position_at(0);
+        match self.open_blocks.last_mut() {
+            Some(block) if block.kind.branch_keyword() == Some(keyword) => {
+                block.branches.push(BlockBranch {
+                    label: label.to_string(),
+                    statements: Vec::new(),
+                    position,
+                });
+            }
+            Some(block) => {
+                let message = format!(
+                    "'{}' is not allowed inside a '{}' block",
+                    keyword,
+                    block_keyword(block.kind)
+                );
+                self.error(DiagnosticCode::MisplacedBranch, position, message);
+            }
+            None => {
+                let parent = match keyword {
+                    "else" => "alt",
+                    "and" => "par",
+                    _ => "critical",
+                };
+                self.error(
+                    DiagnosticCode::MisplacedBranch,
+                    position,
+                    format!("'{}' outside of a '{}' block", keyword, parent),
+                );
+            }
+        }
+    }
+
+    fn parse_message(&mut self, line: &Line) {
+        let text = line.text;
+        let Some((offset, syntax, arrow)) = find_arrow(text) else {
+            self.error(
+                DiagnosticCode::UnrecognizedStatement,
+                line.position_at(0),
+                format!("Unrecognized statement '{}'", text),
+            );
+            return;
+        };
+
+        let from = text[..offset].trim();
+        let mut rest = &text[offset + syntax.len()..];
+        let mut activate_target = false;
+        let mut deactivate_source = false;
+        let marker_offset = offset + syntax.len();
+        match rest.chars().next() {
+            Some('+') => {
+                activate_target = true;
+                rest = &rest[1..];
+            }
+            Some('-') => {
+                deactivate_source = true;
+                rest = &rest[1..];
+            }
+            _ => {}
+        }
+
+        let Some((to, message)) = rest.split_once(':') else {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(text.len()),
+                "Expected ':' followed by message text",
+            );
+            return;
+        };
+        let to = to.trim();
+        if from.is_empty() {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(0),
+                "Expected a sender before the arrow",
+            );
+            return;
+        }
+        if to.is_empty() {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(marker_offset),
+                "Expected a receiver after the arrow",
+            );
+            return;
+        }
+
+        let (Some(from), Some(to)) = (
+            self.resolve_or_declare(line, from),
+            self.resolve_or_declare(line, to),
+        ) else {
+            return;
+        };
+
+        if activate_target {
+            *self.active.entry(to.clone()).or_default() += 1;
+        }
+        if deactivate_source && !self.deactivate(&from) {
+            self.error(
+                DiagnosticCode::InactiveParticipant,
+                line.position_at(marker_offset),
+                format!("Cannot deactivate '{}': it is not active", from),
+            );
+        }
+
+        self.push_statement(SequenceStatement::Message(Message {
+            from,
+            to,
+            arrow,
+            text: message.trim().to_string(),
+            activate_target,
+            deactivate_source,
+            position: line.position_at(0),
+        }));
+    }
+}
+
+fn find_arrow(text: &str) -> Option<(usize, &'static str, MessageArrow)> {
+    let colon = text.find(':').unwrap_or(text.len());
+    (0..colon)
+        .filter(|&offset| text.is_char_boundary(offset))
+        .find_map(|offset| {
+            ARROWS
+                .iter()
+                .find(|(syntax, _)| text[offset..].starts_with(syntax))
+                .map(|&(syntax, arrow)| (offset, syntax, arrow))
+        })
+}
+
+pub(crate) fn block_keyword(kind: BlockKind) -> &'static str {
+    match kind {
+        BlockKind::Loop => "loop",
+        BlockKind::Alt => "alt",
+        BlockKind::Opt => "opt",
+        BlockKind::Par => "par",
+        BlockKind::Critical => "critical",
+        BlockKind::Break => "break",
+        BlockKind::Rect => "rect",
+        BlockKind::Box => "box",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_participants_and_messages() {
+        let content = "sequenceDiagram\n    participant A as Alice\n    actor B\n    A->>+B: Hello\n    B-->>-A: Hi\n    A-)C: Async";
+        let result = parse(content, 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let ast = &result.ast;
+        assert_eq!(ast.participants.len(), 3);
+        assert_eq!(
+            ast.participant("A").unwrap().alias.as_deref(),
+            Some("Alice")
+        );
+        assert_eq!(ast.participant("B").unwrap().kind, ParticipantKind::Actor);
+        assert!(!ast.participant("C").unwrap().declared);
+
+        let messages = ast.messages();
+        assert_eq!(messages[0].arrow, MessageArrow::SolidArrow);
+        assert!(messages[0].activate_target);
+        assert_eq!(messages[1].arrow, MessageArrow::DottedArrow);
+        assert!(messages[1].deactivate_source);
+        assert_eq!(messages[2].arrow, MessageArrow::SolidOpen);
+    }
+
+    #[test]
+    fn test_parse_blocks_and_notes() {
+        let content = "sequenceDiagram\n  A->>B: ping\n  alt ok\n    B->>A: pong\n  else failed\n    Note over A,B: retry\n  end\n  loop every minute\n    A->>B: ping\n  end";
+        let result = parse(content, 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        let SequenceStatement::Block(alt) = &result.ast.statements[1] else {
+            panic!("expected a block");
+        };
+        assert_eq!(alt.kind, BlockKind::Alt);
+        assert_eq!(alt.branches.len(), 2);
+        assert_eq!(alt.branches[1].label, "failed");
+        assert!(matches!(
+            alt.branches[1].statements[0],
+            SequenceStatement::Note(_)
+        ));
+        assert_eq!(result.ast.messages().len(), 3);
+    }
+
+    #[test]
+    fn test_block_and_activation_errors() {
+        let result = parse(
+            "sequenceDiagram\n  loop forever\n    A->>B: hi\n  end\n  end",
+            1,
+        );
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!((result.errors[0].line, result.errors[0].column), (5, 3));
+        assert_eq!(result.errors[0].code, DiagnosticCode::UnmatchedEnd);
+
+        let unclosed = parse("sequenceDiagram\n  opt maybe\n    A->>B: hi", 1);
+        assert_eq!(
+            unclosed.errors[0].message,
+            "Unclosed 'opt' block: missing 'end'"
+        );
+
+        let inactive = parse("sequenceDiagram\n  A->>B: hi\n  deactivate B", 1);
+        assert_eq!(inactive.errors.len(), 1);
+        assert_eq!(inactive.errors[0].code, DiagnosticCode::InactiveParticipant);
+        assert_eq!(
+            (inactive.errors[0].line, inactive.errors[0].column),
+            (3, 14)
+        );
+
+        let shorthand = parse("sequenceDiagram\n  A->>-B: hi", 1);
+        assert_eq!(shorthand.errors[0].column, 7);
+
+        for keyword in ["activate", "deactivate", "destroy"] {
+            let bare = parse(&format!("sequenceDiagram\n  {}", keyword), 1);
+            assert_eq!(bare.errors.len(), 1);
+            assert_eq!(bare.errors[0].code, DiagnosticCode::IncompleteStatement);
+            assert_eq!(
+                (bare.errors[0].line, bare.errors[0].column),
+                (2, keyword.len() + 3)
+            );
+        }
+    }
+
+    #[test]
+    fn test_undeclared_references() {
+        let alias = parse(
+            "sequenceDiagram\n  participant A as Alice\n  Alice->>B: hi",
+            1,
+        );
+        assert_eq!(alias.errors.len(), 1);
+        assert_eq!((alias.errors[0].line, alias.errors[0].column), (3, 3));
+
+        let note = parse("sequenceDiagram\n  A->>B: hi\n  Note right of C: who?", 1);
+        assert_eq!(note.errors[0].message, "Undeclared participant 'C'");
+        assert_eq!(note.errors[0].column, 17);
+    }
+}
@@ -0,0 +1,293 @@
+use super::{
+    identity, ContentFormat, DiagramBlock, MermaidParser, ParseResult, ParsedDiagram, Position,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+/// Replace the text between two 1-based positions, as reported by the editor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub start: Position,
+    pub end: Position,
+    pub text: String,
+}
+
+/// New line range of a diagram whose content did not change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagramMove {
+    pub id: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// How the document's diagrams changed after a batch of edits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagramDelta {
+    pub version: u64,
+    pub added: Vec<ParsedDiagram>,
+    pub changed: Vec<ParsedDiagram>,
+    pub removed: Vec<String>,
+    pub moved: Vec<DiagramMove>,
+    /// IDs of all current diagrams in document order
+    pub order: Vec<String>,
+    pub total_errors: usize,
+    pub parsing_time_ms: u128,
+}
+
+/// A document kept in memory so edits only re-validate the diagrams they touch
+pub struct DocumentSession {
+    content: String,
+    format: ContentFormat,
+    version: u64,
+    diagrams: Vec<ParsedDiagram>,
+    error_counts: Vec<usize>,
+}
+
+impl DocumentSession {
+    /// Parse the whole document once and keep the result for later edits
+    pub fn open(
+        parser: &MermaidParser,
+        content: String,
+        format: ContentFormat,
+    ) -> (Self, ParseResult) {
+        let start_time = Instant::now();
+        let mut seen_ids = HashMap::new();
+        let (diagrams, error_counts): (Vec<_>, Vec<_>) = parser
+            .blocks(&content, format)
+            .into_iter()
+            .map(|block| parser.build_diagram(block, &mut seen_ids))
+            .unzip();
+
+        let result = ParseResult {
+            diagrams: diagrams.clone(),
+            total_errors: error_counts.iter().sum(),
+            parsing_time_ms: start_time.elapsed().as_millis(),
+        };
+        let session = DocumentSession {
+            content,
+            format,
+            version: 0,
+            diagrams,
+            error_counts,
+        };
+        (session, result)
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn diagrams(&self) -> &[ParsedDiagram] {
+        &self.diagrams
+    }
+
+    pub fn format(&self) -> ContentFormat {
+        self.format
+    }
+
+    /// Apply the edits in order and re-validate only diagrams whose content changed
+    pub fn apply_edits(&mut self, parser: &MermaidParser, edits: &[TextEdit]) -> DiagramDelta {
+        let start_time = Instant::now();
+        apply_text_edits(&mut self.content, edits);
+        self.version += 1;
+
+        let previous = std::mem::take(&mut self.diagrams);
+        let previous_counts = std::mem::take(&mut self.error_counts);
+        let mut seen_ids = HashMap::new();
+        let (mut current, error_counts): (Vec<_>, Vec<_>) = parser
+            .blocks(&self.content, self.format)
+            .into_iter()
+            .map(|block| match reusable(&previous, &block) {
+                Some(index) => {
+                    let mut diagram = previous[index].clone();
+                    diagram.name = identity::explicit_name(&block.content, block.fence_info());
+                    diagram.fence = block.fence;
+                    diagram.id = identity::base_id(
+                        diagram.name.as_deref(),
+                        diagram.diagram_type,
+                        &diagram.fingerprint,
+                        &mut seen_ids,
+                    );
+                    diagram.start_line = block.start_line;
+                    diagram.end_line = block.end_line;
+                    (diagram, previous_counts[index])
+                }
+                None => parser.build_diagram(block, &mut seen_ids),
+            })
+            .unzip();
+        identity::reconcile(&previous, &mut current);
+
+        let delta = diff(&previous, &current, self.version, &error_counts, start_time);
+        self.diagrams = current;
+        self.error_counts = error_counts;
+        delta
+    }
+}
+
+/// Apply edits in order, each relative to the text after the previous one
+pub fn apply_text_edits(content: &mut String, edits: &[TextEdit]) {
+    for edit in edits {
+        let start = offset_of(content, edit.start);
+        let end = offset_of(content, edit.end).max(start);
+        content.replace_range(start..end, &edit.text);
+    }
+}
+
+/// A previous diagram with identical content can be reused without re-validating,
+/// unless its errors mention line numbers that have since moved
+fn reusable(previous: &[ParsedDiagram], block: &DiagramBlock) -> Option<usize> {
+    previous.iter().position(|diagram| {
+        diagram.content == block.content
+            && (diagram.start_line == block.start_line || !diagram.has_error)
+    })
+}
+
+fn diff(
+    previous: &[ParsedDiagram],
+    current: &[ParsedDiagram],
+    version: u64,
+    error_counts: &[usize],
+    start_time: Instant,
+) -> DiagramDelta {
+    let old_by_id: HashMap<&str, &ParsedDiagram> =
+        previous.iter().map(|d| (d.id.as_str(), d)).collect();
+    let current_ids: HashSet<&str> = current.iter().map(|d| d.id.as_str()).collect();
+
+    let mut delta = DiagramDelta {
+        version,
+        added: Vec::new(),
+        changed: Vec::new(),
+        removed: previous
+            .iter()
+            .filter(|d| !current_ids.contains(d.id.as_str()))
+            .map(|d| d.id.clone())
+            .collect(),
+        moved: Vec::new(),
+        order: current.iter().map(|d| d.id.clone()).collect(),
+        total_errors: error_counts.iter().sum(),
+        parsing_time_ms: 0,
+    };
+
+    for diagram in current {
+        match old_by_id.get(diagram.id.as_str()) {
+            None => delta.added.push(diagram.clone()),
+            Some(old)
+                if old.content != diagram.content
+                    || old.name != diagram.name
+                    || old.diagram_type != diagram.diagram_type
+                    || old.has_error != diagram.has_error
+                    || old.error_message != diagram.error_message =>
+            {
+                delta.changed.push(diagram.clone())
+            }
+            Some(old)
+                if (old.start_line, old.end_line) != (diagram.start_line, diagram.end_line) =>
+            {
+                delta.moved.push(DiagramMove {
+                    id: diagram.id.clone(),
+                    start_line: diagram.start_line,
+                    end_line: diagram.end_line,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    delta.parsing_time_ms = start_time.elapsed().as_millis();
+    delta
+}
+
+/// Byte offset of a 1-based line and character column, clamped to the document
+fn offset_of(content: &str, position: Position) -> usize {
+    let mut offset = 0;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        if index + 1 == position.line.max(1) {
+            let text = line.trim_end_matches(['\r', '\n']);
+            let column = text
+                .char_indices()
+                .nth(position.column.saturating_sub(1))
+                .map_or(text.len(), |(byte, _)| byte);
+            return offset + column;
+        }
+        offset += line.len();
+    }
+    content.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mermaid_parser::DiagramType;
+
+    const DOCUMENT: &str = "# Design\n\n```mermaid\ngraph TD\n  A --> B\n```\n\n```mermaid\nsequenceDiagram\n  A->>B: hi\n```\n";
+
+    fn edit(line: usize, column: usize, end_column: usize, text: &str) -> TextEdit {
+        TextEdit {
+            start: Position { line, column },
+            end: Position {
+                line,
+                column: end_column,
+            },
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_edit_inside_one_diagram() {
+        let parser = MermaidParser::new().unwrap();
+        let (mut session, first) =
+            DocumentSession::open(&parser, DOCUMENT.to_string(), ContentFormat::Markdown);
+
+        let delta = session.apply_edits(&parser, &[edit(5, 9, 10, "C")]);
+        assert_eq!(delta.version, 1);
+        assert!(delta.added.is_empty() && delta.removed.is_empty() && delta.moved.is_empty());
+        assert_eq!(delta.changed.len(), 1);
+        assert_eq!(delta.changed[0].id, first.diagrams[0].id);
+        assert_eq!(delta.changed[0].content, "graph TD\n  A --> C");
+        assert_eq!(
+            delta.order,
+            vec![first.diagrams[0].id.clone(), first.diagrams[1].id.clone()]
+        );
+
+        let full = parser.parse_content_as(session.content(), ContentFormat::Markdown);
+        assert_eq!(full.diagrams[0].content, delta.changed[0].content);
+    }
+
+    #[test]
+    fn test_moved_added_and_removed_diagrams() {
+        let parser = MermaidParser::new().unwrap();
+        let (mut session, first) =
+            DocumentSession::open(&parser, DOCUMENT.to_string(), ContentFormat::Markdown);
+
+        let delta = session.apply_edits(
+            &parser,
+            &[edit(1, 1, 1, "```mermaid\npie\n  \"a\" : 1\n```\n")],
+        );
+        assert_eq!(delta.added.len(), 1);
+        assert_eq!(delta.added[0].diagram_type, DiagramType::Pie);
+        assert!(delta.changed.is_empty());
+        assert_eq!(delta.moved.len(), 2);
+        assert_eq!(delta.moved[0].id, first.diagrams[0].id);
+        assert_eq!(delta.moved[0].start_line, 8);
+
+        // Deleting the sequence diagram's fence lines leaves plain Markdown
+        let delta = session.apply_edits(
+            &parser,
+            &[TextEdit {
+                start: Position {
+                    line: 11,
+                    column: 1,
+                },
+                end: Position {
+                    line: 16,
+                    column: 1,
+                },
+                text: String::new(),
+            }],
+        );
+        assert_eq!(delta.removed, vec![first.diagrams[1].id.clone()]);
+        assert_eq!(delta.order.len(), 2);
+    }
+}
@@ -0,0 +1,49 @@
+use super::comments;
+use super::config::Preamble;
+use super::Position;
+
+/// One non-blank, non-comment source line with its absolute line number
+pub(crate) struct Line<'a> {
+    /// Trimmed text, without a trailing `;`
+    pub text: &'a str,
+    pub number: usize,
+    pub indent: usize,
+}
+
+impl Line<'_> {
+    /// Position of a byte offset into the trimmed text
+    pub fn position_at(&self, offset: usize) -> Position {
+        Position {
+            line: self.number,
+            column: self.indent + self.text[..offset].chars().count() + 1,
+        }
+    }
+
+    /// Position of `part`, a subslice of the trimmed text; anything else, such as an
+    /// empty literal, falls back to the end of the line
+    pub fn position_of(&self, part: &str) -> Position {
+        let offset = (part.as_ptr() as usize).wrapping_sub(self.text.as_ptr() as usize);
+        let inside = part.len() <= self.text.len() && offset <= self.text.len() - part.len();
+        if inside && self.text.is_char_boundary(offset) {
+            self.position_at(offset)
+        } else {
+            self.position_at(self.text.len())
+        }
+    }
+}
+
+/// Iterate the statement lines of a line-oriented diagram, without `%%` comments and
+/// skipping blank lines, frontmatter and directives
+pub(crate) fn lines(content: &str, start_line: usize) -> impl Iterator<Item = Line<'_>> {
+    let preamble = Preamble::scan(content);
+    content.lines().enumerate().filter_map(move |(index, raw)| {
+        let raw = comments::code(raw);
+        let text = raw.trim();
+        let statement = !text.is_empty() && !preamble.contains(index);
+        statement.then(|| Line {
+            text: text.trim_end_matches(';').trim_end(),
+            number: start_line + index,
+            indent: raw.chars().count() - raw.trim_start().chars().count(),
+        })
+    })
+}
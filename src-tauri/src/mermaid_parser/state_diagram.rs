@@ -0,0 +1,458 @@
+use super::flowchart::Direction;
+use super::source::{self, Line};
+use super::{AstResult, DiagnosticCode, Position, SyntaxError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+static STATE_DECLARATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^state\s+(?:"(?P<label>[^"]*)"\s+as\s+(?P<alias>\w+)|(?P<id>\w+)(?:\s*:\s*(?P<description>[^{]+?))?)\s*(?:<<(?P<stereotype>\w+)>>)?\s*(?P<body>\{)?$"#,
+    )
+    .unwrap()
+});
+
+static TRANSITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?P<from>\[\*\]|\w+)(?::::\w+)?\s*-->\s*(?P<to>\[\*\]|\w+)(?::::\w+)?\s*(?::\s*(?P<label>.*))?$",
+    )
+    .unwrap()
+});
+
+static DESCRIPTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?P<id>\w+)\s*:\s*(?P<text>.+)$").unwrap());
+
+static BARE_STATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?P<id>\w+)(?::::\w+)?$").unwrap());
+
+static NOTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^note\s+(?P<side>left|right)\s+of\s+(?P<state>\w+)\s*(?::\s*(?P<text>.*))?$")
+        .unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StateKind {
+    Simple,
+    Start,
+    End,
+    Choice,
+    Fork,
+    Join,
+    Composite,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateNode {
+    /// Start and end pseudo-states get scoped IDs such as `[*]start` or `Active/[*]end`
+    pub id: String,
+    pub label: Option<String>,
+    pub descriptions: Vec<String>,
+    pub kind: StateKind,
+    /// Enclosing composite state
+    pub parent: Option<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    pub label: Option<String>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteSide {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateNote {
+    pub state: String,
+    pub side: NoteSide,
+    pub text: String,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StateDiagramAst {
+    pub direction: Option<Direction>,
+    pub states: Vec<StateNode>,
+    pub transitions: Vec<Transition>,
+    pub notes: Vec<StateNote>,
+}
+
+impl StateDiagramAst {
+    #[cfg(test)]
+    pub fn state(&self, id: &str) -> Option<&StateNode> {
+        self.states.iter().find(|state| state.id == id)
+    }
+
+    /// States directly inside `parent`, or at the top level for `None`
+    pub fn children_of(&self, parent: Option<&str>) -> Vec<&StateNode> {
+        self.states
+            .iter()
+            .filter(|state| state.parent.as_deref() == parent)
+            .collect()
+    }
+}
+
+struct Parser {
+    ast: StateDiagramAst,
+    errors: Vec<SyntaxError>,
+    composites: Vec<(String, Position)>,
+    /// A multi-line note waiting for `end note`
+    open_note: Option<StateNote>,
+}
+
+/// Parse a `stateDiagram` / `stateDiagram-v2` into states, transitions and notes.
+///
+/// Each composite state gets its own `[*]` start and end pseudo-states.
+pub fn parse(content: &str, start_line: usize) -> AstResult<StateDiagramAst> {
+    let mut parser = Parser {
+        ast: StateDiagramAst::default(),
+        errors: Vec::new(),
+        composites: Vec::new(),
+        open_note: None,
+    };
+
+    let mut lines = source::lines(content, start_line);
+    match lines.next() {
+        Some(line) if line.text == "stateDiagram" || line.text == "stateDiagram-v2" => {}
+        Some(line) => {
+            parser.error(
+                DiagnosticCode::InvalidDeclaration,
+                line.position_at(0),
+                format!("Expected 'stateDiagram' declaration, found '{}'", line.text),
+            );
+            return parser.finish();
+        }
+        None => return parser.finish(),
+    }
+
+    for line in lines {
+        parser.parse_line(&line);
+    }
+    parser.finish()
+}
+
+impl Parser {
+    fn error(&mut self, code: DiagnosticCode, position: Position, message: impl Into<String>) {
+        self.errors.push(SyntaxError::new(
+            code,
+            position.line,
+            position.column,
+            message,
+        ));
+    }
+
+    fn finish(mut self) -> AstResult<StateDiagramAst> {
+        if let Some(note) = self.open_note.take() {
+            self.error(
+                DiagnosticCode::UnclosedBlock,
+                note.position,
+                "Unclosed note: missing 'end note'",
+            );
+        }
+        while let Some((name, position)) = self.composites.pop() {
+            self.error(
+                DiagnosticCode::UnclosedBlock,
+                position,
+                format!("Unclosed state '{}': missing '}}'", name),
+            );
+        }
+        AstResult {
+            ast: self.ast,
+            errors: self.errors,
+        }
+    }
+
+    fn current_parent(&self) -> Option<String> {
+        self.composites.last().map(|(name, _)| name.clone())
+    }
+
+    /// Find or implicitly create a state in the current composite
+    fn state_mut(&mut self, id: &str, kind: StateKind, position: Position) -> &mut StateNode {
+        let index = match self.ast.states.iter().position(|state| state.id == id) {
+            Some(index) => index,
+            None => {
+                let parent = self.current_parent();
+                self.ast.states.push(StateNode {
+                    id: id.to_string(),
+                    label: None,
+                    descriptions: Vec::new(),
+                    kind,
+                    parent,
+                    position,
+                });
+                self.ast.states.len() - 1
+            }
+        };
+        &mut self.ast.states[index]
+    }
+
+    /// Resolve a transition endpoint, turning `[*]` into the scope's start or end state
+    fn endpoint(&mut self, name: &str, is_source: bool, position: Position) -> String {
+        if name != "[*]" {
+            self.state_mut(name, StateKind::Simple, position);
+            return name.to_string();
+        }
+        let prefix = self
+            .current_parent()
+            .map(|parent| format!("{}/", parent))
+            .unwrap_or_default();
+        let (id, kind) = if is_source {
+            (format!("{}[*]start", prefix), StateKind::Start)
+        } else {
+            (format!("{}[*]end", prefix), StateKind::End)
+        };
+        self.state_mut(&id, kind, position);
+        id
+    }
+
+    fn parse_line(&mut self, line: &Line) {
+        let text = line.text;
+
+        if let Some(note) = &mut self.open_note {
+            if text == "end note" {
+                let note = self.open_note.take().unwrap();
+                self.ast.notes.push(note);
+            } else {
+                if !note.text.is_empty() {
+                    note.text.push('\n');
+                }
+                note.text.push_str(text);
+            }
+            return;
+        }
+
+        if text == "}" {
+            if self.composites.pop().is_none() {
+                self.error(
+                    DiagnosticCode::UnexpectedCloser,
+                    line.position_at(0),
+                    "'}' without a matching '{'",
+                );
+            }
+            return;
+        }
+        // Separator between concurrent regions of a composite state
+        if text == "--" {
+            return;
+        }
+
+        let (keyword, rest) = match text.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim()),
+            None => (text, ""),
+        };
+        match keyword {
+            "state" => self.parse_state_declaration(line),
+            "direction" => match Direction::from_keyword(rest) {
+                Some(direction) => self.ast.direction = Some(direction),
+                None if rest.is_empty() => self.error(
+                    DiagnosticCode::UnknownDirection,
+                    line.position_at(line.text.len()),
+                    "Expected a direction",
+                ),
+                None => self.error(
+                    DiagnosticCode::UnknownDirection,
+                    line.position_of(rest),
+                    format!("Unknown direction '{}'", rest),
+                ),
+            },
+            "note" => self.parse_note(line),
+            "classDef" | "class" | "style" | "hide" | "scale" | "title" => {}
+            _ if keyword.starts_with("accTitle") || keyword.starts_with("accDescr") => {}
+            _ => self.parse_transition_or_state(line),
+        }
+    }
+
+    fn parse_state_declaration(&mut self, line: &Line) {
+        let Some(captures) = STATE_DECLARATION.captures(line.text) else {
+            self.error(
+                DiagnosticCode::InvalidIdentifier,
+                line.position_at(0),
+                format!("Invalid state declaration '{}'", line.text),
+            );
+            return;
+        };
+
+        let name = captures
+            .name("alias")
+            .or_else(|| captures.name("id"))
+            .unwrap();
+        let id = name.as_str().to_string();
+        let position = line.position_of(name.as_str());
+        let kind = match captures.name("stereotype").map(|s| s.as_str()) {
+            None => StateKind::Simple,
+            Some("choice") => StateKind::Choice,
+            Some("fork") => StateKind::Fork,
+            Some("join") => StateKind::Join,
+            Some(other) => {
+                self.error(
+                    DiagnosticCode::InvalidValue,
+                    line.position_of(other),
+                    format!("Unknown state type '<<{}>>'", other),
+                );
+                StateKind::Simple
+            }
+        };
+        let is_composite = captures.name("body").is_some();
+
+        let state = self.state_mut(&id, kind, position);
+        state.position = position;
+        if is_composite {
+            state.kind = StateKind::Composite;
+        } else if kind != StateKind::Simple {
+            state.kind = kind;
+        }
+        if let Some(label) = captures.name("label") {
+            state.label = Some(label.as_str().to_string());
+        }
+        if let Some(description) = captures.name("description") {
+            state
+                .descriptions
+                .push(description.as_str().trim().to_string());
+        }
+
+        if is_composite {
+            self.composites.push((id, position));
+        }
+    }
+
+    fn parse_note(&mut self, line: &Line) {
+        let Some(captures) = NOTE.captures(line.text) else {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line.position_at(0),
+                "Expected 'note left of <state>' or 'note right of <state>'",
+            );
+            return;
+        };
+        let state = captures["state"].to_string();
+        self.state_mut(
+            &state,
+            StateKind::Simple,
+            line.position_of(&captures["state"]),
+        );
+        let note = StateNote {
+            state,
+            side: if &captures["side"] == "left" {
+                NoteSide::Left
+            } else {
+                NoteSide::Right
+            },
+            text: captures
+                .name("text")
+                .map(|text| text.as_str().trim().to_string())
+                .unwrap_or_default(),
+            position: line.position_at(0),
+        };
+        if captures.name("text").is_some() {
+            self.ast.notes.push(note);
+        } else {
+            self.open_note = Some(note);
+        }
+    }
+
+    fn parse_transition_or_state(&mut self, line: &Line) {
+        let text = line.text;
+
+        if let Some(captures) = TRANSITION.captures(text) {
+            let position = line.position_at(0);
+            let from = self.endpoint(&captures["from"], true, position);
+            let to_position = line.position_of(captures.name("to").unwrap().as_str());
+            let to = self.endpoint(&captures["to"], false, to_position);
+            self.ast.transitions.push(Transition {
+                from,
+                to,
+                label: captures
+                    .name("label")
+                    .map(|label| label.as_str().trim().to_string())
+                    .filter(|label| !label.is_empty()),
+                position,
+            });
+            return;
+        }
+
+        if let Some(captures) = DESCRIPTION.captures(text) {
+            let description = captures["text"].trim().to_string();
+            self.state_mut(&captures["id"], StateKind::Simple, line.position_at(0))
+                .descriptions
+                .push(description);
+            return;
+        }
+
+        if let Some(captures) = BARE_STATE.captures(text) {
+            self.state_mut(&captures["id"], StateKind::Simple, line.position_at(0));
+            return;
+        }
+
+        self.error(
+            DiagnosticCode::UnrecognizedStatement,
+            line.position_at(0),
+            format!("Unrecognized state diagram statement '{}'", text),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_states_and_transitions() {
+        let result = parse(
+            "stateDiagram-v2\n  [*] --> Idle\n  state \"Waiting for input\" as Idle\n  Idle --> Busy : start\n  Busy : working hard\n  state check <<choice>>\n  Busy --> check\n  check --> [*]",
+            1,
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let ast = result.ast;
+
+        assert_eq!(ast.state("[*]start").unwrap().kind, StateKind::Start);
+        assert_eq!(ast.state("[*]end").unwrap().kind, StateKind::End);
+        assert_eq!(
+            ast.state("Idle").unwrap().label.as_deref(),
+            Some("Waiting for input")
+        );
+        assert_eq!(
+            ast.state("Busy").unwrap().descriptions,
+            vec!["working hard"]
+        );
+        assert_eq!(ast.state("check").unwrap().kind, StateKind::Choice);
+        assert_eq!(ast.transitions.len(), 4);
+        assert_eq!(ast.transitions[1].label.as_deref(), Some("start"));
+    }
+
+    #[test]
+    fn test_composites_and_notes() {
+        let result = parse(
+            "stateDiagram\n  state Active {\n    [*] --> Running\n    Running --> [*]\n  }\n  note right of Active\n    two lines\n    of text\n  end note\n  Active --> Done",
+            1,
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let ast = result.ast;
+
+        assert_eq!(ast.state("Active").unwrap().kind, StateKind::Composite);
+        assert_eq!(ast.children_of(Some("Active")).len(), 3);
+        assert!(ast.state("Active/[*]start").is_some());
+        assert_eq!(ast.state("Done").unwrap().parent, None);
+        assert_eq!(ast.notes[0].text, "two lines\nof text");
+    }
+
+    #[test]
+    fn test_state_errors() {
+        let errors = parse("stateDiagram-v2\n  state Open {\n    A --> B\n  A -> B", 1).errors;
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 4);
+        assert!(errors[1].message.contains("Unclosed state 'Open'"));
+
+        for declaration in ["stateDiagram", "stateDiagram-v2"] {
+            let errors = parse(&format!("{}\n  direction", declaration), 1).errors;
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Expected a direction");
+            assert_eq!((errors[0].line, errors[0].column), (2, 12));
+        }
+    }
+}
@@ -0,0 +1,1066 @@
+use super::source::{self, Line};
+use super::{DiagnosticCode, DiagramType, SyntaxError};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static QUADRANT_POINT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<name>[^:\[]+?)(?::::[\w-]+)?\s*:\s*\[(?P<coords>[^\]]*)\]").unwrap()
+});
+
+static PACKET_FIELD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?:(?P<start>\d+)(?:\s*-\s*(?P<end>\d+))?|\+(?P<bits>\d+))\s*:\s*"[^"]*"$"#)
+        .unwrap()
+});
+
+static ARCHITECTURE_NODE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:group|service)\s+[\w-]+(?:\([^)]*\))?(?:\[[^\]]*\])?(?:\s+in\s+[\w-]+)?$")
+        .unwrap()
+});
+
+static ARCHITECTURE_JUNCTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^junction\s+[\w-]+(?:\s+in\s+[\w-]+)?$").unwrap());
+
+static ARCHITECTURE_EDGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^[\w-]+(?:\{group\})?\s*:\s*(?P<from>\w+)\s*<?-(?:\[[^\]]*\])?->?\s*(?P<to>\w+)\s*:\s*[\w-]+(?:\{group\})?$",
+    )
+    .unwrap()
+});
+
+static C4_ELEMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?P<name>[A-Za-z_]\w*)\s*\((?P<args>.*)\)\s*\{?$").unwrap());
+
+static RADAR_CURVE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^[\w-]+(?:\["[^"]*"\])?\s*\{(?P<values>[^}]*)\}$"#).unwrap());
+
+static TREEMAP_ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^"[^"]*"(?:\s*:\s*(?P<value>[^:\s]+))?(?:\s*:::[\w-]+)?$"#).unwrap()
+});
+
+static PIE_SLICE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^"[^"]*"\s*:\s*(?P<value>.*)$"#).unwrap());
+
+static ER_RELATIONSHIP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^(?:[\w-]+|"[^"]*")\s*(?P<op>[|}{ox<>*]{0,2}(?:--|\.\.)[|}{ox<>*]{0,2})\s*(?:[\w-]+|"[^"]*")\s*(?P<label>:.*)?$"#,
+    )
+    .unwrap()
+});
+
+/// Crow's-foot cardinalities on both ends, e.g. `||--o{` or `}|..|{`
+static ER_CARDINALITY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:\|o|\|\||\}o|\}\|)(?:--|\.\.)(?:o\||\|\||o\{|\|\{)$").unwrap()
+});
+
+static REQUIREMENT_RELATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[\w-]+\s*(?:-\s*(?P<kind>\w+)\s*->|<-\s*(?P<reverse>\w+)\s*-)\s*[\w-]+$").unwrap()
+});
+
+/// Kanban metadata keys and the priorities Mermaid understands
+const KANBAN_KEYS: &[&str] = &["assigned", "ticket", "priority"];
+const KANBAN_PRIORITIES: &[&str] = &["Very High", "High", "Low", "Very Low"];
+
+const GITGRAPH_COMMANDS: &[&str] = &[
+    "commit",
+    "branch",
+    "checkout",
+    "switch",
+    "merge",
+    "cherry-pick",
+];
+
+const GANTT_SETTINGS: &[&str] = &[
+    "title",
+    "section",
+    "dateFormat",
+    "axisFormat",
+    "tickInterval",
+    "excludes",
+    "includes",
+    "todayMarker",
+    "weekday",
+    "weekend",
+    "displayMode",
+    "click",
+];
+
+const REQUIREMENT_KINDS: &[&str] = &[
+    "requirement",
+    "functionalRequirement",
+    "interfaceRequirement",
+    "performanceRequirement",
+    "physicalRequirement",
+    "designConstraint",
+    "element",
+];
+const REQUIREMENT_RISKS: &[&str] = &["Low", "Medium", "High"];
+const REQUIREMENT_METHODS: &[&str] = &["Analysis", "Inspection", "Test", "Demonstration"];
+const REQUIREMENT_RELATIONS: &[&str] = &[
+    "contains",
+    "copies",
+    "derives",
+    "satisfies",
+    "verifies",
+    "refines",
+    "traces",
+];
+
+/// Statement checks for diagram types that have no full grammar.
+///
+/// These catch lines that cannot belong to the diagram and values Mermaid would reject,
+/// such as quadrant points outside `[0, 1]` or sankey rows without a numeric value.
+/// Brackets and blocks are left to the balance check.
+pub(crate) fn check(
+    content: &str,
+    start_line: usize,
+    diagram_type: DiagramType,
+) -> Vec<SyntaxError> {
+    let mut checker = Checker {
+        errors: Vec::new(),
+        next_bit: 0,
+        first_indent: None,
+    };
+
+    let mut lines = source::lines(content, start_line);
+    let Some(declaration) = lines.next() else {
+        return Vec::new();
+    };
+    if diagram_type == DiagramType::XyChart {
+        checker.xychart_declaration(&declaration);
+    }
+
+    let check_line: fn(&mut Checker, &Line) = match diagram_type {
+        DiagramType::Er => Checker::er,
+        DiagramType::Gantt => Checker::gantt,
+        DiagramType::Pie => Checker::pie,
+        DiagramType::Journey => Checker::journey,
+        DiagramType::GitGraph => Checker::gitgraph,
+        DiagramType::Requirement => Checker::requirement,
+        DiagramType::Mindmap => Checker::mindmap,
+        DiagramType::Timeline => Checker::timeline,
+        DiagramType::ZenUml => Checker::zenuml,
+        DiagramType::Quadrant => Checker::quadrant,
+        DiagramType::Sankey => Checker::sankey,
+        DiagramType::XyChart => Checker::xychart,
+        DiagramType::Block => Checker::block,
+        DiagramType::Packet => Checker::packet,
+        DiagramType::Architecture => Checker::architecture,
+        DiagramType::Kanban => Checker::kanban,
+        DiagramType::Radar => Checker::radar,
+        DiagramType::Treemap => Checker::treemap,
+        c4 if c4.is_c4() => Checker::c4,
+        _ => return Vec::new(),
+    };
+    for line in lines {
+        // Accessibility statements are allowed in every diagram type
+        if !matches!(
+            keyword(line.text).0,
+            "accTitle" | "accTitle:" | "accDescr" | "accDescr:"
+        ) {
+            check_line(&mut checker, &line);
+        }
+    }
+    checker.errors
+}
+
+struct Checker {
+    errors: Vec<SyntaxError>,
+    /// First bit of the next packet field
+    next_bit: u64,
+    /// Indentation of the first kanban column or the mindmap root
+    first_indent: Option<usize>,
+}
+
+/// The first word of a statement and the trimmed rest
+fn keyword(text: &str) -> (&str, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+fn is_number(text: &str) -> bool {
+    text.trim().parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+impl Checker {
+    fn error(&mut self, code: DiagnosticCode, line: &Line, part: &str, message: impl Into<String>) {
+        let position = line.position_of(part);
+        self.errors.push(SyntaxError::new(
+            code,
+            position.line,
+            position.column,
+            message,
+        ));
+    }
+
+    fn unrecognized(&mut self, line: &Line, expected: &str) {
+        self.error(
+            DiagnosticCode::UnrecognizedStatement,
+            line,
+            line.text,
+            format!(
+                "Unrecognized statement '{}': expected {}",
+                line.text, expected
+            ),
+        );
+    }
+
+    /// `title`-like keywords that need text after them
+    fn require_text(&mut self, line: &Line, word: &str, rest: &str) {
+        if rest.is_empty() {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line,
+                line.text,
+                format!("'{}' needs a label", word),
+            );
+        }
+    }
+
+    /// Every comma-separated entry of `list` must be a number
+    fn numbers(&mut self, line: &Line, list: &str) {
+        for item in list.split(',') {
+            let value = item.trim();
+            let value = value
+                .rsplit_once(':')
+                .map_or(value, |(_, value)| value.trim());
+            if !is_number(value) {
+                self.error(
+                    DiagnosticCode::InvalidValue,
+                    line,
+                    item.trim_start(),
+                    format!("Expected a number, found '{}'", item.trim()),
+                );
+            }
+        }
+    }
+
+    fn quadrant(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        match word {
+            "title" | "x-axis" | "y-axis" | "quadrant-1" | "quadrant-2" | "quadrant-3"
+            | "quadrant-4" => self.require_text(line, word, rest),
+            "classDef" => {}
+            _ => {
+                let Some(caps) = QUADRANT_POINT.captures(line.text) else {
+                    return self.unrecognized(line, "a point such as 'Name: [0.3, 0.6]'");
+                };
+                let coords = caps.name("coords").map_or("", |m| m.as_str());
+                let values: Vec<&str> = coords.split(',').collect();
+                if values.len() != 2 {
+                    return self.error(
+                        DiagnosticCode::IncompleteStatement,
+                        line,
+                        line.text,
+                        "A quadrant point needs exactly two coordinates",
+                    );
+                }
+                for value in values {
+                    let in_range = value
+                        .trim()
+                        .parse::<f64>()
+                        .is_ok_and(|value| (0.0..=1.0).contains(&value));
+                    if !in_range {
+                        self.error(
+                            DiagnosticCode::InvalidValue,
+                            line,
+                            value.trim_start(),
+                            format!(
+                                "Quadrant coordinates must be between 0 and 1, found '{}'",
+                                value.trim()
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn sankey(&mut self, line: &Line) {
+        let fields = csv_fields(line.text);
+        if fields.len() != 3 {
+            return self.error(
+                DiagnosticCode::IncompleteStatement,
+                line,
+                line.text,
+                format!(
+                    "Expected 'source,target,value', found {} field{}",
+                    fields.len(),
+                    if fields.len() == 1 { "" } else { "s" }
+                ),
+            );
+        }
+        let value = fields[2];
+        if !value.trim().parse::<f64>().is_ok_and(|value| value >= 0.0) {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                value,
+                format!(
+                    "Sankey values must be non-negative numbers, found '{}'",
+                    value.trim()
+                ),
+            );
+        }
+    }
+
+    fn xychart_declaration(&mut self, line: &Line) {
+        let (_, orientation) = keyword(line.text);
+        if !matches!(orientation, "" | "horizontal" | "vertical") {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                orientation,
+                format!(
+                    "Unknown chart orientation '{}': expected horizontal or vertical",
+                    orientation
+                ),
+            );
+        }
+    }
+
+    fn xychart(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        match word {
+            "title" => self.require_text(line, word, rest),
+            "x-axis" | "y-axis" => {
+                if let Some((min, max)) = rest.split_once("-->") {
+                    let min = min.split_whitespace().last().unwrap_or(min);
+                    for bound in [min, max] {
+                        if !is_number(bound) {
+                            self.error(
+                                DiagnosticCode::InvalidValue,
+                                line,
+                                bound.trim(),
+                                format!(
+                                    "Expected a number for the axis range, found '{}'",
+                                    bound.trim()
+                                ),
+                            );
+                        }
+                    }
+                } else if word == "y-axis" && rest.contains('[') {
+                    self.error(
+                        DiagnosticCode::InvalidValue,
+                        line,
+                        rest,
+                        "The y-axis takes a numeric range such as '0 --> 100', not categories",
+                    );
+                }
+            }
+            "line" | "bar" => match rest.find('[').zip(rest.rfind(']')) {
+                Some((open, close)) if open < close => self.numbers(line, &rest[open + 1..close]),
+                _ => self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line,
+                    line.text,
+                    format!("'{}' needs its values in brackets, such as [1, 2, 3]", word),
+                ),
+            },
+            _ => self.unrecognized(line, "title, x-axis, y-axis, line or bar"),
+        }
+    }
+
+    fn block(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if word == "columns" && rest != "auto" && !rest.parse::<u32>().is_ok_and(|n| n > 0) {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                if rest.is_empty() { line.text } else { rest },
+                format!(
+                    "'columns' takes a positive number or 'auto', found '{}'",
+                    rest
+                ),
+            );
+        }
+    }
+
+    fn packet(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if word == "title" {
+            return self.require_text(line, word, rest);
+        }
+        let Some(caps) = PACKET_FIELD.captures(line.text) else {
+            return self.unrecognized(line, "a field such as '0-15: \"Source Port\"'");
+        };
+        let number = |name: &str| caps.name(name).and_then(|m| m.as_str().parse::<u64>().ok());
+        let (start, end) = match (number("start"), number("bits")) {
+            (Some(start), _) => (start, number("end").unwrap_or(start)),
+            (None, Some(bits)) if bits > 0 => (self.next_bit, self.next_bit + bits - 1),
+            _ => {
+                return self.error(
+                    DiagnosticCode::InvalidValue,
+                    line,
+                    line.text,
+                    "A packet field needs at least one bit",
+                )
+            }
+        };
+        if start != self.next_bit {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                line.text,
+                format!(
+                    "Packet field starts at bit {}, expected bit {}",
+                    start, self.next_bit
+                ),
+            );
+        }
+        if end < start {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                line.text,
+                format!(
+                    "Packet field ends at bit {} before it starts at {}",
+                    end, start
+                ),
+            );
+        }
+        self.next_bit = end.max(start) + 1;
+    }
+
+    fn architecture(&mut self, line: &Line) {
+        if ARCHITECTURE_NODE.is_match(line.text) || ARCHITECTURE_JUNCTION.is_match(line.text) {
+            return;
+        }
+        let Some(caps) = ARCHITECTURE_EDGE.captures(line.text) else {
+            return self.unrecognized(
+                line,
+                "a group, service, junction or edge such as 'a:R --> L:b'",
+            );
+        };
+        for side in [&caps["from"], &caps["to"]] {
+            if !matches!(side, "L" | "R" | "T" | "B") {
+                self.error(
+                    DiagnosticCode::InvalidValue,
+                    line,
+                    side,
+                    format!("Unknown edge side '{}': expected L, R, T or B", side),
+                );
+            }
+        }
+    }
+
+    fn kanban(&mut self, line: &Line) {
+        let column_indent = *self.first_indent.get_or_insert(line.indent);
+        if line.indent < column_indent {
+            return self.error(
+                DiagnosticCode::UnrecognizedStatement,
+                line,
+                line.text,
+                "Kanban items must be indented at least as far as the first column",
+            );
+        }
+        let Some((_, metadata)) = line.text.split_once("@{") else {
+            return;
+        };
+        let metadata = metadata.trim_end().trim_end_matches('}');
+        for entry in metadata.split(',') {
+            let Some((key, value)) = entry.split_once(':') else {
+                continue;
+            };
+            let key = key.trim();
+            let value = value.trim().trim_matches(|ch| ch == '\'' || ch == '"');
+            if !KANBAN_KEYS.contains(&key) {
+                self.error(
+                    DiagnosticCode::InvalidValue,
+                    line,
+                    entry.trim_start(),
+                    format!(
+                        "Unknown kanban metadata '{}': expected {}",
+                        key,
+                        KANBAN_KEYS.join(", ")
+                    ),
+                );
+            } else if key == "priority" && !KANBAN_PRIORITIES.contains(&value) {
+                self.error(
+                    DiagnosticCode::InvalidValue,
+                    line,
+                    entry.trim_start(),
+                    format!(
+                        "Unknown priority '{}': expected {}",
+                        value,
+                        KANBAN_PRIORITIES.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn radar(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        match word {
+            "title" | "axis" => self.require_text(line, word, rest),
+            "curve" => match RADAR_CURVE.captures(rest) {
+                Some(caps) => {
+                    let values = caps.name("values").map_or("", |m| m.as_str());
+                    self.numbers(line, values);
+                }
+                None => self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line,
+                    line.text,
+                    "A curve needs an id and values, such as 'curve a{1, 2, 3}'",
+                ),
+            },
+            "max" | "min" | "ticks" => {
+                if !is_number(rest) {
+                    self.error(
+                        DiagnosticCode::InvalidValue,
+                        line,
+                        if rest.is_empty() { line.text } else { rest },
+                        format!("'{}' takes a number, found '{}'", word, rest),
+                    );
+                }
+            }
+            "showLegend" => {}
+            "graticule" => {
+                if !matches!(rest, "circle" | "polygon") {
+                    self.error(
+                        DiagnosticCode::InvalidValue,
+                        line,
+                        if rest.is_empty() { line.text } else { rest },
+                        format!("Unknown graticule '{}': expected circle or polygon", rest),
+                    );
+                }
+            }
+            _ => self.unrecognized(
+                line,
+                "title, axis, curve, max, min, ticks, showLegend or graticule",
+            ),
+        }
+    }
+
+    fn treemap(&mut self, line: &Line) {
+        if keyword(line.text).0 == "classDef" {
+            return;
+        }
+        let Some(caps) = TREEMAP_ITEM.captures(line.text) else {
+            return self.unrecognized(line, "a quoted item such as '\"Name\": 10'");
+        };
+        if let Some(value) = caps.name("value") {
+            if !is_number(value.as_str()) {
+                self.error(
+                    DiagnosticCode::InvalidValue,
+                    line,
+                    value.as_str(),
+                    format!("Expected a number, found '{}'", value.as_str()),
+                );
+            }
+        }
+    }
+
+    fn c4(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if word == "title" {
+            return self.require_text(line, word, rest);
+        }
+        if line.text == "}" {
+            return;
+        }
+        match C4_ELEMENT.captures(line.text) {
+            Some(caps) if caps["args"].trim().is_empty() => self.error(
+                DiagnosticCode::IncompleteStatement,
+                line,
+                line.text,
+                format!("'{}' needs an id", &caps["name"]),
+            ),
+            Some(_) => {}
+            None => self.unrecognized(line, "an element such as 'Person(id, \"Label\")'"),
+        }
+    }
+
+    fn er(&mut self, line: &Line) {
+        // Entities, their attributes and braces have no relationship operator
+        let Some(caps) = ER_RELATIONSHIP.captures(line.text) else {
+            return;
+        };
+        let op = &caps["op"];
+        if !ER_CARDINALITY.is_match(op) {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                op,
+                format!(
+                    "Unknown relationship '{}': expected cardinalities such as '||--o{{'",
+                    op
+                ),
+            );
+        } else if caps.name("label").is_none() {
+            self.error(
+                DiagnosticCode::IncompleteStatement,
+                line,
+                line.text,
+                "A relationship needs a label, such as ': places'",
+            );
+        }
+    }
+
+    fn gantt(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if GANTT_SETTINGS.contains(&word) {
+            return self.require_text(line, word, rest);
+        }
+        if !matches!(word, "inclusiveEndDates" | "topAxis") && !line.text.contains(':') {
+            self.unrecognized(
+                line,
+                "a setting, section or task such as 'Design : d1, 2024-01-01, 3d'",
+            );
+        }
+    }
+
+    fn pie(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if word == "title" {
+            return self.require_text(line, word, rest);
+        }
+        let Some(caps) = PIE_SLICE.captures(line.text) else {
+            return self.unrecognized(line, "a slice such as '\"Dogs\" : 42'");
+        };
+        let value = caps.name("value").unwrap().as_str();
+        if !value.parse::<f64>().is_ok_and(|value| value >= 0.0) {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                if value.is_empty() { line.text } else { value },
+                format!("Pie values must be non-negative numbers, found '{}'", value),
+            );
+        }
+    }
+
+    fn journey(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if matches!(word, "title" | "section") {
+            return self.require_text(line, word, rest);
+        }
+        let Some((_, details)) = line.text.split_once(':') else {
+            return self.unrecognized(line, "a task such as 'Make tea: 5: Me'");
+        };
+        let score = details.split(':').next().unwrap_or(details).trim();
+        if !is_number(score) {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                if score.is_empty() { line.text } else { score },
+                format!("Expected a number for the task score, found '{}'", score),
+            );
+        }
+    }
+
+    fn gitgraph(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        let rest = rest.trim_end_matches(';').trim();
+        match word.trim_end_matches(';') {
+            "commit" => {}
+            "cherry-pick" if !rest.starts_with("id:") => self.error(
+                DiagnosticCode::IncompleteStatement,
+                line,
+                line.text,
+                "'cherry-pick' needs a commit, such as 'cherry-pick id: \"A\"'",
+            ),
+            command if GITGRAPH_COMMANDS.contains(&command) && rest.is_empty() => self.error(
+                DiagnosticCode::IncompleteStatement,
+                line,
+                line.text,
+                format!("'{}' needs a branch name", command),
+            ),
+            command if GITGRAPH_COMMANDS.contains(&command) => {}
+            _ => self.unrecognized(
+                line,
+                "commit, branch, checkout, switch, merge or cherry-pick",
+            ),
+        }
+    }
+
+    fn requirement(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if REQUIREMENT_KINDS.contains(&word) {
+            if rest.trim_end_matches('{').trim().is_empty() {
+                self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line,
+                    line.text,
+                    format!("'{}' needs a name", word),
+                );
+            }
+            return;
+        }
+        if line.text == "}" || matches!(word, "style" | "classDef" | "class") {
+            return;
+        }
+        if let Some(caps) = REQUIREMENT_RELATION.captures(line.text) {
+            let kind = caps.name("kind").or(caps.name("reverse")).unwrap().as_str();
+            if !REQUIREMENT_RELATIONS.contains(&kind) {
+                self.error(
+                    DiagnosticCode::InvalidValue,
+                    line,
+                    kind,
+                    format!(
+                        "Unknown relationship '{}': expected {}",
+                        kind,
+                        REQUIREMENT_RELATIONS.join(", ")
+                    ),
+                );
+            }
+            return;
+        }
+        let Some((key, value)) = line.text.split_once(':') else {
+            return self.unrecognized(
+                line,
+                "a requirement, element, field or relationship such as 'a - satisfies -> b'",
+            );
+        };
+        let value = value.trim();
+        let allowed = match key.trim() {
+            "risk" => REQUIREMENT_RISKS,
+            "verifymethod" => REQUIREMENT_METHODS,
+            _ => return,
+        };
+        if !allowed.iter().any(|name| name.eq_ignore_ascii_case(value)) {
+            self.error(
+                DiagnosticCode::InvalidValue,
+                line,
+                if value.is_empty() { line.text } else { value },
+                format!(
+                    "Unknown {} '{}': expected {}",
+                    key.trim(),
+                    value,
+                    allowed.join(", ")
+                ),
+            );
+        }
+    }
+
+    fn mindmap(&mut self, line: &Line) {
+        // The first line is the root; every other node must be nested below it
+        match self.first_indent {
+            None => self.first_indent = Some(line.indent),
+            Some(root_indent) if line.indent <= root_indent => self.error(
+                DiagnosticCode::UnrecognizedStatement,
+                line,
+                line.text,
+                "A mindmap has a single root; indent this node below it",
+            ),
+            Some(_) => {}
+        }
+    }
+
+    fn timeline(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if matches!(word, "title" | "section") {
+            return self.require_text(line, word, rest);
+        }
+        for event in line.text.split(':').skip(1) {
+            if event.trim().is_empty() {
+                self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line,
+                    line.text,
+                    "A timeline event needs text after ':'",
+                );
+            }
+        }
+    }
+
+    fn zenuml(&mut self, line: &Line) {
+        let (word, rest) = keyword(line.text);
+        if word == "title" {
+            return self.require_text(line, word, rest);
+        }
+        if line.text.starts_with("//") {
+            return;
+        }
+        if let Some((_, receiver)) = line.text.split_once("->") {
+            if receiver.trim().is_empty() || receiver.trim_start().starts_with(':') {
+                self.error(
+                    DiagnosticCode::IncompleteStatement,
+                    line,
+                    line.text,
+                    "A message needs a receiver after '->'",
+                );
+            }
+        }
+    }
+}
+
+/// Split a CSV row, keeping commas inside double quotes
+fn csv_fields(text: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut in_quote = false;
+    let mut start = 0;
+    for (offset, ch) in text.char_indices() {
+        match ch {
+            '"' => in_quote = !in_quote,
+            ',' if !in_quote => {
+                fields.push(&text[start..offset]);
+                start = offset + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&text[start..]);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(content: &str, diagram_type: DiagramType) -> Vec<(usize, String)> {
+        check(content, 1, diagram_type)
+            .into_iter()
+            .map(|e| (e.line, e.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_diagrams_pass() {
+        let cases = [
+            (
+                DiagramType::Quadrant,
+                "quadrantChart\n  title Reach\n  x-axis Low --> High\n  quadrant-1 Expand\n  Campaign A: [0.3, 0.6]\n  B:::hot: [1, 0] radius: 10",
+            ),
+            (
+                DiagramType::Sankey,
+                "sankey-beta\n  Agricultural waste,Bio-conversion,124.729\n  \"Heat, waste\",Losses,26.862",
+            ),
+            (
+                DiagramType::XyChart,
+                "xychart-beta horizontal\n  title \"Sales\"\n  x-axis [jan, feb]\n  y-axis \"Revenue\" 4000 --> 11000\n  bar [5000, 6000]\n  line [5000.5, 6000]",
+            ),
+            (
+                DiagramType::Block,
+                "block-beta\n  columns 3\n  a b c\n  block:group1:2\n    d\n  end",
+            ),
+            (
+                DiagramType::Packet,
+                "packet-beta\n  title TCP\n  0-15: \"Source Port\"\n  16-31: \"Destination Port\"\n  32: \"Flag\"\n  +8: \"Reserved\"",
+            ),
+            (
+                DiagramType::Architecture,
+                "architecture-beta\n  group api(cloud)[API]\n  service db(database)[Database] in api\n  junction center\n  db:L -- R:center\n  center{group}:T <--> B:db",
+            ),
+            (
+                DiagramType::Kanban,
+                "kanban\n  Todo\n    task1[Write docs]@{ assigned: 'ana', priority: 'High' }\n  Done\n    task2",
+            ),
+            (
+                DiagramType::Radar,
+                "radar-beta\n  axis m[\"Math\"], s[\"Science\"]\n  curve a[\"Alice\"]{85, 90}\n  curve b{m: 70, s: 60}\n  max 100\n  graticule polygon\n  showLegend",
+            ),
+            (
+                DiagramType::Treemap,
+                "treemap-beta\n  \"Section\"\n    \"Leaf\": 12\n    \"Other\": 3.5:::big",
+            ),
+            (
+                DiagramType::C4Container,
+                "C4Container\n  title Containers\n  accTitle: Shop\n  Person(customer, \"Customer\")\n  Container_Boundary(b, \"Shop\") {\n    Container(web, \"Web\", \"React\")\n  }\n  Rel(customer, web, \"Uses\")",
+            ),
+            (
+                DiagramType::Er,
+                "erDiagram\n  CUSTOMER ||--o{ ORDER : places\n  ORDER {\n    string id PK \"order -- id\"\n  }\n  \"LINE ITEM\" }|..|{ ORDER : in",
+            ),
+            (
+                DiagramType::Gantt,
+                "gantt\n  title Plan\n  dateFormat YYYY-MM-DD\n  excludes weekends\n  section Build\n  Design : d1, 2024-01-01, 3d\n  inclusiveEndDates",
+            ),
+            (
+                DiagramType::Pie,
+                "pie showData\n  title Pets\n  \"Dogs\" : 386\n  \"Cats\" : 85.5",
+            ),
+            (
+                DiagramType::Journey,
+                "journey\n  title My day\n  section Work\n  Make tea: 5: Me, Cat\n  Commute: 2: Me",
+            ),
+            (
+                DiagramType::GitGraph,
+                "gitGraph\n  commit id: \"A\"\n  branch develop\n  checkout develop\n  commit\n  switch main\n  merge develop tag: \"v1\"\n  cherry-pick id: \"A\"",
+            ),
+            (
+                DiagramType::Requirement,
+                "requirementDiagram\n  requirement login {\n    id: 1\n    text: users sign in: fast\n    risk: high\n    verifymethod: Test\n  }\n  element app {\n    type: service\n  }\n  app - satisfies -> login\n  login <- traces - app",
+            ),
+            (
+                DiagramType::Mindmap,
+                "mindmap\n  root((Ideas))\n    Origins\n      ::icon(fa fa-book)\n    Research",
+            ),
+            (
+                DiagramType::Timeline,
+                "timeline\n  title History\n  section Early\n  2002 : LinkedIn\n  2004 : Facebook : Google\n       : Gmail\n  2005",
+            ),
+            (
+                DiagramType::ZenUml,
+                "zenuml\n  title Checkout\n  @Actor Alice\n  // the order flow\n  Alice->Shop: order\n  Shop.pay() {\n    return ok\n  }",
+            ),
+        ];
+        for (diagram_type, content) in cases {
+            assert_eq!(messages(content, diagram_type), vec![], "{}", diagram_type);
+        }
+    }
+
+    #[test]
+    fn test_invalid_statements() {
+        assert_eq!(
+            messages(
+                "quadrantChart\n  A: [1.5, 0.2]\n  oops",
+                DiagramType::Quadrant
+            ),
+            vec![
+                (
+                    2,
+                    "Quadrant coordinates must be between 0 and 1, found '1.5'".to_string()
+                ),
+                (
+                    3,
+                    "Unrecognized statement 'oops': expected a point such as 'Name: [0.3, 0.6]'"
+                        .to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            messages("sankey-beta\n  a,b\n  a,b,lots", DiagramType::Sankey),
+            vec![
+                (
+                    2,
+                    "Expected 'source,target,value', found 2 fields".to_string()
+                ),
+                (
+                    3,
+                    "Sankey values must be non-negative numbers, found 'lots'".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            messages("xychart-beta sideways\n  bar [1, x]", DiagramType::XyChart),
+            vec![
+                (
+                    1,
+                    "Unknown chart orientation 'sideways': expected horizontal or vertical"
+                        .to_string()
+                ),
+                (2, "Expected a number, found 'x'".to_string()),
+            ]
+        );
+        assert_eq!(
+            messages(
+                "packet-beta\n  0-7: \"A\"\n  9-15: \"B\"",
+                DiagramType::Packet
+            ),
+            vec![(
+                3,
+                "Packet field starts at bit 9, expected bit 8".to_string()
+            )]
+        );
+        let errors = check(
+            "architecture-beta\n  a:X --> L:b",
+            1,
+            DiagramType::Architecture,
+        );
+        assert_eq!(errors[0].code, DiagnosticCode::InvalidValue);
+        assert_eq!((errors[0].line, errors[0].column), (2, 5));
+        assert_eq!(
+            messages("C4Dynamic\n  Person()\n  just text", DiagramType::C4Dynamic),
+            vec![
+                (2, "'Person' needs an id".to_string()),
+                (
+                    3,
+                    "Unrecognized statement 'just text': expected an element such as 'Person(id, \"Label\")'"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_statements_in_other_types() {
+        let cases = [
+            (
+                DiagramType::Er,
+                "erDiagram\n  A ||--x{ B : has\n  A ||--|{ C",
+                vec![
+                    (
+                        2,
+                        "Unknown relationship '||--x{': expected cardinalities such as '||--o{'",
+                    ),
+                    (3, "A relationship needs a label, such as ': places'"),
+                ],
+            ),
+            (
+                DiagramType::Gantt,
+                "gantt\n  dateFormat\n  Design 3d",
+                vec![
+                    (2, "'dateFormat' needs a label"),
+                    (3, "Unrecognized statement 'Design 3d': expected a setting, section or task such as 'Design : d1, 2024-01-01, 3d'"),
+                ],
+            ),
+            (
+                DiagramType::Pie,
+                "pie\n  \"Dogs\" : many\n  Cats 3",
+                vec![
+                    (2, "Pie values must be non-negative numbers, found 'many'"),
+                    (3, "Unrecognized statement 'Cats 3': expected a slice such as '\"Dogs\" : 42'"),
+                ],
+            ),
+            (
+                DiagramType::Journey,
+                "journey\n  section\n  Make tea: great: Me",
+                vec![
+                    (2, "'section' needs a label"),
+                    (3, "Expected a number for the task score, found 'great'"),
+                ],
+            ),
+            (
+                DiagramType::GitGraph,
+                "gitGraph\n  branch\n  cherry-pick\n  rebase main",
+                vec![
+                    (2, "'branch' needs a branch name"),
+                    (3, "'cherry-pick' needs a commit, such as 'cherry-pick id: \"A\"'"),
+                    (4, "Unrecognized statement 'rebase main': expected commit, branch, checkout, switch, merge or cherry-pick"),
+                ],
+            ),
+            (
+                DiagramType::Requirement,
+                "requirementDiagram\n  requirement {\n    risk: extreme\n  }\n  a - needs -> b",
+                vec![
+                    (2, "'requirement' needs a name"),
+                    (3, "Unknown risk 'extreme': expected Low, Medium, High"),
+                    (5, "Unknown relationship 'needs': expected contains, copies, derives, satisfies, verifies, refines, traces"),
+                ],
+            ),
+            (
+                DiagramType::Mindmap,
+                "mindmap\n  Root\n    Child\n  Second root",
+                vec![(4, "A mindmap has a single root; indent this node below it")],
+            ),
+            (
+                DiagramType::Timeline,
+                "timeline\n  2004 : Facebook :",
+                vec![(2, "A timeline event needs text after ':'")],
+            ),
+            (
+                DiagramType::ZenUml,
+                "zenuml\n  title\n  Alice->: hi",
+                vec![
+                    (2, "'title' needs a label"),
+                    (3, "A message needs a receiver after '->'"),
+                ],
+            ),
+        ];
+        for (diagram_type, content, expected) in cases {
+            let expected: Vec<(usize, String)> = expected
+                .into_iter()
+                .map(|(line, message)| (line, message.to_string()))
+                .collect();
+            assert_eq!(
+                messages(content, diagram_type),
+                expected,
+                "{}",
+                diagram_type
+            );
+        }
+    }
+}
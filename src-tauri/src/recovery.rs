@@ -0,0 +1,177 @@
+use crate::file_io::{self, SaveOptions};
+use crate::file_manager::FileType;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Present while the app runs; finding it at startup means the last session crashed
+const SESSION_MARKER: &str = "session.lock";
+
+/// An unsaved buffer as last journaled, shaped like `FileContent` so the editor can
+/// reopen it directly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub id: String,
+    pub name: String,
+    /// `None` for untitled buffers
+    pub path: Option<String>,
+    pub content: String,
+    pub file_type: FileType,
+    /// Disk version the buffer was based on, so saving a recovered buffer still detects conflicts
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default = "Utc::now")]
+    pub journaled_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    /// The previous session ended without shutting down cleanly
+    pub unclean_shutdown: bool,
+    /// Buffers left over from earlier sessions, most recently journaled first
+    pub documents: Vec<JournalEntry>,
+}
+
+/// Journals of unsaved buffers, one JSON file per document in a folder of the app data
+/// directory.
+///
+/// Journals stay until their document is saved or discarded, so buffers still dirty when
+/// the window closes are offered again at the next start just like after a crash.
+pub struct Journal {
+    dir: PathBuf,
+    unclean_shutdown: bool,
+    recovered: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Load leftover journals and mark the session as running
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let marker = dir.join(SESSION_MARKER);
+        let unclean_shutdown = marker.exists();
+
+        let mut recovered: Vec<JournalEntry> = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let text = fs::read_to_string(&path).ok()?;
+                serde_json::from_str(&text).ok()
+            })
+            .collect();
+        recovered.sort_by_key(|entry| Reverse(entry.journaled_at));
+
+        fs::write(&marker, std::process::id().to_string())?;
+        Ok(Journal {
+            dir: dir.to_path_buf(),
+            unclean_shutdown,
+            recovered,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn report(&self) -> RecoveryReport {
+        RecoveryReport {
+            unclean_shutdown: self.unclean_shutdown,
+            documents: self.recovered.clone(),
+        }
+    }
+
+    /// Journal a dirty buffer, replacing its previous journal
+    pub fn write(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        let json = serde_json::to_vec(entry).map_err(io::Error::other)?;
+        file_io::atomic_write(&self.entry_path(&entry.id), &json, &SaveOptions::default())?;
+        // A reopened recovered buffer belongs to this session from now on
+        self.recovered.retain(|recovered| recovered.id != entry.id);
+        Ok(())
+    }
+
+    /// Drop a document's journal after it was saved or discarded
+    pub fn remove(&mut self, id: &str) -> io::Result<()> {
+        self.recovered.retain(|recovered| recovered.id != id);
+        match fs::remove_file(self.entry_path(id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove the session marker on a clean shutdown; journals of buffers that are still
+    /// dirty are kept
+    pub fn close(&mut self) -> io::Result<()> {
+        fs::remove_file(self.dir.join(SESSION_MARKER))
+    }
+
+    /// Document IDs are generated UUIDs, but anything else is reduced to a safe file name
+    fn entry_path(&self, id: &str) -> PathBuf {
+        let name: String = id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{}.json", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, content: &str) -> JournalEntry {
+        JournalEntry {
+            id: id.to_string(),
+            name: "Untitled".to_string(),
+            path: None,
+            content: content.to_string(),
+            file_type: FileType::Markdown,
+            content_hash: None,
+            size: None,
+            journaled_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_recovers_after_unclean_shutdown() {
+        let dir = std::env::temp_dir().join(format!("parch-recovery-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        // First session crashes with two dirty buffers, one of which was saved
+        let mut journal = Journal::open(&dir).unwrap();
+        assert!(!journal.report().unclean_shutdown);
+        journal.write(&entry("a", "graph TD\n  A --> B")).unwrap();
+        journal.write(&entry("b", "pie")).unwrap();
+        journal.remove("b").unwrap();
+        drop(journal);
+
+        let mut journal = Journal::open(&dir).unwrap();
+        let report = journal.report();
+        assert!(report.unclean_shutdown);
+        assert_eq!(report.documents.len(), 1);
+        assert_eq!(report.documents[0].content, "graph TD\n  A --> B");
+
+        // A clean shutdown keeps every unsaved buffer, leftover or written this session
+        journal.write(&entry("c", "graph LR")).unwrap();
+        journal.close().unwrap();
+        let journal = Journal::open(&dir).unwrap();
+        let report = journal.report();
+        assert!(!report.unclean_shutdown);
+        let mut ids: Vec<&str> = report.documents.iter().map(|d| d.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "c"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
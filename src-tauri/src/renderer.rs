@@ -0,0 +1,200 @@
+use crate::mermaid_parser::{self, AstResult, DiagramType, SyntaxError};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+mod class_diagram;
+mod flowchart;
+mod layout;
+mod sequence;
+mod state_diagram;
+mod svg;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderOptions {
+    pub theme: Theme,
+    pub font_size: f64,
+    /// Empty space around the drawing
+    pub padding: f64,
+    /// Canvas colour instead of the theme's; `transparent` leaves it out
+    pub background: Option<String>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            theme: Theme::Light,
+            font_size: 14.0,
+            padding: 16.0,
+            background: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum RenderError {
+    /// No native renderer exists for this diagram type yet
+    #[error("Rendering '{0}' diagrams is not supported")]
+    Unsupported(DiagramType),
+    /// The diagram has syntax errors, so there is no reliable AST to draw
+    #[error("Cannot render diagram{}", describe_first(.0))]
+    Syntax(Vec<SyntaxError>),
+}
+
+fn describe_first(errors: &[SyntaxError]) -> String {
+    match errors.first() {
+        Some(error) => format!(
+            ": line {}, column {}: {} [{}]",
+            error.line, error.column, error.message, error.code
+        ),
+        None => String::new(),
+    }
+}
+
+/// Render one diagram to a standalone SVG document without a webview.
+///
+/// Supports flowchart, sequence, class and state diagrams, as named by
+/// `MermaidParser::detect_diagram_type`.
+pub fn render_svg(
+    content: &str,
+    diagram_type: DiagramType,
+    options: &RenderOptions,
+) -> Result<String, RenderError> {
+    fn checked<T>(result: AstResult<T>) -> Result<T, RenderError> {
+        if result.errors.is_empty() {
+            Ok(result.ast)
+        } else {
+            Err(RenderError::Syntax(result.errors))
+        }
+    }
+
+    match diagram_type {
+        DiagramType::Flowchart => {
+            let ast = checked(mermaid_parser::flowchart::parse(content, 1))?;
+            Ok(flowchart::render(&ast, options))
+        }
+        DiagramType::Sequence => {
+            let ast = checked(mermaid_parser::sequence::parse(content, 1))?;
+            Ok(sequence::render(&ast, options))
+        }
+        DiagramType::Class => {
+            let ast = checked(mermaid_parser::class_diagram::parse(content, 1))?;
+            Ok(class_diagram::render(&ast, options))
+        }
+        DiagramType::State => {
+            let ast = checked(mermaid_parser::state_diagram::parse(content, 1))?;
+            Ok(state_diagram::render(&ast, options))
+        }
+        other => Err(RenderError::Unsupported(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Compare against `tests/golden/<name>`; set `UPDATE_GOLDEN=1` to rewrite the files
+    fn assert_golden(name: &str, svg: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, svg).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "missing golden file {}; run with UPDATE_GOLDEN=1",
+                path.display()
+            )
+        });
+        assert!(
+            svg == expected,
+            "{} differs from the golden file; run with UPDATE_GOLDEN=1 to accept",
+            name
+        );
+    }
+
+    fn render(content: &str, diagram_type: DiagramType) -> String {
+        render_svg(content, diagram_type, &RenderOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_golden_flowchart() {
+        let svg = render(
+            "flowchart LR\n  A[Start] --> B{Ready?}\n  B -->|yes| C([Ship it])\n  B -.->|no| D[(Queue)]\n  D ==> A\n  subgraph backend [Backend]\n    C\n    D\n  end\n  style A fill:#f9f,stroke:#333",
+            DiagramType::Flowchart,
+        );
+        assert_golden("flowchart.svg", &svg);
+    }
+
+    #[test]
+    fn test_golden_sequence() {
+        let svg = render(
+            "sequenceDiagram\n  autonumber\n  actor U as User\n  participant API\n  U->>+API: GET /orders\n  loop every page\n    API-->>API: fetch\n  end\n  alt found\n    API-->>U: 200 OK\n  else missing\n    API--xU: 404\n  end\n  deactivate API\n  Note over U,API: done",
+            DiagramType::Sequence,
+        );
+        assert_golden("sequence.svg", &svg);
+    }
+
+    #[test]
+    fn test_golden_class() {
+        let svg = render(
+            "classDiagram\n  class Animal {\n    <<abstract>>\n    +String name\n    +speak()* String\n  }\n  class Duck~T~ {\n    +List~T~ eggs\n    +swim()$\n  }\n  Animal <|-- Duck\n  Duck \"1\" *-- \"many\" Egg : lays\n  note for Duck \"can fly\"",
+            DiagramType::Class,
+        );
+        assert_golden("class.svg", &svg);
+    }
+
+    #[test]
+    fn test_golden_state() {
+        let svg = render(
+            "stateDiagram-v2\n  [*] --> Idle\n  Idle --> Active : start\n  state Active {\n    [*] --> Running\n    Running --> Paused : pause\n    Paused --> Running : resume\n  }\n  Active --> [*]\n  note right of Idle : waiting",
+            DiagramType::State,
+        );
+        assert_golden("state.svg", &svg);
+    }
+
+    #[test]
+    fn test_render_is_deterministic_and_escapes_text() {
+        let content = "graph TD\n  A[\"a < b & c\"] --> B";
+        let svg = render(content, DiagramType::Flowchart);
+        assert_eq!(svg, render(content, DiagramType::Flowchart));
+        assert!(svg.contains("a &lt; b &amp; c"));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+
+        let dark = render_svg(
+            content,
+            DiagramType::Flowchart,
+            &RenderOptions {
+                theme: Theme::Dark,
+                ..RenderOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(dark.contains("#1e1e2e"));
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let options = RenderOptions::default();
+        assert!(matches!(
+            render_svg("pie\n  \"a\" : 1", DiagramType::Pie, &options),
+            Err(RenderError::Unsupported(_))
+        ));
+        let error = render_svg("graph TD\n  A --> ", DiagramType::Flowchart, &options).unwrap_err();
+        assert!(matches!(error, RenderError::Syntax(_)));
+        assert!(error
+            .to_string()
+            .starts_with("Cannot render diagram: line 2"));
+    }
+}
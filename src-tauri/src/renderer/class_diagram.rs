@@ -0,0 +1,340 @@
+use super::layout::{self, Rect, Spacing};
+use super::svg::{self, Anchor, Stroke, Svg};
+use super::RenderOptions;
+use crate::mermaid_parser::class_diagram::{
+    Attribute, ClassDiagramAst, ClassNode, Method, RelationEnd, RelationLine, Visibility,
+};
+use crate::mermaid_parser::flowchart::Direction;
+use std::collections::HashMap;
+
+const PADDING_X: f64 = 10.0;
+const SECTION_PADDING: f64 = 5.0;
+const MIN_WIDTH: f64 = 80.0;
+const CLUSTER_PADDING: f64 = 14.0;
+
+const CLUSTER_LAYER: usize = 0;
+const EDGE_LAYER: usize = 1;
+const NODE_LAYER: usize = 2;
+const LABEL_LAYER: usize = 3;
+
+/// One text line of a class box with its CSS style
+struct BoxLine {
+    text: String,
+    style: &'static str,
+}
+
+/// Header, attribute and method compartments of a class box
+struct ClassBox {
+    header: Vec<BoxLine>,
+    attributes: Vec<BoxLine>,
+    methods: Vec<BoxLine>,
+}
+
+/// Render a class diagram with UML compartments, relationship ends and notes
+pub fn render(ast: &ClassDiagramAst, options: &RenderOptions) -> String {
+    let mut svg = Svg::new(options);
+    let direction = ast.direction.unwrap_or(Direction::TB);
+    let line_height = svg.line_height();
+
+    let boxes: Vec<ClassBox> = ast.classes.iter().map(class_box).collect();
+    let mut sizes: Vec<(f64, f64)> = boxes.iter().map(|b| box_size(&svg, b)).collect();
+    let index: HashMap<&str, usize> = ast
+        .classes
+        .iter()
+        .enumerate()
+        .map(|(i, class)| (class.name.as_str(), i))
+        .collect();
+
+    let mut edges: Vec<(usize, usize)> = ast
+        .relationships
+        .iter()
+        .map(|relation| (index[relation.from.as_str()], index[relation.to.as_str()]))
+        .collect();
+
+    // Notes join the layout as extra nodes next to the class they describe
+    let note_offset = sizes.len();
+    for (i, note) in ast.notes.iter().enumerate() {
+        let (width, height) = svg.text_size(&note.text);
+        sizes.push((width + 2.0 * PADDING_X, height + 2.0 * SECTION_PADDING));
+        if let Some(&class) = note.class.as_deref().and_then(|class| index.get(class)) {
+            edges.push((note_offset + i, class));
+        }
+    }
+
+    let rects = layout::layered(
+        &sizes,
+        &edges,
+        direction,
+        &Spacing {
+            // Tall enough for a label and two cardinalities on one relationship
+            rank: 80.0,
+            node: 40.0,
+        },
+    );
+    let mut extents = rects.clone();
+
+    svg.layer(CLUSTER_LAYER);
+    for namespace in &ast.namespaces {
+        let members: Vec<Rect> = namespace
+            .classes
+            .iter()
+            .filter_map(|class| index.get(class.as_str()).map(|&i| rects[i]))
+            .collect();
+        let Some(inner) = layout::bounds(&members) else {
+            continue;
+        };
+        let inner = inner.inflate(CLUSTER_PADDING, CLUSTER_PADDING);
+        let rect = Rect::new(
+            inner.x,
+            inner.y - line_height,
+            inner.width,
+            inner.height + line_height,
+        );
+        extents.push(rect);
+        let fill = svg.palette.cluster_fill;
+        let stroke = svg.palette.cluster_stroke;
+        svg.rect(rect, 0.0, fill, stroke, "");
+        svg.text(
+            (rect.center().0, rect.y + line_height / 2.0 + 4.0),
+            &namespace.name,
+            Anchor::Middle,
+            "font-weight:bold",
+        );
+    }
+
+    for relation in &ast.relationships {
+        let (from, to) = (
+            rects[index[relation.from.as_str()]],
+            rects[index[relation.to.as_str()]],
+        );
+        let points = layout::route(&from, &to);
+        extents.extend(points.iter().map(|&(x, y)| Rect::new(x, y, 0.0, 0.0)));
+        svg.layer(EDGE_LAYER);
+        svg.polyline(
+            &points,
+            Stroke {
+                color: svg.palette.edge,
+                width: 1.0,
+                dashed: relation.line == RelationLine::Dashed,
+            },
+            relation.from_end.map(marker),
+            relation.to_end.map(marker),
+        );
+
+        svg.layer(LABEL_LAYER);
+        if let Some(label) = &relation.label {
+            svg.label(layout::midpoint(&points), label);
+        }
+        let (start, end) = (points[0], points[points.len() - 1]);
+        if let Some(cardinality) = &relation.from_cardinality {
+            svg.text(near_end(start, end), cardinality, Anchor::Middle, "");
+        }
+        if let Some(cardinality) = &relation.to_cardinality {
+            svg.text(near_end(end, start), cardinality, Anchor::Middle, "");
+        }
+    }
+
+    svg.layer(NODE_LAYER);
+    for (class_box, rect) in boxes.iter().zip(&rects) {
+        draw_class(&mut svg, class_box, *rect);
+    }
+
+    for (i, note) in ast.notes.iter().enumerate() {
+        let rect = rects[note_offset + i];
+        if let Some(&class) = note.class.as_deref().and_then(|class| index.get(class)) {
+            svg.layer(EDGE_LAYER);
+            let edge = svg.palette.edge;
+            svg.polyline(
+                &layout::route(&rect, &rects[class]),
+                Stroke {
+                    color: edge,
+                    width: 1.0,
+                    dashed: true,
+                },
+                None,
+                None,
+            );
+        }
+        svg.layer(NODE_LAYER);
+        let fill = svg.palette.note_fill;
+        let stroke = svg.palette.note_stroke;
+        svg.rect(rect, 0.0, fill, stroke, "");
+        svg.text(rect.center(), &note.text, Anchor::Middle, "");
+    }
+
+    let bounds = layout::bounds(&extents).unwrap_or(Rect::new(0.0, 0.0, 0.0, 0.0));
+    svg.finish(bounds, options.padding)
+}
+
+fn marker(end: RelationEnd) -> &'static str {
+    match end {
+        RelationEnd::Inheritance => "triangle",
+        RelationEnd::Composition => "diamond-filled",
+        RelationEnd::Aggregation => "diamond",
+        RelationEnd::Association => "arrow",
+        RelationEnd::Lollipop => "lollipop",
+    }
+}
+
+/// Position for a cardinality label a little way along the line from `end`
+fn near_end(end: (f64, f64), other: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (other.0 - end.0, other.1 - end.1);
+    let length = (dx * dx + dy * dy).sqrt().max(1.0);
+    let (ux, uy) = (dx / length, dy / length);
+    (end.0 + ux * 20.0 - uy * 10.0, end.1 + uy * 20.0 + ux * 10.0)
+}
+
+fn visibility(visibility: Option<Visibility>) -> &'static str {
+    match visibility {
+        Some(Visibility::Public) => "+",
+        Some(Visibility::Private) => "-",
+        Some(Visibility::Protected) => "#",
+        Some(Visibility::Package) => "~",
+        None => "",
+    }
+}
+
+/// Mermaid writes generics as `List~T~`; show them as `List<T>`
+fn generic_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            // A tilde before a type name opens a generic, any other one closes it
+            '~' if chars.peek().is_some_and(|next| next.is_alphanumeric()) => out.push('<'),
+            '~' => out.push('>'),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn attribute_line(attribute: &Attribute) -> BoxLine {
+    let text = match &attribute.type_name {
+        Some(type_name) => format!(
+            "{}{} {}",
+            visibility(attribute.visibility),
+            generic_text(type_name),
+            attribute.name
+        ),
+        None => format!("{}{}", visibility(attribute.visibility), attribute.name),
+    };
+    BoxLine {
+        text,
+        style: if attribute.is_static {
+            "text-decoration:underline"
+        } else {
+            ""
+        },
+    }
+}
+
+fn method_line(method: &Method) -> BoxLine {
+    let mut text = format!(
+        "{}{}({})",
+        visibility(method.visibility),
+        method.name,
+        generic_text(&method.parameters.join(", "))
+    );
+    if let Some(return_type) = &method.return_type {
+        text.push(' ');
+        text.push_str(&generic_text(return_type));
+    }
+    let style = if method.is_static {
+        "text-decoration:underline"
+    } else if method.is_abstract {
+        "font-style:italic"
+    } else {
+        ""
+    };
+    BoxLine { text, style }
+}
+
+fn class_box(class: &ClassNode) -> ClassBox {
+    let mut header: Vec<BoxLine> = class
+        .annotations
+        .iter()
+        .map(|annotation| BoxLine {
+            text: format!("«{}»", annotation),
+            style: "",
+        })
+        .collect();
+    let mut name = class.label.clone().unwrap_or_else(|| class.name.clone());
+    if let Some(generic) = &class.generic {
+        name = format!("{}<{}>", name, generic_text(generic));
+    }
+    header.push(BoxLine {
+        text: name,
+        style: "font-weight:bold",
+    });
+    ClassBox {
+        header,
+        attributes: class.attributes.iter().map(attribute_line).collect(),
+        methods: class.methods.iter().map(method_line).collect(),
+    }
+}
+
+fn section_height(svg: &Svg, lines: &[BoxLine]) -> f64 {
+    let content = if lines.is_empty() {
+        0.0
+    } else {
+        lines.len() as f64 * svg.line_height()
+    };
+    content + 2.0 * SECTION_PADDING
+}
+
+fn box_size(svg: &Svg, class_box: &ClassBox) -> (f64, f64) {
+    let width = class_box
+        .header
+        .iter()
+        .chain(&class_box.attributes)
+        .chain(&class_box.methods)
+        .map(|line| svg::text_width(&line.text, svg.font_size))
+        .fold(0.0, f64::max)
+        + 2.0 * PADDING_X;
+    let height = section_height(svg, &class_box.header)
+        + section_height(svg, &class_box.attributes)
+        + section_height(svg, &class_box.methods);
+    (width.max(MIN_WIDTH), height)
+}
+
+fn draw_class(svg: &mut Svg, class_box: &ClassBox, rect: Rect) {
+    let fill = svg.palette.node_fill;
+    let stroke = svg.palette.node_stroke;
+    let line_height = svg.line_height();
+    svg.rect(rect, 0.0, fill, stroke, "");
+
+    let mut y = rect.y + SECTION_PADDING;
+    for line in &class_box.header {
+        svg.text(
+            (rect.center().0, y + line_height / 2.0),
+            &line.text,
+            Anchor::Middle,
+            line.style,
+        );
+        y += line_height;
+    }
+    for section in [&class_box.attributes, &class_box.methods] {
+        y += SECTION_PADDING;
+        svg.polyline(
+            &[(rect.x, y), (rect.right(), y)],
+            Stroke {
+                color: stroke,
+                width: 1.0,
+                dashed: false,
+            },
+            None,
+            None,
+        );
+        y += SECTION_PADDING;
+        for line in section.iter() {
+            svg.text(
+                (rect.x + PADDING_X, y + line_height / 2.0),
+                &line.text,
+                Anchor::Start,
+                line.style,
+            );
+            y += line_height;
+        }
+    }
+}
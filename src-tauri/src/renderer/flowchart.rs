@@ -0,0 +1,413 @@
+use super::layout::{self, Rect, Spacing};
+use super::svg::{self, Anchor, Stroke, Svg};
+use super::RenderOptions;
+use crate::mermaid_parser::flowchart::{
+    ArrowHead, Direction, EdgeStroke, FlowNode, FlowchartAst, NodeShape,
+};
+use std::collections::{HashMap, HashSet};
+
+const PADDING_X: f64 = 16.0;
+const PADDING_Y: f64 = 10.0;
+const CLUSTER_PADDING: f64 = 12.0;
+
+const CLUSTER_LAYER: usize = 0;
+const EDGE_LAYER: usize = 1;
+const NODE_LAYER: usize = 2;
+const LABEL_LAYER: usize = 3;
+
+/// Render a flowchart; subgraphs are drawn as clusters around their members
+pub fn render(ast: &FlowchartAst, options: &RenderOptions) -> String {
+    let mut svg = Svg::new(options);
+    let direction = ast.direction.unwrap_or(Direction::TB);
+
+    let subgraph_ids: HashSet<&str> = ast.subgraphs.iter().map(|s| s.id.as_str()).collect();
+    let nodes: Vec<&FlowNode> = ast
+        .nodes
+        .iter()
+        .filter(|node| !subgraph_ids.contains(node.id.as_str()))
+        .collect();
+    let index: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id.as_str(), i))
+        .collect();
+
+    // Edges to a subgraph are laid out as if they pointed at its first member
+    let endpoint = |id: &str| {
+        index
+            .get(id)
+            .copied()
+            .or_else(|| first_member(ast, id, &index, 0))
+    };
+    let edges: Vec<(usize, usize)> = ast
+        .edges
+        .iter()
+        .filter_map(|edge| Some((endpoint(&edge.from)?, endpoint(&edge.to)?)))
+        .collect();
+
+    let sizes: Vec<(f64, f64)> = nodes.iter().map(|node| node_size(&svg, node)).collect();
+    let rects = layout::layered(
+        &sizes,
+        &edges,
+        direction,
+        &Spacing {
+            rank: 50.0,
+            node: 30.0,
+        },
+    );
+
+    let mut clusters: Vec<(&str, Rect)> = ast
+        .subgraphs
+        .iter()
+        .filter_map(|subgraph| {
+            cluster_rect(ast, &subgraph.id, &index, &rects, svg.line_height(), 0)
+                .map(|rect| (subgraph.id.as_str(), rect))
+        })
+        .collect();
+    // Outer clusters are larger, so drawing by decreasing area keeps them behind
+    clusters.sort_by(|a, b| (b.1.width * b.1.height).total_cmp(&(a.1.width * a.1.height)));
+    let cluster_of: HashMap<&str, Rect> = clusters.iter().copied().collect();
+
+    let mut all_rects: Vec<Rect> = rects.clone();
+    all_rects.extend(clusters.iter().map(|(_, rect)| *rect));
+
+    svg.layer(CLUSTER_LAYER);
+    for (id, rect) in &clusters {
+        let palette_fill = svg.palette.cluster_fill;
+        let palette_stroke = svg.palette.cluster_stroke;
+        svg.rect(*rect, 0.0, palette_fill, palette_stroke, "");
+        let title = ast
+            .subgraphs
+            .iter()
+            .find(|subgraph| subgraph.id == *id)
+            .and_then(|subgraph| subgraph.title.as_deref())
+            .unwrap_or(id);
+        let y = rect.y + svg.line_height() / 2.0 + 4.0;
+        svg.text((rect.center().0, y), title, Anchor::Middle, "");
+    }
+
+    let default_link_style = link_style(ast, "default");
+    for (edge_index, edge) in ast.edges.iter().enumerate() {
+        let rect_of = |id: &str| {
+            index
+                .get(id)
+                .map(|&i| rects[i])
+                .or_else(|| cluster_of.get(id).copied())
+        };
+        let (Some(from), Some(to)) = (rect_of(&edge.from), rect_of(&edge.to)) else {
+            continue;
+        };
+        if edge.stroke == EdgeStroke::Invisible {
+            continue;
+        }
+        let points = layout::route(&from, &to);
+        all_rects.extend(points.iter().map(|&(x, y)| Rect::new(x, y, 0.0, 0.0)));
+
+        let styles = link_style(ast, &edge_index.to_string()).or(default_link_style);
+        let stroke = Stroke {
+            color: styles
+                .and_then(|styles| declaration(styles, "stroke"))
+                .unwrap_or(svg.palette.edge),
+            width: styles
+                .and_then(|styles| declaration(styles, "stroke-width"))
+                .and_then(|width| width.trim_end_matches("px").parse().ok())
+                .unwrap_or(if edge.stroke == EdgeStroke::Thick {
+                    3.5
+                } else {
+                    1.5
+                }),
+            dashed: edge.stroke == EdgeStroke::Dotted,
+        };
+        svg.layer(EDGE_LAYER);
+        svg.polyline(
+            &points,
+            stroke,
+            edge.start_head.map(marker),
+            edge.end_head.map(marker),
+        );
+
+        if let Some(label) = &edge.label {
+            svg.layer(LABEL_LAYER);
+            svg.label(layout::midpoint(&points), label);
+        }
+    }
+
+    svg.layer(NODE_LAYER);
+    for (node, rect) in nodes.iter().zip(&rects) {
+        let styles = node_styles(ast, node);
+        draw_node(&mut svg, node, *rect, &styles);
+    }
+
+    let bounds = layout::bounds(&all_rects).unwrap_or(Rect::new(0.0, 0.0, 0.0, 0.0));
+    svg.finish(bounds, options.padding)
+}
+
+fn marker(head: ArrowHead) -> &'static str {
+    match head {
+        ArrowHead::Arrow => "arrow",
+        ArrowHead::Circle => "circle",
+        ArrowHead::Cross => "cross",
+    }
+}
+
+fn label(node: &FlowNode) -> &str {
+    node.label.as_deref().unwrap_or(&node.id)
+}
+
+fn node_size(svg: &Svg, node: &FlowNode) -> (f64, f64) {
+    let (text_width, text_height) = svg.text_size(label(node));
+    let (width, height) = (text_width + 2.0 * PADDING_X, text_height + 2.0 * PADDING_Y);
+    match node.shape {
+        NodeShape::Circle => {
+            let diameter = text_width.max(text_height) + 2.0 * PADDING_Y;
+            (diameter, diameter)
+        }
+        NodeShape::DoubleCircle => {
+            let diameter = text_width.max(text_height) + 2.0 * PADDING_Y + 10.0;
+            (diameter, diameter)
+        }
+        NodeShape::Rhombus => {
+            let side = text_width + text_height + 2.0 * PADDING_Y;
+            (side, side)
+        }
+        NodeShape::Hexagon
+        | NodeShape::Parallelogram
+        | NodeShape::ParallelogramAlt
+        | NodeShape::Trapezoid
+        | NodeShape::TrapezoidAlt => (width + height, height),
+        NodeShape::Asymmetric => (width + height / 2.0, height),
+        NodeShape::Cylinder => (width, height + 16.0),
+        _ => (width, height),
+    }
+}
+
+fn draw_node(svg: &mut Svg, node: &FlowNode, rect: Rect, styles: &str) {
+    let fill = svg.palette.node_fill;
+    let stroke = svg.palette.node_stroke;
+    let (cx, cy) = rect.center();
+    let (x, y, right, bottom) = (rect.x, rect.y, rect.right(), rect.bottom());
+    let skew = rect.height / 2.0;
+    let mut text_x = cx;
+
+    match node.shape {
+        NodeShape::Default | NodeShape::Rectangle => svg.rect(rect, 0.0, fill, stroke, styles),
+        NodeShape::Round => svg.rect(rect, 5.0, fill, stroke, styles),
+        NodeShape::Stadium => svg.rect(rect, rect.height / 2.0, fill, stroke, styles),
+        NodeShape::Subroutine => {
+            svg.rect(rect, 0.0, fill, stroke, styles);
+            let d = format!(
+                "M{},{} L{},{} M{},{} L{},{}",
+                svg::num(x + 8.0),
+                svg::num(y),
+                svg::num(x + 8.0),
+                svg::num(bottom),
+                svg::num(right - 8.0),
+                svg::num(y),
+                svg::num(right - 8.0),
+                svg::num(bottom)
+            );
+            svg.path(&d, "none", stroke, styles);
+        }
+        NodeShape::Cylinder => {
+            let (rx, ry) = (rect.width / 2.0, 8.0);
+            let d = format!(
+                "M{x},{top} a{rx},{ry} 0 0 0 {w},0 a{rx},{ry} 0 0 0 -{w},0 l0,{h} a{rx},{ry} 0 0 0 {w},0 l0,-{h}",
+                x = svg::num(x),
+                top = svg::num(y + ry),
+                rx = svg::num(rx),
+                ry = svg::num(ry),
+                w = svg::num(rect.width),
+                h = svg::num(rect.height - 2.0 * ry)
+            );
+            svg.path(&d, fill, stroke, styles);
+        }
+        NodeShape::Circle => svg.circle((cx, cy), rect.width / 2.0, fill, stroke, styles),
+        NodeShape::DoubleCircle => {
+            svg.circle((cx, cy), rect.width / 2.0, fill, stroke, styles);
+            svg.circle((cx, cy), rect.width / 2.0 - 5.0, fill, stroke, styles);
+        }
+        NodeShape::Asymmetric => {
+            text_x += skew / 2.0;
+            svg.polygon(
+                &[
+                    (x, y),
+                    (right, y),
+                    (right, bottom),
+                    (x, bottom),
+                    (x + skew, cy),
+                ],
+                fill,
+                stroke,
+                styles,
+            );
+        }
+        NodeShape::Rhombus => svg.polygon(
+            &[(cx, y), (right, cy), (cx, bottom), (x, cy)],
+            fill,
+            stroke,
+            styles,
+        ),
+        NodeShape::Hexagon => svg.polygon(
+            &[
+                (x + skew, y),
+                (right - skew, y),
+                (right, cy),
+                (right - skew, bottom),
+                (x + skew, bottom),
+                (x, cy),
+            ],
+            fill,
+            stroke,
+            styles,
+        ),
+        NodeShape::Parallelogram => svg.polygon(
+            &[
+                (x + skew, y),
+                (right, y),
+                (right - skew, bottom),
+                (x, bottom),
+            ],
+            fill,
+            stroke,
+            styles,
+        ),
+        NodeShape::ParallelogramAlt => svg.polygon(
+            &[
+                (x, y),
+                (right - skew, y),
+                (right, bottom),
+                (x + skew, bottom),
+            ],
+            fill,
+            stroke,
+            styles,
+        ),
+        NodeShape::Trapezoid => svg.polygon(
+            &[
+                (x + skew, y),
+                (right - skew, y),
+                (right, bottom),
+                (x, bottom),
+            ],
+            fill,
+            stroke,
+            styles,
+        ),
+        NodeShape::TrapezoidAlt => svg.polygon(
+            &[
+                (x, y),
+                (right, y),
+                (right - skew, bottom),
+                (x + skew, bottom),
+            ],
+            fill,
+            stroke,
+            styles,
+        ),
+    }
+
+    // Mermaid's `color:` styles the label text
+    let text_style = declaration(styles, "color")
+        .map(|color| format!("fill:{}", color))
+        .unwrap_or_default();
+    svg.text((text_x, cy), label(node), Anchor::Middle, &text_style);
+}
+
+/// The first node inside a subgraph, looking into nested subgraphs if needed
+fn first_member(
+    ast: &FlowchartAst,
+    id: &str,
+    index: &HashMap<&str, usize>,
+    depth: usize,
+) -> Option<usize> {
+    let subgraph = ast.subgraphs.iter().find(|subgraph| subgraph.id == id)?;
+    if depth > ast.subgraphs.len() {
+        return None;
+    }
+    subgraph
+        .nodes
+        .iter()
+        .find_map(|node| index.get(node.as_str()).copied())
+        .or_else(|| {
+            subgraph
+                .subgraphs
+                .iter()
+                .find_map(|child| first_member(ast, child, index, depth + 1))
+        })
+}
+
+/// Box around a subgraph's nodes and nested subgraphs, with room for the title
+fn cluster_rect(
+    ast: &FlowchartAst,
+    id: &str,
+    index: &HashMap<&str, usize>,
+    rects: &[Rect],
+    title_height: f64,
+    depth: usize,
+) -> Option<Rect> {
+    let subgraph = ast.subgraphs.iter().find(|subgraph| subgraph.id == id)?;
+    if depth > ast.subgraphs.len() {
+        return None;
+    }
+    let mut members: Vec<Rect> = subgraph
+        .nodes
+        .iter()
+        .filter_map(|node| index.get(node.as_str()).map(|&i| rects[i]))
+        .collect();
+    members.extend(
+        subgraph
+            .subgraphs
+            .iter()
+            .filter_map(|child| cluster_rect(ast, child, index, rects, title_height, depth + 1)),
+    );
+    let inner = layout::bounds(&members)?.inflate(CLUSTER_PADDING, CLUSTER_PADDING);
+    Some(Rect::new(
+        inner.x,
+        inner.y - title_height,
+        inner.width,
+        inner.height + title_height,
+    ))
+}
+
+/// CSS for a node from its classes, `class` statements and `style` statements
+fn node_styles(ast: &FlowchartAst, node: &FlowNode) -> String {
+    let mut classes: Vec<&str> = node.classes.iter().map(String::as_str).collect();
+    for assignment in &ast.class_assignments {
+        if assignment.nodes.iter().any(|id| id == &node.id) {
+            classes.push(&assignment.class_name);
+        }
+    }
+
+    let mut declarations = Vec::new();
+    for class in classes {
+        for class_def in &ast.class_defs {
+            if class_def.names.iter().any(|name| name == class) {
+                declarations.push(svg::css_declarations(&class_def.styles));
+            }
+        }
+    }
+    for style in &ast.styles {
+        if style.node == node.id {
+            declarations.push(svg::css_declarations(&style.styles));
+        }
+    }
+    declarations.retain(|declaration| !declaration.is_empty());
+    declarations.join(";")
+}
+
+fn link_style<'a>(ast: &'a FlowchartAst, link: &str) -> Option<&'a str> {
+    ast.link_styles
+        .iter()
+        .rev()
+        .find(|style| style.links.iter().any(|l| l == link))
+        .map(|style| style.styles.as_str())
+}
+
+/// Value of one property in a Mermaid or CSS style list
+fn declaration<'a>(styles: &'a str, property: &str) -> Option<&'a str> {
+    styles
+        .split([',', ';'])
+        .filter_map(|declaration| declaration.split_once(':'))
+        .find(|(name, _)| name.trim() == property)
+        .map(|(_, value)| value.trim())
+}
@@ -0,0 +1,351 @@
+use crate::mermaid_parser::flowchart::Direction;
+
+/// Axis-aligned box in SVG user units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(
+            x,
+            y,
+            self.right().max(other.right()) - x,
+            self.bottom().max(other.bottom()) - y,
+        )
+    }
+
+    pub fn inflate(&self, dx: f64, dy: f64) -> Rect {
+        Rect::new(
+            self.x - dx,
+            self.y - dy,
+            self.width + 2.0 * dx,
+            self.height + 2.0 * dy,
+        )
+    }
+
+    /// Where the line from the center towards `target` leaves the box
+    pub fn boundary_toward(&self, target: (f64, f64)) -> (f64, f64) {
+        let (cx, cy) = self.center();
+        let (dx, dy) = (target.0 - cx, target.1 - cy);
+        if dx == 0.0 && dy == 0.0 {
+            return (cx, cy);
+        }
+        let scale_x = if dx == 0.0 {
+            f64::INFINITY
+        } else {
+            (self.width / 2.0) / dx.abs()
+        };
+        let scale_y = if dy == 0.0 {
+            f64::INFINITY
+        } else {
+            (self.height / 2.0) / dy.abs()
+        };
+        let scale = scale_x.min(scale_y);
+        (cx + dx * scale, cy + dy * scale)
+    }
+}
+
+/// Smallest box containing all the given boxes
+pub fn bounds<'a>(rects: impl IntoIterator<Item = &'a Rect>) -> Option<Rect> {
+    rects.into_iter().fold(None, |acc: Option<Rect>, rect| {
+        Some(acc.map_or(*rect, |acc| acc.union(rect)))
+    })
+}
+
+/// Straight route between two boxes, or a small loop on the right for a self-edge
+pub fn route(from: &Rect, to: &Rect) -> Vec<(f64, f64)> {
+    if from == to {
+        let (x, y) = (from.right(), from.center().1);
+        return vec![
+            (x, y - 6.0),
+            (x + 20.0, y - 12.0),
+            (x + 20.0, y + 12.0),
+            (x, y + 6.0),
+        ];
+    }
+    vec![
+        from.boundary_toward(to.center()),
+        to.boundary_toward(from.center()),
+    ]
+}
+
+/// Unit normal to the line from `a` to `b`, pointing to its right in screen space
+pub fn normal(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = (dx * dx + dy * dy).sqrt().max(1.0);
+    (-dy / length, dx / length)
+}
+
+/// Move a straight route sideways so edges in opposite directions do not overlap
+pub fn offset(points: &mut [(f64, f64)], distance: f64) {
+    let (nx, ny) = normal(points[0], points[points.len() - 1]);
+    for point in points.iter_mut() {
+        point.0 += nx * distance;
+        point.1 += ny * distance;
+    }
+}
+
+/// Point halfway along a route, for its label
+pub fn midpoint(points: &[(f64, f64)]) -> (f64, f64) {
+    if points.len() > 2 {
+        let (a, b) = (points[1], points[2]);
+        return ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    }
+    let (a, b) = (points[0], points[points.len() - 1]);
+    ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+/// Gaps between ranks and between neighbouring nodes of a rank
+pub struct Spacing {
+    pub rank: f64,
+    pub node: f64,
+}
+
+/// Lay out a directed graph in ranks, in the spirit of Sugiyama's method.
+///
+/// Cycles are broken by reversing DFS back edges, nodes are ranked by longest
+/// path, ordered within ranks by barycenter sweeps, and every rank is centered
+/// on the cross axis. Returns one box per node, in input order.
+pub fn layered(
+    sizes: &[(f64, f64)],
+    edges: &[(usize, usize)],
+    direction: Direction,
+    spacing: &Spacing,
+) -> Vec<Rect> {
+    let count = sizes.len();
+    if count == 0 {
+        return Vec::new();
+    }
+    let dag = acyclic_edges(count, edges);
+    let ranks = longest_path_ranks(count, &dag);
+    let layers = order_layers(count, &dag, &ranks);
+
+    let vertical = matches!(direction, Direction::TB | Direction::TD | Direction::BT);
+    let (main_size, cross_size): (Vec<f64>, Vec<f64>) = sizes
+        .iter()
+        .map(|&(width, height)| {
+            if vertical {
+                (height, width)
+            } else {
+                (width, height)
+            }
+        })
+        .unzip();
+
+    let mut main_offset = Vec::with_capacity(layers.len());
+    let mut extents = Vec::with_capacity(layers.len());
+    let mut offset = 0.0;
+    for layer in &layers {
+        let extent = layer.iter().map(|&v| main_size[v]).fold(0.0, f64::max);
+        main_offset.push(offset);
+        extents.push(extent);
+        offset += extent + spacing.rank;
+    }
+    let total_main = offset - spacing.rank;
+
+    let layer_width = |layer: &Vec<usize>| {
+        layer.iter().map(|&v| cross_size[v]).sum::<f64>()
+            + spacing.node * layer.len().saturating_sub(1) as f64
+    };
+    let widest = layers.iter().map(layer_width).fold(0.0, f64::max);
+
+    let mut rects = vec![Rect::new(0.0, 0.0, 0.0, 0.0); count];
+    for (rank, layer) in layers.iter().enumerate() {
+        let mut cross = (widest - layer_width(layer)) / 2.0;
+        for &v in layer {
+            let main = main_offset[rank] + (extents[rank] - main_size[v]) / 2.0;
+            let main = match direction {
+                Direction::BT | Direction::RL => total_main - main - main_size[v],
+                _ => main,
+            };
+            let (width, height) = sizes[v];
+            rects[v] = if vertical {
+                Rect::new(cross, main, width, height)
+            } else {
+                Rect::new(main, cross, width, height)
+            };
+            cross += cross_size[v] + spacing.node;
+        }
+    }
+    rects
+}
+
+/// Drop self-loops and reverse the back edges found by a depth-first search
+fn acyclic_edges(count: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut outgoing = vec![Vec::new(); count];
+    for &(from, to) in edges {
+        if from != to {
+            outgoing[from].push(to);
+        }
+    }
+
+    // 0 = unvisited, 1 = on the DFS stack, 2 = done
+    let mut state = vec![0u8; count];
+    let mut dag = Vec::new();
+    for root in 0..count {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0usize)];
+        state[root] = 1;
+        while let Some(&(node, next)) = stack.last() {
+            if let Some(&target) = outgoing[node].get(next) {
+                stack.last_mut().unwrap().1 += 1;
+                match state[target] {
+                    0 => {
+                        dag.push((node, target));
+                        state[target] = 1;
+                        stack.push((target, 0));
+                    }
+                    1 => dag.push((target, node)),
+                    _ => dag.push((node, target)),
+                }
+            } else {
+                state[node] = 2;
+                stack.pop();
+            }
+        }
+    }
+    dag
+}
+
+fn longest_path_ranks(count: usize, dag: &[(usize, usize)]) -> Vec<usize> {
+    let mut indegree = vec![0usize; count];
+    let mut outgoing = vec![Vec::new(); count];
+    for &(from, to) in dag {
+        indegree[to] += 1;
+        outgoing[from].push(to);
+    }
+    let mut ranks = vec![0usize; count];
+    let mut ready: Vec<usize> = (0..count).rev().filter(|&v| indegree[v] == 0).collect();
+    while let Some(node) = ready.pop() {
+        for &target in &outgoing[node] {
+            ranks[target] = ranks[target].max(ranks[node] + 1);
+            indegree[target] -= 1;
+            if indegree[target] == 0 {
+                ready.push(target);
+            }
+        }
+    }
+    ranks
+}
+
+fn order_layers(count: usize, dag: &[(usize, usize)], ranks: &[usize]) -> Vec<Vec<usize>> {
+    let depth = ranks.iter().max().map_or(0, |max| max + 1);
+    let mut layers = vec![Vec::new(); depth];
+    for node in 0..count {
+        layers[ranks[node]].push(node);
+    }
+
+    let mut position = vec![0.0; count];
+    let index_positions = |layers: &Vec<Vec<usize>>, position: &mut Vec<f64>| {
+        for layer in layers {
+            for (index, &node) in layer.iter().enumerate() {
+                position[node] = index as f64;
+            }
+        }
+    };
+    index_positions(&layers, &mut position);
+
+    for sweep in 0..4 {
+        let downward = sweep % 2 == 0;
+        let order: Vec<usize> = if downward {
+            (1..depth).collect()
+        } else {
+            (0..depth.saturating_sub(1)).rev().collect()
+        };
+        for rank in order {
+            let barycenter = |node: usize| {
+                let neighbours: Vec<f64> = dag
+                    .iter()
+                    .filter_map(|&(from, to)| {
+                        if downward && to == node && ranks[from] < rank {
+                            Some(position[from])
+                        } else if !downward && from == node && ranks[to] > rank {
+                            Some(position[to])
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                if neighbours.is_empty() {
+                    position[node]
+                } else {
+                    neighbours.iter().sum::<f64>() / neighbours.len() as f64
+                }
+            };
+            let mut keyed: Vec<(f64, usize)> = layers[rank]
+                .iter()
+                .map(|&node| (barycenter(node), node))
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[rank] = keyed.into_iter().map(|(_, node)| node).collect();
+            index_positions(&layers, &mut position);
+        }
+    }
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layered_ranks_follow_edges() {
+        let sizes = vec![(40.0, 20.0); 4];
+        let spacing = Spacing {
+            rank: 30.0,
+            node: 10.0,
+        };
+        // A cycle 0 -> 1 -> 2 -> 0 plus a branch 0 -> 3
+        let rects = layered(
+            &sizes,
+            &[(0, 1), (1, 2), (2, 0), (0, 3)],
+            Direction::TD,
+            &spacing,
+        );
+        assert_eq!(rects[0].y, 0.0);
+        assert_eq!(rects[1].y, 50.0);
+        assert_eq!(rects[3].y, 50.0);
+        assert_eq!(rects[2].y, 100.0);
+
+        let rects = layered(&sizes[..2], &[(0, 1)], Direction::RL, &spacing);
+        assert!(rects[0].x > rects[1].x);
+        assert_eq!(rects[0].y, rects[1].y);
+    }
+
+    #[test]
+    fn test_boundary_toward() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 40.0);
+        assert_eq!(rect.boundary_toward((50.0, 100.0)), (50.0, 40.0));
+        assert_eq!(rect.boundary_toward((200.0, 20.0)), (100.0, 20.0));
+    }
+}
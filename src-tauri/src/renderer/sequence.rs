@@ -0,0 +1,504 @@
+use super::layout::{self, Rect};
+use super::svg::{self, Anchor, Stroke, Svg};
+use super::RenderOptions;
+use crate::mermaid_parser::sequence::{
+    Block, BlockKind, Message, MessageArrow, Note, NotePlacement, ParticipantKind, SequenceAst,
+    SequenceStatement,
+};
+use std::collections::HashMap;
+
+const BOX_MIN_WIDTH: f64 = 100.0;
+const PADDING_X: f64 = 14.0;
+const PARTICIPANT_GAP: f64 = 40.0;
+const SELF_MESSAGE_WIDTH: f64 = 36.0;
+const ACTIVATION_WIDTH: f64 = 10.0;
+
+const BACKGROUND_LAYER: usize = 0;
+const LIFELINE_LAYER: usize = 1;
+const FRAME_LAYER: usize = 2;
+const ACTIVATION_LAYER: usize = 3;
+const MESSAGE_LAYER: usize = 4;
+const NOTE_LAYER: usize = 5;
+const PARTICIPANT_LAYER: usize = 6;
+
+struct Renderer<'a> {
+    ast: &'a SequenceAst,
+    svg: Svg,
+    index: HashMap<&'a str, usize>,
+    centers: Vec<f64>,
+    widths: Vec<f64>,
+    y: f64,
+    number: usize,
+    /// Start heights of the open activations of each participant
+    active: HashMap<usize, Vec<f64>>,
+    extents: Vec<Rect>,
+}
+
+/// Render a sequence diagram top to bottom, with participants mirrored at the bottom
+pub fn render(ast: &SequenceAst, options: &RenderOptions) -> String {
+    let svg = Svg::new(options);
+    let index = ast
+        .participants
+        .iter()
+        .enumerate()
+        .map(|(i, participant)| (participant.id.as_str(), i))
+        .collect();
+    let mut renderer = Renderer {
+        ast,
+        svg,
+        index,
+        centers: Vec::new(),
+        widths: Vec::new(),
+        y: 0.0,
+        number: 0,
+        active: HashMap::new(),
+        extents: Vec::new(),
+    };
+    renderer.place_participants();
+
+    let line_height = renderer.svg.line_height();
+    let box_height = renderer.box_height();
+    let mut top = 0.0;
+    if let Some(title) = &ast.title {
+        let center = renderer.centers.last().copied().unwrap_or(0.0) / 2.0;
+        renderer.svg.layer(PARTICIPANT_LAYER);
+        renderer.svg.text(
+            (center, line_height / 2.0),
+            title,
+            Anchor::Middle,
+            "font-weight:bold",
+        );
+        let (width, _) = renderer.svg.text_size(title);
+        renderer
+            .extents
+            .push(Rect::new(center - width / 2.0, 0.0, width, line_height));
+        top = line_height + 10.0;
+    }
+
+    renderer.y = top + box_height + 20.0;
+    renderer.statements(&ast.statements, 0);
+
+    let bottom = renderer.y + 10.0;
+    let mut open: Vec<(usize, Vec<f64>)> = renderer.active.drain().collect();
+    open.sort_by_key(|(participant, _)| *participant);
+    for (participant, starts) in open {
+        for (level, start) in starts.iter().enumerate() {
+            renderer.activation_box(participant, level, *start, bottom);
+        }
+    }
+
+    for participant in 0..ast.participants.len() {
+        let x = renderer.centers[participant];
+        renderer.svg.layer(LIFELINE_LAYER);
+        let edge = renderer.svg.palette.node_stroke;
+        renderer.svg.polyline(
+            &[(x, top + box_height), (x, bottom)],
+            Stroke {
+                color: edge,
+                width: 0.5,
+                dashed: true,
+            },
+            None,
+            None,
+        );
+        renderer.participant(participant, top);
+        renderer.participant(participant, bottom);
+    }
+    let bounds = layout::bounds(&renderer.extents).unwrap_or(Rect::new(0.0, 0.0, 0.0, 0.0));
+    renderer.svg.finish(bounds, options.padding)
+}
+
+impl Renderer<'_> {
+    fn display_name(&self, participant: usize) -> &str {
+        let participant = &self.ast.participants[participant];
+        participant.alias.as_deref().unwrap_or(&participant.id)
+    }
+
+    fn box_height(&self) -> f64 {
+        let has_actor = self
+            .ast
+            .participants
+            .iter()
+            .any(|participant| participant.kind == ParticipantKind::Actor);
+        let text = self.svg.line_height() + 20.0;
+        if has_actor {
+            text + 36.0
+        } else {
+            text
+        }
+    }
+
+    fn message_text(&mut self, message: &Message) -> String {
+        if self.ast.autonumber {
+            self.number += 1;
+            format!("{}. {}", self.number, message.text)
+        } else {
+            message.text.clone()
+        }
+    }
+
+    /// Space participants so every message label fits between its lifelines
+    fn place_participants(&mut self) {
+        let font_size = self.svg.font_size;
+        self.widths = (0..self.ast.participants.len())
+            .map(|i| {
+                let (width, _) = self.svg.text_size(self.display_name(i));
+                (width + 2.0 * PADDING_X).max(BOX_MIN_WIDTH)
+            })
+            .collect();
+
+        let count = self.widths.len();
+        let mut gaps: Vec<f64> = (1..count)
+            .map(|i| self.widths[i - 1] / 2.0 + self.widths[i] / 2.0 + PARTICIPANT_GAP)
+            .collect();
+
+        let mut spans: Vec<(usize, usize, f64)> = Vec::new();
+        for message in self.ast.messages() {
+            let (Some(&from), Some(&to)) = (
+                self.index.get(message.from.as_str()),
+                self.index.get(message.to.as_str()),
+            ) else {
+                continue;
+            };
+            let width = svg::text_width(&message.text, font_size) + 30.0;
+            if from == to {
+                // Self messages put their label to the right of the loop
+                if from + 1 < count {
+                    spans.push((from, from + 1, width + SELF_MESSAGE_WIDTH));
+                }
+            } else {
+                spans.push((from.min(to), from.max(to), width));
+            }
+        }
+        spans.sort_by_key(|&(from, to, _)| to - from);
+        for (from, to, width) in spans {
+            let current: f64 = gaps[from..to].iter().sum();
+            if current < width {
+                gaps[to - 1] += width - current;
+            }
+        }
+
+        let mut x = self.widths.first().map_or(0.0, |width| width / 2.0);
+        self.centers = Vec::with_capacity(count);
+        for i in 0..count {
+            if i > 0 {
+                x += gaps[i - 1];
+            }
+            self.centers.push(x);
+        }
+    }
+
+    fn participant(&mut self, participant: usize, top: f64) {
+        let x = self.centers[participant];
+        let width = self.widths[participant];
+        let height = self.box_height();
+        let name = self.display_name(participant).to_string();
+        let fill = self.svg.palette.node_fill;
+        let stroke = self.svg.palette.node_stroke;
+        let rect = Rect::new(x - width / 2.0, top, width, height);
+        self.extents.push(rect);
+        self.svg.layer(PARTICIPANT_LAYER);
+
+        if self.ast.participants[participant].kind == ParticipantKind::Actor {
+            let (head, neck, hip, feet) = (top + 10.0, top + 18.0, top + 30.0, top + 42.0);
+            self.svg.circle((x, head), 8.0, fill, stroke, "");
+            let d = format!(
+                "M{x},{neck} L{x},{hip} M{l},{arms} L{r},{arms} M{x},{hip} L{l},{feet} M{x},{hip} L{r},{feet}",
+                x = svg::num(x),
+                neck = svg::num(neck),
+                hip = svg::num(hip),
+                l = svg::num(x - 12.0),
+                r = svg::num(x + 12.0),
+                arms = svg::num(neck + 4.0),
+                feet = svg::num(feet)
+            );
+            self.svg.path(&d, "none", stroke, "");
+            let text_y = top + height - self.svg.line_height() / 2.0 - 4.0;
+            self.svg.text((x, text_y), &name, Anchor::Middle, "");
+        } else {
+            self.svg.rect(rect, 3.0, fill, stroke, "");
+            self.svg
+                .text((x, top + height / 2.0), &name, Anchor::Middle, "");
+        }
+    }
+
+    fn activation_box(&mut self, participant: usize, level: usize, start: f64, end: f64) {
+        let x = self.centers[participant] - ACTIVATION_WIDTH / 2.0 + level as f64 * 4.0;
+        let fill = self.svg.palette.accent;
+        let stroke = self.svg.palette.node_stroke;
+        self.svg.layer(ACTIVATION_LAYER);
+        self.svg.rect(
+            Rect::new(x, start, ACTIVATION_WIDTH, (end - start).max(4.0)),
+            0.0,
+            fill,
+            stroke,
+            "",
+        );
+    }
+
+    fn activate(&mut self, participant: &str) {
+        if let Some(&index) = self.index.get(participant) {
+            self.active.entry(index).or_default().push(self.y);
+        }
+    }
+
+    fn deactivate(&mut self, participant: &str) {
+        let Some(&index) = self.index.get(participant) else {
+            return;
+        };
+        let Some(start) = self.active.get_mut(&index).and_then(Vec::pop) else {
+            return;
+        };
+        let level = self.active[&index].len();
+        let end = self.y;
+        self.activation_box(index, level, start, end);
+    }
+
+    fn statements(&mut self, statements: &[SequenceStatement], depth: usize) {
+        for statement in statements {
+            match statement {
+                SequenceStatement::Message(message) => self.message(message),
+                SequenceStatement::Activation(activation) => {
+                    if activation.active {
+                        self.activate(&activation.participant);
+                    } else {
+                        self.deactivate(&activation.participant);
+                    }
+                }
+                SequenceStatement::Note(note) => self.note(note),
+                SequenceStatement::Block(block) => self.block(block, depth),
+            }
+        }
+    }
+
+    fn message(&mut self, message: &Message) {
+        let (Some(&from), Some(&to)) = (
+            self.index.get(message.from.as_str()),
+            self.index.get(message.to.as_str()),
+        ) else {
+            return;
+        };
+        let text = self.message_text(message);
+        let (_, text_height) = self.svg.text_size(&text);
+        self.y += text_height;
+
+        let (start_marker, end_marker) = match message.arrow {
+            MessageArrow::Solid | MessageArrow::Dotted => (None, None),
+            MessageArrow::SolidArrow | MessageArrow::DottedArrow => (None, Some("arrow")),
+            MessageArrow::SolidCross | MessageArrow::DottedCross => (None, Some("cross")),
+            MessageArrow::SolidOpen | MessageArrow::DottedOpen => (None, Some("open-arrow")),
+            MessageArrow::BidirectionalSolid | MessageArrow::BidirectionalDotted => {
+                (Some("arrow"), Some("arrow"))
+            }
+        };
+        let stroke = Stroke {
+            color: self.svg.palette.edge,
+            width: 1.5,
+            dashed: matches!(
+                message.arrow,
+                MessageArrow::Dotted
+                    | MessageArrow::DottedArrow
+                    | MessageArrow::DottedCross
+                    | MessageArrow::DottedOpen
+                    | MessageArrow::BidirectionalDotted
+            ),
+        };
+
+        self.svg.layer(MESSAGE_LAYER);
+        let (x1, x2) = (self.centers[from], self.centers[to]);
+        if from == to {
+            let right = x1 + SELF_MESSAGE_WIDTH;
+            self.svg.polyline(
+                &[
+                    (x1, self.y),
+                    (right, self.y),
+                    (right, self.y + 20.0),
+                    (x1, self.y + 20.0),
+                ],
+                stroke,
+                start_marker,
+                end_marker,
+            );
+            self.svg.text(
+                (right + 6.0, self.y - text_height / 2.0 + 4.0),
+                &text,
+                Anchor::Start,
+                "",
+            );
+            self.y += 20.0;
+        } else {
+            self.svg.polyline(
+                &[(x1, self.y), (x2, self.y)],
+                stroke,
+                start_marker,
+                end_marker,
+            );
+            self.svg.text(
+                ((x1 + x2) / 2.0, self.y - text_height / 2.0 - 2.0),
+                &text,
+                Anchor::Middle,
+                "",
+            );
+        }
+
+        if message.activate_target {
+            self.activate(&message.to);
+        }
+        if message.deactivate_source {
+            self.deactivate(&message.from);
+        }
+        self.y += 20.0;
+    }
+
+    fn note(&mut self, note: &Note) {
+        let participants: Vec<usize> = note
+            .participants
+            .iter()
+            .filter_map(|participant| self.index.get(participant.as_str()).copied())
+            .collect();
+        let Some(&first) = participants.first() else {
+            return;
+        };
+        let last = participants.last().copied().unwrap_or(first);
+        let (text_width, text_height) = self.svg.text_size(&note.text);
+        let width = (text_width + 20.0).max(80.0);
+        let height = text_height + 16.0;
+
+        let (x1, x2) = (self.centers[first], self.centers[last]);
+        let x = match note.placement {
+            NotePlacement::LeftOf => x1 - 10.0 - width,
+            NotePlacement::RightOf => x1 + 10.0,
+            NotePlacement::Over if first == last => x1 - width / 2.0,
+            NotePlacement::Over => x1.min(x2) - 40.0,
+        };
+        let width = match note.placement {
+            NotePlacement::Over if first != last => width.max((x2 - x1).abs() + 80.0),
+            _ => width,
+        };
+        let rect = Rect::new(x, self.y, width, height);
+        self.extents.push(rect);
+
+        let fill = self.svg.palette.note_fill;
+        let stroke = self.svg.palette.note_stroke;
+        self.svg.layer(NOTE_LAYER);
+        self.svg.rect(rect, 0.0, fill, stroke, "");
+        self.svg.text(rect.center(), &note.text, Anchor::Middle, "");
+        self.y += height + 10.0;
+    }
+
+    /// Participants that any statement inside the block refers to
+    fn involved(&self, statements: &[SequenceStatement], out: &mut Vec<usize>) {
+        for statement in statements {
+            let ids: Vec<&str> = match statement {
+                SequenceStatement::Message(message) => vec![&message.from, &message.to],
+                SequenceStatement::Activation(activation) => vec![&activation.participant],
+                SequenceStatement::Note(note) => {
+                    note.participants.iter().map(String::as_str).collect()
+                }
+                SequenceStatement::Block(block) => {
+                    for branch in &block.branches {
+                        self.involved(&branch.statements, out);
+                    }
+                    Vec::new()
+                }
+            };
+            out.extend(ids.iter().filter_map(|id| self.index.get(id).copied()));
+        }
+    }
+
+    fn block(&mut self, block: &Block, depth: usize) {
+        let mut involved = Vec::new();
+        for branch in &block.branches {
+            self.involved(&branch.statements, &mut involved);
+        }
+        let (first, last) = match (involved.iter().min(), involved.iter().max()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => (0, self.centers.len().saturating_sub(1)),
+        };
+        if self.centers.is_empty() {
+            return;
+        }
+        let inset = depth as f64 * 6.0;
+        let left = self.centers[first] - self.widths[first] / 2.0 - 10.0 + inset;
+        let right = self.centers[last] + self.widths[last] / 2.0 + 10.0 - inset;
+
+        let keyword = format!("{:?}", block.kind).to_lowercase();
+        let label = block
+            .branches
+            .first()
+            .map(|branch| branch.label.as_str())
+            .unwrap_or("");
+        // The keyword is bold, so allow for wider glyphs than the estimate
+        let keyword_width = svg::text_width(&keyword, self.svg.font_size) * 1.15 + 14.0;
+        let header_width =
+            keyword_width + svg::text_width(&format!("[{}]", label), self.svg.font_size) + 12.0;
+        let right = right.max(left + header_width);
+
+        let line_height = self.svg.line_height();
+        let top = self.y;
+        let mut separators = Vec::new();
+        self.y += line_height + 10.0;
+        for (index, branch) in block.branches.iter().enumerate() {
+            if index > 0 {
+                separators.push((self.y, branch.label.clone()));
+                self.y += line_height + 10.0;
+            }
+            self.statements(&branch.statements, depth + 1);
+        }
+        let rect = Rect::new(left, top, right - left, self.y - top);
+        self.y += 10.0;
+        self.extents.push(rect);
+
+        let edge = self.svg.palette.edge;
+        if block.kind == BlockKind::Rect {
+            let fill = block
+                .branches
+                .first()
+                .map(|branch| branch.label.trim())
+                .filter(|label| !label.is_empty())
+                .unwrap_or(self.svg.palette.accent)
+                .to_string();
+            self.svg.layer(BACKGROUND_LAYER);
+            self.svg.rect(rect, 0.0, &fill, "none", "");
+            return;
+        }
+
+        self.svg.layer(FRAME_LAYER);
+        self.svg.rect(rect, 0.0, "none", edge, "");
+        let text_y = top + line_height / 2.0 + 4.0;
+        self.svg.text(
+            (left + 6.0, text_y),
+            &keyword,
+            Anchor::Start,
+            "font-weight:bold",
+        );
+        if !label.is_empty() {
+            self.svg.text(
+                (left + keyword_width, text_y),
+                &format!("[{}]", label),
+                Anchor::Start,
+                "",
+            );
+        }
+        for (y, label) in separators {
+            self.svg.polyline(
+                &[(left, y), (right, y)],
+                Stroke {
+                    color: edge,
+                    width: 1.0,
+                    dashed: true,
+                },
+                None,
+                None,
+            );
+            if !label.is_empty() {
+                self.svg.text(
+                    ((left + right) / 2.0, y + line_height / 2.0 + 4.0),
+                    &format!("[{}]", label),
+                    Anchor::Middle,
+                    "",
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use super::layout::{self, Rect, Spacing};
+use super::svg::{Anchor, Stroke, Svg};
+use super::RenderOptions;
+use crate::mermaid_parser::flowchart::Direction;
+use crate::mermaid_parser::state_diagram::{NoteSide, StateDiagramAst, StateKind, StateNode};
+use std::collections::HashMap;
+
+const PADDING_X: f64 = 14.0;
+const PADDING_Y: f64 = 8.0;
+const CLUSTER_PADDING: f64 = 14.0;
+const NOTE_GAP: f64 = 20.0;
+
+const CLUSTER_LAYER: usize = 0;
+const EDGE_LAYER: usize = 1;
+const NODE_LAYER: usize = 2;
+const LABEL_LAYER: usize = 3;
+
+/// Render a state diagram; composite states are drawn as clusters around their children
+pub fn render(ast: &StateDiagramAst, options: &RenderOptions) -> String {
+    let mut svg = Svg::new(options);
+    let direction = ast.direction.unwrap_or(Direction::TB);
+    let vertical = matches!(direction, Direction::TB | Direction::TD | Direction::BT);
+
+    let states: Vec<&StateNode> = ast
+        .states
+        .iter()
+        .filter(|state| state.kind != StateKind::Composite)
+        .collect();
+    let index: HashMap<&str, usize> = states
+        .iter()
+        .enumerate()
+        .map(|(i, state)| (state.id.as_str(), i))
+        .collect();
+
+    // Transitions into a composite state are laid out against its first child
+    // and transitions out of it against its last, so they stay outside the box
+    let endpoint = |id: &str, last: bool| {
+        index
+            .get(id)
+            .copied()
+            .or_else(|| edge_child(ast, id, &index, last, 0))
+    };
+    let edges: Vec<(usize, usize)> = ast
+        .transitions
+        .iter()
+        .filter_map(|transition| {
+            Some((
+                endpoint(&transition.from, true)?,
+                endpoint(&transition.to, false)?,
+            ))
+        })
+        .collect();
+
+    let sizes: Vec<(f64, f64)> = states
+        .iter()
+        .map(|state| state_size(&svg, state, vertical))
+        .collect();
+    let clustered = ast
+        .states
+        .iter()
+        .any(|state| state.kind == StateKind::Composite);
+    let rects = layout::layered(
+        &sizes,
+        &edges,
+        direction,
+        &Spacing {
+            // Leave room for a cluster's title and padding between ranks
+            rank: if clustered {
+                50.0 + svg.line_height() + CLUSTER_PADDING
+            } else {
+                50.0
+            },
+            node: 40.0,
+        },
+    );
+
+    let mut clusters: Vec<(&StateNode, Rect)> = ast
+        .states
+        .iter()
+        .filter(|state| state.kind == StateKind::Composite)
+        .filter_map(|state| {
+            composite_rect(ast, &state.id, &index, &rects, svg.line_height(), 0)
+                .map(|rect| (state, rect))
+        })
+        .collect();
+    clusters.sort_by(|a, b| (b.1.width * b.1.height).total_cmp(&(a.1.width * a.1.height)));
+    let cluster_of: HashMap<&str, Rect> = clusters
+        .iter()
+        .map(|(state, rect)| (state.id.as_str(), *rect))
+        .collect();
+    let rect_of = |id: &str| {
+        index
+            .get(id)
+            .map(|&i| rects[i])
+            .or_else(|| cluster_of.get(id).copied())
+    };
+
+    let mut extents = rects.clone();
+    svg.layer(CLUSTER_LAYER);
+    for (state, rect) in &clusters {
+        extents.push(*rect);
+        let fill = svg.palette.cluster_fill;
+        let stroke = svg.palette.cluster_stroke;
+        svg.rect(*rect, 8.0, fill, stroke, "");
+        let title = state.label.as_deref().unwrap_or(&state.id);
+        let y = rect.y + svg.line_height() / 2.0 + 4.0;
+        svg.text((rect.center().0, y), title, Anchor::Middle, "");
+    }
+
+    for transition in &ast.transitions {
+        let (Some(from), Some(to)) = (rect_of(&transition.from), rect_of(&transition.to)) else {
+            continue;
+        };
+        let mut points = layout::route(&from, &to);
+        let reversed = ast
+            .transitions
+            .iter()
+            .any(|other| other.from == transition.to && other.to == transition.from);
+        if reversed && from != to {
+            layout::offset(&mut points, 8.0);
+        }
+        extents.extend(points.iter().map(|&(x, y)| Rect::new(x, y, 0.0, 0.0)));
+        svg.layer(EDGE_LAYER);
+        let edge = svg.palette.edge;
+        svg.polyline(
+            &points,
+            Stroke {
+                color: edge,
+                width: 1.5,
+                dashed: false,
+            },
+            None,
+            Some("arrow"),
+        );
+        if let Some(label) = &transition.label {
+            let (mut x, mut y) = layout::midpoint(&points);
+            if reversed && from != to {
+                // Keep the labels of a back-and-forth pair on their own side
+                let (nx, ny) = layout::normal(points[0], points[points.len() - 1]);
+                let (width, height) = svg.text_size(label);
+                let distance = nx.abs() * width / 2.0 + ny.abs() * height / 2.0 + 4.0;
+                x += nx * distance;
+                y += ny * distance;
+            }
+            let (width, height) = svg.text_size(label);
+            extents.push(Rect::new(x - width / 2.0, y - height / 2.0, width, height));
+            svg.layer(LABEL_LAYER);
+            svg.label((x, y), label);
+        }
+    }
+
+    svg.layer(NODE_LAYER);
+    for (state, rect) in states.iter().zip(&rects) {
+        draw_state(&mut svg, state, *rect);
+    }
+
+    for note in &ast.notes {
+        let Some(anchor) = rect_of(&note.state) else {
+            continue;
+        };
+        let (width, height) = svg.text_size(&note.text);
+        let (width, height) = (width + 2.0 * PADDING_X, height + 2.0 * PADDING_Y);
+        let x = match note.side {
+            NoteSide::Left => anchor.x - NOTE_GAP - width,
+            NoteSide::Right => anchor.right() + NOTE_GAP,
+        };
+        let rect = Rect::new(x, anchor.center().1 - height / 2.0, width, height);
+        extents.push(rect);
+
+        let edge = svg.palette.edge;
+        svg.layer(EDGE_LAYER);
+        svg.polyline(
+            &layout::route(&rect, &anchor),
+            Stroke {
+                color: edge,
+                width: 1.0,
+                dashed: true,
+            },
+            None,
+            None,
+        );
+        svg.layer(NODE_LAYER);
+        let fill = svg.palette.note_fill;
+        let stroke = svg.palette.note_stroke;
+        svg.rect(rect, 0.0, fill, stroke, "");
+        svg.text(rect.center(), &note.text, Anchor::Middle, "");
+    }
+
+    let bounds = layout::bounds(&extents).unwrap_or(Rect::new(0.0, 0.0, 0.0, 0.0));
+    svg.finish(bounds, options.padding)
+}
+
+fn state_size(svg: &Svg, state: &StateNode, vertical: bool) -> (f64, f64) {
+    match state.kind {
+        StateKind::Start => (14.0, 14.0),
+        StateKind::End => (18.0, 18.0),
+        StateKind::Choice => (24.0, 24.0),
+        StateKind::Fork | StateKind::Join if vertical => (70.0, 8.0),
+        StateKind::Fork | StateKind::Join => (8.0, 70.0),
+        StateKind::Simple | StateKind::Composite => {
+            let (title_width, title_height) =
+                svg.text_size(state.label.as_deref().unwrap_or(&state.id));
+            let mut width = title_width;
+            let mut height = title_height + 2.0 * PADDING_Y;
+            if !state.descriptions.is_empty() {
+                let (text_width, text_height) = svg.text_size(&state.descriptions.join("\n"));
+                width = width.max(text_width);
+                height += text_height + PADDING_Y;
+            }
+            ((width + 2.0 * PADDING_X).max(60.0), height)
+        }
+    }
+}
+
+fn draw_state(svg: &mut Svg, state: &StateNode, rect: Rect) {
+    let fill = svg.palette.node_fill;
+    let stroke = svg.palette.node_stroke;
+    let edge = svg.palette.edge;
+    let (cx, cy) = rect.center();
+
+    match state.kind {
+        StateKind::Start => svg.circle((cx, cy), rect.width / 2.0, edge, edge, ""),
+        StateKind::End => {
+            svg.circle((cx, cy), rect.width / 2.0, "none", edge, "");
+            svg.circle((cx, cy), rect.width / 2.0 - 4.0, edge, edge, "");
+        }
+        StateKind::Choice => svg.polygon(
+            &[
+                (cx, rect.y),
+                (rect.right(), cy),
+                (cx, rect.bottom()),
+                (rect.x, cy),
+            ],
+            fill,
+            stroke,
+            "",
+        ),
+        StateKind::Fork | StateKind::Join => svg.rect(rect, 2.0, edge, edge, ""),
+        StateKind::Simple | StateKind::Composite => {
+            svg.rect(rect, 8.0, fill, stroke, "");
+            let title = state.label.as_deref().unwrap_or(&state.id);
+            let line_height = svg.line_height();
+            if state.descriptions.is_empty() {
+                svg.text((cx, cy), title, Anchor::Middle, "");
+                return;
+            }
+            let divider = rect.y + line_height + PADDING_Y;
+            svg.text(
+                (cx, rect.y + PADDING_Y + line_height / 2.0),
+                title,
+                Anchor::Middle,
+                "",
+            );
+            svg.polyline(
+                &[(rect.x, divider), (rect.right(), divider)],
+                Stroke {
+                    color: stroke,
+                    width: 1.0,
+                    dashed: false,
+                },
+                None,
+                None,
+            );
+            let descriptions = state.descriptions.join("\n");
+            let (_, height) = svg.text_size(&descriptions);
+            svg.text(
+                (cx, divider + PADDING_Y / 2.0 + height / 2.0),
+                &descriptions,
+                Anchor::Middle,
+                "",
+            );
+        }
+    }
+}
+
+/// The first (or last) laid-out state inside a composite, looking into nested composites
+fn edge_child(
+    ast: &StateDiagramAst,
+    id: &str,
+    index: &HashMap<&str, usize>,
+    last: bool,
+    depth: usize,
+) -> Option<usize> {
+    if depth > ast.states.len() {
+        return None;
+    }
+    let mut children = ast.children_of(Some(id));
+    if last {
+        children.reverse();
+    }
+    children
+        .iter()
+        .find_map(|child| index.get(child.id.as_str()).copied())
+        .or_else(|| {
+            children
+                .iter()
+                .find_map(|child| edge_child(ast, &child.id, index, last, depth + 1))
+        })
+}
+
+/// Box around a composite state's children, with room for its title
+fn composite_rect(
+    ast: &StateDiagramAst,
+    id: &str,
+    index: &HashMap<&str, usize>,
+    rects: &[Rect],
+    title_height: f64,
+    depth: usize,
+) -> Option<Rect> {
+    if depth > ast.states.len() {
+        return None;
+    }
+    let members: Vec<Rect> = ast
+        .children_of(Some(id))
+        .iter()
+        .filter_map(|child| match index.get(child.id.as_str()) {
+            Some(&i) => Some(rects[i]),
+            None => composite_rect(ast, &child.id, index, rects, title_height, depth + 1),
+        })
+        .collect();
+    let inner = layout::bounds(&members)?.inflate(CLUSTER_PADDING, CLUSTER_PADDING);
+    Some(Rect::new(
+        inner.x,
+        inner.y - title_height,
+        inner.width,
+        inner.height + title_height,
+    ))
+}
@@ -0,0 +1,429 @@
+use super::layout::Rect;
+use super::{RenderOptions, Theme};
+use std::fmt::Write;
+
+const FONT_FAMILY: &str = "trebuchet ms, verdana, arial, sans-serif";
+
+/// Colours used by all diagram types for one theme
+pub struct Palette {
+    pub background: &'static str,
+    pub node_fill: &'static str,
+    pub node_stroke: &'static str,
+    pub text: &'static str,
+    pub edge: &'static str,
+    pub cluster_fill: &'static str,
+    pub cluster_stroke: &'static str,
+    pub note_fill: &'static str,
+    pub note_stroke: &'static str,
+    pub accent: &'static str,
+}
+
+impl Palette {
+    pub fn for_theme(theme: Theme) -> Self {
+        match theme {
+            Theme::Light => Palette {
+                background: "#ffffff",
+                node_fill: "#ececff",
+                node_stroke: "#9370db",
+                text: "#333333",
+                edge: "#333333",
+                cluster_fill: "#ffffde",
+                cluster_stroke: "#aaaa33",
+                note_fill: "#fff5ad",
+                note_stroke: "#aaaa33",
+                accent: "#f4f4f4",
+            },
+            Theme::Dark => Palette {
+                background: "#1e1e2e",
+                node_fill: "#1f2020",
+                node_stroke: "#cccccc",
+                text: "#e0e0e0",
+                edge: "#d0d0d0",
+                cluster_fill: "#2c2c3c",
+                cluster_stroke: "#888888",
+                note_fill: "#3c3c1c",
+                note_stroke: "#aaaa33",
+                accent: "#444444",
+            },
+        }
+    }
+}
+
+/// Arrowhead and end markers; `auto-start-reverse` lets one marker serve both ends
+const MARKERS: &[(&str, &str, &str)] = &[
+    (
+        "arrow",
+        "0 0 10 10",
+        r#"<path d="M0,0 L10,5 L0,10 z" fill="EDGE"/>"#,
+    ),
+    (
+        "open-arrow",
+        "0 0 10 10",
+        r#"<path d="M0,0 L10,5 L0,10" fill="none" stroke="EDGE" stroke-width="1.5"/>"#,
+    ),
+    (
+        "circle",
+        "0 0 10 10",
+        r#"<circle cx="5" cy="5" r="4" fill="EDGE"/>"#,
+    ),
+    (
+        "cross",
+        "0 0 10 10",
+        r#"<path d="M1,1 L9,9 M1,9 L9,1" stroke="EDGE" stroke-width="2"/>"#,
+    ),
+    (
+        "triangle",
+        "0 0 10 10",
+        r#"<path d="M0,0 L10,5 L0,10 z" fill="BACKGROUND" stroke="EDGE"/>"#,
+    ),
+    (
+        "diamond",
+        "0 0 12 10",
+        r#"<path d="M0,5 L6,0 L12,5 L6,10 z" fill="BACKGROUND" stroke="EDGE"/>"#,
+    ),
+    (
+        "diamond-filled",
+        "0 0 12 10",
+        r#"<path d="M0,5 L6,0 L12,5 L6,10 z" fill="EDGE"/>"#,
+    ),
+    (
+        "lollipop",
+        "0 0 10 10",
+        r#"<circle cx="5" cy="5" r="4" fill="BACKGROUND" stroke="EDGE"/>"#,
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    Start,
+    Middle,
+}
+
+/// Stroke of a line or path
+#[derive(Debug, Clone, Copy)]
+pub struct Stroke<'a> {
+    pub color: &'a str,
+    pub width: f64,
+    pub dashed: bool,
+}
+
+/// Builds an SVG document from primitives drawn onto numbered layers
+pub struct Svg {
+    pub palette: Palette,
+    pub font_size: f64,
+    background: Option<String>,
+    layers: Vec<String>,
+    current: usize,
+}
+
+impl Svg {
+    pub fn new(options: &RenderOptions) -> Self {
+        Svg {
+            palette: Palette::for_theme(options.theme),
+            font_size: options.font_size,
+            background: options.background.clone(),
+            layers: vec![String::new()],
+            current: 0,
+        }
+    }
+
+    /// Draw subsequent primitives on `layer`; higher layers are painted on top
+    pub fn layer(&mut self, layer: usize) {
+        if self.layers.len() <= layer {
+            self.layers.resize(layer + 1, String::new());
+        }
+        self.current = layer;
+    }
+
+    fn out(&mut self) -> &mut String {
+        &mut self.layers[self.current]
+    }
+
+    pub fn line_height(&self) -> f64 {
+        self.font_size * 1.5
+    }
+
+    /// Width and height of a possibly multi-line label
+    pub fn text_size(&self, text: &str) -> (f64, f64) {
+        let lines = label_lines(text);
+        let width = lines
+            .iter()
+            .map(|line| text_width(line, self.font_size))
+            .fold(0.0, f64::max);
+        (width, lines.len() as f64 * self.line_height())
+    }
+
+    pub fn rect(&mut self, rect: Rect, radius: f64, fill: &str, stroke: &str, style: &str) {
+        let element = format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{}" fill="{}" stroke="{}"{}/>"#,
+            num(rect.x),
+            num(rect.y),
+            num(rect.width),
+            num(rect.height),
+            num(radius),
+            fill,
+            stroke,
+            style_attribute(style)
+        );
+        self.out().push_str(&element);
+    }
+
+    pub fn circle(
+        &mut self,
+        center: (f64, f64),
+        radius: f64,
+        fill: &str,
+        stroke: &str,
+        style: &str,
+    ) {
+        let element = format!(
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}"{}/>"#,
+            num(center.0),
+            num(center.1),
+            num(radius),
+            fill,
+            stroke,
+            style_attribute(style)
+        );
+        self.out().push_str(&element);
+    }
+
+    pub fn polygon(&mut self, points: &[(f64, f64)], fill: &str, stroke: &str, style: &str) {
+        let points: Vec<String> = points
+            .iter()
+            .map(|(x, y)| format!("{},{}", num(*x), num(*y)))
+            .collect();
+        let element = format!(
+            r#"<polygon points="{}" fill="{}" stroke="{}"{}/>"#,
+            points.join(" "),
+            fill,
+            stroke,
+            style_attribute(style)
+        );
+        self.out().push_str(&element);
+    }
+
+    pub fn path(&mut self, d: &str, fill: &str, stroke: &str, style: &str) {
+        let element = format!(
+            r#"<path d="{}" fill="{}" stroke="{}"{}/>"#,
+            d,
+            fill,
+            stroke,
+            style_attribute(style)
+        );
+        self.out().push_str(&element);
+    }
+
+    /// Polyline with optional markers at either end
+    pub fn polyline(
+        &mut self,
+        points: &[(f64, f64)],
+        stroke: Stroke,
+        start_marker: Option<&str>,
+        end_marker: Option<&str>,
+    ) {
+        let mut d = String::new();
+        for (index, (x, y)) in points.iter().enumerate() {
+            let command = if index == 0 { 'M' } else { 'L' };
+            let _ = write!(d, "{}{},{} ", command, num(*x), num(*y));
+        }
+        let mut element = format!(
+            r#"<path d="{}" fill="none" stroke="{}" stroke-width="{}""#,
+            d.trim_end(),
+            stroke.color,
+            num(stroke.width)
+        );
+        if stroke.dashed {
+            element.push_str(r#" stroke-dasharray="4 3""#);
+        }
+        if let Some(marker) = start_marker {
+            let _ = write!(element, r#" marker-start="url(#{})""#, marker);
+        }
+        if let Some(marker) = end_marker {
+            let _ = write!(element, r#" marker-end="url(#{})""#, marker);
+        }
+        element.push_str("/>");
+        self.out().push_str(&element);
+    }
+
+    /// Text centered vertically on `y`, one `<tspan>` per label line
+    pub fn text(&mut self, position: (f64, f64), text: &str, anchor: Anchor, style: &str) {
+        let lines = label_lines(text);
+        let line_height = self.line_height();
+        let first = position.1 - (lines.len() as f64 - 1.0) * line_height / 2.0;
+        let anchor = match anchor {
+            Anchor::Start => "start",
+            Anchor::Middle => "middle",
+        };
+        let mut element = format!(
+            r#"<text x="{}" y="{}" text-anchor="{}" dominant-baseline="central" fill="{}"{}>"#,
+            num(position.0),
+            num(first),
+            anchor,
+            self.palette.text,
+            style_attribute(style)
+        );
+        for (index, line) in lines.iter().enumerate() {
+            let _ = write!(
+                element,
+                r#"<tspan x="{}" y="{}">{}</tspan>"#,
+                num(position.0),
+                num(first + index as f64 * line_height),
+                escape(line)
+            );
+        }
+        element.push_str("</text>");
+        self.out().push_str(&element);
+    }
+
+    /// Label on a background box so it stays readable on top of lines
+    pub fn label(&mut self, center: (f64, f64), text: &str) {
+        let (width, height) = self.text_size(text);
+        let background = self.palette.background;
+        self.rect(
+            Rect::new(
+                center.0 - width / 2.0 - 2.0,
+                center.1 - height / 2.0,
+                width + 4.0,
+                height,
+            ),
+            0.0,
+            background,
+            "none",
+            "",
+        );
+        self.text(center, text, Anchor::Middle, "");
+    }
+
+    /// Close the document; `bounds` becomes the view box, plus `padding` on each side
+    pub fn finish(self, bounds: Rect, padding: f64) -> String {
+        let view = bounds.inflate(padding, padding);
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}" font-family="{}" font-size="{}">"#,
+            num(view.width),
+            num(view.height),
+            num(view.x),
+            num(view.y),
+            num(view.width),
+            num(view.height),
+            FONT_FAMILY,
+            num(self.font_size)
+        );
+        svg.push_str("<defs>");
+        for (id, view_box, shape) in MARKERS {
+            let shape = shape
+                .replace("EDGE", self.palette.edge)
+                .replace("BACKGROUND", self.palette.background);
+            let _ = write!(
+                svg,
+                r#"<marker id="{}" viewBox="{}" refX="{}" refY="5" markerUnits="userSpaceOnUse" markerWidth="10" markerHeight="10" orient="auto-start-reverse">{}</marker>"#,
+                id,
+                view_box,
+                if view_box.starts_with("0 0 12") {
+                    12
+                } else {
+                    10
+                },
+                shape
+            );
+        }
+        svg.push_str("</defs>");
+        let background = self
+            .background
+            .as_deref()
+            .unwrap_or(self.palette.background);
+        if !matches!(background, "transparent" | "none") {
+            let _ = write!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                num(view.x),
+                num(view.y),
+                num(view.width),
+                num(view.height),
+                escape(background)
+            );
+        }
+        for layer in &self.layers {
+            svg.push_str(layer);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Format a coordinate with at most one decimal, so output is stable and compact
+pub fn num(value: f64) -> String {
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded == rounded.trunc() {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{:.1}", rounded)
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Split a label on `<br>` tags and newlines, as Mermaid does
+pub fn label_lines(text: &str) -> Vec<&str> {
+    let mut lines = vec![text];
+    for separator in ["<br/>", "<br />", "<br>", "\n"] {
+        lines = lines
+            .into_iter()
+            .flat_map(|line| line.split(separator))
+            .collect();
+    }
+    lines.into_iter().map(str::trim).collect()
+}
+
+/// Approximate rendered width; good enough for layout without font metrics
+pub fn text_width(text: &str, font_size: f64) -> f64 {
+    text.chars()
+        .map(|ch| match ch {
+            'i' | 'l' | 'j' | '.' | ',' | ':' | ';' | '\'' | '|' | '!' => 0.3,
+            'm' | 'w' | 'M' | 'W' => 0.85,
+            ch if ch.is_uppercase() => 0.68,
+            ' ' => 0.32,
+            _ => 0.56,
+        })
+        .sum::<f64>()
+        * font_size
+}
+
+/// Turn Mermaid `fill:#f9f,stroke:#333` style lists into CSS declarations
+pub fn css_declarations(styles: &str) -> String {
+    let mut declarations = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for ch in styles.chars() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                declarations.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    declarations.push(current);
+    declarations
+        .iter()
+        .map(|declaration| declaration.trim())
+        .filter(|declaration| declaration.contains(':'))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn style_attribute(style: &str) -> String {
+    if style.is_empty() {
+        String::new()
+    } else {
+        format!(r#" style="{}""#, escape(style))
+    }
+}
@@ -4,6 +4,8 @@ use tauri::{WebviewWindow, Emitter};
 use tauri_plugin_store::{Store, StoreExt};
 use anyhow::{Result, Context};
 
+use crate::error::AppError;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowSettings {
     #[serde(rename = "alwaysOnTop")]
@@ -28,14 +30,18 @@ pub struct ApplicationState {
     pub cursor_position: Option<(u32, u32)>, // (line, column)
     #[serde(rename = "lastFilePath")]
     pub last_file_path: Option<String>,
-    #[serde(rename = "lastFileContent")]
-    pub last_file_content: Option<String>,
     #[serde(rename = "lastFileName")]
     pub last_file_name: Option<String>,
     #[serde(rename = "hasUnsavedChanges")]
     pub has_unsaved_changes: bool,
     #[serde(rename = "showTreeView")]
     pub show_tree_view: bool,
+    /// Folders opened as workspaces, in the order they were first opened
+    #[serde(rename = "workspaceRoots", default)]
+    pub workspace_roots: Vec<String>,
+    /// Rotating `.bak` copies kept when a save replaces a file; 0 keeps none
+    #[serde(rename = "backupCount", default)]
+    pub backup_count: usize,
 }
 
 impl Default for WindowSettings {
@@ -59,10 +65,11 @@ impl Default for ApplicationState {
             active_diagram_index: -1,
             cursor_position: None,
             last_file_path: None,
-            last_file_content: None,
             last_file_name: None,
             has_unsaved_changes: false,
             show_tree_view: false, // Off by default as requested
+            workspace_roots: Vec::new(),
+            backup_count: 0,
         }
     }
 }
@@ -91,6 +98,9 @@ impl Default for WindowState {
     }
 }
 
+/// Upper bound on `backup_count`, so a typo can't fill a folder with copies
+const MAX_BACKUPS: usize = 20;
+
 // Global state manager
 static WINDOW_STATE_MANAGER: LazyLock<Mutex<Option<WindowStateManager>>> = LazyLock::new(|| {
     Mutex::new(None)
@@ -246,43 +256,43 @@ impl WindowStateManager {
 
 // Tauri command implementations
 #[tauri::command]
-pub async fn set_always_on_top(window: WebviewWindow, enabled: bool) -> Result<(), String> {
-    window.set_always_on_top(enabled).map_err(|e| e.to_string())?;
+pub async fn set_always_on_top(window: WebviewWindow, enabled: bool) -> Result<(), AppError> {
+    window.set_always_on_top(enabled)?;
     
     WindowStateManager::update_setting(|settings| {
         settings.always_on_top = enabled;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn set_click_through(window: WebviewWindow, enabled: bool) -> Result<(), String> {
-    window.set_ignore_cursor_events(enabled).map_err(|e| e.to_string())?;
+pub async fn set_click_through(window: WebviewWindow, enabled: bool) -> Result<(), AppError> {
+    window.set_ignore_cursor_events(enabled)?;
     
     WindowStateManager::update_setting(|settings| {
         settings.click_through = enabled;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn set_opacity(window: WebviewWindow, opacity: f64) -> Result<(), String> {
+pub async fn set_opacity(window: WebviewWindow, opacity: f64) -> Result<(), AppError> {
     let clamped_opacity = opacity.max(0.1).min(1.0);
     
     WindowStateManager::update_setting(|settings| {
         settings.opacity = clamped_opacity;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     // Emit an event to the frontend to update the visual opacity
-    window.emit("opacity-changed", clamped_opacity).map_err(|e| e.to_string())?;
+    window.emit("opacity-changed", clamped_opacity)?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_window_settings(window: WebviewWindow) -> Result<WindowSettings, String> {
+pub async fn get_window_settings(window: WebviewWindow) -> Result<WindowSettings, AppError> {
     let mut settings = WindowStateManager::get_current_settings();
     
     // Update with current window position and size
@@ -296,64 +306,64 @@ pub async fn get_window_settings(window: WebviewWindow) -> Result<WindowSettings
 }
 
 #[tauri::command]
-pub async fn save_window_state(window: WebviewWindow) -> Result<(), String> {
-    WindowStateManager::save_window_geometry(&window).map_err(|e| e.to_string())
+pub async fn save_window_state(window: WebviewWindow) -> Result<(), AppError> {
+    Ok(WindowStateManager::save_window_geometry(&window)?)
 }
 
 #[tauri::command]
-pub async fn restore_window_state(window: WebviewWindow) -> Result<(), String> {
-    WindowStateManager::restore_window_state(&window).map_err(|e| e.to_string())
+pub async fn restore_window_state(window: WebviewWindow) -> Result<(), AppError> {
+    Ok(WindowStateManager::restore_window_state(&window)?)
 }
 
 #[tauri::command]
-pub async fn set_split_pane_size(size: f64) -> Result<(), String> {
+pub async fn set_split_pane_size(size: f64) -> Result<(), AppError> {
     let clamped_size = size.max(0.1).min(0.9);
     
     WindowStateManager::update_setting(|settings| {
         settings.split_pane_size = clamped_size;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
 
 // Application state commands
 #[tauri::command]
-pub async fn get_application_state() -> Result<ApplicationState, String> {
+pub async fn get_application_state() -> Result<ApplicationState, AppError> {
     Ok(WindowStateManager::get_current_app_state())
 }
 
 #[tauri::command]
-pub async fn update_theme(theme: String) -> Result<(), String> {
+pub async fn update_theme(theme: String) -> Result<(), AppError> {
     WindowStateManager::update_app_state(|app_state| {
         app_state.theme = theme;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn update_settings_panel_state(show: bool) -> Result<(), String> {
+pub async fn update_settings_panel_state(show: bool) -> Result<(), AppError> {
     WindowStateManager::update_app_state(|app_state| {
         app_state.show_settings = show;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn update_active_diagram_index(index: i32) -> Result<(), String> {
+pub async fn update_active_diagram_index(index: i32) -> Result<(), AppError> {
     WindowStateManager::update_app_state(|app_state| {
         app_state.active_diagram_index = index;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn update_cursor_position(line: u32, column: u32) -> Result<(), String> {
+pub async fn update_cursor_position(line: u32, column: u32) -> Result<(), AppError> {
     WindowStateManager::update_app_state(|app_state| {
         app_state.cursor_position = Some((line, column));
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
@@ -362,24 +372,41 @@ pub async fn update_cursor_position(line: u32, column: u32) -> Result<(), String
 pub async fn update_file_state(
     file_path: Option<String>,
     file_name: Option<String>,
-    file_content: Option<String>,
     has_unsaved_changes: bool,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     WindowStateManager::update_app_state(|app_state| {
         app_state.last_file_path = file_path;
         app_state.last_file_name = file_name;
-        app_state.last_file_content = file_content;
         app_state.has_unsaved_changes = has_unsaved_changes;
-    }).map_err(|e| e.to_string())?;
+    })?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_workspace_root(path: String) -> Result<(), AppError> {
+    WindowStateManager::update_app_state(|app_state| {
+        app_state.workspace_roots.retain(|root| *root != path);
+    })?;
+    crate::file_watcher::unwatch_root(std::path::Path::new(&path))?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn update_backup_count(count: usize) -> Result<(), AppError> {
+    WindowStateManager::update_app_state(|app_state| {
+        app_state.backup_count = count.min(MAX_BACKUPS);
+    })?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub async fn update_tree_view_state(show: bool) -> Result<(), String> {
+pub async fn update_tree_view_state(show: bool) -> Result<(), AppError> {
     WindowStateManager::update_app_state(|app_state| {
         app_state.show_tree_view = show;
-    }).map_err(|e| e.to_string())?;
+    })?;
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,331 @@
+use crate::file_io::content_hash;
+use crate::file_manager::{FileManager, FileType};
+use crate::mermaid_parser::MermaidParser;
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const GITIGNORE: &str = ".gitignore";
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Directory,
+    File,
+}
+
+/// A file or folder in an open workspace.
+///
+/// Folder counts are the sums over everything below them, and folders without any
+/// supported files are left out of the tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceNode {
+    pub name: String,
+    pub path: String,
+    pub kind: NodeKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<WorkspaceNode>,
+    pub diagram_count: usize,
+    pub error_count: usize,
+    /// Set when the file could not be read, in which case both counts are zero
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Hash of a file's bytes when it was scanned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+/// List every supported file below `root`, skipping hidden entries and anything a
+/// `.gitignore` along the way excludes
+pub fn scan(parser: &MermaidParser, root: &Path) -> io::Result<WorkspaceNode> {
+    let mut ignores = Vec::new();
+    let children = scan_dir(parser, root, &mut ignores)?;
+    let name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string());
+    Ok(directory(name, root, children))
+}
+
+fn scan_dir(
+    parser: &MermaidParser,
+    dir: &Path,
+    ignores: &mut Vec<IgnoreFile>,
+) -> io::Result<Vec<WorkspaceNode>> {
+    let pushed = match IgnoreFile::load(dir) {
+        Some(ignore) => {
+            ignores.push(ignore);
+            true
+        }
+        None => false,
+    };
+
+    let mut entries = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut folders = Vec::new();
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Symlinks are not followed, so a link back up the tree can't loop forever
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let is_dir = file_type.is_dir();
+        if name.starts_with('.') || is_ignored(ignores, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            // An unreadable subfolder is left out rather than failing the whole workspace
+            if let Ok(children) = scan_dir(parser, &path, ignores) {
+                if !children.is_empty() {
+                    folders.push(directory(name, &path, children));
+                }
+            }
+        } else if file_type.is_file() && FileManager::is_supported_file(&path) {
+            files.push(file(parser, name, &path));
+        }
+    }
+
+    if pushed {
+        ignores.pop();
+    }
+    folders.extend(files);
+    Ok(folders)
+}
+
+fn directory(name: String, path: &Path, children: Vec<WorkspaceNode>) -> WorkspaceNode {
+    WorkspaceNode {
+        name,
+        path: path.to_string_lossy().into_owned(),
+        kind: NodeKind::Directory,
+        diagram_count: children.iter().map(|child| child.diagram_count).sum(),
+        error_count: children.iter().map(|child| child.error_count).sum(),
+        children,
+        error: None,
+        content_hash: None,
+    }
+}
+
+fn file(parser: &MermaidParser, name: String, path: &Path) -> WorkspaceNode {
+    let mut node = WorkspaceNode {
+        name,
+        path: path.to_string_lossy().into_owned(),
+        kind: NodeKind::File,
+        children: Vec::new(),
+        diagram_count: 0,
+        error_count: 0,
+        error: None,
+        content_hash: None,
+    };
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            node.error = Some(e.to_string());
+            return node;
+        }
+    };
+    node.content_hash = Some(content_hash(&bytes));
+    match String::from_utf8(bytes) {
+        Ok(content) => {
+            let format = FileType::from_path(path).content_format();
+            let result = parser.parse_content_as(&content, format);
+            node.diagram_count = result.diagrams.len();
+            node.error_count = result.total_errors;
+        }
+        Err(e) => node.error = Some(e.to_string()),
+    }
+    node
+}
+
+/// Whether `path` below `root` is left out of the workspace: hidden, or excluded by a
+/// `.gitignore` in `root` or a folder between them. Paths outside `root` are excluded.
+pub fn is_excluded(root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return true;
+    };
+    let components: Vec<_> = relative.components().collect();
+    let mut ignores = Vec::new();
+    let mut current = root.to_path_buf();
+    for (index, component) in components.iter().enumerate() {
+        if component.as_os_str().to_string_lossy().starts_with('.') {
+            return true;
+        }
+        ignores.extend(IgnoreFile::load(&current));
+        current.push(component);
+        let is_dir = index + 1 < components.len();
+        if is_ignored(&ignores, &current, is_dir) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Later rules override earlier ones, and deeper `.gitignore` files come later in `ignores`
+fn is_ignored(ignores: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for ignore in ignores {
+        let Ok(relative) = path.strip_prefix(&ignore.base) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let name = relative.rsplit('/').next().unwrap_or(&relative);
+        for rule in &ignore.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let target = if rule.anchored {
+                relative.as_str()
+            } else {
+                name
+            };
+            if rule.pattern.matches_with(target, MATCH_OPTIONS) {
+                ignored = !rule.negated;
+            }
+        }
+    }
+    ignored
+}
+
+/// The rules of one `.gitignore` file, relative to the folder it lives in
+struct IgnoreFile {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+struct IgnoreRule {
+    pattern: Pattern,
+    negated: bool,
+    dir_only: bool,
+    /// Patterns containing a slash match the whole relative path, others only the file name
+    anchored: bool,
+}
+
+impl IgnoreFile {
+    fn load(dir: &Path) -> Option<Self> {
+        let text = fs::read_to_string(dir.join(GITIGNORE)).ok()?;
+        let rules = text.lines().filter_map(IgnoreRule::parse).collect();
+        Some(IgnoreFile {
+            base: dir.to_path_buf(),
+            rules,
+        })
+    }
+}
+
+impl IgnoreRule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let pattern = Pattern::new(line).ok()?;
+        Some(IgnoreRule {
+            pattern,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_respects_gitignore() {
+        let root = std::env::temp_dir().join(format!("parch-workspace-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["docs/drafts", "build", "notes", ".hidden"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let write = |path: &str, content: &str| fs::write(root.join(path), content).unwrap();
+        write(".gitignore", "build/\n*.tmp.md\n/notes/private.md\n");
+        write("docs/.gitignore", "drafts/*\n!drafts/keep.md\n");
+        write(
+            "docs/design.md",
+            "```mermaid\ngraph TD\n  A --> B\n```\n\n```mermaid\ngraph TD\n  A[ --> B\n```\n",
+        );
+        write("docs/drafts/skip.md", "```mermaid\npie\n```\n");
+        write("docs/drafts/keep.md", "```mermaid\npie\n  \"a\" : 1\n```\n");
+        write("build/out.md", "```mermaid\npie\n```\n");
+        write("notes/private.md", "# private\n");
+        write("notes/scratch.tmp.md", "# scratch\n");
+        write("notes/flow.mmd", "graph LR\n  A --> B\n");
+        write("notes/readme.txt", "not a diagram\n");
+        write(".hidden/secret.md", "# hidden\n");
+
+        let parser = MermaidParser::new().unwrap();
+        let tree = scan(&parser, &root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(tree.kind, NodeKind::Directory);
+        assert_eq!((tree.diagram_count, tree.error_count), (4, 1));
+        let names: Vec<&str> = tree
+            .children
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(names, ["docs", "notes"]);
+
+        let docs = &tree.children[0];
+        assert_eq!(docs.children[0].name, "drafts");
+        assert_eq!(docs.children[0].children.len(), 1);
+        assert_eq!(docs.children[0].children[0].name, "keep.md");
+        assert_eq!(docs.children[1].name, "design.md");
+        assert_eq!(
+            (docs.children[1].diagram_count, docs.children[1].error_count),
+            (2, 1)
+        );
+
+        let notes = &tree.children[1];
+        assert_eq!(notes.children.len(), 1);
+        assert_eq!(notes.children[0].name, "flow.mmd");
+        assert_eq!(notes.children[0].kind, NodeKind::File);
+        assert_eq!(
+            notes.children[0].content_hash.as_deref(),
+            Some(content_hash(b"graph LR\n  A --> B\n").as_str())
+        );
+    }
+
+    #[test]
+    fn test_is_excluded_matches_scan() {
+        let root = std::env::temp_dir().join(format!("parch-excluded-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("docs/drafts")).unwrap();
+        fs::write(root.join(".gitignore"), "build/\n*.tmp.md\n").unwrap();
+        fs::write(root.join("docs/.gitignore"), "drafts/*\n!drafts/keep.md\n").unwrap();
+
+        let excluded = |path: &str| is_excluded(&root, &root.join(path));
+        assert!(!excluded("docs/design.md"));
+        assert!(excluded("build/out.md"));
+        assert!(excluded("notes/scratch.tmp.md"));
+        assert!(excluded("docs/drafts/skip.md"));
+        assert!(!excluded("docs/drafts/keep.md"));
+        assert!(excluded(".hidden/secret.md"));
+        assert!(is_excluded(&root, Path::new("/elsewhere/file.md")));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -47,16 +47,40 @@ function App() {
     }
   }, [fileManagerState.error]);
 
-  // Create a default file on startup if no file exists and no file was restored
+  // Offer every buffer journaled by an earlier session, most recent first, before anything
+  // else opens. The editor holds one document, so buffers kept after the first one is
+  // reopened stay journaled and are offered again at the next start.
+  const [recoveryChecked, setRecoveryChecked] = useState(false);
+  const { restoreFile } = fileManagerActions;
   useEffect(() => {
-    if (!fileManagerState.currentFile && !fileManagerState.isLoading && appState !== null) {
-      // Only create default file if no file state was restored from persistence
-      if (!appState.lastFileContent) {
-        console.log('🚀 Creating default file on startup');
-        fileManagerActions.createNewFile();
+    TauriAPI.getRecoveredDocuments().then(async (report) => {
+      const count = report.documents.length;
+      let restored = false;
+      for (const [index, entry] of report.documents.entries()) {
+        const prefix = index === 0 && report.unclean_shutdown ? 'Parch did not shut down cleanly. ' : '';
+        const position = count > 1 ? ` (${index + 1} of ${count})` : '';
+        const question = restored
+          ? `Keep unsaved changes to ${entry.name} to recover next time${position}?`
+          : `${prefix}Recover unsaved changes to ${entry.name}${position}?`;
+        if (window.confirm(question)) {
+          if (!restored) {
+            restoreFile({ ...entry, isSaved: false });
+            restored = true;
+          }
+        } else {
+          await TauriAPI.discardRecoveredDocument(entry.id);
+        }
       }
+    }).catch(console.error).finally(() => setRecoveryChecked(true));
+  }, [restoreFile]);
+
+  // Create a default file on startup if no file exists and no file was recovered
+  useEffect(() => {
+    if (!fileManagerState.currentFile && !fileManagerState.isLoading && recoveryChecked) {
+      console.log('🚀 Creating default file on startup');
+      fileManagerActions.createNewFile();
     }
-  }, [fileManagerState.currentFile, fileManagerState.isLoading, appState, fileManagerActions]);
+  }, [fileManagerState.currentFile, fileManagerState.isLoading, recoveryChecked, fileManagerActions]);
 
   // Save theme preference when it changes
   useEffect(() => {
@@ -145,7 +169,9 @@ function App() {
   } = useTextEditor({
     initialContent: "",
     validateOnChange: true,
-    debounceMs: 300
+    debounceMs: 300,
+    path: fileManagerState.currentFile?.path,
+    fileType: fileManagerState.currentFile?.fileType
   });
 
   // Sync editor content with text editor for diagram parsing
@@ -1,6 +1,7 @@
 import { useState, useCallback, useRef } from 'react';
 import { TauriAPI } from '../lib/tauri-api';
 import type { FileContent, FileDialogResult, SaveResult } from '../types/tauri';
+import { errorMessage } from '../utils/guards';
 
 export interface FileManagerState {
   currentFile: FileContent | null;
@@ -90,7 +91,7 @@ export function useFileManager(options: UseFileManagerOptions = {}): [FileManage
         options.onContentChanged(newFile.content);
       }
     } catch (error) {
-      setError(`Failed to create new file: ${error}`);
+      setError(`Failed to create new file: ${errorMessage(error)}`);
     } finally {
       updateState({ isLoading: false });
     }
@@ -143,7 +144,7 @@ export function useFileManager(options: UseFileManagerOptions = {}): [FileManage
       }
     } catch (error) {
       console.error('Failed to open file:', error);
-      setError(`Failed to open file: ${error}`);
+      setError(`Failed to open file: ${errorMessage(error)}`);
     } finally {
       updateState({ isLoading: false });
       console.log('=== END OPEN FILE DEBUG ===');
@@ -173,17 +174,16 @@ export function useFileManager(options: UseFileManagerOptions = {}): [FileManage
           console.log('Save successful, updating original content ref');
           originalContentRef.current = state.currentFile.content;
           updateState({ hasUnsavedChanges: false });
-          setCurrentFile({ ...state.currentFile, isSaved: true });
+          setCurrentFile({ ...state.currentFile, isSaved: true, contentHash: result.contentHash, size: result.size });
           console.log('Updated original content ref to:', originalContentRef.current.substring(0, 50) + '...');
-        } else if (result.error) {
-          setError(result.error);
         }
       } else {
         console.log('No path, using Save As');
         // No path, use Save As - inline implementation
         const result: SaveResult = await TauriAPI.saveFileAsDialog(
           state.currentFile.content,
-          state.currentFile.name
+          state.currentFile.name,
+          state.currentFile.id
         );
         
         if (result.success && result.filePath) {
@@ -192,19 +192,19 @@ export function useFileManager(options: UseFileManagerOptions = {}): [FileManage
             path: result.filePath,
             name: extractFileName(result.filePath),
             isSaved: true,
+            contentHash: result.contentHash,
+            size: result.size,
           };
           
           console.log('Save As successful, updating file');
           originalContentRef.current = state.currentFile.content;
           setCurrentFile(updatedFile);
           updateState({ hasUnsavedChanges: false });
-        } else if (result.error) {
-          setError(result.error);
         }
       }
     } catch (error) {
       console.error('Save error:', error);
-      setError(`Failed to save file: ${error}`);
+      setError(`Failed to save file: ${errorMessage(error)}`);
     } finally {
       updateState({ isLoading: false });
       console.log('=== END SAVE FILE DEBUG ===');
@@ -222,7 +222,8 @@ export function useFileManager(options: UseFileManagerOptions = {}): [FileManage
 
       const result: SaveResult = await TauriAPI.saveFileAsDialog(
         state.currentFile.content,
-        state.currentFile.name
+        state.currentFile.name,
+        state.currentFile.id
       );
       
       if (result.success && result.filePath) {
@@ -231,16 +232,16 @@ export function useFileManager(options: UseFileManagerOptions = {}): [FileManage
           path: result.filePath,
           name: extractFileName(result.filePath),
           isSaved: true,
+          contentHash: result.contentHash,
+          size: result.size,
         };
         
         originalContentRef.current = state.currentFile.content;
         setCurrentFile(updatedFile);
         updateState({ hasUnsavedChanges: false });
-      } else if (result.error) {
-        setError(result.error);
       }
     } catch (error) {
-      setError(`Failed to save file: ${error}`);
+      setError(`Failed to save file: ${errorMessage(error)}`);
     } finally {
       updateState({ isLoading: false });
     }
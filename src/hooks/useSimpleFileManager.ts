@@ -1,6 +1,10 @@
-import { useState, useCallback, useRef } from 'react';
+import { useState, useCallback, useRef, useEffect } from 'react';
 import { TauriAPI } from '../lib/tauri-api';
 import type { FileContent, FileDialogResult, SaveResult } from '../types/tauri';
+import { errorMessage } from '../utils/guards';
+
+// How often a buffer with unsaved changes is journaled for crash recovery
+const JOURNAL_INTERVAL_MS = 5000;
 
 // Guards and validation
 const validateFileContent = (file: FileContent | null): boolean => {
@@ -55,6 +59,41 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
     isProcessing: false,
   });
 
+  // Journal the buffer while it has unsaved changes so a crash doesn't lose them;
+  // the backend drops the journal once the file is saved
+  const stateRef = useRef(state);
+  stateRef.current = state;
+  useEffect(() => {
+    let lastJournaled = '';
+    const timer = setInterval(() => {
+      const { currentFile, hasUnsavedChanges } = stateRef.current;
+      if (!currentFile || !hasUnsavedChanges) return;
+      const key = `${currentFile.id}\n${currentFile.content}`;
+      if (key === lastJournaled) return;
+      lastJournaled = key;
+      TauriAPI.journalDocument({
+        id: currentFile.id,
+        name: currentFile.name,
+        path: currentFile.path,
+        content: currentFile.content,
+        fileType: currentFile.fileType,
+        contentHash: currentFile.contentHash,
+        size: currentFile.size,
+      }).catch((error) => {
+        console.warn('Failed to journal unsaved changes:', errorMessage(error));
+      });
+    }, JOURNAL_INTERVAL_MS);
+    return () => clearInterval(timer);
+  }, []);
+
+  // Keep the backend watcher pointed at the open file so outside edits raise file-changed events
+  const currentPath = state.currentFile?.path ?? null;
+  useEffect(() => {
+    TauriAPI.watchFile(currentPath).catch((error) => {
+      console.warn('Failed to watch file:', errorMessage(error));
+    });
+  }, [currentPath]);
+
   const updateState = useCallback((updates: Partial<SimpleFileManagerState>) => {
     console.log('=== UPDATING FILE MANAGER STATE ===');
     console.log('Updates:', updates);
@@ -80,7 +119,7 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
       });
     } catch (error) {
       console.error('Failed to create new file:', error);
-      updateState({ error: `Failed to create new file: ${error}`, isLoading: false });
+      updateState({ error: `Failed to create new file: ${errorMessage(error)}`, isLoading: false });
     }
   }, [updateState]);
 
@@ -131,7 +170,7 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
       }
     } catch (error) {
       console.error('Failed to open file:', error);
-      updateState({ error: `Failed to open file: ${error}`, isLoading: false });
+      updateState({ error: `Failed to open file: ${errorMessage(error)}`, isLoading: false });
     }
   }, [updateState]);
 
@@ -172,7 +211,8 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
         console.log('No path, using Save As');
         result = await TauriAPI.saveFileAsDialog(
           state.currentFile.content,
-          state.currentFile.name
+          state.currentFile.name,
+          state.currentFile.id
         );
       }
       
@@ -182,6 +222,9 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
         let updatedFile = { 
           ...state.currentFile, 
           isSaved: true,
+          // The disk version the next save is checked against
+          contentHash: result.contentHash,
+          size: result.size,
         };
         
         // If we got a new file path (Save As case), update path and name
@@ -200,14 +243,12 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
           hasUnsavedChanges: false,
           isLoading: false 
         });
-      } else if (result.error) {
-        updateState({ error: result.error, isLoading: false });
       } else {
         updateState({ isLoading: false });
       }
     } catch (error) {
       console.error('Failed to save file:', error);
-      updateState({ error: `Failed to save file: ${error}`, isLoading: false });
+      updateState({ error: `Failed to save file: ${errorMessage(error)}`, isLoading: false });
     }
   }, [state.currentFile, updateState]);
 
@@ -224,7 +265,8 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
 
       const result: SaveResult = await TauriAPI.saveFileAsDialog(
         state.currentFile.content,
-        state.currentFile.name
+        state.currentFile.name,
+        state.currentFile.id
       );
       
       console.log('Save As result:', result);
@@ -234,7 +276,9 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
           ...state.currentFile, 
           path: result.filePath,
           name: extractFileName(result.filePath),
-          isSaved: true
+          isSaved: true,
+          contentHash: result.contentHash,
+          size: result.size,
         };
         
         updateState({ 
@@ -242,14 +286,12 @@ export function useSimpleFileManager(): [SimpleFileManagerState, SimpleFileManag
           hasUnsavedChanges: false,
           isLoading: false 
         });
-      } else if (result.error) {
-        updateState({ error: result.error, isLoading: false });
       } else {
         updateState({ isLoading: false });
       }
     } catch (error) {
       console.error('Failed to save file as:', error);
-      updateState({ error: `Failed to save file as: ${error}`, isLoading: false });
+      updateState({ error: `Failed to save file as: ${errorMessage(error)}`, isLoading: false });
     }
   }, [state.currentFile, updateState]);
 
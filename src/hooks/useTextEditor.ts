@@ -1,11 +1,15 @@
 import { useState, useEffect, useCallback, useMemo } from 'react';
 import { mermaidParser } from '../lib/mermaid-parser';
 import type { ParsedDiagram, SyntaxError, Position } from '../types/editor';
+import type { FileType } from '../types/tauri';
 
 interface UseTextEditorOptions {
   initialContent?: string;
   validateOnChange?: boolean;
   debounceMs?: number;
+  // The open file, so diagrams are extracted according to its format
+  path?: string;
+  fileType?: FileType;
 }
 
 interface UseTextEditorReturn {
@@ -26,7 +30,9 @@ export const useTextEditor = (options: UseTextEditorOptions = {}): UseTextEditor
   const {
     initialContent = '',
     validateOnChange = true,
-    debounceMs = 500
+    debounceMs = 500,
+    path,
+    fileType
   } = options;
 
   const [content, setContentState] = useState(initialContent);
@@ -47,7 +53,7 @@ export const useTextEditor = (options: UseTextEditorOptions = {}): UseTextEditor
     
     try {
       // Parse diagrams from content using async method (Rust backend)
-      const parsedDiagrams = await mermaidParser.parseContentAsync(content);
+      const parsedDiagrams = await mermaidParser.parseContentAsync(content, { path, fileType });
       setDiagrams(parsedDiagrams);
 
       // Collect all validation errors
@@ -87,7 +93,7 @@ export const useTextEditor = (options: UseTextEditorOptions = {}): UseTextEditor
     } finally {
       setIsValidating(false);
     }
-  }, [content]);
+  }, [content, path, fileType]);
 
   // Debounced content setter
   const setContent = useCallback((newContent: string) => {
@@ -1,6 +1,12 @@
 import mermaid from 'mermaid';
 import { TauriAPI } from './tauri-api';
-import type { ParsedDiagram, ValidationResult, SyntaxError, MermaidParserInterface } from '../types/editor';
+import type { DiagramType, ParsedDiagram, ValidationResult, SyntaxError, MermaidParserInterface } from '../types/editor';
+import type { FileType } from '../types/tauri';
+
+export interface ParseSource {
+  path?: string;
+  fileType?: FileType;
+}
 
 // Initialize Mermaid with configuration
 mermaid.initialize({
@@ -24,6 +30,8 @@ mermaid.initialize({
 export class MermaidParser implements MermaidParserInterface {
   private static instance: MermaidParser;
   private useRustParser = true;
+  // Last backend parse per document, sent back so diagram IDs survive edits
+  private previousDiagrams = new Map<string, any[]>();
 
   static getInstance(): MermaidParser {
     if (!MermaidParser.instance) {
@@ -33,15 +41,27 @@ export class MermaidParser implements MermaidParserInterface {
   }
 
   /**
-   * Parse content to extract Mermaid diagrams using Rust backend
+   * Parse content to extract Mermaid diagrams using Rust backend.
+   * The file's path and type decide whether it is read as Markdown or a bare diagram.
    */
-  async parseContentAsync(content: string): Promise<ParsedDiagram[]> {
+  async parseContentAsync(content: string, source: ParseSource = {}): Promise<ParsedDiagram[]> {
     if (this.useRustParser) {
       try {
-        const result = await TauriAPI.parseMermaidContent(content);
+        const key = source.path ?? '';
+        const result = await TauriAPI.parseMermaidContent(
+          content,
+          source.fileType,
+          source.path,
+          this.previousDiagrams.get(key)
+        );
+        this.previousDiagrams.set(key, result.diagrams);
         return result.diagrams.map((diagram: any) => ({
           id: diagram.id,
+          name: diagram.name ?? undefined,
+          fingerprint: diagram.fingerprint,
           type: diagram.diagram_type,
+          config: diagram.config ?? undefined,
+          fence: diagram.fence ?? undefined,
           content: diagram.content,
           startLine: diagram.start_line,
           endLine: diagram.end_line,
@@ -190,7 +210,7 @@ export class MermaidParser implements MermaidParserInterface {
   /**
    * Detect diagram type from content
    */
-  private detectDiagramType(content: string): string {
+  private detectDiagramType(content: string): DiagramType {
     const firstLine = content.split('\n')[0].trim().toLowerCase();
     
     if (firstLine.includes('graph') || firstLine.includes('flowchart')) {
@@ -1,5 +1,7 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { WindowSettings, ApplicationState, AppInfo, FileContent, FileDialogResult, SaveResult } from '../types/tauri';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { WindowSettings, ApplicationState, AppInfo, FileContent, FileDialogResult, FileType, SaveResult, TextEdit, RenderOptions, DiagramTask, FormatOptions, FormattedDocument, ExportOptions, BatchExportResult, WorkspaceNode, JournalEntry, RecoveryReport, FileChangedEvent, FileDeletedEvent, FileRenamedEvent } from '../types/tauri';
+import type { DiagramDetection, Fix } from '../types/editor';
 
 /**
  * Tauri API wrapper for Parch application commands
@@ -92,17 +94,19 @@ export class TauriAPI {
   static async updateFileState(
     filePath?: string,
     fileName?: string,
-    fileContent?: string,
     hasUnsavedChanges: boolean = false
   ): Promise<void> {
     return invoke('update_file_state', { 
       file_path: filePath,
       file_name: fileName,
-      file_content: fileContent,
       has_unsaved_changes: hasUnsavedChanges
     });
   }
 
+  static async updateBackupCount(count: number): Promise<void> {
+    return invoke('update_backup_count', { count });
+  }
+
   static async updateTreeViewState(show: boolean): Promise<void> {
     return invoke('update_tree_view_state', { show });
   }
@@ -152,20 +156,65 @@ export class TauriAPI {
   /**
    * Mermaid parsing commands
    */
-  static async parseMermaidContent(content: string): Promise<any> {
-    return invoke('parse_mermaid_content', { content });
+  static async parseMermaidContent(content: string, fileType?: FileType, path?: string, previous?: any[]): Promise<any> {
+    return invoke('parse_mermaid_content', { content, fileType, path, previous });
+  }
+
+  static async matchDiagramIds(previous: any[], current: any[]): Promise<any[]> {
+    return invoke('match_diagram_ids', { previous, current });
+  }
+
+  static async openParseSession(documentId: string, content: string, fileType?: FileType, path?: string): Promise<any> {
+    return invoke('open_parse_session', { documentId, content, fileType, path });
+  }
+
+  static async applyParseEdits(documentId: string, edits: TextEdit[]): Promise<any> {
+    return invoke('apply_parse_edits', { documentId, edits });
+  }
+
+  static async closeParseSession(documentId: string): Promise<void> {
+    return invoke('close_parse_session', { documentId });
+  }
+
+  /**
+   * Validate one diagram; with a `path`, lint rules come from the nearest `.parch.json`
+   */
+  static async validateMermaidDiagram(content: string, startLine?: number, path?: string): Promise<any> {
+    return invoke('validate_mermaid_diagram', { content, startLine, path });
+  }
+
+  /**
+   * Apply one of a diagnostic's `fixes` to the whole document and return the new text
+   */
+  static async applyDiagnosticFix(content: string, fix: Fix): Promise<string> {
+    return invoke('apply_diagnostic_fix', { content, fix });
+  }
+
+  static async listDiagramTasks(content: string, fileType?: FileType, path?: string): Promise<DiagramTask[]> {
+    return invoke('list_diagram_tasks', { content, fileType, path });
   }
 
-  static async validateMermaidDiagram(content: string, startLine?: number): Promise<any> {
-    return invoke('validate_mermaid_diagram', { content, startLine });
+  static async formatDiagram(content: string, options?: FormatOptions): Promise<string> {
+    return invoke('format_diagram', { content, options });
   }
 
-  static async detectDiagramType(content: string): Promise<string> {
+  /**
+   * Format every diagram in a document, leaving the text around them untouched
+   */
+  static async formatDocument(content: string, fileType?: FileType, path?: string, options?: FormatOptions): Promise<FormattedDocument> {
+    return invoke('format_document', { content, fileType, path, options });
+  }
+
+  static async renderDiagramSvg(content: string, options?: RenderOptions): Promise<string> {
+    return invoke('render_diagram_svg', { content, options });
+  }
+
+  static async detectDiagramType(content: string): Promise<DiagramDetection> {
     return invoke('detect_diagram_type', { content });
   }
 
-  static async getParsingStats(content: string): Promise<any> {
-    return invoke('get_parsing_stats', { content });
+  static async getParsingStats(content: string, fileType?: FileType, path?: string): Promise<any> {
+    return invoke('get_parsing_stats', { content, fileType, path });
   }
 
   /**
@@ -183,14 +232,79 @@ export class TauriAPI {
     return invoke('save_file', { fileContent });
   }
 
-  static async saveFileAsDialog(content: string, suggestedName?: string): Promise<SaveResult> {
-    return invoke('save_file_as_dialog', { content, suggestedName });
+  static async saveFileAsDialog(content: string, suggestedName?: string, documentId?: string): Promise<SaveResult> {
+    return invoke('save_file_as_dialog', { content, suggestedName, documentId });
+  }
+
+  /**
+   * Crash recovery: dirty buffers are journaled until they are saved or discarded
+   */
+  static async journalDocument(entry: JournalEntry): Promise<void> {
+    return invoke('journal_document', { entry });
+  }
+
+  static async getRecoveredDocuments(): Promise<RecoveryReport> {
+    return invoke('get_recovered_documents');
+  }
+
+  static async discardRecoveredDocument(documentId: string): Promise<void> {
+    return invoke('discard_recovered_document', { documentId });
+  }
+
+  /**
+   * Export commands
+   */
+  static async exportDiagram(content: string, diagram: string | number, options?: ExportOptions, fileType?: FileType, path?: string, previous?: any[]): Promise<SaveResult> {
+    return invoke('export_diagram', { content, diagram, options, fileType, path, previous });
+  }
+
+  static async exportAllDiagrams(content: string, options?: ExportOptions, fileType?: FileType, path?: string): Promise<BatchExportResult> {
+    return invoke('export_all_diagrams', { content, options, fileType, path });
   }
 
   static async checkFileModified(fileContent: FileContent): Promise<boolean> {
     return invoke('check_file_modified', { fileContent });
   }
 
+  /**
+   * File watching: the open file and every workspace folder report outside changes as events
+   */
+  static async watchFile(path: string | null): Promise<void> {
+    return invoke('watch_file', { path });
+  }
+
+  static async onFileChanged(handler: (event: FileChangedEvent) => void): Promise<UnlistenFn> {
+    return listen<FileChangedEvent>('file-changed', (event) => handler(event.payload));
+  }
+
+  static async onFileDeleted(handler: (event: FileDeletedEvent) => void): Promise<UnlistenFn> {
+    return listen<FileDeletedEvent>('file-deleted', (event) => handler(event.payload));
+  }
+
+  static async onFileRenamed(handler: (event: FileRenamedEvent) => void): Promise<UnlistenFn> {
+    return listen<FileRenamedEvent>('file-renamed', (event) => handler(event.payload));
+  }
+
+  /**
+   * Workspace commands
+   */
+  static async openWorkspaceDialog(): Promise<WorkspaceNode | null> {
+    return invoke('open_workspace_dialog');
+  }
+
+  static async openWorkspace(path: string): Promise<WorkspaceNode> {
+    return invoke('open_workspace', { path });
+  }
+
+  // Folders opened in earlier sessions, scanned again
+  static async getWorkspaces(): Promise<WorkspaceNode[]> {
+    return invoke('get_workspaces');
+  }
+
+  static async removeWorkspaceRoot(path: string): Promise<void> {
+    return invoke('remove_workspace_root', { path });
+  }
+
   static async getSupportedExtensions(): Promise<string[]> {
     return invoke('get_supported_extensions');
   }
@@ -13,11 +13,25 @@ export interface Range {
   endColumn: number;
 }
 
+// Suggested edits that resolve a diagnostic, in 1-based document coordinates
+export interface Fix {
+  title: string;
+  edits: {
+    start: Position;
+    end: Position;
+    text: string;
+  }[];
+}
+
 export interface SyntaxError {
   line: number;
   column: number;
   message: string;
   severity: 'error' | 'warning' | 'info';
+  // Stable diagnostic code such as `MMD0012`
+  code?: string;
+  // Quick fixes, best first
+  fixes?: Fix[];
 }
 
 export interface ValidationResult {
@@ -25,9 +39,63 @@ export interface ValidationResult {
   errors: SyntaxError[];
 }
 
+// Frontmatter and %%{init}%% configuration, as sent by the backend
+export interface DiagramConfig {
+  title?: string;
+  theme?: string;
+  theme_variables?: Record<string, unknown>;
+  config?: Record<string, unknown>;
+}
+
+export type DiagramType =
+  | 'flowchart'
+  | 'sequence'
+  | 'class'
+  | 'state'
+  | 'er'
+  | 'gantt'
+  | 'pie'
+  | 'journey'
+  | 'gitgraph'
+  | 'requirement'
+  | 'c4context'
+  | 'c4container'
+  | 'c4component'
+  | 'c4dynamic'
+  | 'c4deployment'
+  | 'mindmap'
+  | 'timeline'
+  | 'quadrant'
+  | 'sankey'
+  | 'xychart'
+  | 'block'
+  | 'packet'
+  | 'architecture'
+  | 'kanban'
+  | 'zenuml'
+  | 'radar'
+  | 'treemap'
+  | 'unknown';
+
+export interface DiagramFence {
+  kind: 'backtick' | 'tilde' | 'html';
+  info: string;
+  attributes?: Record<string, string>;
+}
+
+export interface DiagramDetection {
+  diagram_type: DiagramType;
+  keyword: string | null;
+  suggestion?: string;
+}
+
 export interface ParsedDiagram {
   id: string;
-  type: string;
+  name?: string;
+  fingerprint?: string;
+  type: DiagramType;
+  config?: DiagramConfig;
+  fence?: DiagramFence;
   content: string;
   startLine: number;
   endLine: number;
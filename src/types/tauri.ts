@@ -16,10 +16,11 @@ export interface ApplicationState {
   activeDiagramIndex: number;
   cursorPosition?: [number, number]; // [line, column]
   lastFilePath?: string;
-  lastFileContent?: string;
   lastFileName?: string;
   hasUnsavedChanges: boolean;
   showTreeView: boolean;
+  workspaceRoots: string[];
+  backupCount: number; // rotating .bak copies kept on save; 0 keeps none
   customColors?: ThemeColors;
 }
 
@@ -38,6 +39,9 @@ export interface FileContent {
   lastModified?: string; // ISO string representation of SystemTime
   isSaved: boolean;
   fileType: FileType;
+  // Disk version at load or last save; saving fails with SAVE_CONFLICT when it no longer matches
+  contentHash?: string;
+  size?: number;
 }
 
 export enum FileType {
@@ -46,16 +50,157 @@ export enum FileType {
   MermaidMarkdown = "MermaidMarkdown"
 }
 
+// Stable error codes sent by the backend; see src-tauri/src/error.rs
+export type ErrorCode =
+  | 'FILE_READ'
+  | 'FILE_WRITE'
+  | 'FILE_METADATA'
+  | 'SAVE_CONFLICT'
+  | 'INVALID_PATH'
+  | 'NO_FILE_PATH'
+  | 'DIALOG_CANCELLED'
+  | 'STATE_STORE'
+  | 'LINT_CONFIG'
+  | 'SESSION_NOT_FOUND'
+  | 'DIAGRAM_NOT_FOUND'
+  | 'PARSE'
+  | 'FORMAT'
+  | 'RENDER'
+  | 'EXPORT'
+  | 'WATCH'
+  | 'WINDOW';
+
+// Both versions of a file that changed on disk since it was opened. Saving again with
+// `contentHash` set to `disk_hash` overwrites the disk version.
+export interface SaveConflict {
+  path: string;
+  ours: string;
+  theirs: string;
+  disk_hash: string;
+  disk_size: number;
+}
+
+// Rejection value of every failing Tauri command
+export interface AppError {
+  code: ErrorCode;
+  message: string;
+  // Set when code is SAVE_CONFLICT
+  conflict?: SaveConflict;
+}
+
 export interface FileDialogResult {
   success: boolean;
   fileContent?: FileContent;
   error?: string;
+  errorCode?: ErrorCode;
 }
 
+// Failed saves reject with an AppError; `success` is false only when the dialog was cancelled
 export interface SaveResult {
   success: boolean;
   filePath?: string;
+  errorCode?: ErrorCode;
+  contentHash?: string;
+  size?: number;
+}
+
+// Incremental parsing: 1-based line/column range replaced by `text`
+export interface TextEdit {
+  start: { line: number; column: number };
+  end: { line: number; column: number };
+  text: string;
+}
+
+export interface RenderOptions {
+  theme?: 'light' | 'dark';
+  font_size?: number;
+  padding?: number;
+}
+
+// A TODO, FIXME, XXX or HACK comment inside a diagram, with document positions
+export interface DiagramTask {
+  diagram_id: string;
+  marker: 'TODO' | 'FIXME' | 'XXX' | 'HACK';
+  text: string;
+  line: number;
+  column: number;
+}
+
+export interface FormatOptions {
+  indent?: number;
+  quotes?: 'minimal' | 'always';
+  sort_declarations?: boolean;
+}
+
+export interface FormattedDocument {
+  content: string;
+  // Number of diagrams whose text changed
+  changed: number;
+  // Diagrams left as they were, such as ones with syntax errors
+  skipped: { start_line: number; reason: string }[];
+}
+
+export interface ExportOptions {
+  format?: 'svg' | 'png' | 'jpeg' | 'pdf';
+  scale?: number;
+  dpi?: number;
+  background?: string;
+  theme?: 'light' | 'dark';
+}
+
+export interface BatchExportResult {
+  success: boolean;
+  folder?: string;
+  files: string[];
+  failures: { diagram_id: string; error: string }[];
+}
+
+// A file or folder in an open workspace; folder counts sum everything below them
+export interface WorkspaceNode {
+  name: string;
+  path: string;
+  kind: 'directory' | 'file';
+  children?: WorkspaceNode[];
+  diagram_count: number;
+  error_count: number;
+  // Set when the file could not be read
   error?: string;
+  // Hex SHA-256 of a file's bytes when it was scanned
+  content_hash?: string;
+}
+
+// Payloads of the file-changed, file-deleted and file-renamed events; hashes are hex SHA-256
+export interface FileChangedEvent {
+  path: string;
+  hash: string;
+}
+
+export interface FileDeletedEvent {
+  path: string;
+}
+
+export interface FileRenamedEvent {
+  from: string;
+  to: string;
+  hash: string;
+}
+
+// An unsaved buffer journaled for crash recovery; untitled buffers have no path
+export interface JournalEntry {
+  id: string;
+  name: string;
+  path?: string;
+  content: string;
+  fileType: FileType;
+  contentHash?: string;
+  size?: number;
+  journaledAt?: string;
+}
+
+export interface RecoveryReport {
+  unclean_shutdown: boolean;
+  // Buffers left over from earlier sessions, most recent first
+  documents: JournalEntry[];
 }
 
 // Window management commands
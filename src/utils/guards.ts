@@ -15,6 +15,15 @@ export const isObject = (value: unknown): value is Record<string, unknown> => {
   return typeof value === 'object' && value !== null && !Array.isArray(value);
 };
 
+// Errors rejected by Tauri commands carry a stable code alongside the message
+export const isAppError = (value: unknown): value is { code: string; message: string } => {
+  return isObject(value) && isString(value.code) && isString(value.message);
+};
+
+export const errorMessage = (error: unknown): string => {
+  return isAppError(error) ? error.message : String(error);
+};
+
 // File content validation
 export interface FileContentLike {
   id: string;